@@ -13,6 +13,14 @@
 //     pub updated_at: chrono::NaiveDateTime,
 // }
 // The endpoint becomes GetClass.
+//
+// Field attributes, as `#[api(...)]`:
+//   read_only            not sent when creating or updating
+//   post_only            only sent when creating
+//   optional             may be null
+//   as_id                sent as `<field>_id` when creating or updating
+//   table_rename = "..." the column header in tables
+//   json = "..."         the key on the wire, when it differs from the field name
 
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
@@ -29,7 +37,31 @@ fn pluralize(name: &syn::Ident) -> String {
     }
 }
 
-#[proc_macro_derive(ApiResource, attributes(endpoint, api))]
+/// Read a string valued `#[api_resource(key = "...")]` attribute on the struct.
+fn get_struct_attribute(input: &DeriveInput, key: &str) -> Option<String> {
+    input.attrs.iter().find_map(|attr| {
+        if !attr.path().is_ident("api_resource") {
+            return None;
+        }
+        let nested = attr
+            .parse_args_with(Punctuated::<Meta, syn::Token![,]>::parse_terminated)
+            .ok()?;
+        nested.iter().find_map(|meta| match meta {
+            Meta::NameValue(name_value) if name_value.path.is_ident(key) => {
+                match &name_value.value {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(lit),
+                        ..
+                    }) => Some(lit.value()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+    })
+}
+
+#[proc_macro_derive(ApiResource, attributes(endpoint, api, api_resource))]
 pub fn api_resource_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = &input.ident;
@@ -49,12 +81,15 @@ pub fn api_resource_derive(input: TokenStream) -> TokenStream {
         _ => panic!("ApiResource only supports structs"),
     };
 
-    let (main_fields, get_fields, post_fields, patch_fields) = process_fields(fields);
+    let (main_fields, get_fields, post_fields, patch_fields, get_filters) = process_fields(fields);
 
     let get_name = format_ident!("{}Get", name);
     let post_name = format_ident!("{}Post", name);
     let patch_name = format_ident!("{}Patch", name);
-    let endpoint = format_ident!("{}", plural_name);
+    let endpoint = match get_struct_attribute(&input, "endpoint") {
+        Some(endpoint) => format_ident!("{}", endpoint),
+        None => format_ident!("{}", plural_name),
+    };
 
     // List of field names to check for Display implementation, in order of preference
     let display_field_options = &[
@@ -79,6 +114,94 @@ pub fn api_resource_derive(input: TokenStream) -> TokenStream {
         }
     };
 
+    // Resources with an updated_at field can be watched for changes
+    let updated_at_impl = if fields
+        .iter()
+        .any(|f| f.ident.as_ref().is_some_and(|i| i == "updated_at"))
+    {
+        quote! {
+            impl crate::client::GetUpdatedAt for #name {
+                fn updated_at(&self) -> chrono::NaiveDateTime {
+                    self.updated_at
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Relations that filters may traverse, e.g. `namespaces__name`
+    let joins: Vec<String> = get_struct_attribute(&input, "joins")
+        .map(|joins| {
+            joins
+                .split(',')
+                .map(|join| join.trim().to_string())
+                .filter(|join| !join.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // The field used for lookups by name, as it is on the wire, unless the
+    // trait default applies
+    let name_field_attr = get_struct_attribute(&input, "name_field");
+    let name_field_json = fields
+        .iter()
+        .find(|f| {
+            f.ident
+                .as_ref()
+                .is_some_and(|i| *i == name_field_attr.as_deref().unwrap_or("name"))
+        })
+        .and_then(|f| get_field_attribute(f, "json"));
+    let name_field = name_field_json.or(name_field_attr).map(|name_field| {
+        quote! { const NAME_FIELD: &'static str = #name_field; }
+    });
+
+    // Relations that can be expanded, as `relation:field` pairs of Ref fields
+    let expand_fn = get_struct_attribute(&input, "expand").map(|expand| {
+        let arms = expand.split(',').filter_map(|pair| {
+            let (relation, field) = pair.trim().split_once(':')?;
+            let relation = relation.trim();
+            let field = format_ident!("{}", field.trim());
+            Some(quote! {
+                #relation => crate::resources::expand_refs(
+                    client,
+                    items.iter().map(|item| &item.#field),
+                )?,
+            })
+        });
+        quote! {
+            fn expand(
+                client: &crate::client::SyncClient<crate::client::Authenticated>,
+                items: &[Self::GetOutput],
+                relations: &[&str],
+            ) -> Result<(), crate::errors::ApiError> {
+                for relation in relations {
+                    match *relation {
+                        #(#arms)*
+                        other => return Err(crate::errors::ApiError::InvalidExpand(format!(
+                            "{} has no relation '{}'",
+                            stringify!(#name),
+                            other
+                        ))),
+                    }
+                }
+                Ok(())
+            }
+        }
+    });
+
+    // Field values of the main struct, for diffing instances
+    let diff_fields = fields
+        .iter()
+        .filter(|f| !has_attribute(f, "post_only"))
+        .map(|f| {
+            let ident = f.ident.as_ref().unwrap();
+            let field = ident.to_string();
+            quote! {
+                (#field, serde_json::to_value(&self.#ident).unwrap_or(serde_json::Value::Null)),
+            }
+        });
+
     let expanded = quote! {
         #[derive(Default, Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, tabled::Tabled)]
         pub struct #name {
@@ -91,11 +214,28 @@ pub fn api_resource_derive(input: TokenStream) -> TokenStream {
             }
         }
 
+        impl crate::client::FromId for #name {
+            fn from_id(id: i32) -> Self {
+                #name {
+                    id,
+                    ..Default::default()
+                }
+            }
+        }
+
         #[derive(Default, Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
         pub struct #get_name {
             #get_fields
         }
 
+        impl crate::client::IntoResourceFilter<#name> for #get_name {
+            fn into_resource_filter(self) -> Vec<crate::types::QueryFilter> {
+                let mut filters = vec![];
+                #get_filters
+                filters
+            }
+        }
+
         #[derive(Default, Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
         pub struct #post_name {
             #post_fields
@@ -108,6 +248,14 @@ pub fn api_resource_derive(input: TokenStream) -> TokenStream {
 
         #display_impl
 
+        impl crate::diff::Diffable for #name {
+            fn field_values(&self) -> Vec<(&'static str, serde_json::Value)> {
+                vec![#(#diff_fields)*]
+            }
+        }
+
+        #updated_at_impl
+
         impl crate::resources::ApiResource for #name {
             type GetParams = #get_name;
             type GetOutput = #name;
@@ -118,6 +266,12 @@ pub fn api_resource_derive(input: TokenStream) -> TokenStream {
             type DeleteParams = ();
             type DeleteOutput = ();
 
+            const JOINS: &'static [&'static str] = &[#(#joins),*];
+
+            #name_field
+
+            #expand_fn
+
             fn endpoint(&self) -> crate::endpoints::Endpoint {
                 crate::endpoints::Endpoint::#endpoint
             }
@@ -156,7 +310,8 @@ fn has_attribute(field: &syn::Field, attr_name: &str) -> bool {
     })
 }
 
-fn get_rename_value(field: &syn::Field) -> Option<String> {
+/// Read a string valued `#[api(key = "...")]` attribute on a field.
+fn get_field_attribute(field: &syn::Field, key: &str) -> Option<String> {
     field.attrs.iter().find_map(|attr| {
         if attr.path().is_ident("api") {
             if let Meta::List(list) = &attr.meta {
@@ -165,7 +320,7 @@ fn get_rename_value(field: &syn::Field) -> Option<String> {
                 {
                     return nested.iter().find_map(|meta| {
                         if let Meta::NameValue(name_value) = meta {
-                            if name_value.path.is_ident("table_rename") {
+                            if name_value.path.is_ident(key) {
                                 if let syn::Expr::Lit(expr_lit) = &name_value.value {
                                     if let syn::Lit::Str(lit) = &expr_lit.lit {
                                         return Some(lit.value());
@@ -189,11 +344,13 @@ fn process_fields(
     proc_macro2::TokenStream,
     proc_macro2::TokenStream,
     proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
 ) {
     let mut main_fields = proc_macro2::TokenStream::new();
     let mut get_fields = proc_macro2::TokenStream::new();
     let mut post_fields = proc_macro2::TokenStream::new();
     let mut patch_fields = proc_macro2::TokenStream::new();
+    let mut get_filters = proc_macro2::TokenStream::new();
 
     for field in fields {
         let name = &field.ident;
@@ -205,7 +362,19 @@ fn process_fields(
         let is_optional = has_attribute(field, "optional");
         let is_as_id = has_attribute(field, "as_id");
 
-        let rename = get_rename_value(field).unwrap_or_else(|| fieldname.clone());
+        let rename = get_field_attribute(field, "table_rename").unwrap_or_else(|| fieldname.clone());
+
+        // The key used on the wire, when it differs from the field name
+        let json = get_field_attribute(field, "json");
+        let serde_rename = json.as_ref().map(|json| quote!(#[serde(rename = #json)]));
+        let id_serde_rename = json.as_ref().map(|json| {
+            let json = if is_as_id {
+                format!("{}_id", json)
+            } else {
+                json.clone()
+            };
+            quote!(#[serde(rename = #json)])
+        });
 
         let id_field_name = if is_as_id {
             format!("{}_id", fieldname)
@@ -213,9 +382,22 @@ fn process_fields(
             fieldname.clone()
         };
         let id_field_ident = syn::Ident::new(&id_field_name, proc_macro2::Span::call_site());
+        // Filters name the field as it is on the wire
+        let id_wire_name = match &json {
+            Some(json) if is_as_id => format!("{}_id", json),
+            Some(json) => json.clone(),
+            None => id_field_name.clone(),
+        };
 
         if !is_post_only {
-            let tabled_attr = if is_optional {
+            let tabled_attr = if is_as_id && !is_optional {
+                // The server may return either the id or the embedded resource
+                quote!(
+                    #[serde(deserialize_with = "crate::resources::id_or_object")]
+                    #[tabled(display_with = "crate::resources::tabled_display_embedded", rename = #rename)]
+                    pub #name: #ty,
+                )
+            } else if is_optional {
                 quote!(
                     #[tabled(display_with = "crate::resources::tabled_display_option", rename = #rename)]
                     pub #name: Option<#ty>,
@@ -228,14 +410,23 @@ fn process_fields(
             };
 
             main_fields.extend(quote! {
+                #serde_rename
                 #tabled_attr
             });
 
-            get_fields.extend(quote! { pub #id_field_ident: Option<#ty>, });
+            let filter_type = if is_as_id { quote!(i32) } else { quote!(#ty) };
+            get_fields.extend(quote! {
+                #id_serde_rename pub #id_field_ident: Option<crate::types::Filterable<#filter_type>>,
+            });
+            get_filters.extend(quote! {
+                if let Some(filter) = &self.#id_field_ident {
+                    filters.push(filter.to_query_filter(#id_wire_name));
+                }
+            });
         }
 
         if is_post_only {
-            post_fields.extend(quote! { pub #id_field_ident: #ty, });
+            post_fields.extend(quote! { #id_serde_rename pub #id_field_ident: #ty, });
         } else if !is_read_only {
             if is_as_id {
                 let id_type = if is_optional {
@@ -243,19 +434,19 @@ fn process_fields(
                 } else {
                     quote!(i32)
                 };
-                patch_fields.extend(quote! { pub #id_field_ident: #id_type, });
-                post_fields.extend(quote! { pub #id_field_ident: #id_type, });
+                patch_fields.extend(quote! { #id_serde_rename pub #id_field_ident: #id_type, });
+                post_fields.extend(quote! { #id_serde_rename pub #id_field_ident: #id_type, });
             } else {
-                patch_fields.extend(quote! { pub #id_field_ident: Option<#ty>, });
+                patch_fields.extend(quote! { #id_serde_rename pub #id_field_ident: Option<#ty>, });
                 let post_type = if is_optional {
                     quote!(Option<#ty>)
                 } else {
                     quote!(#ty)
                 };
-                post_fields.extend(quote! { pub #id_field_ident: #post_type, });
+                post_fields.extend(quote! { #id_serde_rename pub #id_field_ident: #post_type, });
             }
         }
     }
 
-    (main_fields, get_fields, post_fields, patch_fields)
+    (main_fields, get_fields, post_fields, patch_fields, get_filters)
 }
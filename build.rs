@@ -0,0 +1,168 @@
+//! Generates the `generated` module from the vendored OpenAPI document when
+//! the `codegen` feature is enabled, see `src/generated.rs`.
+use serde_json::Value;
+use std::fmt::Write;
+use std::path::Path;
+
+const SPEC: &str = "openapi/hubuum.json";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SPEC);
+    println!("cargo:rerun-if-changed=build.rs");
+    if std::env::var_os("CARGO_FEATURE_CODEGEN").is_none() {
+        return;
+    }
+
+    let spec: Value = serde_json::from_str(
+        &std::fs::read_to_string(SPEC).unwrap_or_else(|err| panic!("reading {}: {}", SPEC, err)),
+    )
+    .unwrap_or_else(|err| panic!("parsing {}: {}", SPEC, err));
+
+    let mut out = String::new();
+    endpoints(&spec, &mut out);
+    schemas(&spec, &mut out);
+
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    std::fs::write(Path::new(&out_dir).join("generated.rs"), out)
+        .expect("writing the generated module");
+}
+
+/// An `Endpoint` enum with one variant per path of the document.
+fn endpoints(spec: &Value, out: &mut String) {
+    let paths = spec["paths"].as_object().expect("the document has paths");
+    let mut endpoints: Vec<(String, &str, Vec<&str>)> = paths
+        .iter()
+        .map(|(path, operations)| {
+            let methods = operations
+                .as_object()
+                .map(|operations| operations.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            (variant(path), path.as_str(), methods)
+        })
+        .collect();
+    endpoints.sort();
+
+    out.push_str("/// The paths of the vendored OpenAPI document.\n");
+    out.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq)]\n");
+    out.push_str("pub enum Endpoint {\n");
+    for (name, path, _) in &endpoints {
+        writeln!(out, "    /// `{}`", path).unwrap();
+        writeln!(out, "    {},", name).unwrap();
+    }
+    out.push_str("}\n\nimpl Endpoint {\n");
+    out.push_str("    pub const ALL: &'static [Endpoint] = &[\n");
+    for (name, _, _) in &endpoints {
+        writeln!(out, "        Endpoint::{},", name).unwrap();
+    }
+    out.push_str("    ];\n\n    pub fn path(&self) -> &'static str {\n        match self {\n");
+    for (name, path, _) in &endpoints {
+        writeln!(out, "            Endpoint::{} => {:?},", name, path).unwrap();
+    }
+    out.push_str("        }\n    }\n\n");
+    out.push_str("    /// The methods the document offers on the path, in OpenAPI spelling.\n");
+    out.push_str("    pub fn methods(&self) -> &'static [&'static str] {\n        match self {\n");
+    for (name, _, methods) in &endpoints {
+        writeln!(out, "            Endpoint::{} => &{:?},", name, methods).unwrap();
+    }
+    out.push_str("        }\n    }\n}\n");
+}
+
+/// A struct per object schema of the document.
+fn schemas(spec: &Value, out: &mut String) {
+    let Some(schemas) = spec["components"]["schemas"].as_object() else {
+        return;
+    };
+    for (name, schema) in schemas {
+        let Some(properties) = schema["properties"].as_object() else {
+            continue;
+        };
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .map(|required| required.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        writeln!(
+            out,
+            "\n/// The `{}` schema of the vendored OpenAPI document.",
+            name
+        )
+        .unwrap();
+        out.push_str("#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]\n");
+        writeln!(out, "pub struct {} {{", name).unwrap();
+        for (field, property) in properties {
+            let ty = rust_type(property);
+            if required.contains(&field.as_str()) {
+                writeln!(out, "    pub {}: {},", ident(field), ty).unwrap();
+            } else {
+                out.push_str("    #[serde(default, skip_serializing_if = \"Option::is_none\")]\n");
+                writeln!(out, "    pub {}: Option<{}>,", ident(field), ty).unwrap();
+            }
+        }
+        out.push_str("}\n");
+    }
+}
+
+fn rust_type(property: &Value) -> String {
+    if let Some(reference) = property["$ref"].as_str() {
+        return reference
+            .rsplit('/')
+            .next()
+            .unwrap_or(reference)
+            .to_string();
+    }
+    let format = property["format"].as_str();
+    match property["type"].as_str() {
+        Some("integer") if format == Some("int32") => "i32".to_string(),
+        Some("integer") => "i64".to_string(),
+        Some("number") => "f64".to_string(),
+        Some("boolean") => "bool".to_string(),
+        Some("string") if format == Some("date-time") => "chrono::NaiveDateTime".to_string(),
+        Some("string") => "String".to_string(),
+        Some("array") => format!("Vec<{}>", rust_type(&property["items"])),
+        _ => "serde_json::Value".to_string(),
+    }
+}
+
+/// `/api/v1/classes/{class_id}/history` becomes `ClassesByClassHistory`.
+fn variant(path: &str) -> String {
+    let mut name = String::new();
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        if segment == "api" || is_version(segment) {
+            continue;
+        }
+        match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(param) => {
+                name.push_str("By");
+                name.push_str(&camel_case(param.strip_suffix("_id").unwrap_or(param)));
+            }
+            None => name.push_str(&camel_case(segment)),
+        }
+    }
+    name
+}
+
+fn is_version(segment: &str) -> bool {
+    segment
+        .strip_prefix('v')
+        .is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn camel_case(s: &str) -> String {
+    s.split(['_', '-', '.'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().map(|c| c.to_ascii_uppercase());
+            first.into_iter().chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+fn ident(field: &str) -> String {
+    const KEYWORDS: &[&str] = &["type", "ref", "match", "move", "self", "struct", "use"];
+    if KEYWORDS.contains(&field) {
+        format!("r#{}", field)
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,260 @@
+//! Compressed archives of snapshots, for moving namespaces between servers.
+//!
+//! An archive is a gzip compressed tarball holding a `manifest.json` and one
+//! JSON file per resource:
+//!
+//! ```text
+//! manifest.json
+//! namespaces/<id>.json
+//! classes/<id>.json
+//! objects/<class id>/<id>.json
+//! ```
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use hubuum_client::{archive, snapshot::{Snapshot, SnapshotScope}};
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let source = SyncClient::new(BaseUrl::from_str("https://old.example.com")?)
+//! #     .login(Credentials::new("user".into(), "pass".into()))?;
+//! # let target = SyncClient::new(BaseUrl::from_str("https://new.example.com")?)
+//! #     .login(Credentials::new("user".into(), "pass".into()))?;
+//! let snapshot = Snapshot::capture(&source, &SnapshotScope::namespaces(&[1]))?;
+//! archive::export(&snapshot, "prod.tar.gz", None)?;
+//!
+//! let (manifest, snapshot) = archive::import("prod.tar.gz")?;
+//! println!("exported at {}", manifest.exported_at);
+//! snapshot.apply(&target, 1)?;
+//! # Ok(())
+//! # }
+//! ```
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::errors::ApiError;
+use crate::logging::{debug, SNAPSHOT};
+use crate::snapshot::{Snapshot, SNAPSHOT_FORMAT_VERSION};
+
+/// The version of the archive layout written by this crate.
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+
+/// Describes the content of an archive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format_version: u32,
+    /// The snapshot format version of the resources in the archive.
+    pub schema_version: u32,
+    /// The version of the server the snapshot was captured from, if known.
+    pub server_version: Option<String>,
+    pub captured_at: chrono::NaiveDateTime,
+    pub exported_at: chrono::NaiveDateTime,
+    pub namespaces: usize,
+    pub classes: usize,
+    pub objects: usize,
+}
+
+impl Manifest {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.format_version != ARCHIVE_FORMAT_VERSION {
+            return Err(ApiError::InvalidArchive(format!(
+                "unsupported archive format version {}",
+                self.format_version
+            )));
+        }
+        if self.schema_version != SNAPSHOT_FORMAT_VERSION {
+            return Err(ApiError::InvalidArchive(format!(
+                "unsupported schema version {}",
+                self.schema_version
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Write a snapshot to a compressed archive.
+pub fn export(
+    snapshot: &Snapshot,
+    path: impl AsRef<Path>,
+    server_version: Option<&str>,
+) -> Result<Manifest, ApiError> {
+    let exported_at = chrono::Utc::now();
+    let manifest = Manifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        schema_version: snapshot.format_version,
+        server_version: server_version.map(str::to_string),
+        captured_at: snapshot.captured_at,
+        exported_at: exported_at.naive_utc(),
+        namespaces: snapshot.namespaces.len(),
+        classes: snapshot.classes.len(),
+        objects: snapshot.objects.len(),
+    };
+
+    let encoder = GzEncoder::new(File::create(path)?, Compression::default());
+    let mut tar = tar::Builder::new(encoder);
+    let mut append = |path: String, data: Vec<u8>| -> Result<(), ApiError> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(exported_at.timestamp().max(0) as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, path, data.as_slice())?;
+        Ok(())
+    };
+
+    append(MANIFEST.to_string(), serde_json::to_vec_pretty(&manifest)?)?;
+    for namespace in &snapshot.namespaces {
+        let path = format!("namespaces/{}.json", namespace.id);
+        append(path, serde_json::to_vec_pretty(namespace)?)?;
+    }
+    for class in &snapshot.classes {
+        let path = format!("classes/{}.json", class.id);
+        append(path, serde_json::to_vec_pretty(class)?)?;
+    }
+    for object in &snapshot.objects {
+        let path = format!("objects/{}/{}.json", object.hubuum_class_id, object.id);
+        append(path, serde_json::to_vec_pretty(object)?)?;
+    }
+
+    tar.into_inner()?.finish()?;
+    debug!(target: SNAPSHOT, "Exported archive with {:?}", manifest);
+    Ok(manifest)
+}
+
+/// Read an archive written by `export`.
+///
+/// The manifest is checked before any resource is read, and the number of
+/// resources found must match the manifest, so a truncated or tampered
+/// archive is rejected as a whole rather than partially applied.
+pub fn import(path: impl AsRef<Path>) -> Result<(Manifest, Snapshot), ApiError> {
+    let mut tar = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    let mut manifest: Option<Manifest> = None;
+    let mut files: Vec<(String, Vec<u8>)> = vec![];
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut data = vec![];
+        entry.read_to_end(&mut data)?;
+        if name == MANIFEST {
+            let parsed: Manifest = serde_json::from_slice(&data)?;
+            parsed.validate()?;
+            manifest = Some(parsed);
+        } else if manifest.is_none() {
+            return Err(ApiError::InvalidArchive(format!(
+                "{} found before the manifest",
+                name
+            )));
+        } else {
+            files.push((name, data));
+        }
+    }
+
+    let manifest =
+        manifest.ok_or_else(|| ApiError::InvalidArchive("missing manifest".to_string()))?;
+    let namespaces = read_all(&files, "namespaces/")?;
+    let classes = read_all(&files, "classes/")?;
+    let objects = read_all(&files, "objects/")?;
+
+    let counts = (namespaces.len(), classes.len(), objects.len());
+    if counts != (manifest.namespaces, manifest.classes, manifest.objects) {
+        return Err(ApiError::InvalidArchive(format!(
+            "manifest lists {} namespaces, {} classes and {} objects, archive holds {:?}",
+            manifest.namespaces, manifest.classes, manifest.objects, counts
+        )));
+    }
+
+    let snapshot = Snapshot {
+        format_version: manifest.schema_version,
+        captured_at: manifest.captured_at,
+        namespaces,
+        classes,
+        objects,
+    };
+    Ok((manifest, snapshot))
+}
+
+/// Deserialize the files under `prefix`, in archive order.
+fn read_all<T: DeserializeOwned>(
+    files: &[(String, Vec<u8>)],
+    prefix: &str,
+) -> Result<Vec<T>, ApiError> {
+    files
+        .iter()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(_, data)| Ok(serde_json::from_slice(data)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::{Class, Namespace, Object};
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            captured_at: chrono::NaiveDateTime::default(),
+            namespaces: vec![Namespace {
+                id: 1,
+                name: "prod".to_string(),
+                ..Default::default()
+            }],
+            classes: vec![Class {
+                id: 2,
+                namespace: Namespace {
+                    id: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            objects: vec![Object {
+                id: 3,
+                namespace_id: 1.into(),
+                hubuum_class_id: 2.into(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.tar.gz", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let path = temp_path("archive");
+        let snapshot = snapshot();
+        let exported = export(&snapshot, &path, Some("0.1.0")).unwrap();
+        let imported = import(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let (manifest, imported) = imported.unwrap();
+        assert_eq!(manifest, exported);
+        assert_eq!(manifest.server_version.as_deref(), Some("0.1.0"));
+        assert_eq!(imported, snapshot);
+    }
+
+    #[test]
+    fn test_import_rejects_missing_manifest() {
+        let path = temp_path("archive-without-manifest");
+        let encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        let mut tar = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(2);
+        header.set_cksum();
+        tar.append_data(&mut header, "namespaces/1.json", &b"{}"[..])
+            .unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let imported = import(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(imported, Err(ApiError::InvalidArchive(_))));
+    }
+}
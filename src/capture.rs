@@ -0,0 +1,344 @@
+//! Recording the traffic of a client as a HAR file.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use hubuum_client::capture::Capture;
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let capture = Capture::new();
+//! let client = SyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//!     .login(Credentials::new("user".into(), "pass".into()))?
+//!     .with_capture(capture.clone());
+//! let result = client.classes().find().execute();
+//! capture.save("sync-run.har")?;
+//! # Ok(())
+//! # }
+//! ```
+use reqwest::header::{HeaderMap, HeaderName, AUTHORIZATION, CONTENT_TYPE, COOKIE, SET_COOKIE};
+use reqwest::{Method, StatusCode, Version};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::errors::ApiError;
+use crate::logging::BodyLog;
+
+/// Replaces the values of headers carrying credentials.
+pub const REDACTED_HEADER: &str = "[redacted]";
+
+/// Records requests and responses of the clients it is attached to.
+///
+/// Clones share the same recording. Credentials in the `Authorization`,
+/// `Cookie` and `Set-Cookie` headers and the signatures of a signing client
+/// are redacted, and logins are never recorded, so the passwords they carry
+/// stay out of the file. Headers and JSON body fields matching the patterns
+/// given to `ClientBuilder::redact` are redacted as in the body log.
+#[derive(Debug, Clone, Default)]
+pub struct Capture {
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+/// What the client recording a request redacts, besides the credential
+/// headers.
+pub(crate) struct Redaction<'a> {
+    pub body_log: &'a BodyLog,
+    /// The header carrying request signatures, if requests are signed.
+    pub signer_header: Option<&'a str>,
+}
+
+impl Redaction<'_> {
+    fn header(&self, name: &HeaderName) -> bool {
+        [AUTHORIZATION, COOKIE, SET_COOKIE].contains(name)
+            || self
+                .signer_header
+                .is_some_and(|header| name.as_str().eq_ignore_ascii_case(header))
+            || self.body_log.redacts(name.as_str())
+    }
+}
+
+/// A request that has been sent but not answered yet.
+pub(crate) struct Pending {
+    started: chrono::DateTime<chrono::Utc>,
+    instant: Instant,
+    request: Request,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Capture::default()
+    }
+
+    /// The number of recorded requests.
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries().clear();
+    }
+
+    /// The recording as a HAR 1.2 document.
+    pub fn to_har(&self) -> Result<serde_json::Value, ApiError> {
+        Ok(serde_json::json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "entries": serde_json::to_value(&*self.entries())?,
+            }
+        }))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ApiError> {
+        std::fs::write(path, serde_json::to_vec_pretty(&self.to_har()?)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn begin(
+        &self,
+        method: &Method,
+        url: &str,
+        headers: &HeaderMap,
+        body: Option<&[u8]>,
+        redaction: &Redaction,
+    ) -> Pending {
+        let headers = har_headers(headers, redaction);
+        let post_data = body.map(|body| Content {
+            size: body.len(),
+            mime_type: mime_type(&headers),
+            text: redaction.body_log.redact(body),
+        });
+        Pending {
+            started: chrono::Utc::now(),
+            instant: Instant::now(),
+            request: Request {
+                method: method.to_string(),
+                url: url.to_string(),
+                http_version: "HTTP/1.1".to_string(),
+                body_size: post_data.as_ref().map_or(0, |data| data.size),
+                headers,
+                query_string: query_string(url),
+                cookies: vec![],
+                headers_size: -1,
+                post_data,
+            },
+        }
+    }
+
+    pub(crate) fn finish(
+        &self,
+        pending: Pending,
+        status: StatusCode,
+        version: Version,
+        headers: &HeaderMap,
+        body: &[u8],
+        redaction: &Redaction,
+    ) {
+        let elapsed = pending.instant.elapsed().as_secs_f64() * 1000.0;
+        let headers = har_headers(headers, redaction);
+        let entry = Entry {
+            started_date_time: pending.started.to_rfc3339(),
+            time: elapsed,
+            request: pending.request,
+            response: Response {
+                status: status.as_u16(),
+                status_text: status.canonical_reason().unwrap_or_default().to_string(),
+                http_version: format!("{:?}", version),
+                content: Content {
+                    size: body.len(),
+                    mime_type: mime_type(&headers),
+                    text: redaction.body_log.redact(body),
+                },
+                headers,
+                cookies: vec![],
+                redirect_url: String::new(),
+                headers_size: -1,
+                body_size: body.len(),
+            },
+            cache: serde_json::Map::new(),
+            timings: Timings {
+                send: 0.0,
+                wait: elapsed,
+                receive: 0.0,
+            },
+        };
+        self.entries().push(entry);
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, Vec<Entry>> {
+        self.entries.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+/// Rebuild a response whose body has been read for the recording.
+pub(crate) fn rebuild(
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Vec<u8>,
+) -> http::Response<Vec<u8>> {
+    let mut response = http::Response::new(body);
+    *response.status_mut() = status;
+    *response.version_mut() = version;
+    *response.headers_mut() = headers;
+    response
+}
+
+fn har_headers(headers: &HeaderMap, redaction: &Redaction) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| NameValue {
+            name: name.to_string(),
+            value: if redaction.header(name) {
+                REDACTED_HEADER.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            },
+        })
+        .collect()
+}
+
+fn mime_type(headers: &[NameValue]) -> String {
+    headers
+        .iter()
+        .find(|header| header.name == CONTENT_TYPE.as_str())
+        .map(|header| header.value.clone())
+        .unwrap_or_default()
+}
+
+fn query_string(url: &str) -> Vec<NameValue> {
+    url::Url::parse(url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| NameValue {
+                    name: name.into_owned(),
+                    value: value.into_owned(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    started_date_time: String,
+    time: f64,
+    request: Request,
+    response: Response,
+    cache: serde_json::Map<String, serde_json::Value>,
+    timings: Timings,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    method: String,
+    url: String,
+    http_version: String,
+    headers: Vec<NameValue>,
+    query_string: Vec<NameValue>,
+    cookies: Vec<NameValue>,
+    headers_size: i64,
+    body_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_data: Option<Content>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    status: u16,
+    status_text: String,
+    http_version: String,
+    headers: Vec<NameValue>,
+    cookies: Vec<NameValue>,
+    content: Content,
+    #[serde(rename = "redirectURL")]
+    redirect_url: String,
+    headers_size: i64,
+    body_size: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Content {
+    size: usize,
+    mime_type: String,
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Timings {
+    send: f64,
+    wait: f64,
+    receive: f64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_capture_redacts_credentials() {
+        let capture = Capture::new();
+        let body_log = BodyLog::new(Default::default(), &["*token*".to_string()]);
+        let redaction = Redaction {
+            body_log: &body_log,
+            signer_header: Some("X-Signature"),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert("x-signature", HeaderValue::from_static("secret-signature"));
+        headers.insert("x-api-token", HeaderValue::from_static("secret-token"));
+        let pending = capture.begin(
+            &Method::POST,
+            "https://api.example.com/api/v1/namespaces/?x=1",
+            &headers,
+            Some(br#"{"name":"prod","token":"secret"}"#),
+            &redaction,
+        );
+        capture.finish(
+            pending,
+            StatusCode::CREATED,
+            Version::HTTP_11,
+            &HeaderMap::new(),
+            br#"{"id":1}"#,
+            &redaction,
+        );
+
+        assert_eq!(capture.len(), 1);
+        let har = capture.to_har().unwrap();
+        let entry = &har["log"]["entries"][0];
+        assert_eq!(har["log"]["version"], "1.2");
+        assert_eq!(entry["request"]["method"], "POST");
+        assert_eq!(
+            entry["request"]["headers"][0],
+            serde_json::json!({"name": "authorization", "value": REDACTED_HEADER})
+        );
+        assert_eq!(entry["request"]["queryString"][0]["value"], "1");
+        assert_eq!(entry["request"]["postData"]["mimeType"], "application/json");
+        assert_eq!(
+            entry["request"]["postData"]["text"],
+            r#"{"name":"prod","token":"[redacted]"}"#
+        );
+        assert_eq!(entry["response"]["status"], 201);
+        assert_eq!(entry["response"]["content"]["text"], r#"{"id":1}"#);
+        assert!(!har.to_string().contains("secret"));
+    }
+}
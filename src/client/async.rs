@@ -1,13 +1,37 @@
-use log::debug;
-use serde_urlencoded;
+use futures::{Stream, StreamExt};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Serialize;
+use std::collections::VecDeque;
 use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 
-use super::{Authenticated, ClientCore, IntoResourceFilter, Unauthenticated, UrlParams};
+use super::builder::ClientBuilder;
+use super::bulk::DEFAULT_BULK_CONCURRENCY;
+use super::cache::SessionCache;
+use super::compression::Compression;
+use super::core::{self, one_or_err, ByteBudget, Page, PageTrail, Paged, RawPage, RequestPlan};
+use super::curl::CurlHook;
+use super::events::{self, Event, EventParser, MAX_RECONNECT_ATTEMPTS};
+use super::failover::Failover;
+use super::numbers::NumberMode;
+use super::retry::{generate_key, RetryPolicy, IDEMPOTENCY_KEY_HEADER};
+use super::scheduler::{Priority, Scheduler};
+use super::task_scope::TaskScope;
+use super::{
+    Authenticated, ClientCore, CreateMode, CreateOptions, IntoResourceFilter, Unauthenticated,
+    UrlParams,
+};
+use crate::capture::{self, Capture};
 use crate::endpoints::Endpoint;
 use crate::errors::ApiError;
+use crate::logging::{self, debug, warn, BodyLog, AUTH, EVENTS, HTTP};
 use crate::resources::ApiResource;
-use crate::resources::{Class, User};
-use crate::types::{BaseUrl, Credentials, FilterOperator, Token};
+use crate::resources::{Class, Object, ObjectRaw, User};
+use crate::types::{
+    BaseUrl, Credentials, Cursor, CursorPage, FilterOperator, RequestSigner, Token,
+    NEXT_CURSOR_HEADER, TOTAL_COUNT_HEADER,
+};
 use crate::QueryFilter;
 
 #[derive(Debug, Clone)]
@@ -15,61 +39,346 @@ pub struct Client<S> {
     http_client: reqwest::Client,
     base_url: BaseUrl,
     state: S,
+    timeout: Option<Duration>,
+    signer: Option<RequestSigner>,
+    cache: Option<SessionCache>,
+    retry: Option<RetryPolicy>,
+    body_log: BodyLog,
+    curl: Option<CurlHook>,
+    capture: Option<Capture>,
+    scheduler: Option<Scheduler>,
+    priority: Priority,
+    case_insensitive_names: bool,
+    failover: Option<Failover>,
+    /// Query parameters appended to every request, see `raw_query`.
+    extra_query: Vec<(String, String)>,
+    max_response_bytes: Option<u64>,
+    compression: Option<Compression>,
+    deadline: Option<Instant>,
+    number_mode: NumberMode,
 }
 
 impl<S> ClientCore for Client<S> {
-    fn build_url(&self, endpoint: &Endpoint, _url_params: UrlParams) -> String {
-        format!(
-            "{}{}",
-            self.base_url.with_trailing_slash(),
-            endpoint.trim_start_matches('/')
-        )
+    fn build_url(&self, endpoint: &Endpoint, url_params: UrlParams) -> String {
+        core::build_url(&self.base_url, endpoint, &url_params)
+    }
+}
+
+/// Fail with the error of an unsuccessful response, reading its body no
+/// further than `limit` bytes.
+async fn check_success(
+    response: reqwest::Response,
+    limit: Option<u64>,
+) -> Result<reqwest::Response, ApiError> {
+    if !response.status().is_success() {
+        let (status, url) = (response.status(), response.url().clone());
+        let body = read_limited(response, &mut ByteBudget::new(limit)).await?;
+        let body = String::from_utf8_lossy(&body).into_owned();
+        return Err(core::response_error(status, &url, body));
+    }
+    Ok(response)
+}
+
+/// Read the body of a response, no further than what is left of `budget`.
+async fn read_limited(
+    mut response: reqwest::Response,
+    budget: &mut ByteBudget,
+) -> Result<Vec<u8>, ApiError> {
+    if budget.left().is_none() {
+        return Ok(response.bytes().await?.to_vec());
+    }
+    let url = response.url().to_string();
+    budget.check(response.content_length(), &url)?;
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        budget.spend(chunk.len() as u64, &url)?;
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Read the body of a response, no further than what is left of `budget`,
+/// and prepare its numbers as `number_mode` says.
+async fn read_body(
+    response: reqwest::Response,
+    budget: &mut ByteBudget,
+    number_mode: NumberMode,
+) -> Result<String, ApiError> {
+    let body = read_limited(response, budget).await?;
+    Ok(number_mode.apply(String::from_utf8_lossy(&body).into_owned()))
+}
+
+async fn read_page<P: Paged>(
+    response: reqwest::Response,
+    body_log: &BodyLog,
+    budget: &mut ByteBudget,
+    number_mode: NumberMode,
+) -> Result<P, ApiError> {
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let link = header(reqwest::header::LINK.as_str());
+    let cursor = header(NEXT_CURSOR_HEADER);
+    let total = header(TOTAL_COUNT_HEADER);
+    let number_mode = match P::RAW {
+        true => NumberMode::Number,
+        false => number_mode,
+    };
+    let text = read_body(response, budget, number_mode).await?;
+    logging::body(body_log, "Response", text.as_bytes());
+    P::parse(text, link.as_deref(), cursor.as_deref(), total.as_deref())
+}
+
+impl<S> Client<S> {
+    /// The client in `state`, keeping all other settings.
+    fn with_state<T>(self, state: T) -> Client<T> {
+        Client {
+            http_client: self.http_client,
+            base_url: self.base_url,
+            state,
+            timeout: self.timeout,
+            signer: self.signer,
+            cache: self.cache,
+            retry: self.retry,
+            body_log: self.body_log,
+            curl: self.curl,
+            capture: self.capture,
+            scheduler: self.scheduler,
+            priority: self.priority,
+            case_insensitive_names: self.case_insensitive_names,
+            failover: self.failover,
+            extra_query: self.extra_query,
+            max_response_bytes: self.max_response_bytes,
+            compression: self.compression,
+            deadline: self.deadline,
+            number_mode: self.number_mode,
+        }
+    }
+
+    /// The budget of `max_response_bytes` for a new call.
+    fn budget(&self) -> ByteBudget {
+        ByteBudget::new(self.max_response_bytes)
+    }
+
+    /// A view of the client whose requests, including retries and further
+    /// pages, all finish by `deadline`.
+    ///
+    /// Requests get at most the time left as their timeout, and none is sent
+    /// or retried once it has passed. Such requests fail with
+    /// `ApiError::DeadlineExceeded`, which holds the items fetched so far
+    /// when a listing is cut short.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Whether the deadline passes within `delay` from now.
+    fn past_deadline(&self, delay: Duration) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() + delay >= deadline)
+    }
+
+    /// Report requests that timed out at the deadline as having exceeded it.
+    fn deadline_error(&self, err: ApiError) -> ApiError {
+        match &err {
+            ApiError::Http(http) if http.is_timeout() && self.past_deadline(Duration::ZERO) => {
+                ApiError::DeadlineExceeded { partial: vec![] }
+            }
+            _ => err,
+        }
+    }
+
+    /// Sign every request with the given signer.
+    pub fn with_signer(mut self, signer: RequestSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Share resolved resources through the given cache, see [`SessionCache`].
+    pub fn with_cache(mut self, cache: SessionCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn cache(&self) -> Option<&SessionCache> {
+        self.cache.as_ref()
+    }
+
+    /// The priority of the requests made through this client, see [`Priority`].
+    ///
+    /// Clients cloned from this one share its scheduler, so a clone with a
+    /// lower priority can be handed to background work.
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Match names case-insensitively in all name lookups made through
+    /// this client, such as `add_filter_name_exact` and `select_by_name`.
+    pub fn with_case_insensitive_names(mut self, enabled: bool) -> Self {
+        self.case_insensitive_names = enabled;
+        self
+    }
+
+    /// Record all requests and responses, see [`Capture`].
+    pub fn with_capture(mut self, capture: Capture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Open a connection to the server ahead of the first request.
+    ///
+    /// Sends a HEAD request to the base URL so that the connection, including
+    /// the TLS handshake, is pooled for the requests that follow. The response
+    /// status is ignored. Idle connections are dropped from the pool after a
+    /// while, so call this shortly before the connection is needed.
+    pub async fn preconnect(&self) -> Result<(), ApiError> {
+        let url = self.base_url.with_trailing_slash();
+        let now = std::time::Instant::now();
+        let response = self
+            .prepare(reqwest::Method::HEAD, &url, None)?
+            .send()
+            .await?;
+        debug!(
+            target: HTTP,
+            "Preconnected to {} in {:?} (HTTP {})",
+            url,
+            now.elapsed(),
+            response.status()
+        );
+        Ok(())
+    }
+
+    fn prepare(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<reqwest::RequestBuilder, ApiError> {
+        let url = match &self.failover {
+            Some(failover) => failover.rewrite(url),
+            None => url.to_string(),
+        };
+        let url = core::append_query(&url, &self.extra_query);
+        let compressed = match (&self.compression, &body) {
+            (Some(compression), Some(body)) => compression.encode(&url, body),
+            _ => None,
+        };
+        let gzip = compressed.is_some();
+        let plan = RequestPlan::new(method, url, compressed.or(body));
+        let mut request = self.http_client.request(plan.method.clone(), &plan.url);
+        if let Some((header, signature)) = plan.signature(self.signer.as_ref())? {
+            request = request.header(header, signature);
+        }
+        if let Some(body) = plan.body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body);
+        }
+        if gzip {
+            request = request.header(reqwest::header::CONTENT_ENCODING, "gzip");
+        }
+        if let Some(timeout) = core::deadline_timeout(self.timeout, self.deadline, Instant::now())?
+        {
+            request = request.timeout(timeout);
+        }
+        Ok(request)
     }
 }
 
 impl Client<Unauthenticated> {
     pub fn new(base_url: BaseUrl) -> Self {
-        Client {
-            http_client: reqwest::Client::new(),
-            base_url,
-            state: Unauthenticated,
+        Self::builder(base_url)
+            .build()
+            .expect("Failed to build HTTP client")
+    }
+
+    pub fn builder(base_url: BaseUrl) -> ClientBuilder<Self> {
+        ClientBuilder::new(base_url)
+    }
+}
+
+impl ClientBuilder<Client<Unauthenticated>> {
+    pub fn build(self) -> Result<Client<Unauthenticated>, ApiError> {
+        let mut builder = reqwest::Client::builder();
+        #[cfg(unix)]
+        if let Some(socket) = self.base_url.unix_socket() {
+            builder = builder.unix_socket(socket);
+        }
+        for (domain, addr) in &self.resolve {
+            builder = builder.resolve(domain, *addr);
         }
+
+        let failover = self.failover();
+        Ok(Client {
+            http_client: builder.build()?,
+            base_url: self.base_url,
+            state: Unauthenticated,
+            timeout: self.timeout,
+            signer: self.signer,
+            cache: None,
+            retry: self.retry,
+            body_log: BodyLog::new(self.log_policy, &self.redact),
+            curl: self.curl,
+            capture: None,
+            scheduler: self.max_in_flight.map(Scheduler::new),
+            priority: Priority::default(),
+            case_insensitive_names: false,
+            failover,
+            extra_query: vec![],
+            max_response_bytes: self.max_response_bytes,
+            compression: self.compression,
+            deadline: None,
+            number_mode: self.number_mode,
+        })
     }
 }
 
 impl Client<Unauthenticated> {
     pub async fn login(self, credentials: Credentials) -> Result<Client<Authenticated>, ApiError> {
-        let token: Token = self
-            .http_client
-            .post(&self.build_url(&Endpoint::Login, UrlParams::default()))
-            .json(&credentials)
+        debug!(target: AUTH, "Logging in to {}", self.base_url.as_str());
+        let response = self
+            .prepare(
+                reqwest::Method::POST,
+                &self.build_url(&Endpoint::Login, UrlParams::default()),
+                Some(serde_json::to_vec(&credentials)?),
+            )?
             .send()
-            .await?
-            .error_for_status()?
-            .json()
             .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let url = response.url().clone();
+            return Err(core::login_error(
+                status,
+                &url,
+                &headers,
+                response.text().await?,
+            ));
+        }
+        let token: Token = response.json().await?;
 
-        Ok(Client {
-            http_client: self.http_client,
-            base_url: self.base_url,
-            state: Authenticated { token: token.token },
-        })
+        let state = Authenticated::new(token.into_string()).with_username(credentials.username());
+        Ok(self.with_state(state))
     }
 
     pub async fn login_with_token(self, token: Token) -> Result<Client<Authenticated>, ApiError> {
         let status = self
-            .http_client
-            .get(self.build_url(&Endpoint::LoginWithToken, UrlParams::default()))
-            .header("Authorization", format!("Bearer {}", token.token))
+            .prepare(
+                reqwest::Method::GET,
+                &self.build_url(&Endpoint::LoginWithToken, UrlParams::default()),
+                None,
+            )?
+            .header("Authorization", format!("Bearer {}", token.as_str()))
             .send()
             .await?;
 
         if status.status().is_success() {
-            Ok(Client {
-                http_client: self.http_client,
-                base_url: self.base_url,
-                state: Authenticated { token: token.token },
-            })
+            Ok(self.with_state(Authenticated::new(token.into_string())))
         } else {
             Err(ApiError::InvalidToken)
         }
@@ -77,67 +386,377 @@ impl Client<Unauthenticated> {
 }
 
 impl Client<Authenticated> {
-    pub fn get_token(&self) -> &str {
-        &self.state.token
+    /// Create a client and log in with the given credentials.
+    pub async fn new_authenticated(
+        base_url: BaseUrl,
+        credentials: Credentials,
+    ) -> Result<Client<Authenticated>, ApiError> {
+        Client::new(base_url).login(credentials).await
     }
 
-    pub async fn get<R: ApiResource>(
-        &self,
-        resource: R,
-        params: R::GetParams,
-    ) -> Result<Vec<R::GetOutput>, ApiError> {
-        let endpoint = resource.endpoint();
-        let url = self.build_url(&endpoint, UrlParams::default());
+    /// Create a client using an existing token, which is validated first.
+    pub async fn new_with_token(
+        base_url: BaseUrl,
+        token: &str,
+    ) -> Result<Client<Authenticated>, ApiError> {
+        Client::new(base_url)
+            .login_with_token(Token::new(token.to_string()))
+            .await
+    }
 
-        let query = serde_urlencoded::to_string(&params)?;
-        let url = if !query.is_empty() {
-            format!("{}?{}", url, query)
-        } else {
-            url
-        };
+    pub fn get_token(&self) -> String {
+        self.state.token()
+    }
 
-        debug!("GET {}", url);
+    /// Spawn a task that revalidates the token every `interval`.
+    ///
+    /// If the token is no longer valid and credentials are given, the task logs
+    /// in again and the new token is used by this client and all its clones.
+    /// Abort the returned handle to stop the task.
+    pub fn spawn_token_keepalive(
+        &self,
+        interval: Duration,
+        credentials: Option<Credentials>,
+    ) -> tokio::task::JoinHandle<()> {
+        let client = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match client.refresh_token(credentials.as_ref()).await {
+                    Ok(()) => debug!(target: AUTH, "Token keep-alive succeeded"),
+                    Err(err) => warn!(target: AUTH, "Token keep-alive failed: {}", err),
+                }
+            }
+        })
+    }
 
+    async fn refresh_token(&self, credentials: Option<&Credentials>) -> Result<(), ApiError> {
         let response = self
-            .http_client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.state.token))
+            .authorized(
+                reqwest::Method::GET,
+                &self.build_url(&Endpoint::LoginWithToken, UrlParams::default()),
+                None,
+            )?
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
+        if response.status().is_success() {
+            return Ok(());
+        }
 
-        debug!("Response: {:?}", response);
-        let obj: Vec<R::GetOutput> = response.json().await?;
-        Ok(obj)
+        let credentials = credentials.ok_or(ApiError::InvalidToken)?;
+        let response = self
+            .prepare(
+                reqwest::Method::POST,
+                &self.build_url(&Endpoint::Login, UrlParams::default()),
+                Some(serde_json::to_vec(credentials)?),
+            )?
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let url = response.url().clone();
+            return Err(core::login_error(
+                status,
+                &url,
+                &headers,
+                response.text().await?,
+            ));
+        }
+        let token: Token = response.json().await?;
+        self.state.set_token(token.into_string());
+        Ok(())
     }
 
-    pub async fn search<R: ApiResource>(
+    fn authorized(
         &self,
+        method: reqwest::Method,
+        url: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<reqwest::RequestBuilder, ApiError> {
+        Ok(self
+            .prepare(method, url, body)?
+            .header("Authorization", format!("Bearer {}", self.state.token())))
+    }
+
+    pub async fn request<R: ApiResource, T: Serialize + std::fmt::Debug, U: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
         resource: R,
-        params: Vec<QueryFilter>,
-    ) -> Result<Vec<R::GetOutput>, ApiError> {
-        let endpoint = resource.endpoint();
-        let params = serde_urlencoded::to_string(&params)?;
+        url_params: UrlParams,
+        query_params: Vec<QueryFilter>,
+        post_params: T,
+    ) -> Result<Option<U>, ApiError> {
+        self.request_with_endpoint(
+            method,
+            &resource.endpoint(),
+            url_params,
+            query_params,
+            post_params,
+        )
+        .await
+    }
 
-        let url = format!(
-            "{}?{}",
-            self.build_url(&endpoint, UrlParams::default()),
-            params
-        );
+    pub async fn request_with_endpoint<T: Serialize + std::fmt::Debug, U: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        endpoint: &Endpoint,
+        url_params: UrlParams,
+        query_params: Vec<QueryFilter>,
+        post_params: T,
+    ) -> Result<Option<U>, ApiError> {
+        endpoint.validate(&method, &url_params)?;
+        let plan = RequestPlan::build(
+            &self.base_url,
+            method,
+            endpoint,
+            &url_params,
+            query_params,
+            post_params,
+        )?;
+        self.execute_plan(plan).await
+    }
 
-        debug!("GET {}", url);
+    /// Send a planned request, following next links for GET requests.
+    async fn execute_plan<U: DeserializeOwned>(
+        &self,
+        plan: RequestPlan,
+    ) -> Result<Option<U>, ApiError> {
+        if plan.method != reqwest::Method::GET {
+            let method = plan.method.clone();
+            let number_mode = self.number_mode_for(&plan);
+            if plan.numbers {
+                number_mode.check_write(plan.body.as_deref())?;
+            }
+            let response = self.send_plan(plan).await?;
+            let text = read_body(response, &mut self.budget(), number_mode).await?;
+            logging::body(&self.body_log, "Response", text.as_bytes());
+            return core::decode_response(&method, text);
+        }
 
-        let response = self
-            .http_client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.state.token))
-            .send()
-            .await?
-            .error_for_status()?;
+        self.paginate::<Page>(plan).await?.decode()
+    }
+
+    /// Send a planned GET request and follow the next links of the pages it
+    /// yields, joining them into one.
+    ///
+    /// Links back to a page fetched before end the pagination, and running
+    /// out of time fails with the items read so far.
+    async fn paginate<P: Paged>(&self, plan: RequestPlan) -> Result<P, ApiError> {
+        let mut trail = PageTrail::new(&plan.url);
+        let mut budget = self.budget();
+        let number_mode = self.number_mode_for(&plan);
+        let next_plan = plan.clone();
+        let response = self.send_plan(plan).await?;
+        let mut page: P = read_page(response, &self.body_log, &mut budget, number_mode).await?;
+        while let Some(next) = trail.follow(page.next()) {
+            debug!(target: HTTP, "GET {} (next page)", next);
+            let next_page = async {
+                let response = self.send_plan(next_plan.next_page(next.clone())).await?;
+                read_page(response, &self.body_log, &mut budget, number_mode).await
+            };
+            match next_page.await.map_err(|err| self.deadline_error(err)) {
+                Ok(next_page) => page.append(next_page)?,
+                Err(ApiError::DeadlineExceeded { .. }) => {
+                    return Err(ApiError::DeadlineExceeded {
+                        partial: page.into_partial(),
+                    })
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(page)
+    }
+
+    /// Send a planned GET request and return the single page it yields.
+    async fn fetch_page(&self, plan: RequestPlan) -> Result<Page, ApiError> {
+        let number_mode = self.number_mode_for(&plan);
+        let response = self.send_plan(plan).await?;
+        read_page(response, &self.body_log, &mut self.budget(), number_mode).await
+    }
+
+    /// How numbers in the response to `plan` are decoded, as `number_mode`
+    /// says for requests on objects and as sent otherwise.
+    fn number_mode_for(&self, plan: &RequestPlan) -> NumberMode {
+        match plan.numbers {
+            true => self.number_mode,
+            false => NumberMode::Number,
+        }
+    }
+
+    async fn send_plan(&self, plan: RequestPlan) -> Result<reqwest::Response, ApiError> {
+        let Some(retry) = &self.retry else {
+            let key = plan.idempotency_key.clone();
+            return self
+                .send_once(plan, key.as_deref())
+                .await
+                .map_err(|err| self.deadline_error(err));
+        };
+        let key = retry.idempotency_key(&plan);
+        let mut attempt = 0;
+        loop {
+            match self.send_once(plan.clone(), key.as_deref()).await {
+                Err(err) if retry.should_retry(&plan, key.is_some(), attempt, &err) => {
+                    let delay = retry.delay(attempt);
+                    if self.past_deadline(delay) {
+                        return Err(ApiError::DeadlineExceeded { partial: vec![] });
+                    }
+                    logging::retry(&plan.method, &plan.url, attempt, delay, &err);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result.map_err(|err| self.deadline_error(err)),
+            }
+        }
+    }
+
+    /// Send a request, failing over to the next replica on connection
+    /// failures until every replica has been tried.
+    async fn send_once(
+        &self,
+        plan: RequestPlan,
+        key: Option<&str>,
+    ) -> Result<reqwest::Response, ApiError> {
+        let Some(failover) = &self.failover else {
+            return self.send_attempt(plan, key).await;
+        };
+        let mut replicas_left = failover.len();
+        loop {
+            match self.send_attempt(plan.clone(), key).await {
+                Err(ApiError::Http(err))
+                    if err.is_connect()
+                        && replicas_left > 1
+                        && err.url().is_some_and(|url| failover.failed(url.as_str())) =>
+                {
+                    replicas_left -= 1;
+                }
+                result => return result,
+            }
+        }
+    }
 
-        debug!("Response: {:?}", response);
-        let obj: Vec<R::GetOutput> = response.json().await?;
-        Ok(obj)
+    async fn send_attempt(
+        &self,
+        plan: RequestPlan,
+        key: Option<&str>,
+    ) -> Result<reqwest::Response, ApiError> {
+        let _permit = match &self.scheduler {
+            Some(scheduler) => Some(scheduler.acquire_async(self.priority).await),
+            None => None,
+        };
+        // Kept to send again uncompressed, should the server refuse gzip.
+        let fallback = self
+            .compression
+            .as_ref()
+            .filter(|compression| compression.applies(plan.body.as_deref()))
+            .map(|compression| (compression, plan.clone()));
+        let (response, gzip) = self.transmit(plan, key).await?;
+        match fallback {
+            Some((compression, plan))
+                if gzip && response.status() == reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE =>
+            {
+                compression.reject(response.url().as_str());
+                let (response, _) = self.transmit(plan, key).await?;
+                check_success(response, self.max_response_bytes).await
+            }
+            _ => check_success(response, self.max_response_bytes).await,
+        }
+    }
+
+    /// Send a request once, returning the response and whether the body was
+    /// sent compressed.
+    async fn transmit(
+        &self,
+        plan: RequestPlan,
+        key: Option<&str>,
+    ) -> Result<(reqwest::Response, bool), ApiError> {
+        let now = std::time::Instant::now();
+        if let Some(body) = &plan.body {
+            logging::body(&self.body_log, "Request", body);
+        }
+        if let Some(hook) = &self.curl {
+            hook.emit(&plan, key);
+        }
+        let mut request = self.authorized(plan.method.clone(), &plan.url, plan.body)?;
+        if let Some(timeout) = plan.timeout {
+            let timeout = core::deadline_timeout(Some(timeout), self.deadline, Instant::now())?;
+            request = request.timeout(timeout.unwrap_or_default());
+        }
+        if let Some(key) = key {
+            request = request.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        for (name, value) in plan.headers {
+            request = request.header(name, value);
+        }
+        let request = request.build()?;
+        let gzip = request
+            .headers()
+            .contains_key(reqwest::header::CONTENT_ENCODING);
+        let response = match &self.capture {
+            None => self.http_client.execute(request).await?,
+            Some(capture) => {
+                let redaction = capture::Redaction {
+                    body_log: &self.body_log,
+                    signer_header: self.signer.as_ref().map(RequestSigner::header),
+                };
+                let pending = capture.begin(
+                    request.method(),
+                    request.url().as_str(),
+                    request.headers(),
+                    request.body().and_then(|body| body.as_bytes()),
+                    &redaction,
+                );
+                let response = self.http_client.execute(request).await?;
+                let (status, version) = (response.status(), response.version());
+                let headers = response.headers().clone();
+                let body = read_limited(response, &mut self.budget()).await?;
+                capture.finish(pending, status, version, &headers, &body, &redaction);
+                capture::rebuild(status, version, headers, body).into()
+            }
+        };
+        logging::response(
+            &plan.method,
+            &plan.url,
+            response.status(),
+            response.content_length(),
+            now.elapsed(),
+        );
+        Ok((response, gzip))
+    }
+
+    /// Fetch the resources matching the given parameters.
+    ///
+    /// Every parameter that is set becomes a filter, using its operator.
+    pub async fn get<R: ApiResource>(
+        &self,
+        resource: R,
+        params: R::GetParams,
+    ) -> Result<Vec<R::GetOutput>, ApiError> {
+        self.request(
+            reqwest::Method::GET,
+            resource,
+            UrlParams::default(),
+            params.into_resource_filter(),
+            (),
+        )
+        .await
+        .map(Option::unwrap_or_default)
+    }
+
+    pub async fn search<R: ApiResource>(
+        &self,
+        resource: R,
+        params: Vec<QueryFilter>,
+    ) -> Result<Vec<R::GetOutput>, ApiError> {
+        self.request(
+            reqwest::Method::GET,
+            resource,
+            UrlParams::default(),
+            params,
+            (),
+        )
+        .await
+        .map(Option::unwrap_or_default)
     }
 
     pub async fn post<R: ApiResource>(
@@ -145,23 +764,39 @@ impl Client<Authenticated> {
         resource: R,
         params: R::PostParams,
     ) -> Result<R::PostOutput, ApiError> {
-        let endpoint = resource.endpoint();
-        let url = self.build_url(&endpoint, UrlParams::default());
-
-        debug!("POST {} with {:?}", &url, params);
-
-        let response = self
-            .http_client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.state.token))
-            .json(&params)
-            .send()
-            .await?
-            .error_for_status()?;
+        self.request(
+            reqwest::Method::POST,
+            resource,
+            UrlParams::default(),
+            vec![],
+            params,
+        )
+        .await
+        .and_then(|opt| opt.ok_or(ApiError::EmptyResult("POST returned empty result".into())))
+    }
 
-        debug!("Response: {:?}", response);
-        let obj: R::PostOutput = response.json().await?;
-        Ok(obj)
+    /// Like `post`, sending `key` as the idempotency key of the request.
+    pub(crate) async fn post_with_key<R: ApiResource>(
+        &self,
+        resource: R,
+        params: R::PostParams,
+        key: String,
+    ) -> Result<R::PostOutput, ApiError> {
+        let url_params = UrlParams::default();
+        let endpoint = resource.endpoint();
+        endpoint.validate(&reqwest::Method::POST, &url_params)?;
+        let plan = RequestPlan::build(
+            &self.base_url,
+            reqwest::Method::POST,
+            &endpoint,
+            &url_params,
+            vec![],
+            params,
+        )?
+        .with_idempotency_key(key);
+        self.execute_plan(plan)
+            .await
+            .and_then(|opt| opt.ok_or(ApiError::EmptyResult("POST returned empty result".into())))
     }
 
     pub async fn patch<R: ApiResource>(
@@ -170,46 +805,126 @@ impl Client<Authenticated> {
         id: i32,
         params: R::PatchParams,
     ) -> Result<R::PatchOutput, ApiError> {
-        let endpoint = resource.endpoint();
-        let url = format!("{}/{}", self.build_url(&endpoint, UrlParams::default()), id);
-
-        debug!("PATCH {} with {:?}", &url, params);
+        let url_params = vec![("patch_id".into(), id.to_string().into())];
+        self.request(reqwest::Method::PATCH, resource, url_params, vec![], params)
+            .await
+            .and_then(|opt| opt.ok_or(ApiError::EmptyResult("PATCH returned empty result".into())))
+    }
 
-        let response = self
-            .http_client
-            .patch(&url)
-            .header("Authorization", format!("Bearer {}", self.state.token))
-            .json(&params)
-            .send()
-            .await?
-            .error_for_status()?;
+    /// Delete the resource with the given id.
+    ///
+    /// Both empty responses, such as 204 No Content, and responses with a
+    /// body count as success; the body is ignored.
+    pub async fn delete<R: ApiResource>(&self, resource: R, id: i32) -> Result<(), ApiError> {
+        let url_params = vec![("delete_id".into(), id.to_string().into())];
+        self.request::<_, _, IgnoredAny>(reqwest::Method::DELETE, resource, url_params, vec![], ())
+            .await
+            .map(|_| ())
+    }
 
-        debug!("Response: {:?}", response);
-        let obj: R::PatchOutput = response.json().await?;
-        Ok(obj)
+    /// Run the operations spawned by `f` together and wait for all of them.
+    ///
+    /// At most as many operations run at a time as the client lets requests
+    /// be in flight, see `ClientBuilder::max_in_flight`, or
+    /// `DEFAULT_BULK_CONCURRENCY` without a limit. Results are in the order
+    /// the operations were spawned; see [`TaskScope`] for stopping at the
+    /// first failure.
+    pub async fn task_scope<'a, T, F>(&self, f: F) -> Vec<Result<T, ApiError>>
+    where
+        F: FnOnce(&mut TaskScope<'a, T>),
+    {
+        let mut scope = TaskScope::new();
+        f(&mut scope);
+        let concurrency = self
+            .scheduler
+            .as_ref()
+            .map_or(DEFAULT_BULK_CONCURRENCY, Scheduler::max_in_flight);
+        scope.run(concurrency).await
     }
 
-    pub async fn delete<R: ApiResource>(
+    /// Subscribe to server-sent events matching the filters.
+    ///
+    /// Dropped streams are reconnected, resuming after the last event seen,
+    /// with a growing delay while the server cannot be reached or answers
+    /// that it is unavailable. The stream ends with an error if it cannot be
+    /// reached again, or at once on other errors such as refused credentials.
+    pub fn subscribe(
         &self,
-        resource: R,
-        id: i32,
-    ) -> Result<R::DeleteOutput, ApiError> {
-        let endpoint = resource.endpoint();
-        let url = format!("{}/{}", self.build_url(&endpoint, UrlParams::default()), id);
+        filters: Vec<QueryFilter>,
+    ) -> impl Stream<Item = Result<Event, ApiError>> {
+        use crate::types::IntoQueryTuples;
+        let url = self.build_url(&Endpoint::Events, UrlParams::default());
+        let query = filters.into_query_string();
+        let url = if !query.is_empty() {
+            format!("{}?{}", url, query)
+        } else {
+            url
+        };
 
-        debug!("DELETE {}", &url);
+        let state = Subscription {
+            client: self.clone(),
+            url,
+            parser: EventParser::default(),
+            response: None,
+            pending: VecDeque::new(),
+            failures: 0,
+            finished: false,
+        };
 
-        let response = self
-            .http_client
-            .delete(&url)
-            .header("Authorization", format!("Bearer {}", self.state.token))
-            .send()
-            .await?
-            .error_for_status()?;
+        futures::stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(event) = state.pending.pop_front() {
+                    return Some((Ok(event), state));
+                }
+                if state.finished {
+                    return None;
+                }
+
+                let delay = events::reconnect_delay(state.parser.retry(), state.failures);
+                match state.response.as_mut() {
+                    Some(response) => match response.chunk().await {
+                        Ok(Some(chunk)) => {
+                            let events = state.parser.feed(&chunk);
+                            if !events.is_empty() {
+                                state.failures = 0;
+                            }
+                            state.pending.extend(events);
+                        }
+                        Ok(None) | Err(_) => {
+                            debug!(target: EVENTS, "Event stream interrupted, reconnecting");
+                            state.response = None;
+                            state.parser.reset();
+                            tokio::time::sleep(delay).await;
+                        }
+                    },
+                    None => match state.connect().await {
+                        Ok(response) => state.response = Some(response),
+                        Err(err)
+                            if events::should_reconnect(&err)
+                                && state.failures < MAX_RECONNECT_ATTEMPTS =>
+                        {
+                            debug!(target: EVENTS, "Event stream connection failed: {}", err);
+                            state.failures += 1;
+                            tokio::time::sleep(delay).await;
+                        }
+                        Err(err) => {
+                            state.finished = true;
+                            return Some((Err(events::stream_error(err)), state));
+                        }
+                    },
+                }
+            }
+        })
+    }
 
-        debug!("Response: {:?}", response);
-        let obj: R::DeleteOutput = response.json().await?;
-        Ok(obj)
+    /// Like [`Client::subscribe`], but the stream ends when the token is cancelled.
+    pub fn subscribe_until(
+        &self,
+        filters: Vec<QueryFilter>,
+        token: CancellationToken,
+    ) -> impl Stream<Item = Result<Event, ApiError>> {
+        self.subscribe(filters)
+            .take_until(async move { token.cancelled().await })
     }
 
     pub fn users(&self) -> Resource<User> {
@@ -219,32 +934,239 @@ impl Client<Authenticated> {
     pub fn classes(&self) -> Resource<Class> {
         Resource::new(self.clone())
     }
+
+    /// Search the objects of a class.
+    ///
+    /// Objects are only searched through the async client, not changed.
+    pub fn find_objects(&self, class_id: i32) -> FilterBuilder<Object> {
+        FilterBuilder::new(
+            self.clone(),
+            vec![("class_id".into(), class_id.to_string().into())],
+        )
+    }
+}
+
+struct Subscription {
+    client: Client<Authenticated>,
+    url: String,
+    parser: EventParser,
+    response: Option<reqwest::Response>,
+    pending: VecDeque<Event>,
+    failures: u32,
+    finished: bool,
+}
+
+impl Subscription {
+    async fn connect(&self) -> Result<reqwest::Response, ApiError> {
+        debug!(target: EVENTS, "SUBSCRIBE {}", self.url);
+        let mut request = self
+            .client
+            .authorized(reqwest::Method::GET, &self.url, None)?
+            .header("Accept", "text/event-stream");
+        if let Some(id) = self.parser.last_event_id() {
+            request = request.header("Last-Event-ID", id);
+        }
+        // The stream stays open for as long as events keep coming.
+        let mut request = request.build()?;
+        *request.timeout_mut() =
+            core::deadline_timeout(None, self.client.deadline, Instant::now())?;
+
+        let response = self.client.http_client.execute(request).await?;
+        check_success(response, self.client.max_response_bytes).await
+    }
 }
 
 pub struct FilterBuilder<T: ApiResource> {
     client: Client<Authenticated>,
     filters: Vec<(String, FilterOperator, String)>,
+    url_params: UrlParams,
+    cancellation: Option<CancellationToken>,
+    cursor: Option<Cursor>,
+    limit: Option<usize>,
+    /// Query parameters sent with the search only, see `raw_query`.
+    query: Vec<(String, String)>,
+    timeout: Option<Duration>,
     _phantom: PhantomData<T>,
 }
 
 impl<T: ApiResource> FilterBuilder<T> {
-    fn new(client: Client<Authenticated>) -> Self {
+    fn new(client: Client<Authenticated>, url_params: UrlParams) -> Self {
         FilterBuilder {
             client,
             filters: Vec::new(),
+            url_params,
+            cancellation: None,
+            cursor: None,
+            limit: None,
+            query: vec![],
+            timeout: None,
             _phantom: PhantomData,
         }
     }
 
+    /// Start after the given cursor, as returned by `execute_page`.
+    pub fn after(mut self, cursor: Cursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// Ask the server for at most `limit` resources per page.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Abort the search with `ApiError::Cancelled` if the token is cancelled.
+    pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Pass an extra query parameter with the search, for server options
+    /// this crate does not model yet.
+    ///
+    /// Unlike filters, the parameter is sent as given, without validation.
+    pub fn raw_query(mut self, key: &str, value: &str) -> Self {
+        self.query.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Override the timeout for the search request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     pub fn add_filter<V: ToString>(mut self, field: &str, op: FilterOperator, value: V) -> Self {
         self.filters
             .push((field.to_string(), op, value.to_string()));
         self
     }
 
+    pub fn add_filter_equals<V: ToString>(self, field: &str, value: V) -> Self {
+        self.add_filter(field, FilterOperator::Equals { is_negated: false }, value)
+    }
+
+    pub fn add_filter_id<V: ToString>(self, value: V) -> Self {
+        self.add_filter_equals("id", value)
+    }
+
+    /// Filter on the name, case-insensitively if the client matches names
+    /// that way.
+    pub fn add_filter_name_exact<V: ToString>(self, value: V) -> Self {
+        if self.client.case_insensitive_names {
+            return self.add_filter_name_ci(value);
+        }
+        self.add_filter_equals(T::NAME_FIELD, value)
+    }
+
+    pub fn add_filter_name_ci<V: ToString>(self, value: V) -> Self {
+        self.add_filter(
+            T::NAME_FIELD,
+            FilterOperator::IEquals { is_negated: false },
+            value,
+        )
+    }
+
+    pub async fn execute_expecting_single_result(self) -> Result<T::GetOutput, ApiError> {
+        let filters = T::build_params(self.filters.clone());
+        one_or_err(self.execute().await?, &filters)
+    }
+
     pub async fn execute(self) -> Result<Vec<T::GetOutput>, ApiError> {
-        let params = T::build_params(self.filters);
-        self.client.search::<T>(T::default(), params).await
+        let plan = self.plan()?;
+        let search = async { Ok(self.client.execute_plan(plan).await?.unwrap_or_default()) };
+        self.until_cancelled(search).await
+    }
+
+    /// Fetch a single page using keyset pagination.
+    ///
+    /// Iterate by passing `next` of the returned page to `after` until it is
+    /// `None`. Requires server support for cursors; servers without it return
+    /// everything in one page without a next cursor.
+    pub async fn execute_page(self) -> Result<CursorPage<T::GetOutput>, ApiError> {
+        let plan = self.plan()?;
+        let fetch = async {
+            let page = self.client.fetch_page(plan).await?;
+            let next = page.next_cursor.clone().map(Cursor::new);
+            let items = page.decode()?.unwrap_or_default();
+            Ok(CursorPage { items, next })
+        };
+        self.until_cancelled(fetch).await
+    }
+
+    /// Fetch page `page` of `per_page` resources using offset pagination,
+    /// counting pages from 1.
+    ///
+    /// Unlike `execute`, the result tells whether another page follows and,
+    /// where the server reports it, how many resources match in total.
+    pub async fn execute_paged(
+        mut self,
+        page: usize,
+        per_page: usize,
+    ) -> Result<crate::types::Page<T::GetOutput>, ApiError> {
+        let page = page.max(1);
+        self.limit = Some(per_page);
+        let plan = self
+            .plan()?
+            .with_query(&[("offset", ((page - 1) * per_page).to_string())]);
+        let fetch = async {
+            let fetched = self.client.fetch_page(plan).await?;
+            let more = fetched.next.is_some() || fetched.next_cursor.is_some();
+            let total = fetched.count;
+            let items = fetched.decode()?.unwrap_or_default();
+            Ok(crate::types::Page::new(items, total, page, per_page, more))
+        };
+        self.until_cancelled(fetch).await
+    }
+
+    async fn until_cancelled<R>(
+        &self,
+        future: impl std::future::Future<Output = Result<R, ApiError>>,
+    ) -> Result<R, ApiError> {
+        match &self.cancellation {
+            Some(token) => token
+                .run_until_cancelled(future)
+                .await
+                .unwrap_or(Err(ApiError::Cancelled)),
+            None => future.await,
+        }
+    }
+
+    fn plan(&self) -> Result<RequestPlan, ApiError> {
+        let params = T::build_params(self.filters.clone());
+        core::validate_filters::<T>(&params)?;
+        let plan = RequestPlan::build(
+            &self.client.base_url,
+            reqwest::Method::GET,
+            &T::default().endpoint(),
+            &self.url_params,
+            params,
+            (),
+        )?;
+        Ok(plan
+            .with_query(&core::cursor_query(self.cursor.as_ref(), self.limit))
+            .with_extra_query(self.query.clone())
+            .with_timeout(self.timeout))
+    }
+}
+
+impl FilterBuilder<Object> {
+    /// Execute the query, keeping the data of the objects as the JSON text
+    /// the server sent, see [`ObjectRaw`].
+    ///
+    /// Like `execute`, every page is fetched. The bodies are read as sent,
+    /// whatever the `NumberMode` of the client.
+    pub async fn execute_raw(self) -> Result<Vec<ObjectRaw>, ApiError> {
+        let plan = self.plan()?;
+        let search = async {
+            Ok(self
+                .client
+                .paginate::<RawPage<ObjectRaw>>(plan)
+                .await?
+                .items)
+        };
+        self.until_cancelled(search).await
     }
 }
 
@@ -261,8 +1183,24 @@ impl<T: ApiResource> Resource<T> {
         }
     }
 
+    /// Pass an extra query parameter with every request made through this
+    /// resource, including searches started from it, for server options
+    /// this crate does not model yet.
+    pub fn raw_query(mut self, key: &str, value: &str) -> Self {
+        self.client
+            .extra_query
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Override the timeout for requests made through this resource.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client.timeout = Some(timeout);
+        self
+    }
+
     pub fn find(&self) -> FilterBuilder<T> {
-        FilterBuilder::new(self.client.clone())
+        FilterBuilder::new(self.client.clone(), UrlParams::default())
     }
 
     pub async fn filter<F: IntoResourceFilter<T>>(
@@ -270,11 +1208,62 @@ impl<T: ApiResource> Resource<T> {
         filter: F,
     ) -> Result<Vec<T::GetOutput>, ApiError> {
         let params = filter.into_resource_filter();
+        core::validate_filters::<T>(&params)?;
         self.client.search::<T>(T::default(), params).await
     }
 
     pub async fn create(&self, params: T::PostParams) -> Result<T::PostOutput, ApiError> {
-        self.client.post::<T>(T::default(), params).await
+        let name = core::post_name::<T>(&params);
+        let created = self.client.post::<T>(T::default(), params).await?;
+        self.forget_missing(name);
+        Ok(created)
+    }
+
+    /// Drop what the session cache remembers of `name` being missing, after
+    /// a resource by that name was created.
+    fn forget_missing(&self, name: Option<String>) {
+        if let (Some(cache), Some(name)) = (self.client.cache(), name) {
+            cache.remove_missing(&name);
+        }
+    }
+
+    /// Like `create`, with `options` deciding what happens if the resource
+    /// exists and which idempotency key to send. A `CreateMode` alone works
+    /// too, with a generated key.
+    pub async fn create_with(
+        &self,
+        params: T::PostParams,
+        options: impl Into<CreateOptions>,
+    ) -> Result<T::PostOutput, ApiError>
+    where
+        T::PostOutput: From<T::GetOutput>,
+    {
+        let options = options.into();
+        let key = options.idempotency_key.unwrap_or_else(generate_key);
+        let created_name = core::post_name::<T>(&params);
+        let name = match options.mode {
+            CreateMode::Fail => None,
+            CreateMode::ReturnExistingOnConflict => created_name.clone(),
+        };
+        let created = self
+            .client
+            .post_with_key::<T>(T::default(), params, key)
+            .await;
+        if created.is_ok() {
+            self.forget_missing(created_name);
+        }
+        match (created, name) {
+            (Err(err), Some(name)) if err.status() == Some(reqwest::StatusCode::CONFLICT) => {
+                debug!(target: HTTP, "{} already exists, fetching it", name);
+                let existing = self
+                    .find()
+                    .add_filter_name_exact(name)
+                    .execute_expecting_single_result()
+                    .await?;
+                Ok(existing.into())
+            }
+            (result, _) => result,
+        }
     }
 
     pub async fn update(
@@ -285,7 +1274,210 @@ impl<T: ApiResource> Resource<T> {
         self.client.patch::<T>(T::default(), id, params).await
     }
 
-    pub async fn delete(&self, id: i32) -> Result<T::DeleteOutput, ApiError> {
+    pub async fn delete(&self, id: i32) -> Result<(), ApiError> {
         self.client.delete::<T>(T::default(), id).await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::testing::{Reply, TestServer};
+    use std::str::FromStr;
+
+    fn authenticated_client() -> Client<Authenticated> {
+        Client {
+            http_client: reqwest::Client::new(),
+            base_url: BaseUrl::from_str("http://127.0.0.1:9").unwrap(),
+            state: Authenticated::new("token".to_string()),
+            timeout: None,
+            signer: None,
+            cache: None,
+            retry: None,
+            body_log: BodyLog::default(),
+            curl: None,
+            capture: None,
+            scheduler: None,
+            priority: Priority::default(),
+            case_insensitive_names: false,
+            failover: None,
+            extra_query: vec![],
+            max_response_bytes: None,
+            compression: None,
+            deadline: None,
+            number_mode: NumberMode::default(),
+        }
+    }
+
+    #[test]
+    fn test_build_url_with_url_params() {
+        let client = authenticated_client();
+        assert_eq!(
+            client.build_url(&Endpoint::Objects, vec![("class_id".into(), "42".into())]),
+            "http://127.0.0.1:9/api/v1/classes/42/"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_search() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let result = authenticated_client()
+            .users()
+            .find()
+            .cancel_on(token)
+            .execute()
+            .await;
+        assert!(matches!(result, Err(ApiError::Cancelled)));
+    }
+
+    #[test]
+    fn test_filter_shortcuts() {
+        let plan = authenticated_client()
+            .users()
+            .find()
+            .add_filter_id(3)
+            .add_filter_name_exact("alice")
+            .plan()
+            .unwrap();
+        assert_eq!(
+            plan.url,
+            "http://127.0.0.1:9/api/v1/iam/users/?id__equals=3&username__equals=alice"
+        );
+
+        let plan = authenticated_client()
+            .with_case_insensitive_names(true)
+            .users()
+            .find()
+            .add_filter_name_exact("Alice")
+            .plan()
+            .unwrap();
+        assert_eq!(
+            plan.url,
+            "http://127.0.0.1:9/api/v1/iam/users/?username__iequals=Alice"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_forgets_missing_name() {
+        use crate::resources::{Class, ClassPost};
+
+        let server = TestServer::start(|_| {
+            Reply::json(
+                201,
+                r#"{"id": 4, "name": "x", "description": "", "json_schema": null,
+                "validate_schema": false, "namespace": {"id": 1, "name": "dc", "description": "",
+                "created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00"},
+                "created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00"}"#,
+            )
+        });
+        let cache = SessionCache::with_negative_ttl(Duration::from_secs(60));
+        let client = Client {
+            base_url: server.base_url(),
+            cache: Some(cache.clone()),
+            ..authenticated_client()
+        };
+        cache.insert_missing::<Class>("x", "Class not found");
+        cache.insert_missing::<Class>("y", "Class not found");
+
+        client
+            .classes()
+            .create(ClassPost {
+                name: "x".to_string(),
+                description: String::new(),
+                namespace_id: 1,
+                json_schema: None,
+                validate_schema: None,
+            })
+            .await
+            .unwrap();
+        assert!(cache.get_missing::<Class>("x").is_none());
+        assert!(cache.get_missing::<Class>("y").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_reports_login_errors() {
+        let server = TestServer::start_rejecting_tokens(|_| Reply::json(401, "{}"));
+        let client = Client {
+            base_url: server.base_url(),
+            ..authenticated_client()
+        };
+        let credentials = Credentials::new("user".into(), "wrong".into());
+        let result = client.refresh_token(Some(&credentials)).await;
+        assert!(
+            matches!(result, Err(ApiError::BadCredentials)),
+            "{:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_raw_reads_pages_as_sent() {
+        let server = TestServer::start(|request| {
+            let (id, next) = match request.path.as_str() {
+                "/api/v1/classes/5/" => (1, "/api/v1/classes/5/?page=2"),
+                _ => (2, "/api/v1/classes/5/"),
+            };
+            let body = format!(
+                r#"[{{"id": {id}, "name": "o{id}", "namespace_id": 1, "hubuum_class_id": 5,
+                "description": "", "data": {{"mass": 1.50}}, "created_at": "2024-01-01T00:00:00",
+                "updated_at": "2024-01-01T00:00:00"}}]"#
+            );
+            Reply::json(200, body).with_header("Link", &format!("<{}>; rel=\"next\"", next))
+        });
+        let client = Client::builder(server.base_url())
+            .number_mode(NumberMode::String)
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .await
+            .unwrap();
+
+        let objects = client.find_objects(5).execute_raw().await.unwrap();
+        let data: Vec<&str> = objects
+            .iter()
+            .map(|object| object.data.as_deref().unwrap().get())
+            .collect();
+        assert_eq!(data, vec![r#"{"mass": 1.50}"#; 2]);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_uncompressed_fallback_within_one_slot() {
+        let server = TestServer::start(|request| match request.header("Content-Encoding") {
+            Some("gzip") => Reply::json(415, r#"{"message": "gzip not supported"}"#),
+            _ => Reply::json(201, request.body_text()),
+        });
+        let client = Client::builder(server.base_url())
+            .max_in_flight(1)
+            .compress_requests(16)
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .await
+            .unwrap();
+
+        let created = tokio::time::timeout(
+            Duration::from_secs(10),
+            client.request_with_endpoint::<_, serde_json::Value>(
+                reqwest::Method::POST,
+                &Endpoint::Classes,
+                UrlParams::default(),
+                vec![],
+                serde_json::json!({ "name": "x".repeat(32) }),
+            ),
+        )
+        .await
+        .expect("the fallback deadlocked")
+        .unwrap()
+        .unwrap();
+        assert_eq!(created["name"], "x".repeat(32));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|request| request.method == "POST"));
+        assert_eq!(requests[0].header("Content-Encoding"), Some("gzip"));
+        assert_eq!(requests[1].header("Content-Encoding"), None);
+    }
+}
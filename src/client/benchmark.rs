@@ -0,0 +1,126 @@
+//! Measuring the latency of endpoints from the client's point of view.
+use std::time::Duration;
+
+/// The latency distribution of one endpoint, from `SyncClient::benchmark`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyProfile {
+    pub path: &'static str,
+    /// The number of successful requests the latencies are based on.
+    pub samples: usize,
+    pub errors: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencyProfile {
+    pub(crate) fn new(path: &'static str, mut samples: Vec<Duration>, errors: usize) -> Self {
+        samples.sort();
+        let total: Duration = samples.iter().sum();
+        LatencyProfile {
+            path,
+            samples: samples.len(),
+            errors,
+            min: samples.first().copied().unwrap_or_default(),
+            mean: total.checked_div(samples.len() as u32).unwrap_or_default(),
+            p50: percentile(&samples, 50),
+            p90: percentile(&samples, 90),
+            p99: percentile(&samples, 99),
+            max: samples.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+impl std::fmt::Display for LatencyProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: n={} errors={} min={:?} p50={:?} p90={:?} p99={:?} max={:?}",
+            self.path, self.samples, self.errors, self.min, self.p50, self.p90, self.p99, self.max
+        )
+    }
+}
+
+/// Where the time of a request to the server goes, from `SyncClient::ping_detailed`.
+///
+/// Phases that could not be measured are `None`: DNS and connect for unix
+/// sockets, and the TLS handshake, which the HTTP backend does not expose.
+/// The connection used to time DNS and connect is separate from the one the
+/// request is sent on, so `first_byte` includes setting up that connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PingReport {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+    /// The time until the response headers arrived.
+    pub first_byte: Duration,
+    /// The time until the response body was read.
+    pub total: Duration,
+    pub status: u16,
+}
+
+impl std::fmt::Display for PingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let phase = |d: Option<Duration>| d.map_or("n/a".to_string(), |d| format!("{:?}", d));
+        write!(
+            f,
+            "HTTP {}: dns={} connect={} tls={} first_byte={:?} total={:?}",
+            self.status,
+            phase(self.dns),
+            phase(self.connect),
+            phase(self.tls),
+            self.first_byte,
+            self.total
+        )
+    }
+}
+
+/// The nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let samples = (1..=100).rev().map(Duration::from_millis).collect();
+        let profile = LatencyProfile::new("/api/v1/classes/", samples, 2);
+        assert_eq!(profile.samples, 100);
+        assert_eq!(profile.errors, 2);
+        assert_eq!(profile.min, Duration::from_millis(1));
+        assert_eq!(profile.p50, Duration::from_millis(50));
+        assert_eq!(profile.p90, Duration::from_millis(90));
+        assert_eq!(profile.p99, Duration::from_millis(99));
+        assert_eq!(profile.max, Duration::from_millis(100));
+        assert_eq!(profile.mean, Duration::from_micros(50500));
+
+        let empty = LatencyProfile::new("/", vec![], 3);
+        assert_eq!((empty.samples, empty.p99), (0, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_ping_report_display() {
+        let report = PingReport {
+            dns: Some(Duration::from_millis(2)),
+            connect: Some(Duration::from_millis(5)),
+            tls: None,
+            first_byte: Duration::from_millis(40),
+            total: Duration::from_millis(42),
+            status: 404,
+        };
+        assert_eq!(
+            report.to_string(),
+            "HTTP 404: dns=2ms connect=5ms tls=n/a first_byte=40ms total=42ms"
+        );
+    }
+}
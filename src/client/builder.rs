@@ -0,0 +1,218 @@
+use std::marker::PhantomData;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use super::compression::Compression;
+use super::curl::CurlHook;
+use super::failover::{Failover, DEFAULT_FAILOVER_RECHECK};
+use super::numbers::NumberMode;
+use super::retry::RetryPolicy;
+use crate::errors::ApiError;
+use crate::logging::LogPolicy;
+use crate::types::{BaseUrl, RequestSigner};
+
+/// Builder for clients that need more than a base URL.
+///
+/// Created via `SyncClient::builder` or `AsyncClient::builder`, and turned
+/// into an unauthenticated client of the same flavor with `build()`.
+#[derive(Debug, Clone)]
+pub struct ClientBuilder<C> {
+    pub(crate) base_url: BaseUrl,
+    pub(crate) resolve: Vec<(String, SocketAddr)>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) signer: Option<RequestSigner>,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) log_policy: LogPolicy,
+    pub(crate) redact: Vec<String>,
+    pub(crate) curl: Option<CurlHook>,
+    pub(crate) max_in_flight: Option<usize>,
+    pub(crate) replicas: Vec<BaseUrl>,
+    pub(crate) failover_recheck: Duration,
+    pub(crate) max_response_bytes: Option<u64>,
+    pub(crate) compression: Option<Compression>,
+    pub(crate) number_mode: NumberMode,
+    _client: PhantomData<C>,
+}
+
+impl<C> ClientBuilder<C> {
+    pub(crate) fn new(base_url: BaseUrl) -> Self {
+        ClientBuilder {
+            base_url,
+            resolve: Vec::new(),
+            timeout: None,
+            signer: None,
+            retry: None,
+            log_policy: LogPolicy::Full,
+            redact: Vec::new(),
+            curl: None,
+            max_in_flight: None,
+            replicas: Vec::new(),
+            failover_recheck: DEFAULT_FAILOVER_RECHECK,
+            max_response_bytes: None,
+            compression: None,
+            number_mode: NumberMode::default(),
+            _client: PhantomData,
+        }
+    }
+
+    /// Resolve `domain` to `addr` instead of using DNS.
+    pub fn resolve(mut self, domain: &str, addr: SocketAddr) -> Self {
+        self.resolve.push((domain.to_string(), addr));
+        self
+    }
+
+    /// Pin the host of the base URL to a specific IP address.
+    pub fn pin_address(self, ip: IpAddr) -> Result<Self, ApiError> {
+        let url = url::Url::parse(self.base_url.as_str())?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| ApiError::UrlNotBase(url.to_string()))?
+            .to_string();
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| ApiError::InvalidScheme(url.scheme().to_string()))?;
+        Ok(self.resolve(&host, SocketAddr::new(ip, port)))
+    }
+
+    /// Prefix all endpoint paths, for servers behind a reverse proxy at a sub-path.
+    pub fn path_prefix(mut self, prefix: &str) -> Self {
+        self.base_url = self.base_url.with_path_prefix(prefix);
+        self
+    }
+
+    /// The default timeout for requests, unless overridden per request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn signer(mut self, signer: RequestSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Whether to log request and response bodies, see [`crate::logging::BODY`].
+    ///
+    /// Disabling this keeps bodies out of the logs regardless of log levels.
+    pub fn log_bodies(self, enabled: bool) -> Self {
+        self.log_policy(if enabled {
+            LogPolicy::Full
+        } else {
+            LogPolicy::None
+        })
+    }
+
+    /// How much of request and response bodies to log.
+    pub fn log_policy(mut self, policy: LogPolicy) -> Self {
+        self.log_policy = policy;
+        self
+    }
+
+    /// Replace the values of JSON fields matching `pattern` in logged bodies.
+    ///
+    /// Patterns match field names at any depth, case insensitively, and may
+    /// use `*` as a wildcard, e.g. `password` or `*secret*`.
+    pub fn redact(mut self, pattern: &str) -> Self {
+        self.redact.push(pattern.to_string());
+        self
+    }
+
+    /// Pass an equivalent `curl` command for every request to `hook`.
+    pub fn curl(mut self, hook: CurlHook) -> Self {
+        self.curl = Some(hook);
+        self
+    }
+
+    /// Allow at most `max` requests in flight at once, admitting waiting
+    /// requests by the priority of their client, see [`super::Priority`].
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = Some(max);
+        self
+    }
+
+    /// Fail over to `replicas`, in order, when connecting to the base URL
+    /// fails.
+    ///
+    /// Requests stick to the replica they failed over to, and go back to the
+    /// base URL after the recheck interval, see `failover_recheck`.
+    pub fn replicas(mut self, replicas: Vec<BaseUrl>) -> Self {
+        self.replicas = replicas;
+        self
+    }
+
+    /// How long to stay on a replica before trying the base URL again.
+    pub fn failover_recheck(mut self, interval: Duration) -> Self {
+        self.failover_recheck = interval;
+        self
+    }
+
+    /// The failover between the base URL and the replicas, if there are any.
+    pub(crate) fn failover(&self) -> Option<Failover> {
+        if self.replicas.is_empty() {
+            return None;
+        }
+        Some(Failover::new(
+            &self.base_url,
+            &self.replicas,
+            self.failover_recheck,
+        ))
+    }
+
+    /// Refuse responses larger than `bytes` with `ApiError::ResponseTooLarge`.
+    ///
+    /// The body is read no further than the limit, so an unexpectedly large
+    /// listing fails instead of being pulled into memory. The pages of a
+    /// listing count towards the limit together, while streams and bulk
+    /// operations, which hold one page at a time, apply it to each page.
+    /// Error responses are read no further than the limit either.
+    pub fn max_response_bytes(mut self, bytes: u64) -> Self {
+        self.max_response_bytes = Some(bytes);
+        self
+    }
+
+    /// Gzip request bodies of at least `min_bytes`, as when creating objects
+    /// with large data, see `DEFAULT_COMPRESSION_THRESHOLD`.
+    ///
+    /// Bodies are signed as sent, compressed. If the server refuses a
+    /// compressed body, the request is sent again uncompressed and
+    /// compression is turned off for that server.
+    pub fn compress_requests(mut self, min_bytes: usize) -> Self {
+        self.compression = Some(Compression::new(min_bytes));
+        self
+    }
+
+    /// How to decode numbers in the data of objects, see [`NumberMode`].
+    pub fn number_mode(mut self, mode: NumberMode) -> Self {
+        self.number_mode = mode;
+        self
+    }
+
+    /// Retry requests that fail for transient reasons, see [`RetryPolicy`].
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use yare::parameterized;
+
+    #[parameterized(
+        https = { "https://api.example.com", "api.example.com", 443 },
+        http = { "http://api.example.com", "api.example.com", 80 },
+        port = { "https://api.example.com:8443/hubuum", "api.example.com", 8443 }
+    )]
+    fn test_pin_address(url: &str, host: &str, port: u16) {
+        let ip = IpAddr::from([10, 0, 0, 1]);
+        let builder = ClientBuilder::<()>::new(BaseUrl::from_str(url).unwrap())
+            .pin_address(ip)
+            .unwrap();
+        assert_eq!(
+            builder.resolve,
+            vec![(host.to_string(), SocketAddr::new(ip, port))]
+        );
+    }
+}
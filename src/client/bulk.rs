@@ -0,0 +1,226 @@
+//! Applying an operation to many resources at once.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::errors::ApiError;
+
+/// The number of requests in flight at once for bulk operations.
+pub const DEFAULT_BULK_CONCURRENCY: usize = 4;
+
+/// The outcome of a bulk operation, per resource id.
+///
+/// A failure for one resource does not stop the others, so the operation
+/// may have partially succeeded even if `failed` is not empty.
+#[derive(Debug)]
+pub struct BulkResult<T> {
+    pub succeeded: Vec<T>,
+    pub failed: Vec<(i32, ApiError)>,
+    pub skipped: Vec<i32>,
+}
+
+impl<T> Default for BulkResult<T> {
+    fn default() -> Self {
+        BulkResult {
+            succeeded: vec![],
+            failed: vec![],
+            skipped: vec![],
+        }
+    }
+}
+
+impl<T> BulkResult<T> {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// The succeeded items, or all failures combined into `ApiError::Multiple`.
+    pub fn into_result(self) -> Result<Vec<T>, ApiError> {
+        match ApiError::from_errors(self.failed.into_iter().map(|(_, err)| err)) {
+            Some(err) => Err(err),
+            None => Ok(self.succeeded),
+        }
+    }
+}
+
+/// Receives the progress of bulk operations, e.g. to drive a progress bar.
+///
+/// Implemented for closures taking the same arguments as `on_item_done`.
+pub trait ProgressSink: Send + Sync {
+    /// Called when an item is done, with the number of items done so far.
+    ///
+    /// `total` is `None` while the number of items is not known yet. Calls
+    /// may come from several threads, but `done` increases by one per call.
+    fn on_item_done(&self, done: usize, total: Option<usize>, result: Result<(), &ApiError>);
+}
+
+impl<F> ProgressSink for F
+where
+    F: Fn(usize, Option<usize>, Result<(), &ApiError>) + Send + Sync,
+{
+    fn on_item_done(&self, done: usize, total: Option<usize>, result: Result<(), &ApiError>) {
+        self(done, total, result)
+    }
+}
+
+/// Counts the items of a bulk operation and reports them to an optional sink.
+pub(crate) struct Progress<'a> {
+    sink: Option<&'a dyn ProgressSink>,
+    done: Mutex<usize>,
+    total: AtomicUsize,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(sink: Option<&'a dyn ProgressSink>, total: Option<usize>) -> Self {
+        Progress {
+            sink,
+            done: Mutex::new(0),
+            total: AtomicUsize::new(total.unwrap_or(usize::MAX)),
+        }
+    }
+
+    pub fn set_total(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Report an item as done, passing its result through.
+    pub fn track<T>(&self, result: Result<T, ApiError>) -> Result<T, ApiError> {
+        let Some(sink) = self.sink else {
+            return result;
+        };
+        // Holding the lock while reporting keeps the calls in order of `done`.
+        let mut done = self.done.lock().unwrap_or_else(|p| p.into_inner());
+        *done += 1;
+        let total = match self.total.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            total => Some(total),
+        };
+        sink.on_item_done(*done, total, result.as_ref().map(|_| ()));
+        result
+    }
+}
+
+/// Spaces out operations to stay within a budget of operations per second.
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// A limiter allowing `per_second` operations per second, or any number if zero.
+    pub fn new(per_second: u32) -> Self {
+        let interval = match per_second {
+            0 => Duration::ZERO,
+            n => Duration::from_secs(1) / n,
+        };
+        RateLimiter {
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Block until the next operation is within the budget.
+    pub fn acquire(&self) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let wait = {
+            let mut next = self.next.lock().unwrap_or_else(|p| p.into_inner());
+            let now = Instant::now();
+            let slot = (*next).max(now);
+            *next = slot + self.interval;
+            slot - now
+        };
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+/// Run `f` over `items` on at most `concurrency` threads.
+///
+/// The results are in the order of `items`.
+pub(crate) fn run<I, O, F>(items: Vec<I>, concurrency: usize, f: F) -> Vec<O>
+where
+    I: Send,
+    O: Send,
+    F: Fn(I) -> O + Sync,
+{
+    let total = items.len();
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new(Vec::with_capacity(total));
+    let next = || queue.lock().unwrap_or_else(|p| p.into_inner()).next();
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, total.max(1)) {
+            scope.spawn(|| {
+                while let Some((index, item)) = next() {
+                    let result = f(item);
+                    results
+                        .lock()
+                        .unwrap_or_else(|p| p.into_inner())
+                        .push((index, result));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap_or_else(|p| p.into_inner());
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_run_is_bounded_and_ordered() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let results = run((0..20).collect(), 3, |n: i32| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            running.fetch_sub(1, Ordering::SeqCst);
+            n * 2
+        });
+
+        assert_eq!(results, (0..20).map(|n| n * 2).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert!(run(Vec::<i32>::new(), 0, |n| n).is_empty());
+    }
+
+    #[test]
+    fn test_progress() {
+        let seen = Mutex::new(vec![]);
+        let sink = |done: usize, total: Option<usize>, result: Result<(), &ApiError>| {
+            seen.lock().unwrap().push((done, total, result.is_ok()));
+        };
+        let progress = Progress::new(Some(&sink), None);
+        let _ = progress.track(Ok(1));
+        progress.set_total(2);
+        let _ = progress.track::<()>(Err(ApiError::Cancelled));
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![(1, None, true), (2, Some(2), false)]
+        );
+        assert!(Progress::new(None, None).track(Ok(())).is_ok());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(100);
+        let start = Instant::now();
+        run((0..6).collect(), 3, |_: i32| limiter.acquire());
+        // The first operation is free, the remaining five are 10ms apart.
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let unlimited = RateLimiter::new(0);
+        let start = Instant::now();
+        (0..1000).for_each(|_| unlimited.acquire());
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,398 @@
+//! Session wide identity map for resolved resources.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use hubuum_client::client::SessionCache;
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let cache = SessionCache::new();
+//! let client = SyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//!     .login(Credentials::new("user".into(), "pass".into()))?
+//!     .with_cache(cache.clone());
+//! for object in client.objects(1).find().execute()? {
+//!     // Every object in the same namespace shares one lookup.
+//!     println!("{}", object.namespace_id.resolve(&client)?.name);
+//! }
+//! println!("hit rate: {:.0}%", cache.stats().hit_rate() * 100.0);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Lookups by name that find nothing can be cached for a while too, so a
+//! loop asking for a name that does not exist fails without a request each
+//! time:
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use hubuum_client::client::SessionCache;
+//! # use std::str::FromStr;
+//! # use std::time::Duration;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = SyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//!     .login(Credentials::new("user".into(), "pass".into()))?
+//!     .with_cache(SessionCache::with_negative_ttl(Duration::from_secs(5)));
+//! for _ in 0..100 {
+//!     // Only the first lookup reaches the server.
+//!     assert!(client.namespace("no-such-namespace").is_err());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::GetID;
+use crate::errors::ApiError;
+use crate::logging::{trace, CACHE};
+
+type Entries = HashMap<(TypeId, i32), Arc<dyn Any + Send + Sync>>;
+type Names = HashMap<(TypeId, String), i32>;
+/// The message of the failed lookup and when to forget it, by type and name.
+type Missing = HashMap<(TypeId, String), (String, Instant)>;
+
+/// Interns resources resolved through `Ref`s by type and id.
+///
+/// Clones share the same cache, so one cache can be attached to several
+/// clients. Entries are never invalidated on their own; call `clear` when
+/// the cached resources may have gone stale. Names found missing are the
+/// exception, they expire after the TTL given to `with_negative_ttl`.
+#[derive(Debug, Clone, Default)]
+pub struct SessionCache {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: Mutex<Entries>,
+    names: Mutex<Names>,
+    missing: Mutex<Missing>,
+    negative_ttl: Option<Duration>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Counters describing how well the cache is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+impl CacheStats {
+    /// The share of lookups served from the cache, between 0 and 1.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl SessionCache {
+    pub fn new() -> Self {
+        SessionCache::default()
+    }
+
+    /// A cache that also remembers names whose lookup found nothing, for
+    /// `ttl`.
+    ///
+    /// Keep the TTL short: a resource created under such a name elsewhere is
+    /// not found through the cache until the entry expires. Inserting the
+    /// name into the cache forgets the miss at once.
+    pub fn with_negative_ttl(ttl: Duration) -> Self {
+        SessionCache {
+            inner: Arc::new(Inner {
+                negative_ttl: Some(ttl),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Look up a resource, counting the lookup as a hit or a miss.
+    pub fn get<T: Clone + Send + Sync + 'static>(&self, id: i32) -> Option<T> {
+        let found = self
+            .entries()
+            .get(&(TypeId::of::<T>(), id))
+            .and_then(|entry| entry.downcast_ref::<T>())
+            .cloned();
+        let (counter, outcome) = match found {
+            Some(_) => (&self.inner.hits, "hit"),
+            None => (&self.inner.misses, "miss"),
+        };
+        trace!(target: CACHE, "{} {} {}", std::any::type_name::<T>(), id, outcome);
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    pub fn insert<T: Clone + Send + Sync + 'static>(&self, id: i32, resource: T) {
+        self.entries()
+            .insert((TypeId::of::<T>(), id), Arc::new(resource));
+    }
+
+    /// Look up a resource by a name it was inserted under with
+    /// `insert_named`, counting the lookup as a hit or a miss.
+    pub fn get_named<T: Clone + Send + Sync + 'static>(&self, name: &str) -> Option<T> {
+        let id = self
+            .names()
+            .get(&(TypeId::of::<T>(), name.to_string()))
+            .copied();
+        match id {
+            Some(id) => self.get(id),
+            None => {
+                trace!(target: CACHE, "{} '{}' miss", std::any::type_name::<T>(), name);
+                self.inner.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Insert a resource that can be looked up by id as well as by `name`.
+    ///
+    /// Names only need to be unique per type, so scoped names should carry
+    /// their scope, as in `"<namespace id>/<class name>"`.
+    pub fn insert_named<T: Clone + Send + Sync + 'static>(&self, name: &str, id: i32, resource: T) {
+        self.names()
+            .insert((TypeId::of::<T>(), name.to_string()), id);
+        self.missing()
+            .remove(&(TypeId::of::<T>(), name.to_string()));
+        self.insert(id, resource);
+    }
+
+    /// The error of an earlier lookup of `name` that found nothing, if it
+    /// has not expired yet. Counts as a hit if there is one.
+    pub fn get_missing<T: 'static>(&self, name: &str) -> Option<ApiError> {
+        self.get_missing_at::<T>(name, Instant::now())
+    }
+
+    /// Remember that looking up `name` found nothing, failing with `message`.
+    /// Does nothing unless the cache was made with `with_negative_ttl`.
+    pub fn insert_missing<T: 'static>(&self, name: &str, message: &str) {
+        self.insert_missing_at::<T>(name, message, Instant::now());
+    }
+
+    /// Forget that looking up `name` found nothing, as when a resource by
+    /// that name has just been created. Applies to every type, and to scoped
+    /// names ending in `/<name>` as well.
+    pub fn remove_missing(&self, name: &str) {
+        let scoped = format!("/{}", name);
+        self.missing()
+            .retain(|(_, key), _| key != name && !key.ends_with(&scoped));
+    }
+
+    fn get_missing_at<T: 'static>(&self, name: &str, now: Instant) -> Option<ApiError> {
+        let mut missing = self.missing();
+        let key = (TypeId::of::<T>(), name.to_string());
+        let (message, expires) = missing.get(&key)?;
+        if *expires <= now {
+            missing.remove(&key);
+            return None;
+        }
+        trace!(target: CACHE, "{} '{}' known missing", std::any::type_name::<T>(), name);
+        self.inner.hits.fetch_add(1, Ordering::Relaxed);
+        Some(ApiError::EmptyResult(message.clone()))
+    }
+
+    fn insert_missing_at<T: 'static>(&self, name: &str, message: &str, now: Instant) {
+        if let Some(ttl) = self.inner.negative_ttl {
+            self.missing().insert(
+                (TypeId::of::<T>(), name.to_string()),
+                (message.to_string(), now + ttl),
+            );
+        }
+    }
+
+    /// Drop all cached resources and reset the statistics.
+    pub fn clear(&self) {
+        self.entries().clear();
+        self.names().clear();
+        self.missing().clear();
+        self.inner.hits.store(0, Ordering::Relaxed);
+        self.inner.misses.store(0, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+            entries: self.entries().len(),
+        }
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, Entries> {
+        // A panic while holding the lock cannot leave the map inconsistent.
+        self.inner
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn names(&self) -> std::sync::MutexGuard<'_, Names> {
+        self.inner
+            .names
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn missing(&self) -> std::sync::MutexGuard<'_, Missing> {
+        self.inner
+            .missing
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Look up a resource by `name` through the cache, if there is one.
+///
+/// Names found, or found missing, by an earlier lookup are answered from the
+/// cache. Otherwise `lookup` runs, and what it finds is cached, as is a
+/// failure to find anything.
+pub(crate) fn resolve_named<T, F>(
+    cache: Option<&SessionCache>,
+    name: &str,
+    lookup: F,
+) -> Result<T, ApiError>
+where
+    T: GetID + Clone + Send + Sync + 'static,
+    F: FnOnce() -> Result<T, ApiError>,
+{
+    let Some(cache) = cache else {
+        return lookup();
+    };
+    if let Some(err) = cache.get_missing::<T>(name) {
+        return Err(err);
+    }
+    if let Some(resource) = cache.get_named(name) {
+        return Ok(resource);
+    }
+    match lookup() {
+        Ok(resource) => {
+            cache.insert_named(name, resource.id(), resource.clone());
+            Ok(resource)
+        }
+        Err(ApiError::EmptyResult(message)) => {
+            cache.insert_missing::<T>(name, &message);
+            Err(ApiError::EmptyResult(message))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::{Class, Namespace};
+
+    #[test]
+    fn test_cache_by_type_and_id() {
+        let cache = SessionCache::new();
+        let namespace = Namespace {
+            id: 1,
+            name: "prod".to_string(),
+            ..Default::default()
+        };
+        cache.insert(1, namespace.clone());
+
+        assert_eq!(cache.get::<Namespace>(1), Some(namespace));
+        assert_eq!(cache.get::<Class>(1), None);
+        assert_eq!(cache.get::<Namespace>(2), None);
+
+        let stats = cache.clone().stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 1));
+        assert!((stats.hit_rate() - 1.0 / 3.0).abs() < f64::EPSILON);
+
+        cache.clear();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn test_cache_by_name() {
+        let cache = SessionCache::new();
+        let namespace = Namespace {
+            id: 1,
+            name: "prod".to_string(),
+            ..Default::default()
+        };
+        cache.insert_named("prod", 1, namespace.clone());
+
+        assert_eq!(
+            cache.get_named::<Namespace>("prod"),
+            Some(namespace.clone())
+        );
+        assert_eq!(cache.get::<Namespace>(1), Some(namespace));
+        assert_eq!(cache.get_named::<Class>("prod"), None);
+        assert_eq!(cache.get_named::<Namespace>("dev"), None);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 2, 1));
+        cache.clear();
+        assert_eq!(cache.get_named::<Namespace>("prod"), None);
+    }
+
+    #[test]
+    fn test_negative_entries_expire() {
+        let cache = SessionCache::with_negative_ttl(Duration::from_secs(5));
+        let now = Instant::now();
+        cache.insert_missing_at::<Namespace>("dev", "Namespace not found", now);
+
+        let err = cache.get_missing_at::<Namespace>("dev", now + Duration::from_secs(4));
+        assert_eq!(
+            err.unwrap().to_string(),
+            "Unexpected empty result: Namespace not found"
+        );
+        assert!(cache.get_missing_at::<Class>("dev", now).is_none());
+        assert!(cache
+            .get_missing_at::<Namespace>("dev", now + Duration::from_secs(5))
+            .is_none());
+        // Expired entries are dropped, not just skipped.
+        assert!(cache.get_missing_at::<Namespace>("dev", now).is_none());
+
+        cache.insert_missing_at::<Class>("1/web", "Class not found", now);
+        cache.insert_missing_at::<Class>("1/webhooks", "Class not found", now);
+        cache.remove_missing("web");
+        assert!(cache.get_missing_at::<Class>("1/web", now).is_none());
+        assert!(cache.get_missing_at::<Class>("1/webhooks", now).is_some());
+
+        // Without a TTL, misses are not remembered.
+        let cache = SessionCache::new();
+        cache.insert_missing::<Namespace>("dev", "Namespace not found");
+        assert!(cache.get_missing::<Namespace>("dev").is_none());
+    }
+
+    #[test]
+    fn test_resolve_named() {
+        let cache = SessionCache::with_negative_ttl(Duration::from_secs(60));
+        let lookups = std::cell::Cell::new(0);
+        let missing = || {
+            lookups.set(lookups.get() + 1);
+            Err::<Namespace, _>(ApiError::EmptyResult("Namespace not found".into()))
+        };
+        assert!(resolve_named(Some(&cache), "dev", missing).is_err());
+        assert!(resolve_named(Some(&cache), "dev", missing).is_err());
+        assert_eq!(lookups.get(), 1);
+
+        // Finding the name replaces the negative entry.
+        let dev = Namespace {
+            id: 2,
+            name: "dev".to_string(),
+            ..Default::default()
+        };
+        cache.insert_named("dev", 2, dev.clone());
+        let found = resolve_named(Some(&cache), "dev", missing).unwrap();
+        assert_eq!((found, lookups.get()), (dev, 1));
+
+        // Other errors are not cached.
+        let failing = || {
+            lookups.set(lookups.get() + 1);
+            Err::<Namespace, _>(ApiError::InvalidToken)
+        };
+        assert!(resolve_named(Some(&cache), "prod", failing).is_err());
+        assert!(resolve_named(Some(&cache), "prod", failing).is_err());
+        assert_eq!(lookups.get(), 3);
+    }
+}
@@ -0,0 +1,118 @@
+//! Compressing large request bodies.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = SyncClient::builder(BaseUrl::from_str("https://api.example.com")?)
+//!     .compress_requests(hubuum_client::client::DEFAULT_COMPRESSION_THRESHOLD)
+//!     .build()?
+//!     .login(Credentials::new("user".into(), "pass".into()))?;
+//! # Ok(())
+//! # }
+//! ```
+use flate2::write::GzEncoder;
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use crate::logging::{warn, HTTP};
+
+/// The body size from which requests are compressed by default, below which
+/// compression costs more time than it saves.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 64 * 1024;
+
+/// Gzip compression of request bodies of at least `min_bytes`.
+///
+/// Servers that do not accept compressed bodies answer with 415 Unsupported
+/// Media Type. The request is then sent again uncompressed, and compression
+/// stays off for that server, for the client and its clones. Other servers,
+/// as the other replicas of a failover setup, keep getting compressed bodies.
+#[derive(Debug, Clone)]
+pub(crate) struct Compression {
+    min_bytes: usize,
+    /// The origins of the servers that refused compressed bodies.
+    rejected: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Compression {
+    pub fn new(min_bytes: usize) -> Self {
+        Compression {
+            min_bytes,
+            rejected: Arc::default(),
+        }
+    }
+
+    /// Whether `body` is large enough to be compressed.
+    pub fn applies(&self, body: Option<&[u8]>) -> bool {
+        body.is_some_and(|body| body.len() >= self.min_bytes)
+    }
+
+    /// The gzipped body, if the request to `url` is sent compressed.
+    pub fn encode(&self, url: &str, body: &[u8]) -> Option<Vec<u8>> {
+        if !self.applies(Some(body)) || self.rejected().contains(&origin(url)) {
+            return None;
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(body).ok()?;
+        encoder.finish().ok()
+    }
+
+    /// Record that the server at `url` refused a compressed body.
+    pub fn reject(&self, url: &str) {
+        let origin = origin(url);
+        if self.rejected().insert(origin.clone()) {
+            warn!(
+                target: HTTP,
+                "{} does not accept compressed request bodies, sending them uncompressed", origin
+            );
+        }
+    }
+
+    fn rejected(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.rejected
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The scheme, host and port of `url`.
+fn origin(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(parsed) => parsed.origin().ascii_serialization(),
+        Err(_) => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    const PRIMARY: &str = "https://primary.example.com/api/v1/classes/1/";
+    const REPLICA: &str = "https://replica.example.com/api/v1/classes/1/";
+
+    #[test]
+    fn test_compression() {
+        let compression = Compression::new(16);
+        assert!(compression.encode(PRIMARY, b"short").is_none());
+        assert!(!compression.applies(None));
+
+        let body = br#"{"data": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"}"#;
+        let encoded = compression.encode(PRIMARY, body).unwrap();
+        assert!(encoded.len() < body.len());
+        let mut decoded = Vec::new();
+        GzDecoder::new(encoded.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+
+        // Refusals only turn compression off for the server refusing it.
+        compression
+            .clone()
+            .reject("https://primary.example.com/api/v1/namespaces/");
+        assert!(compression.encode(PRIMARY, body).is_none());
+        assert!(compression.encode(REPLICA, body).is_some());
+    }
+}
@@ -0,0 +1,1401 @@
+//! Transport independent parts of the clients.
+//!
+//! Both clients turn a request into a [`RequestPlan`] here, send it with
+//! their own HTTP client, and decode the response here. Behavior that does
+//! not depend on blocking or async I/O belongs in this module so that the two
+//! clients do not drift apart.
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use super::{GetID, UrlParams};
+use crate::errors::ApiError;
+use crate::logging::{debug, error, HTTP};
+use crate::resources::ApiResource;
+use crate::types::{Cursor, IntoQueryTuples, QueryFilter, RequestSigner};
+use crate::BaseUrl;
+use crate::Endpoint;
+
+/// A request ready to be sent by either client.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RequestPlan {
+    pub method: reqwest::Method,
+    pub url: String,
+    pub body: Option<Vec<u8>>,
+    /// Headers sent along with those every request carries.
+    pub headers: Vec<(&'static str, String)>,
+    /// The idempotency key chosen by the caller, which makes a POST safe to
+    /// retry. See `RetryPolicy::idempotency_keys` for generated keys.
+    pub idempotency_key: Option<String>,
+    /// Whether the request is on objects, whose data numbers are decoded as
+    /// the `NumberMode` of the client says.
+    pub numbers: bool,
+    /// Query parameters kept on every page of the listing, see
+    /// `FilterBuilder::raw_query`.
+    pub query: Vec<(String, String)>,
+    /// The timeout of the request, overriding the one of the client.
+    pub timeout: Option<Duration>,
+}
+
+impl RequestPlan {
+    pub fn new(method: reqwest::Method, url: String, body: Option<Vec<u8>>) -> Self {
+        RequestPlan {
+            method,
+            url,
+            body,
+            headers: vec![],
+            idempotency_key: None,
+            numbers: false,
+            query: vec![],
+            timeout: None,
+        }
+    }
+
+    /// A GET of the page at `url` of the listing this plan fetches.
+    pub fn next_page(&self, url: String) -> Self {
+        RequestPlan {
+            numbers: self.numbers,
+            query: self.query.clone(),
+            timeout: self.timeout,
+            ..RequestPlan::new(reqwest::Method::GET, append_query(&url, &self.query), None)
+        }
+    }
+
+    /// Send `query` along with the request and the pages following it.
+    pub fn with_extra_query(mut self, query: Vec<(String, String)>) -> Self {
+        self.url = append_query(&self.url, &query);
+        self.query = query;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Leave the numbers of the response as sent, whatever the `NumberMode`
+    /// of the client, as for responses decoded into `ObjectRaw`.
+    pub fn raw(mut self) -> Self {
+        self.numbers = false;
+        self
+    }
+
+    pub fn with_idempotency_key(mut self, key: String) -> Self {
+        self.idempotency_key = Some(key);
+        self
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Plan a request against an endpoint.
+    ///
+    /// The id of the resource for PATCH, PUT and DELETE is taken from the
+    /// `patch_id`, `put_id` and `delete_id` URL parameters respectively.
+    pub fn build<T: Serialize + std::fmt::Debug>(
+        base_url: &BaseUrl,
+        method: reqwest::Method,
+        endpoint: &Endpoint,
+        url_params: &UrlParams,
+        query_params: Vec<QueryFilter>,
+        post_params: T,
+    ) -> Result<Self, ApiError> {
+        let url = build_url(base_url, endpoint, url_params);
+        let url_id = |key: &str| {
+            url_params
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.to_string())
+        };
+
+        let (url, body) = match method {
+            reqwest::Method::GET => {
+                let query = query_params.into_query_string();
+                let url = if !query.is_empty() {
+                    format!("{}?{}", url, query)
+                } else {
+                    url
+                };
+                debug!(target: HTTP, "GET {}", url);
+                (url, None)
+            }
+            reqwest::Method::POST => {
+                debug!(target: HTTP, "POST {}", &url);
+                (url, Some(serde_json::to_vec(&post_params)?))
+            }
+            reqwest::Method::PATCH | reqwest::Method::PUT => {
+                let key = if method == reqwest::Method::PATCH {
+                    "patch_id"
+                } else {
+                    "put_id"
+                };
+                let id = url_id(key).ok_or(ApiError::MissingUrlIdentifier)?;
+                let url = format!("{}{}", url, id);
+                debug!(target: HTTP, "{} {}", method, &url);
+                (url, Some(serde_json::to_vec(&post_params)?))
+            }
+            reqwest::Method::DELETE => {
+                let url = match url_id("delete_id") {
+                    Some(id) => format!("{}{}", url, id),
+                    // Without an id, only filtered deletes are allowed.
+                    None if !query_params.is_empty() => {
+                        format!("{}?{}", url, query_params.into_query_string())
+                    }
+                    None => return Err(ApiError::MissingUrlIdentifier),
+                };
+                debug!(target: HTTP, "DELETE {}", &url);
+                (url, None)
+            }
+            _ => return Err(ApiError::UnsupportedHttpOperation(method.to_string())),
+        };
+
+        Ok(RequestPlan {
+            numbers: *endpoint == Endpoint::Objects,
+            ..RequestPlan::new(method, url, body)
+        })
+    }
+
+    /// Append raw query parameters to the URL of the request.
+    pub fn with_query(mut self, params: &[(&str, String)]) -> Self {
+        if params.is_empty() {
+            return self;
+        }
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        self.url = format!("{}{}{}", self.url, separator, query);
+        self
+    }
+
+    /// The signature header for the request, if a signer is configured.
+    pub fn signature(
+        &self,
+        signer: Option<&RequestSigner>,
+    ) -> Result<Option<(String, String)>, ApiError> {
+        signer
+            .map(|signer| {
+                let body = self.body.as_deref().unwrap_or_default();
+                let signature = signer.sign(&self.method, &self.url, body)?;
+                Ok((signer.header().to_string(), signature))
+            })
+            .transpose()
+    }
+}
+
+pub(crate) const IF_UNMODIFIED_SINCE_HEADER: &str = "If-Unmodified-Since";
+
+/// The HTTP date of a resource timestamp, as sent in `If-Unmodified-Since`.
+///
+/// HTTP dates have a resolution of seconds, so the fraction is truncated,
+/// as servers truncate the modification time they compare the date with.
+/// A change made later within the same second is therefore not noticed.
+pub(crate) fn http_date(time: chrono::NaiveDateTime) -> String {
+    time.and_utc()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+pub(crate) fn build_url(base_url: &BaseUrl, endpoint: &Endpoint, url_params: &UrlParams) -> String {
+    let mut url = format!(
+        "{}{}",
+        base_url.with_trailing_slash(),
+        endpoint.trim_start_matches('/')
+    );
+
+    for (key, value) in url_params {
+        url = url.replace(&format!("{{{}}}", key), value.as_ref());
+    }
+    url
+}
+
+/// Extract the message from an error response body.
+pub(crate) fn error_message(body: String) -> String {
+    match serde_json::from_str::<Value>(&body) {
+        Ok(json) => json["message"]
+            .as_str()
+            .unwrap_or("Error without message.")
+            .to_string(),
+        Err(_) => body,
+    }
+}
+
+/// The error for an unsuccessful response.
+///
+/// Objects rejected by the schema of their class become
+/// `ApiError::ObjectSchemaViolation`, everything else `ApiError::HttpWithBody`.
+pub(crate) fn response_error(status: StatusCode, url: &reqwest::Url, body: String) -> ApiError {
+    if matches!(
+        status,
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY
+    ) {
+        if let Some(err) = schema_violation(url, &body) {
+            return err;
+        }
+    }
+    ApiError::HttpWithBody {
+        status,
+        message: error_message(body),
+        url: url.to_string(),
+    }
+}
+
+/// The error for an unsuccessful login, telling apart what a user should
+/// be told differently.
+pub(crate) fn login_error(
+    status: StatusCode,
+    url: &reqwest::Url,
+    headers: &HeaderMap,
+    body: String,
+) -> ApiError {
+    match status {
+        StatusCode::UNAUTHORIZED => ApiError::BadCredentials,
+        StatusCode::FORBIDDEN => ApiError::AccountLocked(error_message(body)),
+        StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited {
+            retry_after: retry_after(headers),
+        },
+        status if status.is_server_error() => ApiError::ServerUnavailable {
+            status,
+            message: error_message(body),
+        },
+        status => ApiError::HttpWithBody {
+            status,
+            message: error_message(body),
+            url: url.to_string(),
+        },
+    }
+}
+
+/// The delay asked for by a `Retry-After` header given in seconds.
+fn retry_after(headers: &HeaderMap) -> Option<std::time::Duration> {
+    let seconds = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(std::time::Duration::from_secs(seconds))
+}
+
+/// Parse a schema validation failure of an object request.
+///
+/// Objects live below their class, at `classes/{class_id}/` and
+/// `classes/{class_id}/{object_id}`, so failures on the class itself at
+/// `classes/{class_id}` are not taken for one. The offending path is read
+/// from `pointer` or `instance_path`, either at the top level of the body
+/// or in its first `errors` entry.
+fn schema_violation(url: &reqwest::Url, body: &str) -> Option<ApiError> {
+    if !url.path().contains("/api/v1/classes/") {
+        return None;
+    }
+    let mut segments = url
+        .path_segments()?
+        .skip_while(|segment| *segment != "classes");
+    let class = segments.nth(1)?.parse().ok()?;
+    segments.next()?;
+
+    let json: Value = serde_json::from_str(body).ok()?;
+    let detail = match json.get("errors").and_then(|errors| errors.get(0)) {
+        Some(detail) => detail,
+        None => &json,
+    };
+    let pointer = ["pointer", "instance_path"]
+        .iter()
+        .find_map(|key| detail.get(*key).and_then(Value::as_str))?;
+    let message = detail
+        .get("message")
+        .or_else(|| json.get("message"))
+        .and_then(Value::as_str)
+        .unwrap_or("Error without message.");
+    Some(ApiError::ObjectSchemaViolation {
+        class,
+        pointer: pointer.to_string(),
+        message: message.to_string(),
+    })
+}
+
+/// The name a resource is created with, for looking it up after a conflict.
+pub(crate) fn post_name<T: ApiResource>(params: &T::PostParams) -> Option<String> {
+    match serde_json::to_value(params).ok()?.get(T::NAME_FIELD)? {
+        Value::String(name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// The names of the resources in a list response.
+pub(crate) fn names<T: ApiResource>(items: &[Value]) -> Vec<String> {
+    items
+        .iter()
+        .filter_map(|item| item.get(T::NAME_FIELD)?.as_str())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Order names by how well they match `partial`, keeping the best `limit`.
+///
+/// Exact matches come first, then names starting with `partial`, then names
+/// containing it, earlier and in shorter names first. Case is ignored.
+pub(crate) fn rank_suggestions(mut names: Vec<String>, partial: &str, limit: usize) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    names.sort_by_cached_key(|name| {
+        let lower = name.to_lowercase();
+        let position = lower.find(&partial).unwrap_or(usize::MAX);
+        (lower != partial, position, name.len(), name.clone())
+    });
+    names.dedup();
+    names.truncate(limit);
+    names
+}
+
+/// Apply `apply` to the items in order, or none of them.
+///
+/// If an item fails, the items applied so far are undone in reverse order,
+/// then `cleanup` runs, and the failure is returned along with any errors
+/// from undoing.
+pub(crate) fn apply_or_roll_back<I, R>(
+    items: impl IntoIterator<Item = I>,
+    mut apply: impl FnMut(I) -> Result<R, ApiError>,
+    mut undo: impl FnMut(R) -> Result<(), ApiError>,
+    cleanup: impl FnOnce() -> Result<(), ApiError>,
+) -> Result<Vec<R>, ApiError> {
+    let mut applied = vec![];
+    for item in items {
+        match apply(item) {
+            Ok(result) => applied.push(result),
+            Err(err) => {
+                let mut errors = vec![err];
+                errors.extend(applied.into_iter().rev().filter_map(|r| undo(r).err()));
+                errors.extend(cleanup().err());
+                return Err(ApiError::from_errors(errors).expect("the failure"));
+            }
+        }
+    }
+    Ok(applied)
+}
+
+/// The name of `T` without its module path, as used in error messages.
+pub(crate) fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// The only item of a search, or an error naming the type and the filters
+/// that found none or too many.
+pub(crate) fn one_or_err<T>(mut v: Vec<T>, filters: &[QueryFilter]) -> Result<T, ApiError> {
+    let name = short_type_name::<T>();
+    let matching = if filters.is_empty() {
+        String::new()
+    } else {
+        let filters: Vec<String> = filters.iter().map(ToString::to_string).collect();
+        format!(" matching {}", filters.join(", "))
+    };
+
+    match v.len() {
+        1 => Ok(v.pop().unwrap()),
+        0 => Err(ApiError::EmptyResult(format!(
+            "{} not found{}",
+            name, matching
+        ))),
+        count => Err(ApiError::TooManyResults(format!(
+            "Type: {}, Count: {} (expected 1){}",
+            name, count, matching
+        ))),
+    }
+}
+
+/// Pick the resources with `ids` out of `found`, in the order of `ids`,
+/// along with the ids missing from `found`, each once.
+pub(crate) fn by_ids<T: GetID + Clone>(ids: &[i32], found: Vec<T>) -> (Vec<T>, Vec<i32>) {
+    let found: HashMap<i32, T> = found.into_iter().map(|item| (item.id(), item)).collect();
+    let mut missing: Vec<i32> = vec![];
+    let mut resolved = Vec::with_capacity(ids.len());
+    for id in ids {
+        match found.get(id) {
+            Some(item) => resolved.push(item.clone()),
+            None if !missing.contains(id) => missing.push(*id),
+            None => {}
+        }
+    }
+    (resolved, missing)
+}
+
+/// The timeout of a request sent at `now`, shortened to what is left until
+/// `deadline`, or `ApiError::DeadlineExceeded` if nothing is left.
+pub(crate) fn deadline_timeout(
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    now: Instant,
+) -> Result<Option<Duration>, ApiError> {
+    let Some(deadline) = deadline else {
+        return Ok(timeout);
+    };
+    match deadline.checked_duration_since(now) {
+        Some(left) if !left.is_zero() => Ok(Some(timeout.map_or(left, |t| t.min(left)))),
+        _ => Err(ApiError::DeadlineExceeded { partial: vec![] }),
+    }
+}
+
+/// What is left of `max_response_bytes` for the responses of one call, which
+/// all pages of a listing share.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ByteBudget {
+    limit: Option<u64>,
+    spent: u64,
+}
+
+impl ByteBudget {
+    pub fn new(limit: Option<u64>) -> Self {
+        ByteBudget { limit, spent: 0 }
+    }
+
+    /// The bytes left to read, `None` if there is no limit.
+    pub fn left(&self) -> Option<u64> {
+        self.limit.map(|limit| limit.saturating_sub(self.spent))
+    }
+
+    /// Fail with `ApiError::ResponseTooLarge` if `size` more bytes from `url`
+    /// would exceed the limit.
+    pub fn check(&self, size: Option<u64>, url: &str) -> Result<(), ApiError> {
+        match (self.limit, size) {
+            (Some(limit), Some(size)) if self.spent + size > limit => {
+                Err(ApiError::ResponseTooLarge {
+                    limit,
+                    url: url.to_string(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Count `size` bytes read from `url` against the limit.
+    pub fn spend(&mut self, size: u64, url: &str) -> Result<(), ApiError> {
+        self.check(Some(size), url)?;
+        self.spent += size;
+        Ok(())
+    }
+}
+
+/// Decode the body of a successful response.
+///
+/// DELETE requests may return an empty body, as with 204 No Content, which
+/// yields `None`. Bodies returned by DELETE requests are decoded as usual.
+pub(crate) fn decode_response<U: DeserializeOwned>(
+    method: &reqwest::Method,
+    response_text: String,
+) -> Result<Option<U>, ApiError> {
+    if method == reqwest::Method::DELETE && response_text.trim().is_empty() {
+        return Ok(None);
+    }
+
+    match serde_json::from_str(&response_text) {
+        Ok(obj) => Ok(Some(obj)),
+        Err(err) => {
+            error!(target: HTTP, "Failed to deserialize response: {}", err);
+            Err(ApiError::DeserializationError(response_text))
+        }
+    }
+}
+
+/// The fields of a paginated response envelope.
+const ENVELOPE_FIELDS: [&str; 5] = ["results", "count", "next", "previous", "next_cursor"];
+
+/// Whether `value` is a paginated response envelope rather than a resource
+/// that happens to have a `results` array: it has no fields besides those of
+/// an envelope.
+fn is_envelope(value: &serde_json::Map<String, Value>) -> bool {
+    value.get("results").is_some_and(Value::is_array)
+        && value
+            .keys()
+            .all(|key| ENVELOPE_FIELDS.contains(&key.as_str()))
+}
+
+/// One page of a (possibly paginated) GET response.
+///
+/// Servers may return either a bare JSON array, or an envelope like
+/// `{ "results": [...], "count": N, "next": "..." }` with no other fields.
+/// The link to the next
+/// page is taken from the envelope, or from an RFC 5988 `Link` header with
+/// `rel="next"`. Servers offering keyset pagination pass the cursor of the
+/// next page as `next_cursor` in the envelope or in the `X-Next-Cursor` header.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Page {
+    pub items: Value,
+    pub next: Option<String>,
+    pub count: Option<u64>,
+    pub next_cursor: Option<String>,
+}
+
+impl Page {
+    pub fn parse(
+        response_text: String,
+        link_header: Option<&str>,
+        cursor_header: Option<&str>,
+    ) -> Result<Self, ApiError> {
+        // No content, as with 204 responses, is a legitimate empty list.
+        if response_text.trim().is_empty() {
+            return Ok(Page {
+                items: Value::Array(vec![]),
+                next: None,
+                count: Some(0),
+                next_cursor: None,
+            });
+        }
+        let value: Value = match serde_json::from_str(&response_text) {
+            Ok(Value::Null) => Value::Array(vec![]),
+            Ok(value) => value,
+            Err(err) => {
+                error!(target: HTTP, "Failed to deserialize response: {}", err);
+                return Err(ApiError::DeserializationError(response_text));
+            }
+        };
+
+        let link_next = link_header.and_then(|header| parse_link_header(header).remove("next"));
+        let cursor_next = cursor_header.map(str::to_string);
+        match value {
+            Value::Object(mut envelope) if is_envelope(&envelope) => {
+                let next = envelope
+                    .get("next")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .or(link_next);
+                let count = envelope.get("count").and_then(Value::as_u64);
+                let next_cursor = envelope
+                    .get("next_cursor")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .or(cursor_next);
+                Ok(Page {
+                    items: envelope.remove("results").unwrap_or_default(),
+                    next,
+                    count,
+                    next_cursor,
+                })
+            }
+            items => Ok(Page {
+                items,
+                next: link_next,
+                count: None,
+                next_cursor: cursor_next,
+            }),
+        }
+    }
+
+    /// The items of the page, as when it is all there is.
+    pub fn into_items(self) -> Vec<Value> {
+        match self.items {
+            Value::Array(items) => items,
+            _ => vec![],
+        }
+    }
+
+    /// Append the items of the following page, taking over its next link.
+    ///
+    /// Only lists can be paginated, a page that is not one is an error.
+    pub fn append(&mut self, page: Page) -> Result<(), ApiError> {
+        match (&mut self.items, page.items) {
+            (Value::Array(items), Value::Array(more)) => {
+                items.extend(more);
+                self.next = page.next;
+                self.next_cursor = page.next_cursor;
+                Ok(())
+            }
+            (Value::Array(_), items) => Err(not_a_list(&items)),
+            (items, _) => Err(not_a_list(items)),
+        }
+    }
+
+    pub fn decode<U: DeserializeOwned>(self) -> Result<Option<U>, ApiError> {
+        match serde_json::from_value(self.items) {
+            Ok(obj) => Ok(Some(obj)),
+            Err(err) => {
+                error!(target: HTTP, "Failed to deserialize response: {}", err);
+                Err(ApiError::DeserializationError(err.to_string()))
+            }
+        }
+    }
+}
+
+fn not_a_list(items: &Value) -> ApiError {
+    ApiError::DeserializationError(format!(
+        "Expected a list of results in a paginated response, got {}",
+        items
+    ))
+}
+
+/// A page of a listing, as the pagination loops of the clients read and
+/// join them.
+pub(crate) trait Paged: Sized {
+    /// Whether the page is parsed from the body as sent, without the
+    /// `NumberMode` of the client applied.
+    const RAW: bool;
+
+    /// Parse a page from the body and the `Link`, `X-Next-Cursor` and
+    /// `X-Total-Count` headers of the response.
+    fn parse(
+        text: String,
+        link: Option<&str>,
+        cursor: Option<&str>,
+        total: Option<&str>,
+    ) -> Result<Self, ApiError>;
+
+    /// The link to the next page.
+    fn next(&self) -> Option<&str>;
+
+    fn append(&mut self, page: Self) -> Result<(), ApiError>;
+
+    /// The items read so far, for `ApiError::DeadlineExceeded`.
+    fn into_partial(self) -> Vec<Value>;
+}
+
+impl Paged for Page {
+    const RAW: bool = false;
+
+    fn parse(
+        text: String,
+        link: Option<&str>,
+        cursor: Option<&str>,
+        total: Option<&str>,
+    ) -> Result<Self, ApiError> {
+        let mut page = Page::parse(text, link, cursor)?;
+        page.count = page.count.or(total.and_then(|total| total.parse().ok()));
+        Ok(page)
+    }
+
+    fn next(&self) -> Option<&str> {
+        self.next.as_deref()
+    }
+
+    fn append(&mut self, page: Self) -> Result<(), ApiError> {
+        Page::append(self, page)
+    }
+
+    fn into_partial(self) -> Vec<Value> {
+        self.into_items()
+    }
+}
+
+/// A page of items decoded straight from the response text, see
+/// `parse_raw_listing`.
+#[derive(Debug)]
+pub(crate) struct RawPage<U> {
+    pub items: Vec<U>,
+    next: Option<String>,
+}
+
+impl<U: DeserializeOwned + Serialize> Paged for RawPage<U> {
+    const RAW: bool = true;
+
+    fn parse(
+        text: String,
+        link: Option<&str>,
+        _cursor: Option<&str>,
+        _total: Option<&str>,
+    ) -> Result<Self, ApiError> {
+        let (items, next) = parse_raw_listing(&text, link)?;
+        Ok(RawPage { items, next })
+    }
+
+    fn next(&self) -> Option<&str> {
+        self.next.as_deref()
+    }
+
+    fn append(&mut self, page: Self) -> Result<(), ApiError> {
+        self.items.extend(page.items);
+        self.next = page.next;
+        Ok(())
+    }
+
+    fn into_partial(self) -> Vec<Value> {
+        self.items
+            .iter()
+            .filter_map(|item| serde_json::to_value(item).ok())
+            .collect()
+    }
+}
+
+/// The URLs of the pages fetched so far, so that next links leading back to
+/// any of them end the pagination rather than loop.
+#[derive(Debug)]
+pub(crate) struct PageTrail {
+    current: String,
+    visited: HashSet<String>,
+}
+
+impl PageTrail {
+    pub fn new(first: &str) -> Self {
+        PageTrail {
+            current: first.to_string(),
+            visited: HashSet::from([first.to_string()]),
+        }
+    }
+
+    /// Move on to `url`, `false` if it was visited before.
+    pub fn visit(&mut self, url: &str) -> bool {
+        self.current = url.to_string();
+        self.visited.insert(url.to_string())
+    }
+
+    /// The absolute URL of the page that `next` links to, resolved against
+    /// the current page, or `None` if there is no link or it was visited
+    /// before.
+    pub fn follow(&mut self, next: Option<&str>) -> Option<String> {
+        let url = url::Url::parse(&self.current)
+            .ok()?
+            .join(next?)
+            .ok()?
+            .to_string();
+        if !self.visit(&url) {
+            debug!(target: HTTP, "Next link {} was already fetched, stopping", url);
+            return None;
+        }
+        Some(url)
+    }
+}
+
+/// The items of a listing decoded straight from the response text, and the
+/// link to the next page, for items that need the text itself, such as those
+/// holding a `RawValue`.
+///
+/// Takes the same bare arrays and `results` envelopes as `Page::parse`.
+pub(crate) fn parse_raw_listing<U: DeserializeOwned>(
+    text: &str,
+    link_header: Option<&str>,
+) -> Result<(Vec<U>, Option<String>), ApiError> {
+    // The fields besides `results` and `next` are only there to be accepted.
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    #[allow(dead_code)]
+    struct Envelope<U> {
+        results: Vec<U>,
+        #[serde(default)]
+        next: Option<String>,
+        #[serde(default)]
+        count: Option<u64>,
+        #[serde(default)]
+        previous: Option<String>,
+        #[serde(default)]
+        next_cursor: Option<String>,
+    }
+
+    let link_next = link_header.and_then(|header| parse_link_header(header).remove("next"));
+    if text.trim().is_empty() {
+        return Ok((vec![], None));
+    }
+    if let Ok(items) = serde_json::from_str::<Option<Vec<U>>>(text) {
+        return Ok((items.unwrap_or_default(), link_next));
+    }
+    match serde_json::from_str::<Envelope<U>>(text) {
+        Ok(envelope) => Ok((envelope.results, envelope.next.or(link_next))),
+        Err(err) => {
+            error!(target: HTTP, "Failed to deserialize response: {}", err);
+            Err(ApiError::DeserializationError(text.to_string()))
+        }
+    }
+}
+
+/// Check the keys of the filters against the relations the resource allows joining.
+pub(crate) fn validate_filters<T: ApiResource>(filters: &[QueryFilter]) -> Result<(), ApiError> {
+    filters
+        .iter()
+        .try_for_each(|filter| T::validate_filter_key(&filter.key))
+}
+
+/// The query parameters for keyset pagination.
+pub(crate) fn cursor_query(
+    cursor: Option<&Cursor>,
+    limit: Option<usize>,
+) -> Vec<(&'static str, String)> {
+    let mut query = vec![];
+    if let Some(cursor) = cursor {
+        query.push(("cursor", cursor.to_string()));
+    }
+    if let Some(limit) = limit {
+        query.push(("limit", limit.to_string()));
+    }
+    query
+}
+
+/// Append query parameters to a URL, skipping keys the URL already has, as
+/// next links returned by the server may carry them over.
+pub(crate) fn append_query(url: &str, extra: &[(String, String)]) -> String {
+    if extra.is_empty() {
+        return url.to_string();
+    }
+    let Ok(mut parsed) = url::Url::parse(url) else {
+        return url.to_string();
+    };
+    let present: Vec<String> = parsed
+        .query_pairs()
+        .map(|(key, _)| key.into_owned())
+        .collect();
+    {
+        let mut pairs = parsed.query_pairs_mut();
+        for (key, value) in extra {
+            if !present.contains(key) {
+                pairs.append_pair(key, value);
+            }
+        }
+    }
+    parsed.to_string()
+}
+
+/// Split a header on `separator`, except where it appears within a `<URL>`
+/// or a quoted string.
+fn split_header(header: &str, separator: char) -> Vec<&str> {
+    let (mut parts, mut start) = (vec![], 0);
+    let (mut in_url, mut in_quotes) = (false, false);
+    for (pos, c) in header.char_indices() {
+        match c {
+            '<' if !in_quotes => in_url = true,
+            '>' if !in_quotes => in_url = false,
+            '"' if !in_url => in_quotes = !in_quotes,
+            c if c == separator && !in_url && !in_quotes => {
+                parts.push(&header[start..pos]);
+                start = pos + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&header[start..]);
+    parts
+}
+
+/// Parse an RFC 5988 `Link` header into a map of relation to URL.
+pub(crate) fn parse_link_header(header: &str) -> HashMap<String, String> {
+    let mut links = HashMap::new();
+    for link in split_header(header, ',') {
+        let mut parts = split_header(link, ';').into_iter();
+        let url = match parts.next().map(str::trim) {
+            Some(url) if url.starts_with('<') && url.ends_with('>') => &url[1..url.len() - 1],
+            _ => continue,
+        };
+        for param in parts {
+            if let Some((key, value)) = param.trim().split_once('=') {
+                if key.trim().eq_ignore_ascii_case("rel") {
+                    for rel in value.trim().trim_matches('"').split_whitespace() {
+                        links.insert(rel.to_string(), url.to_string());
+                    }
+                }
+            }
+        }
+    }
+    links
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use yare::parameterized;
+
+    fn base_url() -> BaseUrl {
+        BaseUrl::from_str("https://api.example.com").unwrap()
+    }
+
+    #[parameterized(
+        unauthorized = { 401, None, "Login failed: bad credentials" },
+        forbidden = { 403, None, "Login refused: account locked" },
+        rate_limited = { 429, Some("30"), "Rate limited, retry after 30s" },
+        rate_limited_without_delay = { 429, None, "Rate limited" },
+        unavailable = { 503, None, "Server unavailable (503 Service Unavailable): account locked" },
+        other = { 400, None, "HTTP error 400 Bad Request: account locked" }
+    )]
+    fn test_login_error(status: u16, retry_after: Option<&str>, expected: &str) {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = retry_after {
+            headers.insert(reqwest::header::RETRY_AFTER, value.parse().unwrap());
+        }
+        let body = r#"{"message": "account locked"}"#.to_string();
+        let url = reqwest::Url::parse("https://api.example.com/api/v0/auth/login").unwrap();
+        let err = login_error(StatusCode::from_u16(status).unwrap(), &url, &headers, body);
+        assert_eq!(err.to_string(), expected);
+        assert_eq!(err.status().map(|s| s.as_u16()), Some(status));
+    }
+
+    #[parameterized(
+        all_applied = { 4, None, Ok(vec![1, 2, 3]), &[] },
+        rolled_back = { 3, None, Err(1), &["undo 2", "undo 1", "cleanup"] },
+        undo_fails = { 3, Some(1), Err(2), &["undo 2", "undo 1", "cleanup"] },
+        first_fails = { 1, None, Err(1), &["cleanup"] },
+    )]
+    fn test_apply_or_roll_back(
+        failing: i32,
+        failing_undo: Option<i32>,
+        expected: Result<Vec<i32>, usize>,
+        calls: &[&str],
+    ) {
+        let log = std::cell::RefCell::new(vec![]);
+        let result = apply_or_roll_back(
+            [1, 2, 3],
+            |item| match item == failing {
+                true => Err(ApiError::NotFound(format!("item {}", item))),
+                false => Ok(item),
+            },
+            |item| {
+                log.borrow_mut().push(format!("undo {}", item));
+                match Some(item) == failing_undo {
+                    true => Err(ApiError::Cancelled),
+                    false => Ok(()),
+                }
+            },
+            || {
+                log.borrow_mut().push("cleanup".to_string());
+                Ok(())
+            },
+        );
+        match (result, expected) {
+            (Ok(applied), Ok(expected)) => assert_eq!(applied, expected),
+            (Err(err), Err(count)) => {
+                assert_eq!(err.errors().len(), count);
+                assert_eq!(
+                    err.errors()[0].to_string(),
+                    ApiError::NotFound(format!("item {}", failing)).to_string()
+                );
+            }
+            (result, _) => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(log.into_inner(), calls);
+    }
+
+    #[test]
+    fn test_rank_suggestions() {
+        let names = ["db-web01", "Web", "web02", "web01", "aweb"]
+            .map(str::to_string)
+            .to_vec();
+        assert_eq!(
+            rank_suggestions(names.clone(), "web", 4),
+            vec!["Web", "web01", "web02", "aweb"]
+        );
+        assert_eq!(rank_suggestions(names, "WEB0", 10).len(), 5);
+    }
+
+    #[parameterized(
+        patch = { reqwest::Method::PATCH, "patch_id" },
+        put = { reqwest::Method::PUT, "put_id" },
+        delete = { reqwest::Method::DELETE, "delete_id" }
+    )]
+    fn test_plan_with_id(method: reqwest::Method, key: &'static str) {
+        let plan = RequestPlan::build(
+            &base_url(),
+            method.clone(),
+            &Endpoint::Objects,
+            &vec![("class_id".into(), "1".into()), (key.into(), "2".into())],
+            vec![],
+            (),
+        )
+        .unwrap();
+        assert_eq!(plan.method, method);
+        assert_eq!(plan.url, "https://api.example.com/api/v1/classes/1/2");
+    }
+
+    #[parameterized(
+        patch = { reqwest::Method::PATCH },
+        put = { reqwest::Method::PUT },
+        delete = { reqwest::Method::DELETE }
+    )]
+    fn test_plan_without_id(method: reqwest::Method) {
+        let plan = RequestPlan::build(
+            &base_url(),
+            method,
+            &Endpoint::Classes,
+            &UrlParams::default(),
+            vec![],
+            (),
+        );
+        assert!(matches!(plan, Err(ApiError::MissingUrlIdentifier)));
+    }
+
+    #[test]
+    fn test_plan_get_with_query() {
+        let plan = RequestPlan::build(
+            &base_url(),
+            reqwest::Method::GET,
+            &Endpoint::Classes,
+            &UrlParams::default(),
+            vec![QueryFilter {
+                key: "name".to_string(),
+                value: "foo".to_string(),
+                operator: crate::types::FilterOperator::Equals { is_negated: false },
+            }],
+            (),
+        )
+        .unwrap();
+        assert_eq!(
+            plan.url,
+            "https://api.example.com/api/v1/classes/?name__equals=foo"
+        );
+        assert_eq!(plan.body, None);
+    }
+
+    #[parameterized(
+        json = { r#"{"message": "Not allowed"}"#, "Not allowed" },
+        json_without_message = { r#"{"error": "x"}"#, "Error without message." },
+        text = { "Bad gateway", "Bad gateway" }
+    )]
+    fn test_error_message(body: &str, expected: &str) {
+        assert_eq!(error_message(body.to_string()), expected);
+    }
+
+    #[parameterized(
+        top_level = { "/api/v1/classes/3/", r#"{"message": "bad", "pointer": "/ports/0"}"#, Some("/ports/0") },
+        errors_list = { "/api/v1/classes/3/9", r#"{"errors": [{"instance_path": "/name", "message": "bad"}]}"#, Some("/name") },
+        without_pointer = { "/api/v1/classes/3/", r#"{"message": "bad"}"#, None },
+        not_an_object = { "/api/v1/relations/classes/3", r#"{"message": "bad", "pointer": "/x"}"#, None },
+        class_update = { "/api/v1/classes/3", r#"{"message": "bad", "pointer": "/json_schema"}"#, None }
+    )]
+    fn test_schema_violation(path: &str, body: &str, pointer: Option<&str>) {
+        let url = reqwest::Url::parse(&format!("https://api.example.com{}", path)).unwrap();
+        match response_error(StatusCode::BAD_REQUEST, &url, body.to_string()) {
+            ApiError::ObjectSchemaViolation {
+                class,
+                pointer: found,
+                message,
+            } => {
+                assert_eq!((class, message.as_str()), (3, "bad"));
+                assert_eq!(Some(found.as_str()), pointer);
+            }
+            ApiError::HttpWithBody { .. } => assert_eq!(pointer, None),
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_post_name() {
+        use crate::resources::{Namespace, NamespacePost, User, UserPost};
+        let namespace = NamespacePost {
+            name: "prod".to_string(),
+            description: String::new(),
+            group_id: 1,
+        };
+        assert_eq!(post_name::<Namespace>(&namespace).as_deref(), Some("prod"));
+        let user = UserPost {
+            username: "alice".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(post_name::<User>(&user).as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn test_one_or_err_names_filters() {
+        use crate::resources::User;
+        let filters = vec![QueryFilter {
+            key: "username".to_string(),
+            value: "alice".to_string(),
+            operator: crate::types::FilterOperator::Equals { is_negated: false },
+        }];
+        let err = one_or_err(Vec::<User>::new(), &filters).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unexpected empty result: User not found matching username__equals=alice"
+        );
+        let err = one_or_err(vec![User::default(), User::default()], &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Too many results: Type: User, Count: 2 (expected 1)"
+        );
+        assert!(one_or_err(vec![User::default()], &filters).is_ok());
+    }
+
+    #[test]
+    fn test_parse_link_header() {
+        let links = parse_link_header(
+            r#"<https://api.example.com/api/v1/classes/?page=3>; rel="next", <https://api.example.com/api/v1/classes/?page=1>; rel="prev first""#,
+        );
+        assert_eq!(
+            links.get("next").map(String::as_str),
+            Some("https://api.example.com/api/v1/classes/?page=3")
+        );
+        assert_eq!(links.get("prev"), links.get("first"));
+        assert_eq!(links.get("last"), None);
+    }
+
+    #[test]
+    fn test_parse_link_header_with_commas() {
+        let links = parse_link_header(
+            r#"<https://api.example.com/api/v1/classes/?name__in=a,b;c&page=2>; rel="next"; title="a, b", <https://api.example.com/api/v1/classes/?page=9>; rel="last""#,
+        );
+        assert_eq!(
+            links.get("next").map(String::as_str),
+            Some("https://api.example.com/api/v1/classes/?name__in=a,b;c&page=2")
+        );
+        assert_eq!(
+            links.get("last").map(String::as_str),
+            Some("https://api.example.com/api/v1/classes/?page=9")
+        );
+    }
+
+    #[test]
+    fn test_page_bare_array() {
+        let page = Page::parse("[1, 2]".into(), None, None).unwrap();
+        assert_eq!(page.items, serde_json::json!([1, 2]));
+        assert_eq!(page.next, None);
+        assert_eq!(page.count, None);
+    }
+
+    #[test]
+    fn test_page_envelope() {
+        let page = Page::parse(
+            r#"{"results": [1], "count": 2, "next": "/api/v1/classes/?page=2"}"#.into(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(page.items, serde_json::json!([1]));
+        assert_eq!(page.count, Some(2));
+        let mut trail = PageTrail::new("https://api.example.com/api/v1/classes/?page=1");
+        assert_eq!(
+            trail.follow(page.next.as_deref()),
+            Some("https://api.example.com/api/v1/classes/?page=2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_page_resource_with_results_field() {
+        let body = r#"{"id": 1, "name": "run", "results": [1, 2]}"#;
+        let page = Page::parse(body.into(), None, None).unwrap();
+        assert_eq!(page.items, serde_json::from_str::<Value>(body).unwrap());
+        assert_eq!(page.count, None);
+
+        let listing: Result<(Vec<Value>, _), _> = parse_raw_listing(body, None);
+        assert!(matches!(listing, Err(ApiError::DeserializationError(_))));
+    }
+
+    #[test]
+    fn test_page_link_header_and_append() {
+        let mut page = Page::parse(
+            "[1]".into(),
+            Some(r#"<https://api.example.com/api/v1/classes/?page=2>; rel="next""#),
+            None,
+        )
+        .unwrap();
+        assert!(page.next.is_some());
+        page.append(Page::parse("[2]".into(), None, None).unwrap())
+            .unwrap();
+        assert_eq!(page.next, None);
+        let items: Option<Vec<i32>> = page.decode().unwrap();
+        assert_eq!(items, Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_page_append_non_list() {
+        let mut page = Page::parse("[1]".into(), None, None).unwrap();
+        let err = page
+            .append(Page::parse(r#"{"id": 2}"#.into(), None, None).unwrap())
+            .unwrap_err();
+        assert!(matches!(err, ApiError::DeserializationError(_)));
+    }
+
+    #[test]
+    fn test_page_trail_loops() {
+        let mut trail = PageTrail::new("https://api.example.com/a");
+        assert_eq!(trail.follow(Some("https://api.example.com/a")), None);
+        assert_eq!(
+            trail.follow(Some("/b")),
+            Some("https://api.example.com/b".to_string())
+        );
+        assert_eq!(trail.follow(Some("/a")), None);
+        assert_eq!(trail.follow(None), None);
+    }
+
+    #[parameterized(
+        empty = { "" },
+        whitespace = { " \n" },
+        null = { "null" },
+        empty_array = { "[]" },
+        empty_envelope = { r#"{"results": [], "count": 0}"# }
+    )]
+    fn test_empty_list_page(body: &str) {
+        let page = Page::parse(body.to_string(), None, None).unwrap();
+        let items: Option<Vec<Value>> = page.decode().unwrap();
+        assert_eq!(items, Some(vec![]));
+    }
+
+    #[test]
+    fn test_decode_delete_response() {
+        let decoded: Option<Value> =
+            decode_response(&reqwest::Method::DELETE, String::new()).unwrap();
+        assert_eq!(decoded, None);
+        let decoded: Option<Value> =
+            decode_response(&reqwest::Method::DELETE, r#"{"id": 1}"#.into()).unwrap();
+        assert_eq!(decoded, Some(serde_json::json!({"id": 1})));
+        let ignored: Option<serde::de::IgnoredAny> =
+            decode_response(&reqwest::Method::DELETE, r#"{"id": 1}"#.into()).unwrap();
+        assert!(ignored.is_some());
+        assert!(decode_response::<Value>(&reqwest::Method::DELETE, "deleted".into()).is_err());
+        assert!(decode_response::<Value>(&reqwest::Method::POST, String::new()).is_err());
+    }
+
+    #[parameterized(
+        envelope = { r#"{"results": [1], "next_cursor": "abc"}"#, None },
+        header = { "[1]", Some("abc") },
+        envelope_over_header = { r#"{"results": [1], "next_cursor": "abc"}"#, Some("def") }
+    )]
+    fn test_page_next_cursor(body: &str, header: Option<&str>) {
+        let page = Page::parse(body.into(), None, header).unwrap();
+        assert_eq!(page.next_cursor.as_deref(), Some("abc"));
+        assert_eq!(page.next, None);
+    }
+
+    #[test]
+    fn test_plan_with_query() {
+        let plan = RequestPlan::new(
+            reqwest::Method::GET,
+            "https://api.example.com/api/v1/classes/?name__equals=foo".into(),
+            None,
+        )
+        .with_query(&[("limit", "10".into()), ("cursor", "a b".into())]);
+        assert_eq!(
+            plan.url,
+            "https://api.example.com/api/v1/classes/?name__equals=foo&limit=10&cursor=a+b"
+        );
+    }
+
+    #[parameterized(
+        plain = { "name", true },
+        object_namespace = { "namespaces__name", true },
+        object_class = { "classes__name", true },
+        unknown_join = { "groups__name", false },
+        missing_field = { "namespaces__", false },
+        nested = { "namespaces__groups__name", false }
+    )]
+    fn test_validate_object_filters(key: &str, valid: bool) {
+        let filters = vec![QueryFilter {
+            key: key.to_string(),
+            value: "prod".to_string(),
+            operator: crate::types::FilterOperator::Equals { is_negated: false },
+        }];
+        let result = validate_filters::<crate::resources::Object>(&filters);
+        assert_eq!(result.is_ok(), valid, "{:?}", result);
+    }
+
+    #[test]
+    fn test_by_ids() {
+        use crate::client::FromId;
+        use crate::resources::Class;
+
+        let found = vec![Class::from_id(3), Class::from_id(1), Class::from_id(2)];
+        let (resolved, missing) = by_ids(&[2, 3, 2], found.clone());
+        let ids: Vec<i32> = resolved.iter().map(GetID::id).collect();
+        assert_eq!(ids, vec![2, 3, 2]);
+        assert!(missing.is_empty());
+
+        let (resolved, missing) = by_ids(&[1, 4, 5, 4], found);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(missing, vec![4, 5]);
+    }
+
+    #[parameterized(
+        none = { "https://api.example.com/api/v1/classes/", &[], "https://api.example.com/api/v1/classes/" },
+        first = { "https://api.example.com/api/v1/classes/", &[("trace", "1")], "https://api.example.com/api/v1/classes/?trace=1" },
+        appended = { "https://api.example.com/api/v1/classes/?name=a", &[("trace", "1"), ("x y", "&")], "https://api.example.com/api/v1/classes/?name=a&trace=1&x+y=%26" },
+        present = { "https://api.example.com/api/v1/classes/?trace=1&page=2", &[("trace", "1")], "https://api.example.com/api/v1/classes/?trace=1&page=2" },
+    )]
+    fn test_append_query(url: &str, extra: &[(&str, &str)], expected: &str) {
+        let extra: Vec<(String, String)> = extra
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(append_query(url, &extra), expected);
+    }
+
+    #[parameterized(
+        no_limit = { None, Some(10), true },
+        unknown_size = { Some(5), None, true },
+        within = { Some(10), Some(10), true },
+        beyond = { Some(10), Some(11), false },
+    )]
+    fn test_byte_budget_check(limit: Option<u64>, size: Option<u64>, ok: bool) {
+        let budget = ByteBudget::new(limit);
+        let result = budget.check(size, "https://api.example.com/api/v1/classes/");
+        assert_eq!(result.is_ok(), ok);
+        if let Err(err) = result {
+            assert_eq!(
+                err.to_string(),
+                "Response from https://api.example.com/api/v1/classes/ is larger than 10 bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn test_byte_budget_spans_responses() {
+        let url = "https://api.example.com/api/v1/classes/";
+        let mut budget = ByteBudget::new(Some(10));
+        budget.spend(6, url).unwrap();
+        assert_eq!(budget.left(), Some(4));
+        assert!(budget.check(Some(5), url).is_err());
+        budget.spend(4, url).unwrap();
+        assert!(budget.spend(1, url).is_err());
+        assert_eq!(budget.left(), Some(0));
+    }
+
+    #[test]
+    fn test_parse_raw_listing() {
+        type Items = Vec<Box<serde_json::value::RawValue>>;
+        let text = |items: &Items| -> Vec<String> {
+            items.iter().map(|item| item.get().to_string()).collect()
+        };
+
+        let (items, next): (Items, _) = parse_raw_listing(
+            r#"[{"a": 1.0}, 2]"#,
+            Some(r#"</api/v1/x?page=2>; rel="next""#),
+        )
+        .unwrap();
+        assert_eq!(text(&items), vec![r#"{"a": 1.0}"#, "2"]);
+        assert_eq!(next.as_deref(), Some("/api/v1/x?page=2"));
+
+        let (items, next): (Items, _) =
+            parse_raw_listing(r#"{"results": [3], "count": 9, "next": "/p2"}"#, None).unwrap();
+        assert_eq!(text(&items), vec!["3"]);
+        assert_eq!(next.as_deref(), Some("/p2"));
+
+        let (items, _): (Items, _) = parse_raw_listing("null", None).unwrap();
+        assert!(items.is_empty());
+        assert!(parse_raw_listing::<Box<serde_json::value::RawValue>>("{}", None).is_err());
+    }
+
+    #[parameterized(
+        whole_second = { 0 },
+        half_second = { 500_000_000 },
+        last_nanosecond = { 999_999_999 },
+    )]
+    fn test_http_date_truncates_the_fraction(nanos: u32) {
+        let time = chrono::NaiveDate::from_ymd_opt(2024, 3, 5)
+            .unwrap()
+            .and_hms_nano_opt(14, 7, 9, nanos)
+            .unwrap();
+        assert_eq!(http_date(time), "Tue, 05 Mar 2024 14:07:09 GMT");
+    }
+
+    #[test]
+    fn test_deadline_timeout() {
+        let now = Instant::now();
+        let second = Duration::from_secs(1);
+        assert_eq!(deadline_timeout(None, None, now).unwrap(), None);
+        assert_eq!(
+            deadline_timeout(Some(second), None, now).unwrap(),
+            Some(second)
+        );
+        assert_eq!(
+            deadline_timeout(None, Some(now + second), now).unwrap(),
+            Some(second)
+        );
+        assert_eq!(
+            deadline_timeout(Some(second * 5), Some(now + second), now).unwrap(),
+            Some(second)
+        );
+        assert_eq!(
+            deadline_timeout(Some(second), Some(now + second * 5), now).unwrap(),
+            Some(second)
+        );
+        assert!(matches!(
+            deadline_timeout(Some(second), Some(now), now),
+            Err(ApiError::DeadlineExceeded { partial }) if partial.is_empty()
+        ));
+    }
+}
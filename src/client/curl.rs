@@ -0,0 +1,109 @@
+//! Reproducing requests as `curl` commands, for troubleshooting.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use hubuum_client::client::CurlHook;
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = SyncClient::builder(BaseUrl::from_str("https://api.example.com")?)
+//!     .curl(CurlHook::new(|command| eprintln!("{}", command)))
+//!     .build()?
+//!     .login(Credentials::new("user".into(), "pass".into()))?;
+//! # Ok(())
+//! # }
+//! ```
+use std::sync::Arc;
+
+use super::core::RequestPlan;
+use super::retry::IDEMPOTENCY_KEY_HEADER;
+use crate::logging::{debug, CURL};
+
+/// The shell variable standing in for the token in generated commands.
+pub const TOKEN_PLACEHOLDER: &str = "$HUBUUM_TOKEN";
+
+/// Receives an equivalent `curl` command for every request a client sends.
+///
+/// The bearer token is never included; the commands refer to
+/// `$HUBUUM_TOKEN` instead. Request signatures are left out as well, as
+/// they would not be valid for a replayed request anyway.
+#[derive(Clone)]
+pub struct CurlHook(Arc<dyn Fn(&str) + Send + Sync>);
+
+impl CurlHook {
+    pub fn new(hook: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        CurlHook(Arc::new(hook))
+    }
+
+    /// Log the commands at debug level to [`crate::logging::CURL`].
+    pub fn log() -> Self {
+        CurlHook::new(|command| debug!(target: CURL, "{}", command))
+    }
+
+    pub(crate) fn emit(&self, plan: &RequestPlan, idempotency_key: Option<&str>) {
+        (self.0)(&command(plan, idempotency_key))
+    }
+}
+
+impl std::fmt::Debug for CurlHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("CurlHook")
+    }
+}
+
+fn command(plan: &RequestPlan, idempotency_key: Option<&str>) -> String {
+    let mut command = format!("curl -X {} {}", plan.method, quote(&plan.url));
+    command.push_str(&format!(
+        " -H \"Authorization: Bearer {}\"",
+        TOKEN_PLACEHOLDER
+    ));
+    if let Some(key) = idempotency_key {
+        command.push_str(&format!(
+            " -H {}",
+            quote(&format!("{}: {}", IDEMPOTENCY_KEY_HEADER, key))
+        ));
+    }
+    for (name, value) in &plan.headers {
+        command.push_str(&format!(" -H {}", quote(&format!("{}: {}", name, value))));
+    }
+    if let Some(body) = &plan.body {
+        command.push_str(" -H 'Content-Type: application/json' --data-raw ");
+        command.push_str(&quote(&String::from_utf8_lossy(body)));
+    }
+    command
+}
+
+/// Quote a string for POSIX shells.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let get = RequestPlan::new(
+            reqwest::Method::GET,
+            "https://api.example.com/api/v1/classes/?name__equals=it's".to_string(),
+            None,
+        );
+        assert_eq!(
+            command(&get, None),
+            "curl -X GET 'https://api.example.com/api/v1/classes/?name__equals=it'\\''s' \
+             -H \"Authorization: Bearer $HUBUUM_TOKEN\""
+        );
+
+        let post = RequestPlan::new(
+            reqwest::Method::POST,
+            "https://api.example.com/api/v1/namespaces/".to_string(),
+            Some(br#"{"name":"prod"}"#.to_vec()),
+        );
+        assert_eq!(
+            command(&post, Some("abc")),
+            "curl -X POST 'https://api.example.com/api/v1/namespaces/' \
+             -H \"Authorization: Bearer $HUBUUM_TOKEN\" -H 'Idempotency-Key: abc' \
+             -H 'Content-Type: application/json' --data-raw '{\"name\":\"prod\"}'"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Server-sent event subscriptions.
+//!
+//! The server pushes change events as a `text/event-stream`. Both clients
+//! reconnect when the stream drops, passing the id of the last event seen in
+//! the `Last-Event-ID` header so that the server can resume where it left off.
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+use crate::errors::ApiError;
+
+/// The delay before reconnecting, unless the server asks for another one.
+pub(crate) const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+/// The number of reconnects attempted in a row without receiving any events.
+pub(crate) const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+
+/// Whether to reconnect after failing to connect with `err`: on transport
+/// failures and on the statuses of servers that are busy or restarting, but
+/// not on refused credentials or permissions.
+pub(crate) fn should_reconnect(err: &ApiError) -> bool {
+    matches!(err, ApiError::Http(_)) || super::retry::is_transient(err)
+}
+
+/// The delay before reconnecting after `failures` failed attempts in a row,
+/// doubling from the delay the server asked for or the default one.
+pub(crate) fn reconnect_delay(retry: Option<Duration>, failures: u32) -> Duration {
+    retry
+        .unwrap_or(DEFAULT_RECONNECT_DELAY)
+        .saturating_mul(2u32.saturating_pow(failures))
+}
+
+/// The error ending a subscription that could not reconnect.
+pub(crate) fn stream_error(err: ApiError) -> ApiError {
+    match err {
+        ApiError::Http(err) => ApiError::EventStream(err.to_string()),
+        err => err,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// The resume token of the event, if the server sent one.
+    pub id: Option<String>,
+    /// The event type, `message` unless the server says otherwise.
+    pub kind: String,
+    pub data: String,
+}
+
+impl Event {
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, ApiError> {
+        Ok(serde_json::from_str(&self.data)?)
+    }
+}
+
+/// Incremental parser for the `text/event-stream` format.
+#[derive(Debug, Default)]
+pub(crate) struct EventParser {
+    buffer: Vec<u8>,
+    kind: Option<String>,
+    data: Vec<String>,
+    last_event_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl EventParser {
+    /// Feed a chunk of the stream, returning the events it completed.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = vec![];
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(event) = self.process_line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Drop the partly read event of a stream that was cut off, keeping the
+    /// resume id and the reconnect delay for the next connection.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.kind = None;
+        self.data.clear();
+    }
+
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.kind = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            "id" => self.last_event_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let kind = self.kind.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(Event {
+            id: self.last_event_id.clone(),
+            kind: kind.unwrap_or_else(|| "message".to_string()),
+            data: std::mem::take(&mut self.data).join("\n"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_single_event() {
+        let mut parser = EventParser::default();
+        let events = parser.feed(b"id: 42\nevent: object_updated\ndata: {\"id\": 1}\n\n");
+        assert_eq!(
+            events,
+            vec![Event {
+                id: Some("42".to_string()),
+                kind: "object_updated".to_string(),
+                data: "{\"id\": 1}".to_string(),
+            }]
+        );
+        assert_eq!(parser.last_event_id(), Some("42"));
+    }
+
+    #[test]
+    fn test_parse_split_chunks_and_multiline_data() {
+        let mut parser = EventParser::default();
+        assert!(parser.feed(b"data: first\r\nda").is_empty());
+        assert!(parser.feed(b"ta: second\r\n").is_empty());
+        let events = parser.feed(b"\r\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "message");
+        assert_eq!(events[0].data, "first\nsecond");
+        assert_eq!(events[0].id, None);
+    }
+
+    #[test]
+    fn test_parse_comments_retry_and_resume_id() {
+        let mut parser = EventParser::default();
+        assert!(parser.feed(b"id: 7\n\n").is_empty());
+        let events = parser.feed(b": keep-alive\nretry: 1500\n\ndata: x\n\n");
+        assert_eq!(parser.retry(), Some(Duration::from_millis(1500)));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, Some("7".to_string()));
+    }
+
+    #[test]
+    fn test_reset_drops_partial_event() {
+        let mut parser = EventParser::default();
+        assert!(parser
+            .feed(b"id: 3\nretry: 100\n\nevent: gone\ndata: half\ndata: ha")
+            .is_empty());
+        parser.reset();
+        let events = parser.feed(b"data: whole\n\n");
+        assert_eq!(
+            events,
+            vec![Event {
+                id: Some("3".to_string()),
+                kind: "message".to_string(),
+                data: "whole".to_string(),
+            }]
+        );
+        assert_eq!(parser.retry(), Some(Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,160 @@
+//! Failing over between replicas of a server.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = SyncClient::builder(BaseUrl::from_str("https://hubuum-a.example.com")?)
+//!     .replicas(vec![BaseUrl::from_str("https://hubuum-b.example.com")?])
+//!     .build()?
+//!     .login(Credentials::new("user".into(), "pass".into()))?;
+//! // Served by hubuum-b while hubuum-a refuses connections.
+//! let namespaces = client.namespaces().find().execute()?;
+//! # Ok(())
+//! # }
+//! ```
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::logging::{debug, warn, HTTP};
+use crate::types::BaseUrl;
+
+/// How long the client stays on a replica before trying the primary again.
+pub const DEFAULT_FAILOVER_RECHECK: Duration = Duration::from_secs(30);
+
+/// An ordered list of base URLs, the first being the primary, and which of
+/// them requests currently go to.
+///
+/// Requests are built against the primary and rewritten to the active
+/// replica when sent. The active replica only changes when a connection to
+/// it fails, so requests stick to one replica. Once a replica has been active
+/// for the recheck interval, the next request goes to the primary again.
+/// Clones share the active replica.
+#[derive(Debug, Clone)]
+pub(crate) struct Failover {
+    urls: Arc<Vec<String>>,
+    recheck: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    active: usize,
+    since: Instant,
+}
+
+impl Failover {
+    pub fn new(primary: &BaseUrl, replicas: &[BaseUrl], recheck: Duration) -> Self {
+        let urls = std::iter::once(primary)
+            .chain(replicas)
+            .map(BaseUrl::with_trailing_slash)
+            .collect();
+        Failover {
+            urls: Arc::new(urls),
+            recheck,
+            state: Arc::new(Mutex::new(State {
+                active: 0,
+                since: Instant::now(),
+            })),
+        }
+    }
+
+    /// The number of base URLs, including the primary.
+    pub fn len(&self) -> usize {
+        self.urls.len()
+    }
+
+    /// Point a URL built against the primary at the active replica.
+    pub fn rewrite(&self, url: &str) -> String {
+        let Some(path) = url.strip_prefix(self.urls[0].as_str()) else {
+            return url.to_string();
+        };
+        let mut state = self.state();
+        if state.active != 0 && state.since.elapsed() >= self.recheck {
+            debug!(target: HTTP, "Rechecking primary {}", self.urls[0]);
+            state.active = 0;
+            state.since = Instant::now();
+        }
+        format!("{}{}", self.urls[state.active], path)
+    }
+
+    /// Record that connecting to `url` failed, moving on to the next replica
+    /// if `url` was on the active one.
+    ///
+    /// Returns false if `url` is not on any of the replicas.
+    pub fn failed(&self, url: &str) -> bool {
+        let Some(index) = self.urls.iter().position(|base| url.starts_with(base)) else {
+            return false;
+        };
+        let mut state = self.state();
+        if state.active == index {
+            state.active = (index + 1) % self.urls.len();
+            state.since = Instant::now();
+            warn!(
+                target: HTTP,
+                "Connection to {} failed, failing over to {}",
+                self.urls[index],
+                self.urls[state.active]
+            );
+        }
+        true
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn failover(recheck: Duration) -> Failover {
+        Failover::new(
+            &BaseUrl::from_str("https://a.example.com").unwrap(),
+            &[
+                BaseUrl::from_str("https://b.example.com/hubuum").unwrap(),
+                BaseUrl::from_str("https://c.example.com").unwrap(),
+            ],
+            recheck,
+        )
+    }
+
+    #[test]
+    fn test_failover_is_sticky() {
+        let failover = failover(DEFAULT_FAILOVER_RECHECK);
+        let url = "https://a.example.com/api/v1/classes/";
+        assert_eq!(failover.rewrite(url), url);
+
+        assert!(failover.failed(url));
+        let rewritten = failover.rewrite(url);
+        assert_eq!(rewritten, "https://b.example.com/hubuum/api/v1/classes/");
+        assert_eq!(failover.clone().rewrite(url), rewritten);
+
+        // A late failure of a replica no longer active changes nothing.
+        assert!(failover.failed(url));
+        assert_eq!(failover.rewrite(url), rewritten);
+
+        assert!(failover.failed(&rewritten));
+        assert!(failover.failed("https://c.example.com/api/v1/classes/"));
+        assert_eq!(failover.rewrite(url), url);
+
+        assert!(!failover.failed("https://d.example.com/"));
+        assert_eq!(
+            failover.rewrite("https://d.example.com/"),
+            "https://d.example.com/"
+        );
+        assert_eq!(failover.len(), 3);
+    }
+
+    #[test]
+    fn test_failover_rechecks_primary() {
+        let failover = failover(Duration::ZERO);
+        let url = "https://a.example.com/api/v1/classes/";
+        assert!(failover.failed(url));
+        assert_eq!(failover.rewrite(url), url);
+    }
+}
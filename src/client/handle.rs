@@ -0,0 +1,1009 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use super::bulk::{self, BulkResult, DEFAULT_BULK_CONCURRENCY};
+use super::cache;
+use super::sync::{Client, Resource};
+use super::{Authenticated, GetID, UrlParams};
+use crate::diff::{self, JsonOp};
+use crate::endpoints::Endpoint;
+use crate::errors::ApiError;
+use crate::logging::{debug, HTTP};
+use crate::resources::{
+    ApiResource, Class, ClassRelation, Group, Namespace, Neighbor, Object, ObjectPatch,
+    ObjectRelation, Permission, PermissionSet, RelationDirection, Revision,
+};
+
+/// A resource fetched from the server, bundled with the client that fetched it.
+///
+/// Handles are created via `Resource::select` and friends, and allow follow-up
+/// operations on the resource without having to look it up again.
+pub struct Handle<T: ApiResource> {
+    client: Client<Authenticated>,
+    resource: T::GetOutput,
+}
+
+impl<T: ApiResource> Handle<T>
+where
+    T::GetOutput: GetID,
+{
+    pub(crate) fn new(client: Client<Authenticated>, resource: T::GetOutput) -> Self {
+        Handle { client, resource }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.resource.id()
+    }
+
+    pub fn resource(&self) -> &T::GetOutput {
+        &self.resource
+    }
+
+    pub fn into_resource(self) -> T::GetOutput {
+        self.resource
+    }
+
+    pub fn client(&self) -> &Client<Authenticated> {
+        &self.client
+    }
+}
+
+/// The handles resolved from a set of ids, see `Resource::resolve_handles`.
+pub struct Resolved<T: ApiResource> {
+    /// The handles, in the order the ids were asked for.
+    pub handles: Vec<Handle<T>>,
+    /// The ids the server did not return, because they do not exist or are
+    /// not visible to the user.
+    pub missing: Vec<i32>,
+}
+
+impl<T: ApiResource> Resolved<T> {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl Handle<Object> {
+    /// The change history of the object, as recorded by the server.
+    pub fn history(&self) -> Result<Vec<Revision>, ApiError> {
+        history(&self.client, &Endpoint::ObjectHistory, self.url_params())
+    }
+
+    fn url_params(&self) -> UrlParams {
+        vec![
+            (
+                "class_id".into(),
+                self.resource.hubuum_class_id.to_string().into(),
+            ),
+            ("object_id".into(), self.resource.id.to_string().into()),
+        ]
+    }
+
+    /// The relations with the object on either end.
+    pub fn relations(&self) -> Result<Vec<ObjectRelation>, ApiError> {
+        Ok(self
+            .client
+            .request_with_endpoint(
+                reqwest::Method::GET,
+                &Endpoint::ObjectRelationsForObject,
+                self.url_params(),
+                vec![],
+                (),
+            )?
+            .unwrap_or_default())
+    }
+
+    /// The objects related to this one, in both directions.
+    ///
+    /// The class relations are fetched together, and the related objects
+    /// with one request per class, so the number of requests does not grow
+    /// with the number of relations.
+    pub fn neighbors(&self) -> Result<Vec<Neighbor>, ApiError> {
+        let id = self.resource.id;
+        let relations = self.relations()?;
+        let ids: Vec<i32> = relations.iter().map(|r| r.class_relation_id).collect();
+        let class_relations = resolve_all(&self.client.class_relation(), &ids, "ClassRelation")?;
+
+        let ends: Vec<(RelationDirection, i32, i32)> = relations
+            .iter()
+            .map(|relation| {
+                let class_relation = &class_relations[&relation.class_relation_id];
+                if relation.from_hubuum_object_id == id {
+                    (
+                        RelationDirection::Outgoing,
+                        class_relation.to_hubuum_class_id,
+                        relation.to_hubuum_object_id,
+                    )
+                } else {
+                    (
+                        RelationDirection::Incoming,
+                        class_relation.from_hubuum_class_id,
+                        relation.from_hubuum_object_id,
+                    )
+                }
+            })
+            .collect();
+        let mut by_class: HashMap<i32, Vec<i32>> = HashMap::new();
+        for &(_, class_id, object_id) in &ends {
+            by_class.entry(class_id).or_default().push(object_id);
+        }
+        let mut objects = HashMap::new();
+        for (class_id, ids) in by_class {
+            let found = resolve_all(&self.client.objects(class_id), &ids, "Object")?;
+            objects.extend(
+                found
+                    .into_iter()
+                    .map(|(id, object)| ((class_id, id), object)),
+            );
+        }
+
+        Ok(relations
+            .into_iter()
+            .zip(ends)
+            .map(|(relation, (direction, class_id, object_id))| Neighbor {
+                relation,
+                direction,
+                object: objects[&(class_id, object_id)].clone(),
+            })
+            .collect())
+    }
+
+    /// Move the object to another namespace.
+    ///
+    /// The permissions of the target namespace are checked before the object
+    /// is patched, so a move that the server would refuse fails early with
+    /// `ApiError::MoveForbidden` instead of a generic HTTP error. The move
+    /// needs one of the groups of the user to be allowed to create objects
+    /// in the target namespace. Clients logged in with a token do not know
+    /// their user, and only check that some group is allowed to. A target
+    /// namespace that does not exist fails with `ApiError::NotFound`.
+    pub fn move_to_namespace(&self, namespace_id: i32) -> Result<Handle<Object>, ApiError> {
+        let object = &self.resource;
+        if object.namespace_id == namespace_id {
+            return Ok(Handle::new(self.client.clone(), object.clone()));
+        }
+
+        let forbidden = |reason: &str| {
+            ApiError::MoveForbidden(format!(
+                "object {} ({}) cannot be moved from namespace {} to namespace {}: {}",
+                object.id, object.name, object.namespace_id, namespace_id, reason
+            ))
+        };
+
+        let permissions: Vec<Permission> = self
+            .client
+            .request_with_endpoint(
+                reqwest::Method::GET,
+                &Endpoint::NamespacePermissions,
+                vec![("namespace_id".into(), namespace_id.to_string().into())],
+                vec![],
+                (),
+            )
+            .map_err(|err| match err {
+                ApiError::HttpWithBody {
+                    status: StatusCode::NOT_FOUND,
+                    ..
+                } => ApiError::NotFound(format!("namespace {}", namespace_id)),
+                ApiError::HttpWithBody {
+                    status, message, ..
+                } if is_denied(status) => forbidden(&message),
+                err => err,
+            })?
+            .unwrap_or_default();
+
+        let groups = self.user_groups()?;
+        let allowed = permissions.iter().any(|permission| {
+            permission.has_create_object
+                && groups
+                    .as_ref()
+                    .is_none_or(|groups| groups.contains(&permission.group_id))
+        });
+        if !allowed {
+            return Err(forbidden(&match self.client.username() {
+                Some(username) => format!("no group of {} may create objects there", username),
+                None => "no group may create objects there".to_string(),
+            }));
+        }
+
+        let patch = ObjectPatch {
+            namespace_id: Some(namespace_id.into()),
+            ..Default::default()
+        };
+
+        let url_params: UrlParams =
+            vec![("class_id".into(), object.hubuum_class_id.to_string().into())];
+        let moved = self
+            .client
+            .patch(Object::default(), object.id, url_params, patch)
+            .map_err(|err| match err {
+                ApiError::HttpWithBody {
+                    status: StatusCode::NOT_FOUND,
+                    ..
+                } => ApiError::NotFound(format!("object {}", object.id)),
+                ApiError::HttpWithBody {
+                    status, message, ..
+                } if is_denied(status) => forbidden(&message),
+                err => err,
+            })?;
+
+        Ok(Handle::new(self.client.clone(), moved))
+    }
+
+    /// The ids of the groups of the user the client logged in as, `None` if
+    /// it logged in with a token.
+    fn user_groups(&self) -> Result<Option<Vec<i32>>, ApiError> {
+        let Some(username) = self.client.username() else {
+            return Ok(None);
+        };
+        let user = self
+            .client
+            .users()
+            .find()
+            .add_filter_name_exact(username)
+            .execute_expecting_single_result()?;
+        let groups = self
+            .client
+            .groups()
+            .find()
+            .add_filter_equals("users__id", user.id)
+            .execute()?;
+        Ok(Some(groups.iter().map(|group| group.id).collect()))
+    }
+
+    /// The operations an update of the object data to `data` would perform.
+    pub fn data_changes(&self, data: &Value) -> Vec<JsonOp> {
+        diff::json(self.resource.data.as_ref().unwrap_or(&Value::Null), data)
+    }
+
+    /// Replace the data of the object.
+    ///
+    /// The changed paths are logged at debug level before the object is
+    /// patched, without their values, which only appear in the body log as
+    /// part of the request. The request is skipped if the data is unchanged.
+    pub fn update_data(&self, data: Value) -> Result<Handle<Object>, ApiError> {
+        let object = &self.resource;
+        let changes = self.data_changes(&data);
+        if changes.is_empty() {
+            debug!(
+                target: HTTP,
+                "Data of object {} ({}) is unchanged",
+                object.id, object.name
+            );
+            return Ok(Handle::new(self.client.clone(), object.clone()));
+        }
+        let paths: Vec<&str> = changes.iter().map(JsonOp::path).collect();
+        debug!(
+            target: HTTP,
+            "Object {} ({}) data changes at {}",
+            object.id,
+            object.name,
+            paths.join(", ")
+        );
+
+        let patch = ObjectPatch {
+            data: Some(data),
+            ..Default::default()
+        };
+        let url_params: UrlParams =
+            vec![("class_id".into(), object.hubuum_class_id.to_string().into())];
+        let updated = self
+            .client
+            .patch(Object::default(), object.id, url_params, patch)?;
+
+        Ok(Handle::new(self.client.clone(), updated))
+    }
+
+    /// Patch the object, unless someone else changed it since this handle
+    /// fetched it.
+    ///
+    /// The `updated_at` seen here is compared with the one on the server
+    /// before patching, and sent along as `If-Unmodified-Since` so that
+    /// servers honoring it also catch changes made in between. Either way, a
+    /// concurrent change fails with `ApiError::Conflict` and leaves the object
+    /// as the other editor left it; fetch it again to retry.
+    pub fn update_if_unchanged(&self, patch: ObjectPatch) -> Result<Handle<Object>, ApiError> {
+        let object = &self.resource;
+        let current = self
+            .client
+            .objects(object.hubuum_class_id.id())
+            .find()
+            .add_filter_id(object.id)
+            .execute_expecting_single_result()?;
+        if current.updated_at != object.updated_at {
+            return Err(ApiError::Conflict(format!(
+                "object {} ({}) was updated at {}, after {}",
+                object.id, object.name, current.updated_at, object.updated_at
+            )));
+        }
+
+        let url_params: UrlParams =
+            vec![("class_id".into(), object.hubuum_class_id.to_string().into())];
+        let updated = self.client.patch_unmodified_since(
+            Object::default(),
+            object.id,
+            url_params,
+            patch,
+            object.updated_at,
+        )?;
+
+        Ok(Handle::new(self.client.clone(), updated))
+    }
+}
+
+impl Handle<Class> {
+    /// The change history of the class, as recorded by the server.
+    pub fn history(&self) -> Result<Vec<Revision>, ApiError> {
+        let url_params: UrlParams = vec![("class_id".into(), self.resource.id.to_string().into())];
+        history(&self.client, &Endpoint::ClassHistory, url_params)
+    }
+
+    /// Counts of what the class contains and takes part in.
+    pub fn stats(&self) -> Result<ClassStats, ApiError> {
+        let relations = class_relations(&self.client, &[self.resource.id])?;
+        Ok(ClassStats {
+            objects: self.object_count()?,
+            class_relations: relations.len(),
+            object_relations: object_relation_count(&self.client, &relations)?,
+        })
+    }
+
+    /// The relations with the class on either end.
+    pub fn relations(&self) -> Result<Vec<ClassRelation>, ApiError> {
+        let url_params: UrlParams = vec![("class_id".into(), self.resource.id.to_string().into())];
+        Ok(self
+            .client
+            .request_with_endpoint(
+                reqwest::Method::GET,
+                &Endpoint::ClassRelationsForClass,
+                url_params,
+                vec![],
+                (),
+            )?
+            .unwrap_or_default())
+    }
+
+    /// The classes at the other end of the relations of the class.
+    ///
+    /// A class related to itself is included once. The classes are fetched
+    /// by their ids, and fail with `ApiError::NotFound` if any of them cannot
+    /// be, as when they are not visible to the user.
+    pub fn related_classes(&self) -> Result<Vec<Class>, ApiError> {
+        let id = self.resource.id;
+        let mut ids: Vec<i32> = self
+            .relations()?
+            .iter()
+            .map(|relation| {
+                if relation.from_hubuum_class_id == id {
+                    relation.to_hubuum_class_id
+                } else {
+                    relation.from_hubuum_class_id
+                }
+            })
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+
+        let mut classes = resolve_all(&self.client.classes(), &ids, "Class")?;
+        Ok(ids.iter().filter_map(|id| classes.remove(id)).collect())
+    }
+
+    /// The objects of the class.
+    pub fn objects(&self) -> Resource<Object> {
+        self.client.objects(self.resource.id)
+    }
+
+    /// The number of objects in the class.
+    pub fn object_count(&self) -> Result<usize, ApiError> {
+        self.objects().count()
+    }
+
+    /// Delete the class, unless it still contains objects.
+    ///
+    /// Refuses with `ApiError::NotEmpty` if any object is left; deleting
+    /// through `Resource::delete` forces the deletion regardless.
+    pub fn delete_if_empty(&self) -> Result<(), ApiError> {
+        let class = &self.resource;
+        let count = self.object_count()?;
+        if count > 0 {
+            return Err(ApiError::NotEmpty(format!(
+                "class {} ({}) still contains {} objects",
+                class.id, class.name, count
+            )));
+        }
+        self.client.classes().delete(class.id)
+    }
+}
+
+impl Handle<Group> {
+    /// The permissions granted to the group, across all namespaces.
+    pub fn permissions(&self) -> Result<Vec<Permission>, ApiError> {
+        let url_params: UrlParams = vec![("group_id".into(), self.resource.id.to_string().into())];
+        Ok(self
+            .client
+            .request_with_endpoint(
+                reqwest::Method::GET,
+                &Endpoint::GroupPermissions,
+                url_params,
+                vec![],
+                (),
+            )?
+            .unwrap_or_default())
+    }
+
+    /// Grant the group `set` on each of the namespaces.
+    ///
+    /// Namespaces where the group already holds permissions have them
+    /// replaced by `set`. Failures are recorded per namespace id and do not
+    /// stop the other namespaces.
+    pub fn apply_permission_template(
+        &self,
+        namespaces: &[i32],
+        set: PermissionSet,
+    ) -> BulkResult<Permission> {
+        let group_id = self.resource.id;
+        debug!(
+            target: HTTP,
+            "Applying permissions of group {} to {} namespaces",
+            group_id,
+            namespaces.len()
+        );
+        let outcomes = bulk::run(
+            namespaces.to_vec(),
+            DEFAULT_BULK_CONCURRENCY,
+            |namespace_id| {
+                let permissions = self.client.namespace_permissions(namespace_id);
+                let outcome = permissions
+                    .find()
+                    .add_filter_equals("group_id", group_id)
+                    .execute()
+                    .and_then(|existing| match existing.first() {
+                        Some(permission) => permissions.update(permission.id, set.to_patch()),
+                        None => permissions.create(set.to_post(group_id)),
+                    });
+                (namespace_id, outcome)
+            },
+        );
+
+        let mut result = BulkResult::default();
+        for (namespace_id, outcome) in outcomes {
+            match outcome {
+                Ok(permission) => result.succeeded.push(permission),
+                Err(err) => result.failed.push((namespace_id, err)),
+            }
+        }
+        result
+    }
+}
+
+/// Counts of the content of a namespace, see `Handle<Namespace>::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NamespaceStats {
+    pub classes: usize,
+    pub objects: usize,
+    /// Relations with a class of the namespace on either end.
+    pub class_relations: usize,
+    pub object_relations: usize,
+}
+
+/// Counts of the content of a class, see `Handle<Class>::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClassStats {
+    pub objects: usize,
+    /// Relations with the class on either end.
+    pub class_relations: usize,
+    pub object_relations: usize,
+}
+
+/// Everything `Handle<Namespace>::delete_recursive` would remove, in the
+/// order it is removed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NamespaceDeletion {
+    pub namespace_id: i32,
+    pub object_relations: Vec<i32>,
+    pub class_relations: Vec<i32>,
+    /// Objects as (class id, object id).
+    pub objects: Vec<(i32, i32)>,
+    pub classes: Vec<i32>,
+    pub permissions: Vec<i32>,
+}
+
+impl NamespaceDeletion {
+    /// A token identifying this exact plan, to pass to `delete_recursive`.
+    ///
+    /// The token changes whenever the content of the namespace does, so a
+    /// deletion confirmed against an older listing is refused.
+    pub fn confirm_token(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.namespace_id.to_le_bytes());
+        let groups: [Vec<i32>; 5] = [
+            self.object_relations.clone(),
+            self.class_relations.clone(),
+            self.objects.iter().map(|(_, id)| *id).collect(),
+            self.classes.clone(),
+            self.permissions.clone(),
+        ];
+        for mut ids in groups {
+            ids.sort_unstable();
+            hasher.update((ids.len() as u64).to_le_bytes());
+            for id in ids {
+                hasher.update(id.to_le_bytes());
+            }
+        }
+        hex::encode(&hasher.finalize()[..8])
+    }
+}
+
+impl std::fmt::Display for NamespaceDeletion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "namespace {}: {} object relations, {} class relations, {} objects, {} classes, {} permissions",
+            self.namespace_id,
+            self.object_relations.len(),
+            self.class_relations.len(),
+            self.objects.len(),
+            self.classes.len(),
+            self.permissions.len()
+        )
+    }
+}
+
+impl Handle<Namespace> {
+    /// The class with the given name in this namespace.
+    ///
+    /// Names are resolved through the session cache, if there is one.
+    pub fn class(&self, name: &str) -> Result<Handle<Class>, ApiError> {
+        let key = format!("{}/{}", self.resource.id, name);
+        let class = cache::resolve_named(self.client.cache(), &key, || {
+            self.client
+                .classes()
+                .find()
+                .add_filter_equals("namespace_id", self.resource.id)
+                .add_filter_name_exact(name)
+                .execute_expecting_single_result()
+        })?;
+        Ok(Handle::new(self.client.clone(), class))
+    }
+
+    /// List what `delete_recursive` would remove, without removing anything.
+    ///
+    /// Relations are included if either end is a class of the namespace,
+    /// even if the other end lives in another namespace.
+    pub fn deletion_plan(&self) -> Result<NamespaceDeletion, ApiError> {
+        let namespace_id = self.resource.id;
+        let classes: Vec<i32> = self
+            .client
+            .classes()
+            .find()
+            .add_filter_equals("namespace_id", namespace_id)
+            .execute()?
+            .iter()
+            .map(|class| class.id)
+            .collect();
+
+        let mut plan = NamespaceDeletion {
+            namespace_id,
+            ..Default::default()
+        };
+        for &class_id in &classes {
+            for object in self.client.objects(class_id).find().execute()? {
+                plan.objects.push((class_id, object.id));
+            }
+        }
+        plan.class_relations = class_relations(&self.client, &classes)?;
+        for &class_relation_id in &plan.class_relations {
+            for relation in self
+                .client
+                .object_relation()
+                .find()
+                .add_filter_equals("class_relation_id", class_relation_id)
+                .execute()?
+            {
+                plan.object_relations.push(relation.id);
+            }
+        }
+        plan.classes = classes;
+        plan.permissions = self
+            .client
+            .namespace_permissions(namespace_id)
+            .find()
+            .execute()?
+            .iter()
+            .map(|permission| permission.id)
+            .collect();
+        Ok(plan)
+    }
+
+    /// Counts of what the namespace contains.
+    ///
+    /// Objects and object relations are counted without being downloaded
+    /// where the server reports `X-Total-Count`.
+    pub fn stats(&self) -> Result<NamespaceStats, ApiError> {
+        let classes: Vec<i32> = self
+            .client
+            .classes()
+            .find()
+            .add_filter_equals("namespace_id", self.resource.id)
+            .execute()?
+            .iter()
+            .map(|class| class.id)
+            .collect();
+        let relations = class_relations(&self.client, &classes)?;
+
+        let mut stats = NamespaceStats {
+            classes: classes.len(),
+            class_relations: relations.len(),
+            ..Default::default()
+        };
+        for &class_id in &classes {
+            stats.objects += self.client.objects(class_id).count()?;
+        }
+        stats.object_relations = object_relation_count(&self.client, &relations)?;
+        Ok(stats)
+    }
+
+    /// Delete the namespace and everything in it.
+    ///
+    /// `confirm_token` must be the token of a `deletion_plan` taken earlier;
+    /// the plan is listed again and nothing is deleted unless the tokens
+    /// match. Object relations go first, then class relations, objects,
+    /// classes, permissions and finally the namespace. A stage with failures
+    /// stops the deletion before the next stage starts, and its errors are
+    /// returned together.
+    pub fn delete_recursive(&self, confirm_token: &str) -> Result<NamespaceDeletion, ApiError> {
+        let plan = self.deletion_plan()?;
+        if plan.confirm_token() != confirm_token {
+            return Err(ApiError::ConfirmationMismatch(format!(
+                "the content of {} changed since the token was issued",
+                plan
+            )));
+        }
+        debug!(target: HTTP, "Deleting {}", plan);
+
+        let client = &self.client;
+        let stage = |deletions: Vec<Result<(), ApiError>>| match ApiError::from_errors(
+            deletions.into_iter().filter_map(Result::err),
+        ) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        };
+        stage(
+            plan.object_relations
+                .iter()
+                .map(|&id| client.object_relation().delete(id))
+                .collect(),
+        )?;
+        stage(
+            plan.class_relations
+                .iter()
+                .map(|&id| client.class_relation().delete(id))
+                .collect(),
+        )?;
+        stage(
+            plan.objects
+                .iter()
+                .map(|&(class_id, id)| client.objects(class_id).delete(id))
+                .collect(),
+        )?;
+        stage(
+            plan.classes
+                .iter()
+                .map(|&id| client.classes().delete(id))
+                .collect(),
+        )?;
+        stage(
+            plan.permissions
+                .iter()
+                .map(|&id| client.namespace_permissions(plan.namespace_id).delete(id))
+                .collect(),
+        )?;
+        client.namespaces().delete(plan.namespace_id)?;
+        Ok(plan)
+    }
+}
+
+/// The ids of the class relations with any of the classes on either end.
+fn class_relations(client: &Client<Authenticated>, classes: &[i32]) -> Result<Vec<i32>, ApiError> {
+    let mut ids = vec![];
+    for &class_id in classes {
+        for key in ["from_hubuum_class_id", "to_hubuum_class_id"] {
+            for relation in client
+                .class_relation()
+                .find()
+                .add_filter_equals(key, class_id)
+                .execute()?
+            {
+                if !ids.contains(&relation.id) {
+                    ids.push(relation.id);
+                }
+            }
+        }
+    }
+    Ok(ids)
+}
+
+fn object_relation_count(
+    client: &Client<Authenticated>,
+    class_relations: &[i32],
+) -> Result<usize, ApiError> {
+    let mut count = 0;
+    for &class_relation_id in class_relations {
+        count += client
+            .object_relation()
+            .find()
+            .add_filter_equals("class_relation_id", class_relation_id)
+            .count()?;
+    }
+    Ok(count)
+}
+
+fn history(
+    client: &Client<Authenticated>,
+    endpoint: &Endpoint,
+    url_params: UrlParams,
+) -> Result<Vec<Revision>, ApiError> {
+    client
+        .request_with_endpoint(reqwest::Method::GET, endpoint, url_params, vec![], ())?
+        .ok_or(ApiError::EmptyResult(
+            "History returned empty result".into(),
+        ))
+}
+
+fn is_denied(status: StatusCode) -> bool {
+    matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+}
+
+/// The resources with the given ids by id, failing with `NotFound` for
+/// the ids the server did not return.
+fn resolve_all<T>(
+    resource: &Resource<T>,
+    ids: &[i32],
+    kind: &str,
+) -> Result<HashMap<i32, T::GetOutput>, ApiError>
+where
+    T: ApiResource,
+    T::GetOutput: GetID + Clone,
+{
+    let resolved = resource.resolve_handles(ids)?;
+    if !resolved.is_complete() {
+        let missing: Vec<String> = resolved.missing.iter().map(i32::to_string).collect();
+        return Err(ApiError::NotFound(format!(
+            "{} {}",
+            kind,
+            missing.join(", ")
+        )));
+    }
+    Ok(resolved
+        .handles
+        .into_iter()
+        .map(|handle| (handle.id(), handle.into_resource()))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::testing::{Reply, TestServer};
+    use crate::Credentials;
+
+    const STAMPS: &str =
+        r#""created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00""#;
+
+    fn object_json(namespace_id: i32) -> String {
+        format!(
+            r#"{{"id": 1, "name": "host", "namespace_id": {}, "hubuum_class_id": 5, "description": "", "data": null, {}}}"#,
+            namespace_id, STAMPS
+        )
+    }
+
+    fn permissions_json(grants: &[(i32, bool)]) -> String {
+        let rows: Vec<String> = grants
+            .iter()
+            .map(|(group_id, create)| {
+                format!(
+                    r#"{{"id": {group_id}, "namespace_id": 2, "group_id": {group_id},
+                    "has_read_namespace": true, "has_update_namespace": false,
+                    "has_delete_namespace": false, "has_delegate_namespace": false,
+                    "has_create_class": false, "has_read_class": true,
+                    "has_update_class": false, "has_delete_class": false,
+                    "has_create_object": {create}, "has_read_object": true,
+                    "has_update_object": {create}, "has_delete_object": false, {STAMPS}}}"#
+                )
+            })
+            .collect();
+        format!("[{}]", rows.join(","))
+    }
+
+    /// A server where alice is in group 7, which may create objects in
+    /// namespace 2, while only group 8 may in namespace 3.
+    fn move_server() -> TestServer {
+        TestServer::start(|request| match request.path.as_str() {
+            "/api/v0/auth/login" => Reply::json(200, r#"{"token": "token"}"#),
+            "/api/v1/namespaces/2/permissions/" => {
+                Reply::json(200, permissions_json(&[(7, true), (8, false)]))
+            }
+            "/api/v1/namespaces/3/permissions/" => {
+                Reply::json(200, permissions_json(&[(7, false), (8, true)]))
+            }
+            path if path.starts_with("/api/v1/namespaces/") => {
+                Reply::json(404, r#"{"message": "no such namespace"}"#)
+            }
+            path if path.starts_with("/api/v1/iam/users/") => Reply::json(
+                200,
+                format!(
+                    r#"[{{"id": 1, "username": "alice", "email": "", {}}}]"#,
+                    STAMPS
+                ),
+            ),
+            path if path.starts_with("/api/v1/iam/groups/") => Reply::json(
+                200,
+                format!(
+                    r#"[{{"id": 7, "groupname": "ops", "description": "", {}}}]"#,
+                    STAMPS
+                ),
+            ),
+            "/api/v1/classes/5/1" if request.method == "PATCH" => Reply::json(200, object_json(2)),
+            path => panic!("unexpected request for {}", path),
+        })
+    }
+
+    fn object_handle(server: &TestServer) -> Handle<Object> {
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login(Credentials::new("alice".into(), "secret".into()))
+            .unwrap();
+        Handle::new(client, serde_json::from_str(&object_json(1)).unwrap())
+    }
+
+    fn patches(server: &TestServer) -> usize {
+        server
+            .requests()
+            .iter()
+            .filter(|request| request.method == "PATCH")
+            .count()
+    }
+
+    #[test]
+    fn test_move_to_namespace_with_permission() {
+        let server = move_server();
+        let moved = object_handle(&server).move_to_namespace(2).unwrap();
+        assert_eq!(moved.resource().namespace_id, 2);
+        assert_eq!(patches(&server), 1);
+    }
+
+    #[test]
+    fn test_move_to_namespace_without_permission() {
+        let server = move_server();
+        let Err(err) = object_handle(&server).move_to_namespace(3) else {
+            panic!("the move was not refused");
+        };
+        assert!(matches!(err, ApiError::MoveForbidden(ref reason) if reason.contains("alice")));
+        assert_eq!(patches(&server), 0);
+    }
+
+    #[test]
+    fn test_move_to_missing_namespace() {
+        let server = move_server();
+        let Err(err) = object_handle(&server).move_to_namespace(9) else {
+            panic!("the move was not refused");
+        };
+        assert!(matches!(err, ApiError::NotFound(_)));
+        assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+        assert_eq!(patches(&server), 0);
+    }
+
+    #[test]
+    fn test_confirm_token_follows_content() {
+        let plan = NamespaceDeletion {
+            namespace_id: 1,
+            objects: vec![(2, 3), (2, 4)],
+            classes: vec![2],
+            ..Default::default()
+        };
+        let reordered = NamespaceDeletion {
+            objects: vec![(2, 4), (2, 3)],
+            ..plan.clone()
+        };
+        let grown = NamespaceDeletion {
+            permissions: vec![5],
+            ..plan.clone()
+        };
+        assert_eq!(plan.confirm_token(), reordered.confirm_token());
+        assert_ne!(plan.confirm_token(), grown.confirm_token());
+        assert_eq!(plan.confirm_token().len(), 16);
+        assert_eq!(
+            plan.to_string(),
+            "namespace 1: 0 object relations, 0 class relations, 2 objects, 1 classes, 0 permissions"
+        );
+    }
+
+    #[test]
+    fn test_neighbors_fetch_objects_per_class() {
+        let object = |id: i32, class_id: i32| {
+            format!(
+                r#"{{"id": {id}, "name": "o{id}", "namespace_id": 1, "hubuum_class_id": {class_id},
+                "description": "", "data": null, {STAMPS}}}"#
+            )
+        };
+        let server = TestServer::start(move |request| {
+            let body = match request.path.as_str() {
+                "/api/v1/classes/5/1/relations/" => format!(
+                    r#"[{{"id": 10, "from_hubuum_object_id": 1, "to_hubuum_object_id": 7,
+                        "class_relation_id": 100, {STAMPS}}},
+                    {{"id": 11, "from_hubuum_object_id": 8, "to_hubuum_object_id": 1,
+                        "class_relation_id": 101, {STAMPS}}},
+                    {{"id": 12, "from_hubuum_object_id": 1, "to_hubuum_object_id": 9,
+                        "class_relation_id": 102, {STAMPS}}}]"#
+                ),
+                "/api/v1/relations/classes/?id__in=100,101,102" => format!(
+                    r#"[{{"id": 100, "from_hubuum_class_id": 5, "to_hubuum_class_id": 6, {STAMPS}}},
+                    {{"id": 101, "from_hubuum_class_id": 6, "to_hubuum_class_id": 5, {STAMPS}}},
+                    {{"id": 102, "from_hubuum_class_id": 5, "to_hubuum_class_id": 3, {STAMPS}}}]"#
+                ),
+                "/api/v1/classes/6/?id__in=7,8" => format!("[{}, {}]", object(7, 6), object(8, 6)),
+                "/api/v1/classes/3/?id__equals=9" => format!("[{}]", object(9, 3)),
+                path => panic!("unexpected request for {}", path),
+            };
+            Reply::json(200, body)
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        let handle: Handle<Object> =
+            Handle::new(client, serde_json::from_str(&object_json(1)).unwrap());
+
+        let neighbors: Vec<(RelationDirection, i32)> = handle
+            .neighbors()
+            .unwrap()
+            .into_iter()
+            .map(|neighbor| (neighbor.direction, neighbor.object.id))
+            .collect();
+        assert_eq!(
+            neighbors,
+            vec![
+                (RelationDirection::Outgoing, 7),
+                (RelationDirection::Incoming, 8),
+                (RelationDirection::Outgoing, 9),
+            ]
+        );
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[test]
+    fn test_related_classes_fails_on_unresolved() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/api/v1/relations/classes/5/" => Reply::json(
+                200,
+                format!(
+                    r#"[{{"id": 1, "from_hubuum_class_id": 5, "to_hubuum_class_id": 6, {STAMPS}}},
+                    {{"id": 2, "from_hubuum_class_id": 9, "to_hubuum_class_id": 5, {STAMPS}}}]"#
+                ),
+            ),
+            "/api/v1/classes/?id__in=6,9" => Reply::json(
+                200,
+                format!(
+                    r#"[{{"id": 6, "name": "rack", "json_schema": null,
+                    "namespace": {{"id": 1, "name": "dc", "description": "", {STAMPS}}},
+                    "validate_schema": false, "description": "", {STAMPS}}}]"#
+                ),
+            ),
+            path => panic!("unexpected request for {}", path),
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        let class: Handle<Class> = Handle::new(client, crate::client::FromId::from_id(5));
+
+        let Err(err) = class.related_classes() else {
+            panic!("class 9 was not resolved");
+        };
+        assert_eq!(err.to_string(), "Not found: Class 9");
+    }
+}
@@ -1,13 +1,48 @@
 use std::borrow::Cow;
+use std::sync::{Arc, RwLock};
 
 use crate::endpoints::Endpoint;
 use crate::QueryFilter;
 
 pub mod r#async;
+mod benchmark;
+mod builder;
+pub(crate) mod bulk;
+mod cache;
+mod compression;
+mod core;
+mod curl;
+pub mod events;
+mod failover;
+mod handle;
+mod multi;
+mod numbers;
+mod retry;
+pub mod scheduler;
+mod scoped;
 pub mod sync;
+mod task_scope;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod watch;
 
+pub use self::benchmark::{LatencyProfile, PingReport};
+pub use self::builder::ClientBuilder;
+pub use self::bulk::{BulkResult, ProgressSink, DEFAULT_BULK_CONCURRENCY};
+pub use self::cache::{CacheStats, SessionCache};
+pub use self::compression::DEFAULT_COMPRESSION_THRESHOLD;
+pub(crate) use self::core::{one_or_err, short_type_name};
+pub use self::curl::{CurlHook, TOKEN_PLACEHOLDER};
+pub use self::failover::DEFAULT_FAILOVER_RECHECK;
+pub use self::handle::{ClassStats, Handle, NamespaceDeletion, NamespaceStats, Resolved};
+pub use self::multi::{MultiClient, MultiResult};
+pub use self::numbers::NumberMode;
 pub use self::r#async::Client as AsyncClient;
+pub use self::retry::{RetryPolicy, IDEMPOTENCY_KEY_HEADER};
+pub use self::scheduler::Priority;
+pub use self::scoped::{NamespaceScoped, ScopedHandle, ScopedResource};
 pub use self::sync::Client as SyncClient;
+pub use self::task_scope::TaskScope;
 
 use crate::resources::ApiResource;
 
@@ -17,6 +52,48 @@ pub trait GetID {
     fn id(&self) -> i32;
 }
 
+/// Construct a placeholder resource knowing only its id.
+pub trait FromId {
+    fn from_id(id: i32) -> Self;
+}
+
+/// How a create request treats a resource that already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CreateMode {
+    /// Fail with the conflict reported by the server.
+    #[default]
+    Fail,
+    /// On 409 Conflict, look up the resource with the same name and return it.
+    ReturnExistingOnConflict,
+}
+
+/// Options for `create_with`.
+///
+/// Creates made with options always carry an idempotency key, the one given
+/// here or a UUID generated per call, so the retry policy retries them like
+/// any other idempotent request. The key is kept across retries, and a server
+/// deduplicating on it creates the resource only once.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CreateOptions {
+    pub mode: CreateMode,
+    /// The key to send, for callers that retry a create across runs of their
+    /// own and need the server to recognize it.
+    pub idempotency_key: Option<String>,
+}
+
+impl From<CreateMode> for CreateOptions {
+    fn from(mode: CreateMode) -> Self {
+        CreateOptions {
+            mode,
+            ..Default::default()
+        }
+    }
+}
+
+pub trait GetUpdatedAt {
+    fn updated_at(&self) -> chrono::NaiveDateTime;
+}
+
 trait ClientCore {
     fn build_url(&self, endpoint: &Endpoint, url_params: UrlParams) -> String;
 }
@@ -25,10 +102,44 @@ pub trait IntoResourceFilter<T: ApiResource> {
     fn into_resource_filter(self) -> Vec<QueryFilter>;
 }
 
+impl<T: ApiResource> IntoResourceFilter<T> for Vec<QueryFilter> {
+    fn into_resource_filter(self) -> Vec<QueryFilter> {
+        self
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Unauthenticated;
 
 #[derive(Debug, Clone)]
 pub struct Authenticated {
-    token: String,
+    token: Arc<RwLock<String>>,
+    /// The user logged in as, unless the client logged in with a token.
+    username: Option<String>,
+}
+
+impl Authenticated {
+    fn new(token: String) -> Self {
+        Authenticated {
+            token: Arc::new(RwLock::new(token)),
+            username: None,
+        }
+    }
+
+    fn with_username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    fn token(&self) -> String {
+        self.token.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set_token(&self, token: String) {
+        *self.token.write().unwrap_or_else(|e| e.into_inner()) = token;
+    }
 }
@@ -0,0 +1,177 @@
+//! Running the same operation against several servers.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials, MultiClient};
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut servers = MultiClient::new();
+//! for (name, url) in [("staging", "https://staging.example.com"), ("prod", "https://api.example.com")] {
+//!     let client = SyncClient::new(BaseUrl::from_str(url)?).login(Credentials::from_env()?)?;
+//!     servers = servers.with_server(name, client);
+//! }
+//! let hosts = servers.run_parallel(|client| {
+//!     client.objects(1).find().add_filter_name_exact("web01").execute()
+//! });
+//! for (server, result) in &hosts.results {
+//!     println!("{}: {:?}", server, result.as_ref().map(|found| found.len()));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use super::bulk;
+use super::sync::Client;
+use super::Authenticated;
+use crate::errors::ApiError;
+use crate::logging::{debug, HTTP};
+
+/// Authenticated clients to several servers, addressed by name.
+#[derive(Clone, Default)]
+pub struct MultiClient {
+    servers: Vec<(String, Client<Authenticated>)>,
+}
+
+/// The outcome of an operation run by a `MultiClient`, per server.
+///
+/// A failure on one server does not stop the others.
+#[derive(Debug)]
+pub struct MultiResult<T> {
+    /// The result of every server, in the order the servers were added.
+    pub results: Vec<(String, Result<T, ApiError>)>,
+}
+
+impl<T> MultiResult<T> {
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|(_, result)| result.is_ok())
+    }
+
+    /// The result of the server with the given name.
+    pub fn get(&self, server: &str) -> Option<&Result<T, ApiError>> {
+        self.results
+            .iter()
+            .find(|(name, _)| name == server)
+            .map(|(_, result)| result)
+    }
+
+    /// The names of the servers the operation failed on.
+    pub fn failed(&self) -> Vec<&str> {
+        self.results
+            .iter()
+            .filter(|(_, result)| result.is_err())
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// The results of all servers, or all failures combined into
+    /// `ApiError::Multiple`.
+    pub fn into_result(self) -> Result<Vec<(String, T)>, ApiError> {
+        let mut succeeded = vec![];
+        let mut failed = vec![];
+        for (name, result) in self.results {
+            match result {
+                Ok(value) => succeeded.push((name, value)),
+                Err(err) => failed.push(err),
+            }
+        }
+        match ApiError::from_errors(failed) {
+            Some(err) => Err(err),
+            None => Ok(succeeded),
+        }
+    }
+}
+
+impl MultiClient {
+    pub fn new() -> Self {
+        MultiClient::default()
+    }
+
+    /// Add a server under `name`, replacing any server added under the same name.
+    pub fn with_server(mut self, name: &str, client: Client<Authenticated>) -> Self {
+        self.servers.retain(|(existing, _)| existing != name);
+        self.servers.push((name.to_string(), client));
+        self
+    }
+
+    /// The names of the servers, in the order they were added.
+    pub fn servers(&self) -> Vec<&str> {
+        self.servers.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn client(&self, name: &str) -> Option<&Client<Authenticated>> {
+        self.servers
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, client)| client)
+    }
+
+    /// Run `f` against one server after the other.
+    pub fn run<T, F>(&self, f: F) -> MultiResult<T>
+    where
+        T: Send,
+        F: Fn(&Client<Authenticated>) -> Result<T, ApiError> + Sync,
+    {
+        self.run_with(1, f)
+    }
+
+    /// Run `f` against all servers at once, one thread per server.
+    pub fn run_parallel<T, F>(&self, f: F) -> MultiResult<T>
+    where
+        T: Send,
+        F: Fn(&Client<Authenticated>) -> Result<T, ApiError> + Sync,
+    {
+        self.run_with(self.servers.len(), f)
+    }
+
+    fn run_with<T, F>(&self, concurrency: usize, f: F) -> MultiResult<T>
+    where
+        T: Send,
+        F: Fn(&Client<Authenticated>) -> Result<T, ApiError> + Sync,
+    {
+        debug!(
+            target: HTTP,
+            "Running on {} servers, {} at a time",
+            self.servers.len(),
+            concurrency
+        );
+        let results = bulk::run(
+            self.servers.iter().collect(),
+            concurrency,
+            |(name, client)| (name.clone(), f(client)),
+        );
+        MultiResult { results }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::StatusCode;
+
+    fn result() -> MultiResult<usize> {
+        MultiResult {
+            results: vec![
+                ("staging".to_string(), Ok(2)),
+                (
+                    "prod".to_string(),
+                    Err(ApiError::HttpWithBody {
+                        status: StatusCode::SERVICE_UNAVAILABLE,
+                        message: "down".to_string(),
+                        url: "https://prod.example.com/api/v1/classes/".to_string(),
+                    }),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_multi_result() {
+        let result = result();
+        assert!(!result.is_success());
+        assert_eq!(result.failed(), vec!["prod"]);
+        assert!(matches!(result.get("staging"), Some(Ok(2))));
+        assert!(result.get("dev").is_none());
+        assert!(matches!(
+            result.into_result(),
+            Err(ApiError::HttpWithBody { status, .. }) if status == StatusCode::SERVICE_UNAVAILABLE
+        ));
+    }
+}
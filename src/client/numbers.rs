@@ -0,0 +1,264 @@
+//! Decoding numbers in object data without losing precision.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use hubuum_client::client::NumberMode;
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = SyncClient::builder(BaseUrl::from_str("https://api.example.com")?)
+//!     .number_mode(NumberMode::String)
+//!     .build()?
+//!     .login(Credentials::new("user".into(), "pass".into()))?;
+//! for object in client.objects(1).find().execute()? {
+//!     // Numbers arrive as strings, exactly as the server sent them.
+//!     println!("{}", object.data.unwrap_or_default()["mass"]);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::ApiError;
+
+/// How numbers in the `data` of objects are decoded.
+///
+/// By default numbers that do not fit an `i64` or `u64` go through an `f64`,
+/// which silently rounds values such as `3.14159265358979323846`. Building
+/// with the `arbitrary-precision` feature keeps every number exactly as
+/// sent, for all of the crate. Where that is not an option, `String` keeps
+/// them exact as strings instead.
+///
+/// Only responses on objects are affected. Since data read as strings would
+/// be written back as strings, a client in `String` mode refuses to send
+/// object data; write data through a client in `Number` mode, or keep it as
+/// sent with `execute_raw` and `create_raw`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberMode {
+    /// As JSON numbers.
+    #[default]
+    Number,
+    /// As JSON strings holding the number as the server sent it.
+    String,
+}
+
+impl NumberMode {
+    /// Rewrite a response body to decode numbers as this mode says.
+    ///
+    /// Bodies that are not valid JSON are left as they are, to fail decoding
+    /// with the usual error.
+    pub(crate) fn apply(self, text: String) -> String {
+        match self {
+            NumberMode::Number => text,
+            NumberMode::String => quote_data_numbers(&text).unwrap_or(text),
+        }
+    }
+
+    /// Check that a request body on objects may be sent in this mode, which
+    /// in `String` mode means it carries no data.
+    pub(crate) fn check_write(self, body: Option<&[u8]>) -> Result<(), ApiError> {
+        let data = body
+            .and_then(|body| serde_json::from_slice::<serde_json::Value>(body).ok())
+            .is_some_and(|body| !body["data"].is_null());
+        match self {
+            NumberMode::String if data => Err(ApiError::UnsupportedHttpOperation(
+                "writing object data with numbers decoded as strings".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The JSON `text` with every number within the value of a `data` field
+/// turned into a string, or `None` if `text` is not valid JSON.
+fn quote_data_numbers(text: &str) -> Option<String> {
+    let mut quoter = Quoter {
+        text: text.as_bytes(),
+        pos: 0,
+        out: Vec::with_capacity(text.len()),
+    };
+    quoter.value(false)?;
+    quoter.whitespace();
+    if quoter.pos != quoter.text.len() {
+        return None;
+    }
+    String::from_utf8(quoter.out).ok()
+}
+
+/// Copies JSON text, quoting numbers where asked to.
+///
+/// Only ASCII bytes are ever split on, so the copy stays valid UTF-8.
+struct Quoter<'a> {
+    text: &'a [u8],
+    pos: usize,
+    out: Vec<u8>,
+}
+
+impl<'a> Quoter<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    /// Copy the bytes up to `end`.
+    fn copy_to(&mut self, end: usize) {
+        self.out.extend_from_slice(&self.text[self.pos..end]);
+        self.pos = end;
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        (self.peek()? == byte).then(|| self.copy_to(self.pos + 1))
+    }
+
+    fn whitespace(&mut self) {
+        let end = self.scan(|byte| byte.is_ascii_whitespace());
+        self.copy_to(end);
+    }
+
+    /// The position of the first byte from here that `accept` rejects.
+    fn scan(&self, accept: impl Fn(u8) -> bool) -> usize {
+        self.pos
+            + self.text[self.pos..]
+                .iter()
+                .take_while(|byte| accept(**byte))
+                .count()
+    }
+
+    fn value(&mut self, quote: bool) -> Option<()> {
+        self.whitespace();
+        match self.peek()? {
+            b'{' => self.object(quote),
+            b'[' => self.array(quote),
+            b'"' => self.string().map(|_| ()),
+            b'-' | b'0'..=b'9' => {
+                let end = self
+                    .scan(|byte| matches!(byte, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'));
+                if quote {
+                    self.out.push(b'"');
+                    self.copy_to(end);
+                    self.out.push(b'"');
+                } else {
+                    self.copy_to(end);
+                }
+                Some(())
+            }
+            _ => {
+                let end = self.scan(|byte| byte.is_ascii_lowercase());
+                (end > self.pos).then(|| self.copy_to(end))
+            }
+        }
+    }
+
+    fn object(&mut self, quote: bool) -> Option<()> {
+        self.expect(b'{')?;
+        self.whitespace();
+        if self.peek()? == b'}' {
+            return self.expect(b'}');
+        }
+        loop {
+            self.whitespace();
+            let key = self.string()?;
+            self.whitespace();
+            self.expect(b':')?;
+            self.value(quote || key == b"data")?;
+            self.whitespace();
+            match self.peek()? {
+                b',' => self.expect(b',')?,
+                _ => return self.expect(b'}'),
+            }
+        }
+    }
+
+    fn array(&mut self, quote: bool) -> Option<()> {
+        self.expect(b'[')?;
+        self.whitespace();
+        if self.peek()? == b']' {
+            return self.expect(b']');
+        }
+        loop {
+            self.value(quote)?;
+            self.whitespace();
+            match self.peek()? {
+                b',' => self.expect(b',')?,
+                _ => return self.expect(b']'),
+            }
+        }
+    }
+
+    /// Copy a string, returning its contents as they appear in the text.
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let text = self.text;
+        let start = self.pos + 1;
+        self.expect(b'"')?;
+        let mut end = start;
+        loop {
+            match text.get(end)? {
+                b'\\' => end += 2,
+                b'"' => break,
+                _ => end += 1,
+            }
+        }
+        self.copy_to(end + 1);
+        Some(&text[start..end])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        data_numbers = {
+            r#"{"id": 7, "data": {"mass": 3.14159265358979323846, "n": [1, -2e-3]}}"#,
+            Some(r#"{"id": 7, "data": {"mass": "3.14159265358979323846", "n": ["1", "-2e-3"]}}"#),
+        },
+        listing = {
+            r#"[{"data":1.5,"name":"data"},{"data":null}]"#,
+            Some(r#"[{"data":"1.5","name":"data"},{"data":null}]"#),
+        },
+        escaped_keys = {
+            r#"{"da\"ta": 1, "data": "1 \" 2", "x": {"data": [true, 0]}}"#,
+            Some(r#"{"da\"ta": 1, "data": "1 \" 2", "x": {"data": [true, "0"]}}"#),
+        },
+        unicode = {
+            r#"{"data": {"名前": "ø", "v": 1}}"#,
+            Some(r#"{"data": {"名前": "ø", "v": "1"}}"#),
+        },
+        invalid = { r#"{"data": [1, 2"#, None },
+        trailing = { r#"{"data": 1} x"#, None },
+    )]
+    fn test_quote_data_numbers(text: &str, expected: Option<&str>) {
+        assert_eq!(quote_data_numbers(text).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_apply() {
+        let text = r#"{"data": {"mass": 0.1000000000000000055511151231257827}}"#.to_string();
+        assert_eq!(NumberMode::Number.apply(text.clone()), text);
+        let value: serde_json::Value =
+            serde_json::from_str(&NumberMode::String.apply(text)).unwrap();
+        assert_eq!(
+            value["data"]["mass"],
+            "0.1000000000000000055511151231257827"
+        );
+        assert_eq!(NumberMode::String.apply("oops".into()), "oops");
+    }
+
+    #[parameterized(
+        number_mode = { NumberMode::Number, Some(r#"{"data": {"mass": 1.5}}"#), true },
+        data = { NumberMode::String, Some(r#"{"data": {"mass": 1.5}}"#), false },
+        no_data = { NumberMode::String, Some(r#"{"name": "sample"}"#), true },
+        null_data = { NumberMode::String, Some(r#"{"data": null}"#), true },
+        no_body = { NumberMode::String, None, true },
+    )]
+    fn test_check_write(mode: NumberMode, body: Option<&str>, allowed: bool) {
+        let result = mode.check_write(body.map(str::as_bytes));
+        assert_eq!(result.is_ok(), allowed, "{:?}", result);
+    }
+
+    #[cfg(feature = "arbitrary-precision")]
+    #[test]
+    fn test_arbitrary_precision() {
+        let value: serde_json::Value =
+            serde_json::from_str(r#"{"mass": 3.14159265358979323846}"#).unwrap();
+        assert_eq!(value["mass"].to_string(), "3.14159265358979323846");
+    }
+}
@@ -0,0 +1,197 @@
+//! Retrying requests that failed for transient reasons.
+use reqwest::{Method, StatusCode};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::core::RequestPlan;
+use crate::errors::ApiError;
+
+/// The header carrying the idempotency key of a POST.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// When and how often to retry failed requests.
+///
+/// Only requests that are safe to repeat are retried: GET, HEAD, OPTIONS,
+/// PUT and DELETE, and POSTs carrying an idempotency key. PATCH and plain
+/// POSTs are never retried, as repeating them may apply a change twice.
+/// Connection errors, timeouts and the statuses 429, 502, 503 and 504 count
+/// as transient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    backoff: Duration,
+    idempotency_keys: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            backoff: Duration::from_millis(200),
+            idempotency_keys: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32) -> Self {
+        RetryPolicy {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// The delay before the first retry, doubled for every further retry.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Send a generated `Idempotency-Key` with every POST, making them retryable.
+    ///
+    /// The key is kept across retries of the same request, so a server that
+    /// honors the header creates the resource only once.
+    pub fn idempotency_keys(mut self) -> Self {
+        self.idempotency_keys = true;
+        self
+    }
+
+    /// The key to send with a request, if any: the one the caller chose, or
+    /// a generated one for POSTs if enabled.
+    pub(crate) fn idempotency_key(&self, plan: &RequestPlan) -> Option<String> {
+        plan.idempotency_key
+            .clone()
+            .or_else(|| (self.idempotency_keys && plan.method == Method::POST).then(generate_key))
+    }
+
+    /// Whether to retry after the given failed attempt, counting from zero.
+    pub(crate) fn should_retry(
+        &self,
+        plan: &RequestPlan,
+        has_key: bool,
+        attempt: u32,
+        err: &ApiError,
+    ) -> bool {
+        attempt < self.max_retries && is_idempotent(&plan.method, has_key) && is_transient(err)
+    }
+
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(attempt))
+    }
+}
+
+fn is_idempotent(method: &Method, has_key: bool) -> bool {
+    match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE => true,
+        Method::POST => has_key,
+        _ => false,
+    }
+}
+
+pub(crate) fn is_transient(err: &ApiError) -> bool {
+    match err {
+        ApiError::Http(err) => err.is_connect() || err.is_timeout(),
+        ApiError::HttpWithBody { status, .. } => matches!(
+            *status,
+            StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        ),
+        _ => false,
+    }
+}
+
+/// A key unique to this process and request, formatted as a version 4 UUID.
+pub(crate) fn generate_key() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let mut hasher = Sha256::new();
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.update(nanos.to_le_bytes());
+    hasher.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    let mut bytes = hasher.finalize()[..16].to_vec();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use yare::parameterized;
+
+    fn unavailable() -> ApiError {
+        ApiError::HttpWithBody {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: String::new(),
+            url: "http://localhost/".to_string(),
+        }
+    }
+
+    #[parameterized(
+        get = { Method::GET, false, true },
+        put = { Method::PUT, false, true },
+        delete = { Method::DELETE, false, true },
+        patch = { Method::PATCH, false, false },
+        post = { Method::POST, false, false },
+        post_with_key = { Method::POST, true, true },
+    )]
+    fn test_should_retry_idempotent_methods(method: Method, has_key: bool, expected: bool) {
+        let plan = RequestPlan::new(method, "http://localhost/".to_string(), None);
+        let policy = RetryPolicy::new(1);
+        assert_eq!(
+            policy.should_retry(&plan, has_key, 0, &unavailable()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_should_retry_transient_errors_within_budget() {
+        let plan = RequestPlan::new(Method::GET, "http://localhost/".to_string(), None);
+        let policy = RetryPolicy::new(2);
+        let not_found = ApiError::HttpWithBody {
+            status: StatusCode::NOT_FOUND,
+            message: String::new(),
+            url: "http://localhost/".to_string(),
+        };
+        assert!(!policy.should_retry(&plan, false, 0, &not_found));
+        assert!(policy.should_retry(&plan, false, 1, &unavailable()));
+        assert!(!policy.should_retry(&plan, false, 2, &unavailable()));
+        assert_eq!(policy.delay(2), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn test_idempotency_keys() {
+        let post = RequestPlan::new(Method::POST, "http://localhost/".to_string(), None);
+        let get = RequestPlan::new(Method::GET, "http://localhost/".to_string(), None);
+        assert_eq!(RetryPolicy::default().idempotency_key(&post), None);
+
+        let policy = RetryPolicy::default().idempotency_keys();
+        let key = policy.idempotency_key(&post).unwrap();
+        assert_eq!(key.len(), 36);
+        assert_eq!(&key[14..15], "4");
+        assert!(matches!(&key[19..20], "8" | "9" | "a" | "b"), "{}", key);
+        assert_ne!(policy.idempotency_key(&post), Some(key));
+        assert_eq!(policy.idempotency_key(&get), None);
+
+        // A key chosen by the caller is sent even without generated keys.
+        let chosen = post.with_idempotency_key("order-17".to_string());
+        assert_eq!(
+            RetryPolicy::default().idempotency_key(&chosen).as_deref(),
+            Some("order-17")
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Admission of requests by priority, for clients shared by interactive and
+//! background work.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use hubuum_client::client::Priority;
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = SyncClient::builder(BaseUrl::from_str("https://api.example.com")?)
+//!     .max_in_flight(4)
+//!     .build()?
+//!     .login(Credentials::new("user".into(), "pass".into()))?;
+//! let sync = client.clone().with_priority(Priority::Background);
+//! std::thread::spawn(move || sync.objects(1).find().execute());
+//! // Waits behind at most the requests already in flight, never behind
+//! // queued background requests.
+//! let namespace = client.namespaces().select_by_name("prod")?;
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::BTreeSet;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use tokio::sync::Notify;
+
+/// The priority of the requests made through a client.
+///
+/// Only matters when the client has a scheduler, see
+/// `ClientBuilder::max_in_flight`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Background,
+}
+
+/// Limits the requests in flight, admitting waiting requests by priority and
+/// then in the order they arrived.
+///
+/// Clones share the same limit, so all clients cloned from one client
+/// compete for the same slots whatever their priority.
+#[derive(Debug, Clone)]
+pub(crate) struct Scheduler {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    max_in_flight: usize,
+    state: Mutex<State>,
+    blocking: Condvar,
+    waiting: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    in_flight: usize,
+    next: u64,
+    queue: BTreeSet<Ticket>,
+}
+
+type Ticket = (Priority, u64);
+
+impl State {
+    fn enqueue(&mut self, priority: Priority) -> Ticket {
+        let ticket = (priority, self.next);
+        self.next += 1;
+        self.queue.insert(ticket);
+        ticket
+    }
+
+    /// Admit the ticket if a slot is free and no ticket is ahead of it.
+    fn admit(&mut self, ticket: Ticket, max_in_flight: usize) -> bool {
+        if self.in_flight >= max_in_flight || self.queue.first() != Some(&ticket) {
+            return false;
+        }
+        self.queue.remove(&ticket);
+        self.in_flight += 1;
+        true
+    }
+}
+
+/// A slot for one request, freed when dropped.
+#[derive(Debug)]
+pub(crate) struct Permit {
+    scheduler: Scheduler,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.scheduler.state().in_flight -= 1;
+        self.scheduler.wake();
+    }
+}
+
+/// Removes a ticket from the queue if its request gives up waiting.
+struct Waiting<'a> {
+    scheduler: &'a Scheduler,
+    ticket: Ticket,
+    admitted: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if !self.admitted {
+            self.scheduler.state().queue.remove(&self.ticket);
+            self.scheduler.wake();
+        }
+    }
+}
+
+impl Scheduler {
+    pub(crate) fn new(max_in_flight: usize) -> Self {
+        Scheduler {
+            inner: Arc::new(Inner {
+                max_in_flight: max_in_flight.max(1),
+                state: Mutex::new(State::default()),
+                blocking: Condvar::new(),
+                waiting: Notify::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn max_in_flight(&self) -> usize {
+        self.inner.max_in_flight
+    }
+
+    /// Wait for a slot, blocking the thread.
+    pub(crate) fn acquire(&self, priority: Priority) -> Permit {
+        let mut state = self.state();
+        let ticket = state.enqueue(priority);
+        while !state.admit(ticket, self.inner.max_in_flight) {
+            state = self
+                .inner
+                .blocking
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        drop(state);
+        // Another slot may be free for the next ticket in line.
+        self.wake();
+        self.permit()
+    }
+
+    /// Wait for a slot without blocking the thread.
+    pub(crate) async fn acquire_async(&self, priority: Priority) -> Permit {
+        let mut waiting = Waiting {
+            scheduler: self,
+            ticket: self.state().enqueue(priority),
+            admitted: false,
+        };
+        loop {
+            let mut notified = std::pin::pin!(self.inner.waiting.notified());
+            notified.as_mut().enable();
+            if self.state().admit(waiting.ticket, self.inner.max_in_flight) {
+                waiting.admitted = true;
+                self.wake();
+                return self.permit();
+            }
+            notified.await;
+        }
+    }
+
+    fn permit(&self) -> Permit {
+        Permit {
+            scheduler: self.clone(),
+        }
+    }
+
+    fn wake(&self) {
+        self.inner.blocking.notify_all();
+        self.inner.waiting.notify_waiters();
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_admission_by_priority() {
+        let scheduler = Scheduler::new(1);
+        let held = scheduler.acquire(Priority::Normal);
+
+        let order = Arc::new(Mutex::new(vec![]));
+        let spawn = |priority: Priority| {
+            let (scheduler, order) = (scheduler.clone(), order.clone());
+            let handle = std::thread::spawn(move || {
+                let _permit = scheduler.acquire(priority);
+                order.lock().unwrap().push(priority);
+            });
+            // Let the thread queue up before the next one.
+            std::thread::sleep(Duration::from_millis(50));
+            handle
+        };
+        let handles = vec![
+            spawn(Priority::Background),
+            spawn(Priority::Normal),
+            spawn(Priority::High),
+        ];
+        drop(held);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![Priority::High, Priority::Normal, Priority::Background]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_wait_leaves_queue() {
+        let scheduler = Scheduler::new(1);
+        let held = scheduler.acquire_async(Priority::Normal).await;
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(10),
+            scheduler.acquire_async(Priority::High),
+        )
+        .await;
+        assert!(waiting.is_err());
+        assert!(scheduler.state().queue.is_empty());
+        drop(held);
+        let _permit = scheduler.acquire(Priority::Background);
+    }
+}
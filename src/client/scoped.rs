@@ -0,0 +1,336 @@
+//! A view of a client restricted to a single namespace.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = SyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//!     .login(Credentials::new("user".into(), "pass".into()))?;
+//! let tenant = client.scoped_to_namespace(3);
+//! // Only classes of namespace 3, whatever else the filters say.
+//! let classes = tenant.classes().find().execute()?;
+//! // Fails with `ApiError::OutOfScope` if object 7 is in another namespace.
+//! tenant.objects(1).delete(7)?;
+//! # Ok(())
+//! # }
+//! ```
+use serde::Serialize;
+use serde_json::Value;
+
+use super::sync::{Client, FilterBuilder, Resource};
+use super::{short_type_name, Authenticated, GetID};
+use crate::errors::ApiError;
+use crate::resources::{
+    ApiResource, Class, ClassRelation, Object, ObjectPatch, ObjectRelation, Revision,
+};
+use crate::Handle;
+
+/// The field holding the namespace of classes and objects.
+const NAMESPACE_FIELD: &str = "namespace_id";
+
+/// A client that only sees and changes the classes and objects of one
+/// namespace.
+///
+/// Searches get a filter on the namespace, and every mutation is checked to
+/// target the namespace before it is sent, so automation working on behalf
+/// of one tenant cannot touch the resources of another by mistake.
+#[derive(Clone)]
+pub struct NamespaceScoped {
+    client: Client<Authenticated>,
+    namespace_id: i32,
+}
+
+impl NamespaceScoped {
+    pub(crate) fn new(client: Client<Authenticated>, namespace_id: i32) -> Self {
+        NamespaceScoped {
+            client,
+            namespace_id,
+        }
+    }
+
+    pub fn namespace_id(&self) -> i32 {
+        self.namespace_id
+    }
+
+    pub fn classes(&self) -> ScopedResource<Class> {
+        ScopedResource {
+            resource: self.client.classes(),
+            namespace_id: self.namespace_id,
+        }
+    }
+
+    pub fn objects(&self, class_id: i32) -> ScopedResource<Object> {
+        ScopedResource {
+            resource: self.client.objects(class_id),
+            namespace_id: self.namespace_id,
+        }
+    }
+}
+
+/// A `Resource` restricted to one namespace, see `NamespaceScoped`.
+pub struct ScopedResource<T: ApiResource> {
+    resource: Resource<T>,
+    namespace_id: i32,
+}
+
+impl<T: ApiResource> ScopedResource<T> {
+    pub fn find(&self) -> FilterBuilder<T> {
+        self.resource
+            .find()
+            .add_filter_equals(NAMESPACE_FIELD, self.namespace_id)
+    }
+
+    pub fn select(&self, id: i32) -> Result<ScopedHandle<T>, ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        let resource = self
+            .find()
+            .add_filter_id(id)
+            .execute_expecting_single_result()
+            .map_err(|err| match err {
+                ApiError::EmptyResult(_) => {
+                    out_of_scope::<T>(self.namespace_id, &format!("id {}", id))
+                }
+                err => err,
+            })?;
+        Ok(self.bind(Handle::new(self.resource.client().clone(), resource)))
+    }
+
+    pub fn select_by_name(&self, name: &str) -> Result<ScopedHandle<T>, ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        let resource = self
+            .find()
+            .add_filter_name_exact(name)
+            .execute_expecting_single_result()?;
+        Ok(self.bind(Handle::new(self.resource.client().clone(), resource)))
+    }
+
+    /// Create a resource, which must be created in the namespace.
+    pub fn create(&self, params: T::PostParams) -> Result<T::PostOutput, ApiError> {
+        self.check_params(&params, true)?;
+        self.resource.create(params)
+    }
+
+    /// Update a resource of the namespace, without moving it elsewhere.
+    pub fn update(&self, id: i32, params: T::PatchParams) -> Result<T::PatchOutput, ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        self.check_params(&params, false)?;
+        self.select(id)?;
+        self.resource.update(id, params)
+    }
+
+    /// Delete a resource of the namespace.
+    pub fn delete(&self, id: i32) -> Result<(), ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        self.select(id)?;
+        self.resource.delete(id)
+    }
+
+    fn check_params(&self, params: &impl Serialize, required: bool) -> Result<(), ApiError> {
+        check_params::<T>(self.namespace_id, params, required)
+    }
+
+    fn bind(&self, handle: Handle<T>) -> ScopedHandle<T> {
+        ScopedHandle {
+            handle,
+            namespace_id: self.namespace_id,
+        }
+    }
+}
+
+/// A `Handle` to a resource of the namespace, see `NamespaceScoped`.
+///
+/// Follow-up operations stay within the namespace, and unlike a `Handle`
+/// it does not give out the unrestricted client.
+pub struct ScopedHandle<T: ApiResource> {
+    handle: Handle<T>,
+    namespace_id: i32,
+}
+
+impl<T: ApiResource> ScopedHandle<T>
+where
+    T::GetOutput: GetID,
+{
+    pub fn id(&self) -> i32 {
+        self.handle.id()
+    }
+
+    pub fn namespace_id(&self) -> i32 {
+        self.namespace_id
+    }
+
+    pub fn resource(&self) -> &T::GetOutput {
+        self.handle.resource()
+    }
+
+    pub fn into_resource(self) -> T::GetOutput {
+        self.handle.into_resource()
+    }
+
+    fn rebind(&self, handle: Handle<T>) -> ScopedHandle<T> {
+        ScopedHandle {
+            handle,
+            namespace_id: self.namespace_id,
+        }
+    }
+}
+
+impl ScopedHandle<Object> {
+    /// See `Handle::<Object>::history`.
+    pub fn history(&self) -> Result<Vec<Revision>, ApiError> {
+        self.handle.history()
+    }
+
+    /// See `Handle::<Object>::relations`.
+    pub fn relations(&self) -> Result<Vec<ObjectRelation>, ApiError> {
+        self.handle.relations()
+    }
+
+    /// See `Handle::<Object>::update_data`.
+    pub fn update_data(&self, data: Value) -> Result<ScopedHandle<Object>, ApiError> {
+        self.handle
+            .update_data(data)
+            .map(|handle| self.rebind(handle))
+    }
+
+    /// See `Handle::<Object>::update_if_unchanged`; the patch may not move
+    /// the object out of the namespace.
+    pub fn update_if_unchanged(
+        &self,
+        patch: ObjectPatch,
+    ) -> Result<ScopedHandle<Object>, ApiError> {
+        check_params::<Object>(self.namespace_id, &patch, false)?;
+        self.handle
+            .update_if_unchanged(patch)
+            .map(|handle| self.rebind(handle))
+    }
+}
+
+impl ScopedHandle<Class> {
+    /// See `Handle::<Class>::history`.
+    pub fn history(&self) -> Result<Vec<Revision>, ApiError> {
+        self.handle.history()
+    }
+
+    /// See `Handle::<Class>::relations`.
+    pub fn relations(&self) -> Result<Vec<ClassRelation>, ApiError> {
+        self.handle.relations()
+    }
+
+    /// The objects of the class within the namespace.
+    pub fn objects(&self) -> ScopedResource<Object> {
+        ScopedResource {
+            resource: self.handle.objects(),
+            namespace_id: self.namespace_id,
+        }
+    }
+
+    /// See `Handle::<Class>::delete_if_empty`.
+    pub fn delete_if_empty(&self) -> Result<(), ApiError> {
+        self.handle.delete_if_empty()
+    }
+}
+
+/// Check that `params` target the namespace, if they name one at all.
+///
+/// Creation has to name the namespace, while a patch may leave it out.
+fn check_params<T>(
+    namespace_id: i32,
+    params: &impl Serialize,
+    required: bool,
+) -> Result<(), ApiError> {
+    match serde_json::to_value(params)?.get(NAMESPACE_FIELD) {
+        Some(Value::Null) | None if !required => Ok(()),
+        Some(value) if value.as_i64() == Some(namespace_id.into()) => Ok(()),
+        Some(value) => Err(out_of_scope::<T>(
+            namespace_id,
+            &format!("namespace {}", value),
+        )),
+        None => Err(out_of_scope::<T>(namespace_id, "no namespace")),
+    }
+}
+
+fn out_of_scope<T>(namespace_id: i32, target: &str) -> ApiError {
+    ApiError::OutOfScope(format!(
+        "{} targets {}, outside namespace {}",
+        short_type_name::<T>(),
+        target,
+        namespace_id
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::testing::{Reply, TestServer};
+    use crate::resources::ObjectPost;
+
+    #[test]
+    fn test_check_params() {
+        let post = |namespace_id: i32| ObjectPost {
+            namespace_id: namespace_id.into(),
+            ..Default::default()
+        };
+        assert!(check_params::<Object>(3, &post(3), true).is_ok());
+        let err = check_params::<Object>(3, &post(4), true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Outside of scope: Object targets namespace 4, outside namespace 3"
+        );
+
+        assert!(check_params::<Object>(3, &ObjectPatch::default(), false).is_ok());
+        assert!(matches!(
+            check_params::<Object>(3, &ObjectPatch::default(), true),
+            Err(ApiError::OutOfScope(_))
+        ));
+        let moved = ObjectPatch {
+            namespace_id: Some(4.into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            check_params::<Object>(3, &moved, false),
+            Err(ApiError::OutOfScope(_))
+        ));
+    }
+
+    #[test]
+    fn test_select_stays_in_scope() {
+        let server = TestServer::start(|request| {
+            assert!(
+                request.path.contains("namespace_id__equals=3"),
+                "unscoped request for {}",
+                request.path
+            );
+            Reply::json(
+                200,
+                r#"[{"id": 7, "name": "host", "namespace_id": 3, "hubuum_class_id": 5,
+                "description": "", "data": null, "created_at": "2024-01-01T00:00:00",
+                "updated_at": "2024-01-01T00:00:00"}]"#,
+            )
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        let handle = client.scoped_to_namespace(3).objects(5).select(7).unwrap();
+        assert_eq!(handle.namespace_id(), 3);
+
+        let moved = ObjectPatch {
+            namespace_id: Some(4.into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            handle.update_if_unchanged(moved),
+            Err(ApiError::OutOfScope(_))
+        ));
+        assert_eq!(server.requests().len(), 1);
+    }
+}
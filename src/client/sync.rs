@@ -1,20 +1,56 @@
-use log::{debug, error, trace};
-use reqwest::blocking::Response;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::StatusCode;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::any::type_name;
 use std::borrow::Cow;
+use std::io::Read;
 use std::marker::PhantomData;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use super::{Authenticated, ClientCore, IntoResourceFilter, Unauthenticated, UrlParams};
+use super::benchmark::{LatencyProfile, PingReport};
+use super::builder::ClientBuilder;
+use super::bulk::{
+    self, BulkResult, Progress, ProgressSink, RateLimiter, DEFAULT_BULK_CONCURRENCY,
+};
+use super::cache::{self, SessionCache};
+use super::compression::Compression;
+use super::core::{self, one_or_err, ByteBudget, Page, PageTrail, Paged, RawPage, RequestPlan};
+use super::curl::CurlHook;
+use super::events::{self, Event, EventParser, MAX_RECONNECT_ATTEMPTS};
+use super::failover::Failover;
+use super::numbers::NumberMode;
+use super::retry::{generate_key, RetryPolicy, IDEMPOTENCY_KEY_HEADER};
+use super::scheduler::{Priority, Scheduler};
+use super::scoped::NamespaceScoped;
+use super::{
+    Authenticated, ClientCore, CreateMode, CreateOptions, GetID, Handle, IntoResourceFilter,
+    Resolved, Unauthenticated, UrlParams,
+};
+use crate::capture::{self, Capture};
 use crate::endpoints::Endpoint;
 use crate::errors::ApiError;
-use crate::resources::{ApiResource, Class, ClassRelation, Group, Namespace, Object, User};
-use crate::types::{BaseUrl, Credentials, FilterOperator, Token};
+use crate::logging::{self, debug, warn, BodyLog, AUTH, EVENTS, HTTP};
+use crate::resources::{
+    ids_filter, ApiResource, Class, ClassRelation, Group, Namespace, NamespacePost, Object,
+    ObjectRaw, Permission, PermissionSet, Template, TokenEntry, User, UserExpanded, Webhook,
+    EXPAND_CHUNK,
+};
+use crate::types::{
+    BaseUrl, Credentials, Cursor, CursorPage, FilterOperator, RequestSigner, Token,
+    NEXT_CURSOR_HEADER, TOTAL_COUNT_HEADER,
+};
 use crate::{ObjectRelation, QueryFilter};
 
-#[derive(Deserialize, Debug)]
-struct DeleteResponse;
+/// How long `ping_detailed` waits without a timeout configured on the client.
+const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The timeout of requests when none is configured, as the blocking client
+/// of reqwest would apply by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct EmptyPostParams;
@@ -24,20 +60,28 @@ pub struct Client<S> {
     http_client: reqwest::blocking::Client,
     base_url: BaseUrl,
     state: S,
+    timeout: Option<Duration>,
+    signer: Option<RequestSigner>,
+    cache: Option<SessionCache>,
+    retry: Option<RetryPolicy>,
+    body_log: BodyLog,
+    curl: Option<CurlHook>,
+    capture: Option<Capture>,
+    scheduler: Option<Scheduler>,
+    priority: Priority,
+    case_insensitive_names: bool,
+    failover: Option<Failover>,
+    /// Query parameters appended to every request, see `raw_query`.
+    extra_query: Vec<(String, String)>,
+    max_response_bytes: Option<u64>,
+    compression: Option<Compression>,
+    deadline: Option<Instant>,
+    number_mode: NumberMode,
 }
 
 impl<S> ClientCore for Client<S> {
     fn build_url(&self, endpoint: &Endpoint, url_params: UrlParams) -> String {
-        let mut url = format!(
-            "{}{}",
-            self.base_url.with_trailing_slash(),
-            endpoint.trim_start_matches('/')
-        );
-
-        for (key, value) in url_params {
-            url = url.replace(&format!("{{{}}}", key), value.as_ref());
-        }
-        url
+        core::build_url(&self.base_url, endpoint, &url_params)
     }
 }
 
@@ -48,64 +92,313 @@ trait ResponseHandler {
 impl<T> ResponseHandler for Client<T> {
     fn check_success(&self, response: Response) -> Result<Response, ApiError> {
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text()?;
-            let error_message = match serde_json::from_str::<Value>(&body) {
-                Ok(json) => json["message"]
-                    .as_str()
-                    .unwrap_or("Error without message.")
-                    .to_string(),
-                Err(_) => body,
-            };
-            return Err(ApiError::HttpWithBody {
-                status,
-                message: error_message,
-            });
+            let (status, url) = (response.status(), response.url().clone());
+            let body = read_limited(response, &mut self.budget())?;
+            let body = String::from_utf8_lossy(&body).into_owned();
+            return Err(core::response_error(status, &url, body));
         }
         Ok(response)
     }
 }
 
-impl Client<Unauthenticated> {
-    pub fn new(base_url: BaseUrl) -> Self {
+/// Read the body of a response, no further than what is left of `budget`.
+fn read_limited(response: Response, budget: &mut ByteBudget) -> Result<Vec<u8>, ApiError> {
+    let url = response.url().to_string();
+    let Some(left) = budget.left() else {
+        return Ok(response.bytes()?.to_vec());
+    };
+    budget.check(response.content_length(), &url)?;
+    let mut body = Vec::new();
+    response.take(left + 1).read_to_end(&mut body)?;
+    budget.spend(body.len() as u64, &url)?;
+    Ok(body)
+}
+
+impl<S> Client<S> {
+    /// The client in `state`, keeping all other settings.
+    fn with_state<T>(self, state: T) -> Client<T> {
         Client {
-            http_client: reqwest::blocking::Client::new(),
-            base_url,
-            state: Unauthenticated,
+            http_client: self.http_client,
+            base_url: self.base_url,
+            state,
+            timeout: self.timeout,
+            signer: self.signer,
+            cache: self.cache,
+            retry: self.retry,
+            body_log: self.body_log,
+            curl: self.curl,
+            capture: self.capture,
+            scheduler: self.scheduler,
+            priority: self.priority,
+            case_insensitive_names: self.case_insensitive_names,
+            failover: self.failover,
+            extra_query: self.extra_query,
+            max_response_bytes: self.max_response_bytes,
+            compression: self.compression,
+            deadline: self.deadline,
+            number_mode: self.number_mode,
+        }
+    }
+
+    /// The budget of `max_response_bytes` for a new call.
+    fn budget(&self) -> ByteBudget {
+        ByteBudget::new(self.max_response_bytes)
+    }
+
+    /// A view of the client whose requests, including retries and further
+    /// pages, all finish by `deadline`.
+    ///
+    /// Requests get at most the time left as their timeout, and none is sent
+    /// or retried once it has passed. Such requests fail with
+    /// `ApiError::DeadlineExceeded`, which holds the items fetched so far
+    /// when a listing is cut short.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Whether the deadline passes within `delay` from now.
+    fn past_deadline(&self, delay: Duration) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() + delay >= deadline)
+    }
+
+    /// Report requests that timed out at the deadline as having exceeded it.
+    fn deadline_error(&self, err: ApiError) -> ApiError {
+        match &err {
+            ApiError::Http(http) if http.is_timeout() && self.past_deadline(Duration::ZERO) => {
+                ApiError::DeadlineExceeded { partial: vec![] }
+            }
+            _ => err,
+        }
+    }
+
+    /// Sign every request with the given signer.
+    pub fn with_signer(mut self, signer: RequestSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Share resolved resources through the given cache, see [`SessionCache`].
+    pub fn with_cache(mut self, cache: SessionCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn cache(&self) -> Option<&SessionCache> {
+        self.cache.as_ref()
+    }
+
+    /// The priority of the requests made through this client, see [`Priority`].
+    ///
+    /// Clients cloned from this one share its scheduler, so a clone with a
+    /// lower priority can be handed to background work.
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Match names case-insensitively in all name lookups made through
+    /// this client, such as `add_filter_name_exact` and `select_by_name`.
+    pub fn with_case_insensitive_names(mut self, enabled: bool) -> Self {
+        self.case_insensitive_names = enabled;
+        self
+    }
+
+    /// Record all requests and responses, see [`Capture`].
+    pub fn with_capture(mut self, capture: Capture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Time the phases of a request to the base URL of the server.
+    ///
+    /// Any response counts as an answer, whatever its status. Only failing to
+    /// reach the server is an error.
+    pub fn ping_detailed(&self) -> Result<PingReport, ApiError> {
+        let url = url::Url::parse(self.base_url.as_str())?;
+        let timeout = self.timeout.unwrap_or(DEFAULT_PING_TIMEOUT);
+        let (mut dns, mut connect) = (None, None);
+        if self.base_url.unix_socket().is_none() {
+            let host = url
+                .host_str()
+                .ok_or_else(|| ApiError::UrlNotBase(url.to_string()))?
+                .trim_matches(|c| c == '[' || c == ']');
+            let port = url.port_or_known_default().unwrap_or(443);
+
+            let start = Instant::now();
+            let addr = (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::NotFound, host.to_string())
+            })?;
+            dns = Some(start.elapsed());
+
+            let start = Instant::now();
+            TcpStream::connect_timeout(&addr, timeout)?;
+            connect = Some(start.elapsed());
+        }
+
+        let start = Instant::now();
+        let response = self
+            .http_client
+            .get(self.base_url.with_trailing_slash())
+            .timeout(timeout)
+            .send()?;
+        let first_byte = start.elapsed();
+        let status = response.status().as_u16();
+        response.bytes()?;
+        let report = PingReport {
+            dns,
+            connect,
+            tls: None,
+            first_byte,
+            total: start.elapsed(),
+            status,
+        };
+        debug!(target: HTTP, "Ping {}: {}", self.base_url.as_str(), report);
+        Ok(report)
+    }
+
+    /// Open a connection to the server ahead of the first request.
+    ///
+    /// Sends a HEAD request to the base URL so that the connection, including
+    /// the TLS handshake, is pooled for the requests that follow. The response
+    /// status is ignored. Idle connections are dropped from the pool after a
+    /// while, so call this shortly before the connection is needed.
+    pub fn preconnect(&self) -> Result<(), ApiError> {
+        let url = self.base_url.with_trailing_slash();
+        let now = Instant::now();
+        let response = self.prepare(reqwest::Method::HEAD, &url, None)?.send()?;
+        debug!(
+            target: HTTP,
+            "Preconnected to {} in {:?} (HTTP {})",
+            url,
+            now.elapsed(),
+            response.status()
+        );
+        Ok(())
+    }
+
+    fn prepare(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<RequestBuilder, ApiError> {
+        let url = match &self.failover {
+            Some(failover) => failover.rewrite(url),
+            None => url.to_string(),
+        };
+        let url = core::append_query(&url, &self.extra_query);
+        let compressed = match (&self.compression, &body) {
+            (Some(compression), Some(body)) => compression.encode(&url, body),
+            _ => None,
+        };
+        let gzip = compressed.is_some();
+        let plan = RequestPlan::new(method, url, compressed.or(body));
+        let mut request = self.http_client.request(plan.method.clone(), &plan.url);
+        if let Some((header, signature)) = plan.signature(self.signer.as_ref())? {
+            request = request.header(header, signature);
+        }
+        if let Some(body) = plan.body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body);
+        }
+        if gzip {
+            request = request.header(reqwest::header::CONTENT_ENCODING, "gzip");
+        }
+        let timeout = self.timeout.unwrap_or(DEFAULT_TIMEOUT);
+        if let Some(timeout) = core::deadline_timeout(Some(timeout), self.deadline, Instant::now())?
+        {
+            request = request.timeout(timeout);
         }
+        Ok(request)
     }
 }
 
 impl Client<Unauthenticated> {
-    pub fn login(self, credentials: Credentials) -> Result<Client<Authenticated>, ApiError> {
-        let token: Token = self
-            .http_client
-            .post(&self.build_url(&Endpoint::Login, UrlParams::default()))
-            .json(&credentials)
-            .send()?
-            .error_for_status()?
-            .json()?;
+    pub fn new(base_url: BaseUrl) -> Self {
+        Self::builder(base_url)
+            .build()
+            .expect("Failed to build HTTP client")
+    }
+
+    pub fn builder(base_url: BaseUrl) -> ClientBuilder<Self> {
+        ClientBuilder::new(base_url)
+    }
+}
+
+impl ClientBuilder<Client<Unauthenticated>> {
+    pub fn build(self) -> Result<Client<Unauthenticated>, ApiError> {
+        // Timeouts are set per request, so that event streams can go without.
+        let mut builder = reqwest::blocking::Client::builder().timeout(None);
+        #[cfg(unix)]
+        if let Some(socket) = self.base_url.unix_socket() {
+            builder = builder.unix_socket(socket);
+        }
+        for (domain, addr) in &self.resolve {
+            builder = builder.resolve(domain, *addr);
+        }
 
+        let failover = self.failover();
         Ok(Client {
-            http_client: self.http_client,
+            http_client: builder.build()?,
             base_url: self.base_url,
-            state: Authenticated { token: token.token },
+            state: Unauthenticated,
+            timeout: self.timeout,
+            signer: self.signer,
+            cache: None,
+            retry: self.retry,
+            body_log: BodyLog::new(self.log_policy, &self.redact),
+            curl: self.curl,
+            capture: None,
+            scheduler: self.max_in_flight.map(Scheduler::new),
+            priority: Priority::default(),
+            case_insensitive_names: false,
+            failover,
+            extra_query: vec![],
+            max_response_bytes: self.max_response_bytes,
+            compression: self.compression,
+            deadline: None,
+            number_mode: self.number_mode,
         })
     }
+}
+
+impl Client<Unauthenticated> {
+    pub fn login(self, credentials: Credentials) -> Result<Client<Authenticated>, ApiError> {
+        debug!(target: AUTH, "Logging in to {}", self.base_url.as_str());
+        let response = self
+            .prepare(
+                reqwest::Method::POST,
+                &self.build_url(&Endpoint::Login, UrlParams::default()),
+                Some(serde_json::to_vec(&credentials)?),
+            )?
+            .send()?;
+        let status = response.status();
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let url = response.url().clone();
+            return Err(core::login_error(status, &url, &headers, response.text()?));
+        }
+        let token: Token = response.json()?;
+
+        let state = Authenticated::new(token.into_string()).with_username(credentials.username());
+        Ok(self.with_state(state))
+    }
 
     pub fn login_with_token(self, token: Token) -> Result<Client<Authenticated>, ApiError> {
         let status = self
-            .http_client
-            .get(self.build_url(&Endpoint::LoginWithToken, UrlParams::default()))
-            .header("Authorization", format!("Bearer {}", token.token))
+            .prepare(
+                reqwest::Method::GET,
+                &self.build_url(&Endpoint::LoginWithToken, UrlParams::default()),
+                None,
+            )?
+            .header("Authorization", format!("Bearer {}", token.as_str()))
             .send()?;
 
         if status.status().is_success() {
-            Ok(Client {
-                http_client: self.http_client,
-                base_url: self.base_url,
-                state: Authenticated { token: token.token },
-            })
+            Ok(self.with_state(Authenticated::new(token.into_string())))
         } else {
             Err(ApiError::InvalidToken)
         }
@@ -113,8 +406,40 @@ impl Client<Unauthenticated> {
 }
 
 impl Client<Authenticated> {
-    pub fn get_token(&self) -> &str {
-        &self.state.token
+    /// Create a client and log in with the given credentials.
+    pub fn new_authenticated(
+        base_url: BaseUrl,
+        credentials: Credentials,
+    ) -> Result<Client<Authenticated>, ApiError> {
+        Client::new(base_url).login(credentials)
+    }
+
+    /// Create a client using an existing token, which is validated first.
+    pub fn new_with_token(
+        base_url: BaseUrl,
+        token: &str,
+    ) -> Result<Client<Authenticated>, ApiError> {
+        Client::new(base_url).login_with_token(Token::new(token.to_string()))
+    }
+
+    pub fn get_token(&self) -> String {
+        self.state.token()
+    }
+
+    /// The user the client logged in as, `None` if it logged in with a token.
+    pub fn username(&self) -> Option<&str> {
+        self.state.username()
+    }
+
+    fn authorized(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<RequestBuilder, ApiError> {
+        Ok(self
+            .prepare(method, url, body)?
+            .header("Authorization", format!("Bearer {}", self.state.token())))
     }
 
     pub fn request<R: ApiResource, T: Serialize + std::fmt::Debug, U: DeserializeOwned>(
@@ -125,71 +450,252 @@ impl Client<Authenticated> {
         query_params: Vec<QueryFilter>,
         post_params: T,
     ) -> Result<Option<U>, ApiError> {
-        let endpoint = resource.endpoint();
-        let url = self.build_url(&endpoint, url_params.clone());
-
-        let request = match method {
-            reqwest::Method::GET => {
-                use crate::types::IntoQueryTuples;
-                let query = query_params.into_query_string();
-                let url = if !query.is_empty() {
-                    format!("{}?{}", url, query)
-                } else {
-                    url
-                };
-                debug!("GET {}", url);
-                self.http_client.get(&url)
+        self.request_with_endpoint(
+            method,
+            &resource.endpoint(),
+            url_params,
+            query_params,
+            post_params,
+        )
+    }
+
+    pub fn request_with_endpoint<T: Serialize + std::fmt::Debug, U: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        endpoint: &Endpoint,
+        url_params: UrlParams,
+        query_params: Vec<QueryFilter>,
+        post_params: T,
+    ) -> Result<Option<U>, ApiError> {
+        endpoint.validate(&method, &url_params)?;
+        let plan = RequestPlan::build(
+            &self.base_url,
+            method,
+            endpoint,
+            &url_params,
+            query_params,
+            post_params,
+        )?;
+        self.execute_plan(plan)
+    }
+
+    /// Send a planned request, following next links for GET requests.
+    fn execute_plan<U: DeserializeOwned>(&self, plan: RequestPlan) -> Result<Option<U>, ApiError> {
+        if plan.method != reqwest::Method::GET {
+            let (method, numbers) = (plan.method.clone(), plan.numbers);
+            if numbers {
+                self.number_mode.check_write(plan.body.as_deref())?;
             }
-            reqwest::Method::POST => {
-                debug!("POST {} with {:?}", &url, post_params);
-                self.http_client.post(&url).json(&post_params)
+            let response = self.send_plan(plan)?;
+            let text = self.read_body(response, &mut self.budget(), numbers)?;
+            logging::body(&self.body_log, "Response", text.as_bytes());
+            return core::decode_response(&method, text);
+        }
+
+        self.paginate::<Page>(plan)?.decode()
+    }
+
+    /// Send a planned GET request and follow the next links of the pages it
+    /// yields, joining them into one.
+    ///
+    /// Links back to a page fetched before end the pagination, and running
+    /// out of time fails with the items read so far.
+    fn paginate<P: Paged>(&self, plan: RequestPlan) -> Result<P, ApiError> {
+        let mut trail = PageTrail::new(&plan.url);
+        let mut budget = self.budget();
+        let numbers = plan.numbers;
+        let next_plan = plan.clone();
+        let mut page: P = self
+            .send_plan(plan)
+            .and_then(|response| self.read_page(response, &mut budget, numbers))?;
+        while let Some(next) = trail.follow(page.next()) {
+            debug!(target: HTTP, "GET {} (next page)", next);
+            let next_page = self
+                .send_plan(next_plan.next_page(next))
+                .and_then(|response| self.read_page(response, &mut budget, numbers))
+                .map_err(|err| self.deadline_error(err));
+            match next_page {
+                Ok(next_page) => page.append(next_page)?,
+                Err(ApiError::DeadlineExceeded { .. }) => {
+                    return Err(ApiError::DeadlineExceeded {
+                        partial: page.into_partial(),
+                    })
+                }
+                Err(err) => return Err(err),
             }
-            reqwest::Method::PATCH => {
-                let id = url_params
-                    .iter()
-                    .find(|(k, _)| k == "patch_id")
-                    .map(|(_, v)| v)
-                    .ok_or(ApiError::MissingUrlIdentifier)?;
-                let url = format!("{}{}", url, id);
-                debug!("PATCH {} with {:?}", &url, post_params);
-                self.http_client.patch(&url).json(&post_params)
+        }
+        Ok(page)
+    }
+
+    /// Send a planned GET request and return the single page it yields.
+    fn fetch_page(&self, plan: RequestPlan) -> Result<Page, ApiError> {
+        let numbers = plan.numbers;
+        let response = self.send_plan(plan)?;
+        self.read_page(response, &mut self.budget(), numbers)
+    }
+
+    fn send_plan(&self, plan: RequestPlan) -> Result<Response, ApiError> {
+        let Some(retry) = &self.retry else {
+            let key = plan.idempotency_key.clone();
+            return self
+                .send_once(plan, key.as_deref())
+                .map_err(|err| self.deadline_error(err));
+        };
+        let key = retry.idempotency_key(&plan);
+        let mut attempt = 0;
+        loop {
+            match self.send_once(plan.clone(), key.as_deref()) {
+                Err(err) if retry.should_retry(&plan, key.is_some(), attempt, &err) => {
+                    let delay = retry.delay(attempt);
+                    if self.past_deadline(delay) {
+                        return Err(ApiError::DeadlineExceeded { partial: vec![] });
+                    }
+                    logging::retry(&plan.method, &plan.url, attempt, delay, &err);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result.map_err(|err| self.deadline_error(err)),
             }
-            reqwest::Method::DELETE => {
-                let url = format!("{}{:?}", url, post_params);
-                debug!("DELETE {}", &url);
-                self.http_client.delete(&url)
+        }
+    }
+
+    /// Send a request, failing over to the next replica on connection
+    /// failures until every replica has been tried.
+    fn send_once(&self, plan: RequestPlan, key: Option<&str>) -> Result<Response, ApiError> {
+        let Some(failover) = &self.failover else {
+            return self.send_attempt(plan, key);
+        };
+        let mut replicas_left = failover.len();
+        loop {
+            match self.send_attempt(plan.clone(), key) {
+                Err(ApiError::Http(err))
+                    if err.is_connect()
+                        && replicas_left > 1
+                        && err.url().is_some_and(|url| failover.failed(url.as_str())) =>
+                {
+                    replicas_left -= 1;
+                }
+                result => return result,
             }
-            _ => return Err(ApiError::UnsupportedHttpOperation(method.to_string())),
         }
-        .header("Authorization", format!("Bearer {}", self.state.token));
+    }
 
-        let now = std::time::Instant::now();
-        let response = request.send()?;
-        trace!("Request took {:?}", now.elapsed());
-        let response_text = self.check_success(response)?.text()?;
-        debug!("Response: {}", response_text);
-
-        if method == reqwest::Method::DELETE {
-            if response_text.is_empty() {
-                return Ok(None);
-            } else {
-                error!("Expected empty response, got: {}", response_text);
-                return Err(ApiError::DeserializationError(response_text));
+    fn send_attempt(&self, plan: RequestPlan, key: Option<&str>) -> Result<Response, ApiError> {
+        let _permit = self
+            .scheduler
+            .as_ref()
+            .map(|scheduler| scheduler.acquire(self.priority));
+        // Kept to send again uncompressed, should the server refuse gzip.
+        let fallback = self
+            .compression
+            .as_ref()
+            .filter(|compression| compression.applies(plan.body.as_deref()))
+            .map(|compression| (compression, plan.clone()));
+        let (response, gzip) = self.transmit(plan, key)?;
+        match fallback {
+            Some((compression, plan))
+                if gzip && response.status() == StatusCode::UNSUPPORTED_MEDIA_TYPE =>
+            {
+                compression.reject(response.url().as_str());
+                let (response, _) = self.transmit(plan, key)?;
+                self.check_success(response)
             }
+            _ => self.check_success(response),
         }
+    }
 
-        let obj: U = match serde_json::from_str(&response_text) {
-            Ok(obj) => obj,
-            Err(err) => {
-                error!(
-                    "Failed to deserialize response: {} Response text: {}",
-                    err, response_text
+    /// Send a request once, returning the response and whether the body was
+    /// sent compressed.
+    fn transmit(&self, plan: RequestPlan, key: Option<&str>) -> Result<(Response, bool), ApiError> {
+        let now = std::time::Instant::now();
+        if let Some(body) = &plan.body {
+            logging::body(&self.body_log, "Request", body);
+        }
+        if let Some(hook) = &self.curl {
+            hook.emit(&plan, key);
+        }
+        let mut request = self.authorized(plan.method.clone(), &plan.url, plan.body)?;
+        if let Some(timeout) = plan.timeout {
+            let timeout = core::deadline_timeout(Some(timeout), self.deadline, Instant::now())?;
+            request = request.timeout(timeout.unwrap_or_default());
+        }
+        if let Some(key) = key {
+            request = request.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        for (name, value) in plan.headers {
+            request = request.header(name, value);
+        }
+        let request = request.build()?;
+        let gzip = request
+            .headers()
+            .contains_key(reqwest::header::CONTENT_ENCODING);
+        let response = match &self.capture {
+            None => self.http_client.execute(request)?,
+            Some(capture) => {
+                let redaction = capture::Redaction {
+                    body_log: &self.body_log,
+                    signer_header: self.signer.as_ref().map(RequestSigner::header),
+                };
+                let pending = capture.begin(
+                    request.method(),
+                    request.url().as_str(),
+                    request.headers(),
+                    request.body().and_then(|body| body.as_bytes()),
+                    &redaction,
                 );
-                return Err(ApiError::DeserializationError(response_text));
+                let response = self.http_client.execute(request)?;
+                let (status, version) = (response.status(), response.version());
+                let headers = response.headers().clone();
+                let body = read_limited(response, &mut self.budget())?;
+                capture.finish(pending, status, version, &headers, &body, &redaction);
+                capture::rebuild(status, version, headers, body).into()
             }
         };
+        logging::response(
+            &plan.method,
+            &plan.url,
+            response.status(),
+            response.content_length(),
+            now.elapsed(),
+        );
+        Ok((response, gzip))
+    }
+
+    /// Read the body of a response, no further than what is left of
+    /// `budget`, and prepare its numbers as `number_mode` says if it holds
+    /// objects.
+    fn read_body(
+        &self,
+        response: Response,
+        budget: &mut ByteBudget,
+        numbers: bool,
+    ) -> Result<String, ApiError> {
+        let text = String::from_utf8_lossy(&read_limited(response, budget)?).into_owned();
+        Ok(match numbers {
+            true => self.number_mode.apply(text),
+            false => text,
+        })
+    }
 
-        Ok(Some(obj))
+    fn read_page<P: Paged>(
+        &self,
+        response: Response,
+        budget: &mut ByteBudget,
+        numbers: bool,
+    ) -> Result<P, ApiError> {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let link = header(reqwest::header::LINK.as_str());
+        let cursor = header(NEXT_CURSOR_HEADER);
+        let total = header(TOTAL_COUNT_HEADER);
+        let text = self.read_body(response, budget, numbers && !P::RAW)?;
+        logging::body(&self.body_log, "Response", text.as_bytes());
+        P::parse(text, link.as_deref(), cursor.as_deref(), total.as_deref())
     }
 
     pub fn get<R: ApiResource>(
@@ -199,14 +705,16 @@ impl Client<Authenticated> {
         query_params: Vec<QueryFilter>,
         params: R::GetParams,
     ) -> Result<Vec<R::GetOutput>, ApiError> {
+        let mut filters = query_params;
+        filters.extend(params.into_resource_filter());
         self.request(
             reqwest::Method::GET,
             resource,
             url_params,
-            query_params,
-            params,
+            filters,
+            EmptyPostParams,
         )
-        .and_then(|opt| opt.ok_or(ApiError::EmptyResult("GET returned empty result".into())))
+        .map(Option::unwrap_or_default)
     }
 
     pub fn search<R: ApiResource>(
@@ -222,7 +730,7 @@ impl Client<Authenticated> {
             query_params,
             EmptyPostParams,
         )
-        .and_then(|opt| opt.ok_or(ApiError::EmptyResult("SEARCH returned empty result".into())))
+        .map(Option::unwrap_or_default)
     }
 
     pub fn post<R: ApiResource>(
@@ -235,6 +743,29 @@ impl Client<Authenticated> {
             .and_then(|opt| opt.ok_or(ApiError::EmptyResult("POST returned empty result".into())))
     }
 
+    /// Like `post`, sending `key` as the idempotency key of the request.
+    pub(crate) fn post_with_key<R: ApiResource>(
+        &self,
+        resource: R,
+        url_params: UrlParams,
+        params: R::PostParams,
+        key: String,
+    ) -> Result<R::PostOutput, ApiError> {
+        let endpoint = resource.endpoint();
+        endpoint.validate(&reqwest::Method::POST, &url_params)?;
+        let plan = RequestPlan::build(
+            &self.base_url,
+            reqwest::Method::POST,
+            &endpoint,
+            &url_params,
+            vec![],
+            params,
+        )?
+        .with_idempotency_key(key);
+        self.execute_plan(plan)
+            .and_then(|opt| opt.ok_or(ApiError::EmptyResult("POST returned empty result".into())))
+    }
+
     pub fn patch<R: ApiResource>(
         &self,
         resource: R,
@@ -248,22 +779,271 @@ impl Client<Authenticated> {
             .and_then(|opt| opt.ok_or(ApiError::EmptyResult("PATCH returned empty result".into())))
     }
 
+    /// Patch a resource, on the condition that it is unmodified since
+    /// `since`. The server refusing the condition with 412 Precondition
+    /// Failed yields `ApiError::Conflict`.
+    pub(crate) fn patch_unmodified_since<R: ApiResource>(
+        &self,
+        resource: R,
+        id: i32,
+        url_params: UrlParams,
+        params: R::PatchParams,
+        since: chrono::NaiveDateTime,
+    ) -> Result<R::PatchOutput, ApiError> {
+        let mut url_params = url_params;
+        url_params.push(("patch_id".into(), id.to_string().into()));
+        let endpoint = resource.endpoint();
+        endpoint.validate(&reqwest::Method::PATCH, &url_params)?;
+        let plan = RequestPlan::build(
+            &self.base_url,
+            reqwest::Method::PATCH,
+            &endpoint,
+            &url_params,
+            vec![],
+            params,
+        )?
+        .with_header(core::IF_UNMODIFIED_SINCE_HEADER, core::http_date(since));
+        let url = plan.url.clone();
+        match self.execute_plan(plan) {
+            Err(err) if err.status() == Some(StatusCode::PRECONDITION_FAILED) => Err(
+                ApiError::Conflict(format!("{} was modified after {}", url, since)),
+            ),
+            result => result?.ok_or(ApiError::EmptyResult("PATCH returned empty result".into())),
+        }
+    }
+
+    pub fn put<R: ApiResource>(
+        &self,
+        resource: R,
+        id: i32,
+        url_params: UrlParams,
+        params: R::PostParams,
+    ) -> Result<R::PostOutput, ApiError> {
+        let mut url_params = url_params;
+        url_params.push(("put_id".into(), id.to_string().into()));
+        self.request(reqwest::Method::PUT, resource, url_params, vec![], params)
+            .and_then(|opt| opt.ok_or(ApiError::EmptyResult("PUT returned empty result".into())))
+    }
+
     pub fn delete<R: ApiResource>(
         &self,
         resource: R,
         id: i32,
         url_params: UrlParams,
     ) -> Result<(), ApiError> {
-        self.request::<_, _, DeleteResponse>(
+        let mut url_params = url_params;
+        url_params.push(("delete_id".into(), id.to_string().into()));
+        self.request::<_, _, IgnoredAny>(
             reqwest::Method::DELETE,
             resource,
             url_params,
             vec![],
-            id,
+            EmptyPostParams,
         )
         .map(|_| ())
     }
 
+    /// Send `plan` as a HEAD request, or `None` if the server does not
+    /// support HEAD.
+    fn send_head(&self, plan: &RequestPlan) -> Result<Option<Response>, ApiError> {
+        let head = RequestPlan {
+            method: reqwest::Method::HEAD,
+            ..plan.clone()
+        };
+        match self.send_plan(head) {
+            Ok(response) => Ok(Some(response)),
+            Err(err)
+                if matches!(
+                    err.status(),
+                    Some(StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED)
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Check if anything exists where `plan` points with a HEAD request,
+    /// falling back to a GET if the server does not support HEAD.
+    ///
+    /// For collection URLs the HEAD response must carry `X-Total-Count`, as the
+    /// status code alone does not tell if the collection is empty. Without it,
+    /// the fallback GET (limited to a single result) is used. Both are sent
+    /// like any other request, with retries, the deadline and failover
+    /// applying.
+    fn probe(&self, plan: RequestPlan, collection: bool) -> Result<bool, ApiError> {
+        let not_found = |err: &ApiError| err.status() == Some(StatusCode::NOT_FOUND);
+        match self.send_head(&plan) {
+            Ok(Some(_)) if !collection => return Ok(true),
+            Ok(Some(response)) => {
+                let total = response
+                    .headers()
+                    .get(TOTAL_COUNT_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok());
+                if let Some(total) = total {
+                    return Ok(total > 0);
+                }
+            }
+            Ok(None) => {}
+            Err(err) if not_found(&err) => return Ok(false),
+            Err(err) => return Err(err),
+        }
+
+        debug!(target: HTTP, "GET {} (HEAD fallback)", plan.url);
+        let response = match self.send_plan(plan) {
+            Ok(response) => response,
+            Err(err) if not_found(&err) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        if !collection {
+            return Ok(true);
+        }
+        let response_text = self.read_body(response, &mut self.budget(), false)?;
+        match serde_json::from_str::<Vec<Value>>(&response_text) {
+            Ok(items) => Ok(!items.is_empty()),
+            Err(_) => Err(ApiError::DeserializationError(response_text)),
+        }
+    }
+
+    /// Count the items of the collection `plan` lists.
+    ///
+    /// Uses `X-Total-Count` from a HEAD request where the server provides it,
+    /// and otherwise counts the items of a GET. Both are sent like any other
+    /// request, with retries, the deadline and failover applying.
+    fn count(&self, plan: RequestPlan) -> Result<usize, ApiError> {
+        let total = self.send_head(&plan)?.and_then(|response| {
+            response
+                .headers()
+                .get(TOTAL_COUNT_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<usize>().ok())
+        });
+        if let Some(total) = total {
+            return Ok(total);
+        }
+
+        debug!(target: HTTP, "GET {} (HEAD fallback)", plan.url);
+        let items: Option<Vec<Value>> = self.execute_plan(plan)?;
+        Ok(items.map_or(0, |items| items.len()))
+    }
+
+    /// Subscribe to server-sent events matching the filters.
+    ///
+    /// The callback is called for every event received, and the subscription
+    /// ends when it returns `false`. Dropped streams are reconnected, resuming
+    /// after the last event seen, with a growing delay while the server cannot
+    /// be reached or answers that it is unavailable. Other errors, such as
+    /// refused credentials, end the subscription at once.
+    pub fn subscribe<F>(&self, filters: Vec<QueryFilter>, mut callback: F) -> Result<(), ApiError>
+    where
+        F: FnMut(Event) -> bool,
+    {
+        use crate::types::IntoQueryTuples;
+        let url = self.build_url(&Endpoint::Events, UrlParams::default());
+        let query = filters.into_query_string();
+        let url = if !query.is_empty() {
+            format!("{}?{}", url, query)
+        } else {
+            url
+        };
+
+        let mut parser = EventParser::default();
+        let mut failures = 0;
+        loop {
+            let mut request = self
+                .authorized(reqwest::Method::GET, &url, None)?
+                .header("Accept", "text/event-stream");
+            if let Some(id) = parser.last_event_id() {
+                request = request.header("Last-Event-ID", id);
+            }
+            // The stream stays open for as long as events keep coming.
+            let mut request = request.build()?;
+            *request.timeout_mut() = core::deadline_timeout(None, self.deadline, Instant::now())?;
+
+            debug!(target: EVENTS, "SUBSCRIBE {}", url);
+            let connected = self
+                .http_client
+                .execute(request)
+                .map_err(ApiError::from)
+                .and_then(|response| self.check_success(response));
+            match connected {
+                Ok(mut response) => {
+                    let mut buffer = [0u8; 8192];
+                    loop {
+                        match response.read(&mut buffer) {
+                            Ok(0) => break,
+                            Ok(n) => {
+                                for event in parser.feed(&buffer[..n]) {
+                                    failures = 0;
+                                    if !callback(event) {
+                                        return Ok(());
+                                    }
+                                }
+                            }
+                            Err(err) => {
+                                debug!(target: EVENTS, "Event stream interrupted: {}", err);
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(err) if events::should_reconnect(&err) && failures < MAX_RECONNECT_ATTEMPTS => {
+                    debug!(target: EVENTS, "Event stream connection failed: {}", err);
+                    failures += 1;
+                }
+                Err(err) => return Err(events::stream_error(err)),
+            }
+            parser.reset();
+            std::thread::sleep(events::reconnect_delay(parser.retry(), failures));
+        }
+    }
+
+    /// Measure the latency of listing each of the given endpoints.
+    ///
+    /// Every endpoint is fetched `iterations` times, asking for a single
+    /// resource, without retries and without following further pages. Failed
+    /// requests are counted as errors and left out of the latencies. Endpoints
+    /// needing URL parameters, such as `Endpoint::Objects`, are rejected with
+    /// `ApiError::MissingUrlIdentifier`.
+    pub fn benchmark(
+        &self,
+        endpoints: &[Endpoint],
+        iterations: usize,
+    ) -> Result<Vec<LatencyProfile>, ApiError> {
+        if endpoints
+            .iter()
+            .any(|endpoint| endpoint.path().contains('{'))
+        {
+            return Err(ApiError::MissingUrlIdentifier);
+        }
+
+        let mut profiles = vec![];
+        for endpoint in endpoints {
+            let url = self.build_url(endpoint, UrlParams::default());
+            let plan = RequestPlan::new(reqwest::Method::GET, url, None)
+                .with_query(&[("limit", "1".to_string())]);
+            let mut samples = Vec::with_capacity(iterations);
+            let mut errors = 0;
+            for _ in 0..iterations {
+                let start = std::time::Instant::now();
+                match self
+                    .send_once(plan.clone(), None)
+                    .and_then(|response| Ok(response.bytes()?))
+                {
+                    Ok(_) => samples.push(start.elapsed()),
+                    Err(err) => {
+                        debug!(target: HTTP, "Benchmark request failed: {}", err);
+                        errors += 1;
+                    }
+                }
+            }
+            profiles.push(LatencyProfile::new(endpoint.path(), samples, errors));
+        }
+        Ok(profiles)
+    }
+
     pub fn users(&self) -> Resource<User> {
         Resource::new(self.clone(), UrlParams::default())
     }
@@ -276,10 +1056,55 @@ impl Client<Authenticated> {
         Resource::new(self.clone(), UrlParams::default())
     }
 
+    /// The namespace with the given name, as the start of a fluent path
+    /// like `client.namespace("prod")?.class("Host")?.objects()`.
+    ///
+    /// Names are resolved through the session cache, if there is one.
+    pub fn namespace(&self, name: &str) -> Result<Handle<Namespace>, ApiError> {
+        let namespace = cache::resolve_named(self.cache(), name, || {
+            self.namespaces()
+                .select_by_name(name)
+                .map(Handle::into_resource)
+        })?;
+        Ok(Handle::new(self.clone(), namespace))
+    }
+
     pub fn groups(&self) -> Resource<Group> {
         Resource::new(self.clone(), UrlParams::default())
     }
 
+    /// The permission rows of a namespace, one per group.
+    pub fn namespace_permissions(&self, namespace_id: i32) -> Resource<Permission> {
+        Resource::new(
+            self.clone(),
+            vec![("namespace_id", namespace_id.to_string())],
+        )
+    }
+
+    /// The tokens issued to a user.
+    pub fn user_tokens(&self, user_id: i32) -> Resource<TokenEntry> {
+        Resource::new(self.clone(), vec![("user_id", user_id.to_string())])
+    }
+
+    /// A view of the client restricted to the classes and objects of one
+    /// namespace, see [`NamespaceScoped`].
+    pub fn scoped_to_namespace(&self, namespace_id: i32) -> NamespaceScoped {
+        NamespaceScoped::new(self.clone(), namespace_id)
+    }
+
+    /// Fetch the resources with the given ids as handles, as when
+    /// following relations, see `Resource::resolve_handles`.
+    ///
+    /// Only resources listed without URL parameters can be resolved here;
+    /// resolve objects through `objects(class_id)` instead.
+    pub fn resolve_handles<T>(&self, ids: &[i32]) -> Result<Resolved<T>, ApiError>
+    where
+        T: ApiResource,
+        T::GetOutput: GetID + Clone,
+    {
+        Resource::<T>::new(self.clone(), UrlParams::default()).resolve_handles(ids)
+    }
+
     pub fn objects(&self, class_id: i32) -> Resource<Object> {
         Resource::new(self.clone(), vec![("class_id", class_id.to_string())])
     }
@@ -291,12 +1116,24 @@ impl Client<Authenticated> {
     pub fn object_relation(&self) -> Resource<ObjectRelation> {
         Resource::new(self.clone(), UrlParams::default())
     }
+
+    pub fn webhooks(&self) -> Resource<Webhook> {
+        Resource::new(self.clone(), UrlParams::default())
+    }
 }
 
 pub struct FilterBuilder<T: ApiResource> {
     client: Client<Authenticated>,
     filters: Vec<(String, FilterOperator, String)>,
     url_params: UrlParams,
+    cursor: Option<Cursor>,
+    limit: Option<usize>,
+    expand: Vec<String>,
+    concurrency: usize,
+    progress: Option<Arc<dyn ProgressSink>>,
+    /// Query parameters sent with the search only, see `raw_query`.
+    query: Vec<(String, String)>,
+    timeout: Option<Duration>,
     _phantom: PhantomData<T>,
 }
 
@@ -306,10 +1143,67 @@ impl<T: ApiResource> FilterBuilder<T> {
             client,
             url_params,
             filters: Vec::new(),
+            cursor: None,
+            limit: None,
+            expand: Vec::new(),
+            concurrency: DEFAULT_BULK_CONCURRENCY,
+            progress: None,
+            query: vec![],
+            timeout: None,
             _phantom: PhantomData,
         }
     }
 
+    /// Report the progress of bulk operations to `sink`.
+    pub fn progress(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    /// The number of requests in flight at once for bulk operations.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Resolve the given relations of the results in bulk, e.g. `&["namespace", "class"]`.
+    ///
+    /// The related resources are attached to the `Ref` fields of the results,
+    /// so resolving them afterwards does not cost a request per result. The
+    /// search fails with `ApiError::NotFound` if some cannot be resolved.
+    pub fn expand(mut self, relations: &[&str]) -> Self {
+        self.expand
+            .extend(relations.iter().map(|relation| relation.to_string()));
+        self
+    }
+
+    /// Start after the given cursor, as returned by `execute_page`.
+    pub fn after(mut self, cursor: Cursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// Ask the server for at most `limit` resources per page.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Pass an extra query parameter with the search, for server options
+    /// this crate does not model yet.
+    ///
+    /// Unlike filters, the parameter is sent as given, without validation.
+    pub fn raw_query(mut self, key: &str, value: &str) -> Self {
+        self.query.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Override the timeout for the search request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     pub fn add_filter<V: ToString>(mut self, field: &str, op: FilterOperator, value: V) -> Self {
         self.filters
             .push((field.to_string(), op, value.to_string()));
@@ -324,18 +1218,377 @@ impl<T: ApiResource> FilterBuilder<T> {
         self.add_filter_equals("id", value)
     }
 
+    /// Filter on the name, case-insensitively if the client matches names
+    /// that way.
     pub fn add_filter_name_exact<V: ToString>(self, value: V) -> Self {
-        self.add_filter_equals("name", value)
+        if self.client.case_insensitive_names {
+            return self.add_filter_name_ci(value);
+        }
+        self.add_filter_equals(T::NAME_FIELD, value)
+    }
+
+    pub fn add_filter_name_ci<V: ToString>(self, value: V) -> Self {
+        self.add_filter(
+            T::NAME_FIELD,
+            FilterOperator::IEquals { is_negated: false },
+            value,
+        )
     }
 
     pub fn execute_expecting_single_result(self) -> Result<T::GetOutput, ApiError> {
-        one_or_err(self.execute()?)
+        let filters = T::build_params(self.filters.clone());
+        one_or_err(self.execute()?, &filters)
+    }
+
+    /// Delete all resources matching the filters.
+    ///
+    /// Issues a single DELETE with the filters as the query string. If the
+    /// server does not support filtered deletes, the matching resources are
+    /// fetched and deleted one by one instead, and all failures are returned
+    /// together as `ApiError::Multiple`.
+    pub fn delete_where(self) -> Result<(), ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        if self.filters.is_empty() {
+            return Err(ApiError::MissingUrlIdentifier);
+        }
+
+        let params = T::build_params(self.filters.clone());
+        core::validate_filters::<T>(&params)?;
+        let result = self.client.request::<_, _, IgnoredAny>(
+            reqwest::Method::DELETE,
+            T::default(),
+            self.url_params.clone(),
+            params,
+            EmptyPostParams,
+        );
+
+        match result {
+            Err(ApiError::HttpWithBody {
+                status: StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED,
+                ..
+            }) => {
+                debug!(target: HTTP, "Filtered DELETE not supported, deleting one by one");
+                let client = self.client.clone();
+                let url_params = self.url_params.clone();
+                let sink = self.progress.clone();
+                let resources = self.execute()?;
+                let progress = Progress::new(sink.as_deref(), Some(resources.len()));
+                let errors: Vec<ApiError> = resources
+                    .into_iter()
+                    .filter_map(|resource| {
+                        let id = resource.id();
+                        progress
+                            .track(client.delete(T::default(), id, url_params.clone()))
+                            .err()
+                    })
+                    .collect();
+                match ApiError::from_errors(errors) {
+                    Some(err) => Err(err),
+                    None => Ok(()),
+                }
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    /// Apply the same patch to every matching resource.
+    pub fn patch_all(self, patch: T::PatchParams) -> Result<BulkResult<T::PatchOutput>, ApiError>
+    where
+        T::GetOutput: GetID,
+        T::PatchParams: Clone + Send + Sync,
+        T::PatchOutput: Send,
+    {
+        self.patch_each(|_| Some(patch.clone()))
+    }
+
+    /// Patch every matching resource with the patch returned for it.
+    ///
+    /// Resources for which `patch` returns `None` are left alone and listed
+    /// as skipped. The patches are computed up front and then sent with the
+    /// configured concurrency.
+    pub fn patch_each<F>(self, mut patch: F) -> Result<BulkResult<T::PatchOutput>, ApiError>
+    where
+        T::GetOutput: GetID,
+        T::PatchParams: Send + Sync,
+        T::PatchOutput: Send,
+        F: FnMut(&T::GetOutput) -> Option<T::PatchParams>,
+    {
+        let client = self.client.clone();
+        let url_params = self.url_params.clone();
+        let concurrency = self.concurrency;
+        let sink = self.progress.clone();
+
+        let mut result = BulkResult::default();
+        let mut patches = vec![];
+        for resource in self.execute()? {
+            match patch(&resource) {
+                Some(params) => patches.push((resource.id(), params)),
+                None => result.skipped.push(resource.id()),
+            }
+        }
+
+        debug!(target: HTTP, "Patching {} resources", patches.len());
+        let progress = Progress::new(sink.as_deref(), Some(patches.len()));
+        let outcomes = bulk::run(patches, concurrency, |(id, params)| {
+            let outcome = client.patch::<T>(T::default(), id, url_params.clone(), params);
+            (id, progress.track(outcome))
+        });
+        for (id, outcome) in outcomes {
+            match outcome {
+                Ok(patched) => result.succeeded.push(patched),
+                Err(err) => result.failed.push((id, err)),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Call `f` with a handle to every matching resource, page by page.
+    ///
+    /// Up to `concurrency` calls run at once, and page fetches and calls
+    /// together are held to `rate` per second, with zero meaning no limit.
+    /// A failing call is recorded in the result and does not stop the others.
+    pub fn for_each<R, F>(
+        mut self,
+        concurrency: usize,
+        rate: u32,
+        f: F,
+    ) -> Result<BulkResult<R>, ApiError>
+    where
+        T::GetOutput: GetID + Send,
+        R: Send,
+        F: Fn(Handle<T>) -> Result<R, ApiError> + Sync,
+    {
+        let limiter = RateLimiter::new(rate);
+        let sink = self.progress.clone();
+        let progress = Progress::new(sink.as_deref(), None);
+        let mut result = BulkResult::default();
+        let mut plan = self.plan()?;
+        let mut trail = PageTrail::new(&plan.url);
+        loop {
+            limiter.acquire();
+            let page = self.client.fetch_page(plan.clone())?;
+            let next_url = trail.follow(page.next.as_deref());
+            let next_cursor = page.next_cursor.clone();
+            if let Some(count) = page.count {
+                progress.set_total(count as usize);
+            }
+            let items: Vec<T::GetOutput> = page.decode()?.unwrap_or_default();
+            self.expand_items(&items)?;
+
+            let client = &self.client;
+            let outcomes = bulk::run(items, concurrency, |item| {
+                limiter.acquire();
+                let id = item.id();
+                (id, progress.track(f(Handle::new(client.clone(), item))))
+            });
+            for (id, outcome) in outcomes {
+                match outcome {
+                    Ok(value) => result.succeeded.push(value),
+                    Err(err) => result.failed.push((id, err)),
+                }
+            }
+
+            plan = match (next_url, next_cursor) {
+                (Some(next), _) => plan.next_page(next),
+                (None, Some(cursor)) => {
+                    self.cursor = Some(Cursor::new(cursor));
+                    self.plan()?
+                }
+                (None, None) => break,
+            };
+            if !trail.visit(&plan.url) {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Fetch the matching resources on a background thread, sending them
+    /// through `sender` page by page as they arrive.
+    ///
+    /// Pages of `page_size` items are requested. The thread stops early when
+    /// the receiver is dropped, and its result is the number of items sent
+    /// or the error that ended the fetch.
+    pub fn stream_to_channel(
+        mut self,
+        sender: mpsc::Sender<T::GetOutput>,
+        page_size: usize,
+    ) -> JoinHandle<Result<usize, ApiError>>
+    where
+        T: Send + 'static,
+        T::GetOutput: Send + 'static,
+    {
+        self.limit = Some(page_size.max(1));
+        std::thread::spawn(move || {
+            let mut sent = 0;
+            let mut plan = self.plan()?;
+            let mut trail = PageTrail::new(&plan.url);
+            loop {
+                let page = self.client.fetch_page(plan.clone())?;
+                let next_url = trail.follow(page.next.as_deref());
+                let next_cursor = page.next_cursor.clone();
+                let items: Vec<T::GetOutput> = page.decode()?.unwrap_or_default();
+                self.expand_items(&items)?;
+                for item in items {
+                    if sender.send(item).is_err() {
+                        debug!(target: HTTP, "Receiver dropped after {} items", sent);
+                        return Ok(sent);
+                    }
+                    sent += 1;
+                }
+
+                plan = match (next_url, next_cursor) {
+                    (Some(next), _) => plan.next_page(next),
+                    (None, Some(cursor)) => {
+                        self.cursor = Some(Cursor::new(cursor));
+                        self.plan()?
+                    }
+                    (None, None) => return Ok(sent),
+                };
+                if !trail.visit(&plan.url) {
+                    return Ok(sent);
+                }
+            }
+        })
     }
 
     pub fn execute(self) -> Result<Vec<T::GetOutput>, ApiError> {
-        let params = T::build_params(self.filters);
-        self.client
-            .search::<T>(T::default(), self.url_params, params)
+        let plan = self.plan()?;
+        let items: Vec<T::GetOutput> = self.client.execute_plan(plan)?.unwrap_or_default();
+        self.expand_items(&items)?;
+        Ok(items)
+    }
+
+    /// Execute the query, returning handles bound to this client.
+    pub fn execute_handles(self) -> Result<Vec<Handle<T>>, ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        let client = self.client.clone();
+        Ok(self
+            .execute()?
+            .into_iter()
+            .map(|resource| Handle::new(client.clone(), resource))
+            .collect())
+    }
+
+    /// The names of the first `limit` matches, fetched as a single page.
+    fn first_names(mut self, limit: usize) -> Result<Vec<String>, ApiError> {
+        self.limit = Some(limit.max(1));
+        let page = self.client.fetch_page(self.plan()?)?;
+        let items: Vec<Value> = page.decode()?.unwrap_or_default();
+        Ok(core::names::<T>(&items))
+    }
+
+    /// The number of matching resources, without fetching them where the
+    /// server can tell.
+    pub fn count(self) -> Result<usize, ApiError> {
+        self.client.count(self.plan()?)
+    }
+
+    /// Fetch a single page using keyset pagination.
+    ///
+    /// Iterate by passing `next` of the returned page to `after` until it is
+    /// `None`. Requires server support for cursors; servers without it return
+    /// everything in one page without a next cursor.
+    pub fn execute_page(self) -> Result<CursorPage<T::GetOutput>, ApiError> {
+        let page = self.client.fetch_page(self.plan()?)?;
+        let next = page.next_cursor.clone().map(Cursor::new);
+        let items: Vec<T::GetOutput> = page.decode()?.unwrap_or_default();
+        self.expand_items(&items)?;
+        Ok(CursorPage { items, next })
+    }
+
+    /// Fetch page `page` of `per_page` resources using offset pagination,
+    /// counting pages from 1.
+    ///
+    /// Unlike `execute`, the result tells whether another page follows and,
+    /// where the server reports it, how many resources match in total.
+    pub fn execute_paged(
+        mut self,
+        page: usize,
+        per_page: usize,
+    ) -> Result<crate::types::Page<T::GetOutput>, ApiError> {
+        let page = page.max(1);
+        self.limit = Some(per_page);
+        let plan = self
+            .plan()?
+            .with_query(&[("offset", ((page - 1) * per_page).to_string())]);
+        let fetched = self.client.fetch_page(plan)?;
+        let more = fetched.next.is_some() || fetched.next_cursor.is_some();
+        let total = fetched.count;
+        let items: Vec<T::GetOutput> = fetched.decode()?.unwrap_or_default();
+        self.expand_items(&items)?;
+        Ok(crate::types::Page::new(items, total, page, per_page, more))
+    }
+
+    fn expand_items(&self, items: &[T::GetOutput]) -> Result<(), ApiError> {
+        if self.expand.is_empty() {
+            return Ok(());
+        }
+        let relations: Vec<&str> = self.expand.iter().map(String::as_str).collect();
+        T::expand(&self.client, items, &relations)
+    }
+
+    fn plan(&self) -> Result<RequestPlan, ApiError> {
+        let params = T::build_params(self.filters.clone());
+        core::validate_filters::<T>(&params)?;
+        let plan = RequestPlan::build(
+            &self.client.base_url,
+            reqwest::Method::GET,
+            &T::default().endpoint(),
+            &self.url_params,
+            params,
+            (),
+        )?;
+        Ok(plan
+            .with_query(&core::cursor_query(self.cursor.as_ref(), self.limit))
+            .with_extra_query(self.query.clone())
+            .with_timeout(self.timeout))
+    }
+}
+
+impl FilterBuilder<User> {
+    /// Execute the query, fetching the groups of every user as well.
+    ///
+    /// The groups are requested with `?expand=groups` in the same request.
+    /// Users returned without groups, as from servers that do not support
+    /// expansion, have their groups looked up one user at a time.
+    pub fn execute_with_groups(self) -> Result<Vec<UserExpanded>, ApiError> {
+        let plan = self.plan()?.with_query(&[("expand", "groups".to_string())]);
+        let items: Vec<Value> = self.client.execute_plan(plan)?.unwrap_or_default();
+
+        let mut users = Vec::with_capacity(items.len());
+        for item in items {
+            if item.get("groups").is_some() {
+                users.push(serde_json::from_value(item)?);
+                continue;
+            }
+            let user: User = serde_json::from_value(item)?;
+            debug!(target: HTTP, "No expanded groups for user {}, looking them up", user.id);
+            let groups = self
+                .client
+                .groups()
+                .find()
+                .add_filter_equals("users__id", user.id)
+                .execute()?;
+            users.push(UserExpanded { user, groups });
+        }
+        Ok(users)
+    }
+}
+
+impl FilterBuilder<Object> {
+    /// Execute the query, keeping the data of the objects as the JSON text
+    /// the server sent, see [`ObjectRaw`].
+    ///
+    /// Like `execute`, every page is fetched. The bodies are read as sent,
+    /// whatever the `NumberMode` of the client. Expansions are not supported.
+    pub fn execute_raw(self) -> Result<Vec<ObjectRaw>, ApiError> {
+        let plan = self.plan()?;
+        Ok(self.client.paginate::<RawPage<ObjectRaw>>(plan)?.items)
     }
 }
 
@@ -352,27 +1605,181 @@ impl<T: ApiResource> Resource<T> {
         K: Into<Cow<'static, str>>,
         V: Into<Cow<'static, str>>,
     {
-        let resource = Resource {
+        Resource {
             client,
             url_params: url_params
                 .into_iter()
                 .map(|(k, v)| (k.into(), v.into()))
                 .collect(),
             _phantom: PhantomData,
-        };
+        }
+    }
+
+    pub(crate) fn client(&self) -> &Client<Authenticated> {
+        &self.client
+    }
 
-        resource
+    /// Pass an extra query parameter with every request made through this
+    /// resource, including searches started from it, for server options
+    /// this crate does not model yet.
+    pub fn raw_query(mut self, key: &str, value: &str) -> Self {
+        self.client
+            .extra_query
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Override the timeout for requests made through this resource.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client.timeout = Some(timeout);
+        self
     }
 
     pub fn find(&self) -> FilterBuilder<T> {
         FilterBuilder::new(self.client.clone(), self.url_params.clone())
     }
 
+    /// Fetch the resources with the given ids as handles, in the order of
+    /// `ids`.
+    ///
+    /// The ids are fetched with `id__in`, up to a hundred per request. Ids
+    /// the server does not return are left out of the handles and listed
+    /// in `Resolved::missing` instead of failing the others.
+    pub fn resolve_handles(&self, ids: &[i32]) -> Result<Resolved<T>, ApiError>
+    where
+        T::GetOutput: GetID + Clone,
+    {
+        let mut unique = ids.to_vec();
+        unique.sort_unstable();
+        unique.dedup();
+        let mut found = vec![];
+        for chunk in unique.chunks(EXPAND_CHUNK) {
+            found.extend(self.filter(vec![ids_filter(chunk)])?);
+        }
+        let (resources, missing) = core::by_ids(ids, found);
+        Ok(Resolved {
+            handles: resources
+                .into_iter()
+                .map(|resource| Handle::new(self.client.clone(), resource))
+                .collect(),
+            missing,
+        })
+    }
+
+    /// Check if a resource with the given id exists, without fetching it.
+    pub fn exists(&self, id: i32) -> Result<bool, ApiError> {
+        let url = self
+            .client
+            .build_url(&T::default().endpoint(), self.url_params.clone());
+        let plan = RequestPlan::new(reqwest::Method::GET, format!("{}{}", url, id), None);
+        self.client.probe(plan, false)
+    }
+
+    /// The number of resources in scope, without fetching them where the
+    /// server can tell.
+    pub fn count(&self) -> Result<usize, ApiError> {
+        self.find().count()
+    }
+
+    /// Check if a resource with the given name exists, without fetching it.
+    pub fn exists_by_name(&self, name: &str) -> Result<bool, ApiError> {
+        let plan = self.find().add_filter_name_exact(name).limit(1).plan()?;
+        self.client.probe(plan, true)
+    }
+
+    pub fn select(&self, id: i32) -> Result<Handle<T>, ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        let resource = self
+            .find()
+            .add_filter_id(id)
+            .execute_expecting_single_result()?;
+        Ok(Handle::new(self.client.clone(), resource))
+    }
+
+    /// Like `select`, resolving the given relations of the resource as well.
+    pub fn select_expanded(&self, id: i32, relations: &[&str]) -> Result<Handle<T>, ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        let resource = self
+            .find()
+            .add_filter_id(id)
+            .expand(relations)
+            .execute_expecting_single_result()?;
+        Ok(Handle::new(self.client.clone(), resource))
+    }
+
+    pub fn select_by_name(&self, name: &str) -> Result<Handle<T>, ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        let resource = self
+            .find()
+            .add_filter_name_exact(name)
+            .execute_expecting_single_result()?;
+        Ok(Handle::new(self.client.clone(), resource))
+    }
+
+    /// Up to `limit` names starting with `prefix`, sorted.
+    ///
+    /// Only a single page of `limit` names is requested, so with more
+    /// matches the server decides which of them are returned.
+    pub fn search_names(&self, prefix: &str, limit: usize) -> Result<Vec<String>, ApiError> {
+        let mut names = self
+            .find()
+            .add_filter(
+                T::NAME_FIELD,
+                FilterOperator::StartsWith { is_negated: false },
+                prefix,
+            )
+            .first_names(limit)?;
+        names.sort();
+        Ok(names)
+    }
+
+    /// Up to `limit` names containing `partial`, best matches first, for
+    /// autocompletion.
+    ///
+    /// Matching ignores case. Exact matches rank first, then names starting
+    /// with `partial`, then the rest, earlier matches and shorter names first.
+    ///
+    /// Every request asks for a single page of at most `limit` names. Names
+    /// starting with `partial` are fetched first; if there are `limit` of
+    /// them, exact matches are fetched as well, and otherwise the names only
+    /// containing `partial` fill up the rest.
+    pub fn suggest(&self, partial: &str, limit: usize) -> Result<Vec<String>, ApiError> {
+        let names_with = |operator| self.find().add_filter(T::NAME_FIELD, operator, partial);
+        let mut names =
+            names_with(FilterOperator::IStartsWith { is_negated: false }).first_names(limit)?;
+        let rest = match names.len() >= limit {
+            true => names_with(FilterOperator::IEquals { is_negated: false }).first_names(limit)?,
+            false => names_with(FilterOperator::IContains { is_negated: false })
+                .first_names(limit + names.len())?,
+        };
+        names.extend(rest);
+        Ok(core::rank_suggestions(names, partial, limit))
+    }
+
+    /// Select by name, ignoring case whatever the client option.
+    pub fn select_by_name_ci(&self, name: &str) -> Result<Handle<T>, ApiError>
+    where
+        T::GetOutput: GetID,
+    {
+        let resource = self
+            .find()
+            .add_filter_name_ci(name)
+            .execute_expecting_single_result()?;
+        Ok(Handle::new(self.client.clone(), resource))
+    }
+
     pub fn filter(
         &self,
         filter: impl IntoResourceFilter<T>,
     ) -> Result<Vec<T::GetOutput>, ApiError> {
         let params = filter.into_resource_filter();
+        core::validate_filters::<T>(&params)?;
         self.client
             .search::<T>(T::default(), self.url_params.clone(), params)
     }
@@ -382,15 +1789,65 @@ impl<T: ApiResource> Resource<T> {
         filter: impl IntoResourceFilter<T>,
     ) -> Result<T::GetOutput, ApiError> {
         let params = filter.into_resource_filter();
-        one_or_err(
+        core::validate_filters::<T>(&params)?;
+        let found =
             self.client
-                .search::<T>(T::default(), self.url_params.clone(), params)?,
-        )
+                .search::<T>(T::default(), self.url_params.clone(), params.clone())?;
+        one_or_err(found, &params)
     }
 
     pub fn create(&self, params: T::PostParams) -> Result<T::PostOutput, ApiError> {
-        self.client
-            .post::<T>(T::default(), self.url_params.clone(), params)
+        let name = core::post_name::<T>(&params);
+        let created = self
+            .client
+            .post::<T>(T::default(), self.url_params.clone(), params)?;
+        self.forget_missing(name);
+        Ok(created)
+    }
+
+    /// Drop what the session cache remembers of `name` being missing, after
+    /// a resource by that name was created.
+    fn forget_missing(&self, name: Option<String>) {
+        if let (Some(cache), Some(name)) = (self.client.cache(), name) {
+            cache.remove_missing(&name);
+        }
+    }
+
+    /// Like `create`, with `options` deciding what happens if the resource
+    /// exists and which idempotency key to send. A `CreateMode` alone works
+    /// too, with a generated key.
+    pub fn create_with(
+        &self,
+        params: T::PostParams,
+        options: impl Into<CreateOptions>,
+    ) -> Result<T::PostOutput, ApiError>
+    where
+        T::PostOutput: From<T::GetOutput>,
+    {
+        let options = options.into();
+        let key = options.idempotency_key.unwrap_or_else(generate_key);
+        let created_name = core::post_name::<T>(&params);
+        let name = match options.mode {
+            CreateMode::Fail => None,
+            CreateMode::ReturnExistingOnConflict => created_name.clone(),
+        };
+        let created =
+            self.client
+                .post_with_key::<T>(T::default(), self.url_params.clone(), params, key);
+        if created.is_ok() {
+            self.forget_missing(created_name);
+        }
+        match (created, name) {
+            (Err(err), Some(name)) if err.status() == Some(StatusCode::CONFLICT) => {
+                debug!(target: HTTP, "{} already exists, fetching it", name);
+                let existing = self
+                    .find()
+                    .add_filter_name_exact(name)
+                    .execute_expecting_single_result()?;
+                Ok(existing.into())
+            }
+            (result, _) => result,
+        }
     }
 
     pub fn update(&self, id: i32, params: T::PatchParams) -> Result<T::PatchOutput, ApiError> {
@@ -398,35 +1855,269 @@ impl<T: ApiResource> Resource<T> {
             .patch::<T>(T::default(), id, self.url_params.clone(), params)
     }
 
+    /// Replace the resource with the given id, asserting its complete state.
+    pub fn replace(&self, id: i32, params: T::PostParams) -> Result<T::PostOutput, ApiError> {
+        self.client
+            .put::<T>(T::default(), id, self.url_params.clone(), params)
+    }
+
     pub fn delete(&self, id: i32) -> Result<(), ApiError> {
         self.client
             .delete::<T>(T::default(), id, self.url_params.clone())
     }
 }
 
-fn one_or_err<T>(mut v: Vec<T>) -> Result<T, ApiError> {
-    let name = type_name::<T>();
-    let name = name.rsplit("::").next().unwrap_or(name);
-
-    if v.len() == 1 {
-        Ok(v.pop().unwrap())
-    } else if v.is_empty() {
-        Err(ApiError::EmptyResult(format!("{} not found", name)))
-    } else {
-        Err(ApiError::TooManyResults(format!(
-            "Type: {}, Count: {} (expected 1)",
-            name,
-            v.len()
-        )))
+#[cfg(feature = "rayon")]
+impl<T: ApiResource + Sync> Resource<T>
+where
+    T::PostParams: Send,
+    T::PostOutput: Send,
+    T::GetOutput: Send,
+{
+    /// Fetch the resources with the given ids, using `threads` connections.
+    ///
+    /// Results are in the order of `ids`. A failed fetch is recorded in the
+    /// result and does not stop the others.
+    pub fn get_many_parallel(
+        &self,
+        ids: &[i32],
+        threads: usize,
+    ) -> Result<BulkResult<T::GetOutput>, ApiError> {
+        self.parallel(threads, ids.to_vec(), |id| {
+            (
+                id,
+                self.find()
+                    .add_filter_id(id)
+                    .execute_expecting_single_result(),
+            )
+        })
+    }
+
+    /// Create the resources, using `threads` connections.
+    ///
+    /// Failures are recorded by the position of their parameters in `params`.
+    pub fn create_many_parallel(
+        &self,
+        params: Vec<T::PostParams>,
+        threads: usize,
+    ) -> Result<BulkResult<T::PostOutput>, ApiError> {
+        let indexed = params.into_iter().enumerate().collect();
+        self.parallel(threads, indexed, |(index, params)| {
+            (index as i32, self.create(params))
+        })
+    }
+
+    /// Delete the resources with the given ids, using `threads` connections.
+    pub fn delete_many_parallel(
+        &self,
+        ids: &[i32],
+        threads: usize,
+    ) -> Result<BulkResult<i32>, ApiError> {
+        self.parallel(threads, ids.to_vec(), |id| {
+            (id, self.delete(id).map(|_| id))
+        })
+    }
+
+    fn parallel<I, R, F>(
+        &self,
+        threads: usize,
+        items: Vec<I>,
+        f: F,
+    ) -> Result<BulkResult<R>, ApiError>
+    where
+        I: Send,
+        R: Send,
+        F: Fn(I) -> (i32, Result<R, ApiError>) + Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.max(1))
+            .build()
+            .map_err(std::io::Error::other)?;
+        debug!(target: HTTP, "Running {} requests on {} threads", items.len(), pool.current_num_threads());
+        let outcomes: Vec<_> = pool.install(|| items.into_par_iter().map(f).collect());
+
+        let mut result = BulkResult::default();
+        for (id, outcome) in outcomes {
+            match outcome {
+                Ok(value) => result.succeeded.push(value),
+                Err(err) => result.failed.push((id, err)),
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl Resource<Namespace> {
+    /// Create a namespace and grant permissions on it to groups.
+    ///
+    /// If any grant fails, the grants made so far and the namespace are
+    /// deleted again, and the error of the failed grant is returned along
+    /// with any errors from undoing the changes.
+    pub fn create_with_permissions(
+        &self,
+        params: NamespacePost,
+        grants: Vec<(i32, PermissionSet)>,
+    ) -> Result<Namespace, ApiError> {
+        let namespace = self.create(params)?;
+        let permissions = self.client.namespace_permissions(namespace.id);
+        core::apply_or_roll_back(
+            grants,
+            |(group_id, set)| {
+                let granted = permissions.create(set.to_post(group_id));
+                if granted.is_err() {
+                    warn!(
+                        target: HTTP,
+                        "Granting permissions on namespace {} to group {} failed, rolling back",
+                        namespace.id,
+                        group_id
+                    );
+                }
+                granted.map(|permission| permission.id)
+            },
+            |id| permissions.delete(id),
+            || self.delete(namespace.id),
+        )?;
+        Ok(namespace)
+    }
+}
+
+impl Resource<Class> {
+    /// Every class along with the number of objects in it.
+    ///
+    /// The objects are counted, not fetched: one count per class, run
+    /// `DEFAULT_BULK_CONCURRENCY` at a time, each answered from
+    /// `X-Total-Count` where the server reports it. Any failed count fails
+    /// the call, with all failures combined.
+    pub fn with_object_counts(&self) -> Result<Vec<(Class, u64)>, ApiError> {
+        let classes = self.find().execute()?;
+        debug!(target: HTTP, "Counting the objects of {} classes", classes.len());
+        let counted = bulk::run(classes, DEFAULT_BULK_CONCURRENCY, |class| {
+            let count = self.client.objects(class.id).count();
+            (class, count)
+        });
+        let mut counts = vec![];
+        let mut errors = vec![];
+        for (class, count) in counted {
+            match count {
+                Ok(count) => counts.push((class, count as u64)),
+                Err(err) => errors.push(err),
+            }
+        }
+        match ApiError::from_errors(errors) {
+            Some(err) => Err(err),
+            None => Ok(counts),
+        }
+    }
+}
+
+impl Resource<User> {
+    /// Like `select`, fetching the groups of the user as well.
+    pub fn select_with_groups(&self, id: i32) -> Result<UserExpanded, ApiError> {
+        let find = self.find().add_filter_id(id);
+        let filters = User::build_params(find.filters.clone());
+        one_or_err(find.execute_with_groups()?, &filters)
+    }
+}
+
+impl Resource<TokenEntry> {
+    /// Revoke the tokens that have not been used since `cutoff`.
+    ///
+    /// Returns the ids of the revoked tokens. A failure to revoke one token
+    /// does not stop the others; all failures are returned together.
+    pub fn prune_stale(&self, cutoff: chrono::NaiveDateTime) -> Result<Vec<i32>, ApiError> {
+        let mut pruned = vec![];
+        let mut errors = vec![];
+        for token in self.find().execute()? {
+            if !token.is_stale(cutoff) {
+                continue;
+            }
+            match self.delete(token.id) {
+                Ok(()) => pruned.push(token.id),
+                Err(err) => errors.push(err),
+            }
+        }
+        debug!(target: AUTH, "Pruned {} stale tokens", pruned.len());
+        match ApiError::from_errors(errors) {
+            Some(err) => Err(err),
+            None => Ok(pruned),
+        }
+    }
+}
+
+impl Resource<Object> {
+    /// Create an object in the class from a template.
+    pub fn create_from_template(
+        &self,
+        template: &Template,
+        vars: &[(&str, &str)],
+    ) -> Result<Object, ApiError> {
+        self.create(template.render(self.class_id()?, vars)?)
+    }
+
+    /// Create an object from an [`ObjectRaw`], as one fetched from another
+    /// server, sending its data on without parsing it.
+    ///
+    /// The object is created in the class of this resource, with the name,
+    /// namespace, description and data of `object`.
+    pub fn create_raw(&self, object: &ObjectRaw) -> Result<ObjectRaw, ApiError> {
+        let endpoint = Object::default().endpoint();
+        endpoint.validate(&reqwest::Method::POST, &self.url_params)?;
+        let plan = RequestPlan::build(
+            &self.client.base_url,
+            reqwest::Method::POST,
+            &endpoint,
+            &self.url_params,
+            vec![],
+            object.as_post(self.class_id()?),
+        )?
+        .raw();
+        self.client
+            .execute_plan(plan)?
+            .ok_or(ApiError::EmptyResult("POST returned empty result".into()))
+    }
+
+    fn class_id(&self) -> Result<i32, ApiError> {
+        self.url_params
+            .iter()
+            .find(|(key, _)| key == "class_id")
+            .and_then(|(_, value)| value.parse().ok())
+            .ok_or(ApiError::MissingUrlIdentifier)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::client::testing::{Reply, TestServer};
     use std::str::FromStr;
     use yare::parameterized;
 
+    fn authenticated_client() -> Client<Authenticated> {
+        Client {
+            http_client: reqwest::blocking::Client::new(),
+            base_url: BaseUrl::from_str("http://127.0.0.1:9").unwrap(),
+            state: Authenticated::new("token".to_string()),
+            timeout: None,
+            signer: None,
+            cache: None,
+            retry: None,
+            body_log: BodyLog::default(),
+            curl: None,
+            capture: None,
+            scheduler: None,
+            priority: Priority::default(),
+            case_insensitive_names: false,
+            failover: None,
+            extra_query: vec![],
+            max_response_bytes: None,
+            compression: None,
+            deadline: None,
+            number_mode: NumberMode::default(),
+        }
+    }
+
     #[parameterized(
         login_foo = { "https://foo.bar.com", Endpoint::Login },
         get_user_foo = { "https://foo.bar.com", Endpoint::Users },
@@ -450,4 +2141,678 @@ mod test {
             )
         );
     }
+
+    #[parameterized(
+        base_url = { "https://example.com/hubuum/", "" },
+        builder = { "https://example.com", "/hubuum" }
+    )]
+    fn test_build_url_with_path_prefix(server: &str, prefix: &str) {
+        let client = Client::builder(BaseUrl::from_str(server).unwrap())
+            .path_prefix(prefix)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            client.build_url(&Endpoint::Classes, UrlParams::default()),
+            "https://example.com/hubuum/api/v1/classes/"
+        );
+    }
+
+    #[test]
+    fn test_past_deadline() {
+        // Nothing is sent once the deadline has passed, so the unreachable
+        // server is never contacted.
+        let result = authenticated_client()
+            .with_deadline(Instant::now())
+            .users()
+            .find()
+            .execute();
+        assert!(matches!(
+            result,
+            Err(ApiError::DeadlineExceeded { partial }) if partial.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_name_field() {
+        assert_eq!(Class::NAME_FIELD, "name");
+        assert_eq!(Group::NAME_FIELD, "groupname");
+        assert_eq!(User::NAME_FIELD, "username");
+    }
+
+    #[test]
+    fn test_expand() {
+        let client = authenticated_client();
+        let object = Object {
+            namespace_id: 1.into(),
+            ..Default::default()
+        };
+        object.namespace_id.fill(Namespace {
+            id: 1,
+            ..Default::default()
+        });
+
+        // Already resolved references do not need a request.
+        assert!(Object::expand(&client, &[object], &["namespace"]).is_ok());
+        assert!(matches!(
+            Object::expand(&client, &[], &["owner"]),
+            Err(ApiError::InvalidExpand(_))
+        ));
+        assert!(matches!(
+            Class::expand(&client, &[], &["namespace"]),
+            Err(ApiError::InvalidExpand(_))
+        ));
+    }
+
+    #[test]
+    fn test_uncompressed_fallback_within_one_slot() {
+        let server = TestServer::start(|request| match request.header("Content-Encoding") {
+            Some("gzip") => Reply::json(415, r#"{"message": "gzip not supported"}"#),
+            _ => Reply::json(201, request.body_text()),
+        });
+        let client = Client::builder(server.base_url())
+            .max_in_flight(1)
+            .compress_requests(16)
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        // A retry holding on to its slot would wait for itself forever.
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let post = |name: &str| {
+                client.request_with_endpoint::<_, Value>(
+                    reqwest::Method::POST,
+                    &Endpoint::Classes,
+                    UrlParams::default(),
+                    vec![],
+                    serde_json::json!({ "name": name.repeat(16) }),
+                )
+            };
+            let _ = sender.send((post("first"), post("second")));
+        });
+        let (first, second) = receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("the fallback deadlocked");
+        assert_eq!(first.unwrap().unwrap()["name"], "first".repeat(16));
+        assert!(second.is_ok());
+
+        let encodings: Vec<Option<String>> = server
+            .requests()
+            .iter()
+            .map(|request| request.header("Content-Encoding").map(str::to_string))
+            .collect();
+        assert_eq!(encodings, vec![Some("gzip".to_string()), None, None]);
+    }
+
+    #[test]
+    fn test_pagination_stops_at_visited_page() {
+        let server = TestServer::start(|request| {
+            let (items, next) = match request.path.as_str() {
+                "/api/v1/classes/" => ("[1]", "/api/v1/classes/?page=2"),
+                "/api/v1/classes/?page=2" => ("[2]", "/api/v1/classes/"),
+                path => panic!("unexpected request for {}", path),
+            };
+            Reply::json(200, items).with_header("Link", &format!("<{}>; rel=\"next\"", next))
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let items: Option<Vec<u32>> = client
+            .request_with_endpoint(
+                reqwest::Method::GET,
+                &Endpoint::Classes,
+                UrlParams::default(),
+                vec![],
+                (),
+            )
+            .unwrap();
+        assert_eq!(items, Some(vec![1, 2]));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_object_handles_with_missing() {
+        let server = TestServer::start(|request| {
+            assert_eq!(request.path, "/api/v1/classes/5/?id__in=3,7,9");
+            let objects: Vec<String> = [7, 3]
+                .iter()
+                .map(|id| {
+                    format!(
+                        r#"{{"id": {id}, "name": "o{id}", "namespace_id": 1, "hubuum_class_id": 5,
+                        "description": "", "data": null, "created_at": "2024-01-01T00:00:00",
+                        "updated_at": "2024-01-01T00:00:00"}}"#
+                    )
+                })
+                .collect();
+            Reply::json(200, format!("[{}]", objects.join(",")))
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let resolved = client.objects(5).resolve_handles(&[9, 7, 3, 7]).unwrap();
+        let ids: Vec<i32> = resolved.handles.iter().map(Handle::id).collect();
+        assert_eq!(ids, vec![7, 3, 7]);
+        assert_eq!(resolved.missing, vec![9]);
+        assert!(!resolved.is_complete());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    /// Two pages of objects with data, the second linking back to the first.
+    fn raw_pages_server() -> TestServer {
+        TestServer::start(|request| {
+            let (id, next) = match request.path.as_str() {
+                "/api/v1/classes/5/" => (1, "/api/v1/classes/5/?page=2"),
+                _ => (2, "/api/v1/classes/5/"),
+            };
+            let body = format!(
+                r#"[{{"id": {id}, "name": "o{id}", "namespace_id": 1, "hubuum_class_id": 5,
+                "description": "", "data": {{"mass": 1.50}}, "created_at": "2024-01-01T00:00:00",
+                "updated_at": "2024-01-01T00:00:00"}}]"#
+            );
+            Reply::json(200, body).with_header("Link", &format!("<{}>; rel=\"next\"", next))
+        })
+    }
+
+    #[test]
+    fn test_execute_raw_reads_pages_as_sent() {
+        let server = raw_pages_server();
+        let client = Client::builder(server.base_url())
+            .number_mode(NumberMode::String)
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let objects = client.objects(5).find().execute_raw().unwrap();
+        let data: Vec<&str> = objects
+            .iter()
+            .map(|object| object.data.as_deref().unwrap().get())
+            .collect();
+        assert_eq!(data, vec![r#"{"mass": 1.50}"#; 2]);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_number_mode_only_rewrites_objects() {
+        let server =
+            TestServer::start(|_| Reply::json(200, r#"[{"id": 1, "data": {"mass": 1.5}}]"#));
+        let client = Client::builder(server.base_url())
+            .number_mode(NumberMode::String)
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let get = |endpoint: Endpoint, url_params: UrlParams| {
+            client
+                .request_with_endpoint::<_, Vec<Value>>(
+                    reqwest::Method::GET,
+                    &endpoint,
+                    url_params,
+                    vec![],
+                    (),
+                )
+                .unwrap()
+                .unwrap()
+        };
+        let objects = get(Endpoint::Objects, vec![("class_id".into(), "5".into())]);
+        assert_eq!(objects[0]["data"]["mass"], "1.5");
+        let classes = get(Endpoint::Classes, UrlParams::default());
+        assert_eq!(classes[0]["data"]["mass"], 1.5);
+    }
+
+    #[test]
+    fn test_number_mode_string_refuses_data_writes() {
+        let server = TestServer::start(|_| {
+            Reply::json(
+                200,
+                r#"{"id": 7, "name": "o7", "namespace_id": 1, "hubuum_class_id": 5,
+                "description": "", "data": {"mass": 1.5}, "created_at": "2024-01-01T00:00:00",
+                "updated_at": "2024-01-01T00:00:00"}"#,
+            )
+        });
+        let client = Client::builder(server.base_url())
+            .number_mode(NumberMode::String)
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        let object: Object = client
+            .request_with_endpoint(
+                reqwest::Method::GET,
+                &Endpoint::Objects,
+                vec![("class_id".into(), "5".into())],
+                vec![],
+                (),
+            )
+            .unwrap()
+            .unwrap();
+        let handle: Handle<Object> = Handle::new(client.clone(), object);
+
+        // Writing back what was read would store the numbers as strings.
+        let mut data = handle.resource().data.clone().unwrap();
+        data["unit"] = "kg".into();
+        assert!(matches!(
+            handle.update_data(data),
+            Err(ApiError::UnsupportedHttpOperation(_))
+        ));
+        let renamed = crate::resources::ObjectPatch {
+            name: Some("renamed".into()),
+            ..Default::default()
+        };
+        assert!(client.objects(5).update(7, renamed).is_ok());
+        let methods: Vec<String> = server.requests().into_iter().map(|r| r.method).collect();
+        assert_eq!(methods, vec!["GET", "PATCH"]);
+    }
+
+    #[test]
+    fn test_create_forgets_missing_name() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let created = Arc::new(AtomicBool::new(false));
+        let exists = created.clone();
+        let server = TestServer::start(move |request| {
+            let namespace = r#"{"id": 4, "name": "x", "description": "",
+                "created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00"}"#;
+            match request.method.as_str() {
+                "POST" => {
+                    exists.store(true, Ordering::SeqCst);
+                    Reply::json(201, namespace)
+                }
+                _ if exists.load(Ordering::SeqCst) => Reply::json(200, format!("[{}]", namespace)),
+                _ => Reply::json(200, "[]"),
+            }
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap()
+            .with_cache(SessionCache::with_negative_ttl(Duration::from_secs(60)));
+
+        assert!(matches!(
+            client.namespace("x"),
+            Err(ApiError::EmptyResult(_))
+        ));
+        client
+            .namespaces()
+            .create(NamespacePost {
+                name: "x".to_string(),
+                description: String::new(),
+                group_id: 1,
+            })
+            .unwrap();
+        assert!(created.load(Ordering::SeqCst));
+        assert_eq!(client.namespace("x").unwrap().id(), 4);
+    }
+
+    #[test]
+    fn test_exists_by_name_follows_name_matching() {
+        let server = TestServer::start(|_| Reply::json(200, "").with_header("X-Total-Count", "1"));
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        assert!(client.namespaces().exists_by_name("Prod").unwrap());
+        assert!(client
+            .with_case_insensitive_names(true)
+            .namespaces()
+            .exists_by_name("Prod")
+            .unwrap());
+        let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/api/v1/namespaces/?name__equals=Prod&limit=1",
+                "/api/v1/namespaces/?name__iequals=Prod&limit=1",
+            ]
+        );
+    }
+
+    #[parameterized(
+        few_prefixed = {
+            "we", 3,
+            &["/api/v1/namespaces/?name__istartswith=we&limit=3",
+              "/api/v1/namespaces/?name__icontains=we&limit=4"],
+            &["web", "ewe", "owe"],
+        },
+        many_prefixed = {
+            "web", 1,
+            &["/api/v1/namespaces/?name__istartswith=web&limit=1",
+              "/api/v1/namespaces/?name__iequals=web&limit=1"],
+            &["web"],
+        },
+    )]
+    fn test_suggest_requests_limited_pages(
+        partial: &str,
+        limit: usize,
+        paths: &[&str],
+        expected: &[&str],
+    ) {
+        let server = TestServer::start(|request| {
+            let names: &[&str] = match request.path.split_once("__").unwrap().1 {
+                query if query.starts_with("istartswith=web") => &["webserver"],
+                query if query.starts_with("istartswith") => &["web"],
+                query if query.starts_with("iequals") => &["web"],
+                _ => &["owe", "web", "ewe"],
+            };
+            let items: Vec<String> = names
+                .iter()
+                .enumerate()
+                .map(|(id, name)| {
+                    format!(
+                        r#"{{"id": {id}, "name": "{name}", "description": "",
+                        "created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00"}}"#
+                    )
+                })
+                .collect();
+            Reply::json(200, format!("[{}]", items.join(",")))
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        assert_eq!(
+            client.namespaces().suggest(partial, limit).unwrap(),
+            expected
+        );
+        let requested: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(requested, paths);
+    }
+
+    #[test]
+    fn test_create_with_permissions_rolls_back() {
+        let server =
+            TestServer::start(
+                |request| match (request.method.as_str(), request.path.as_str()) {
+                    ("POST", "/api/v1/namespaces/") => {
+                        let namespace = Namespace {
+                            id: 4,
+                            ..Default::default()
+                        };
+                        Reply::json(201, serde_json::to_string(&namespace).unwrap())
+                    }
+                    ("POST", _) if request.body_text().contains(r#""group_id":1,"#) => {
+                        let permission = Permission {
+                            id: 11,
+                            ..Default::default()
+                        };
+                        Reply::json(201, serde_json::to_string(&permission).unwrap())
+                    }
+                    ("POST", _) => Reply::json(403, "{}"),
+                    _ => Reply::json(204, ""),
+                },
+            );
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        let post = NamespacePost {
+            name: "x".to_string(),
+            description: String::new(),
+            group_id: 1,
+        };
+        let grants = vec![(1, PermissionSet::all()), (2, PermissionSet::read_only())];
+        let result = client.namespaces().create_with_permissions(post, grants);
+        assert!(result.is_err(), "{:?}", result);
+        let requests: Vec<String> = server
+            .requests()
+            .into_iter()
+            .map(|r| format!("{} {}", r.method, r.path))
+            .collect();
+        assert_eq!(
+            requests,
+            vec![
+                "POST /api/v1/namespaces/",
+                "POST /api/v1/namespaces/4/permissions/",
+                "POST /api/v1/namespaces/4/permissions/",
+                "DELETE /api/v1/namespaces/4/permissions/11",
+                "DELETE /api/v1/namespaces/4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_retries_and_falls_back() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let seen = calls.clone();
+        let server = TestServer::start(move |request| {
+            match (request.method.as_str(), seen.fetch_add(1, Ordering::SeqCst)) {
+                ("HEAD", 0) => Reply::json(503, ""),
+                ("HEAD", 1) => Reply::json(200, "").with_header("X-Total-Count", "7"),
+                ("HEAD", _) => Reply::json(405, ""),
+                _ => Reply::json(200, r#"[{"id": 1}, {"id": 2}]"#),
+            }
+        });
+        let client = Client::builder(server.base_url())
+            .retry(RetryPolicy::new(1).backoff(Duration::ZERO))
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        assert_eq!(client.groups().count().unwrap(), 7);
+        assert_eq!(client.groups().count().unwrap(), 2);
+        let methods: Vec<String> = server.requests().into_iter().map(|r| r.method).collect();
+        assert_eq!(methods, vec!["HEAD", "HEAD", "HEAD", "GET"]);
+    }
+
+    #[test]
+    fn test_signs_compressed_bodies_as_sent() {
+        use hmac::{Hmac, KeyInit, Mac};
+        use sha2::Sha256;
+
+        let server = TestServer::start(|_| Reply::json(201, "{}"));
+        let signer = RequestSigner::new("secret");
+        let client = Client::builder(server.base_url())
+            .signer(signer.clone())
+            .compress_requests(0)
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        client
+            .request_with_endpoint::<_, Value>(
+                reqwest::Method::POST,
+                &Endpoint::Groups,
+                vec![],
+                vec![],
+                serde_json::json!({ "groupname": "ops", "description": "" }),
+            )
+            .unwrap();
+
+        let request = server.requests().pop().unwrap();
+        assert_eq!(request.header("Content-Encoding"), Some("gzip"));
+        // Computed over the bytes received, not through the signer.
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(b"POST\n/api/v1/iam/groups/\n");
+        mac.update(&request.body);
+        let expected = hex::encode(mac.finalize().into_bytes());
+        assert_eq!(request.header(signer.header()), Some(expected.as_str()));
+    }
+
+    #[test]
+    fn test_exists_retries_and_falls_back() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let seen = calls.clone();
+        let server = TestServer::start(move |request| {
+            match (request.method.as_str(), seen.fetch_add(1, Ordering::SeqCst)) {
+                ("HEAD", 0) => Reply::json(503, ""),
+                ("HEAD", 1) => Reply::json(404, ""),
+                ("HEAD", _) => Reply::json(405, ""),
+                _ => Reply::json(200, "{}"),
+            }
+        });
+        let client = Client::builder(server.base_url())
+            .retry(RetryPolicy::new(1).backoff(Duration::ZERO))
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        assert!(!client.groups().exists(7).unwrap());
+        assert!(client.groups().exists(7).unwrap());
+        let methods: Vec<String> = server.requests().into_iter().map(|r| r.method).collect();
+        assert_eq!(methods, vec!["HEAD", "HEAD", "HEAD", "GET"]);
+    }
+
+    #[test]
+    fn test_search_options_stay_off_the_patches() {
+        let server = TestServer::start(|request| {
+            let group = r#"{"id": 1, "groupname": "g1", "description": "",
+                "created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00"}"#;
+            match request.method.as_str() {
+                "GET" => Reply::json(200, format!("[{}]", group)),
+                _ => Reply::json(200, group),
+            }
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let result = client
+            .groups()
+            .find()
+            .raw_query("verbose", "1")
+            .timeout(Duration::from_secs(5))
+            .patch_all(crate::resources::GroupPatch::default())
+            .unwrap();
+        assert_eq!(result.succeeded.len(), 1);
+        let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            vec!["/api/v1/iam/groups/?verbose=1", "/api/v1/iam/groups/1"]
+        );
+    }
+
+    #[test]
+    fn test_max_response_bytes_spans_pages() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/api/v1/classes/" => Reply::json(200, "[1, 2, 3]")
+                .with_header("Link", "</api/v1/classes/?page=2>; rel=\"next\""),
+            "/api/v1/iam/groups/" => Reply::json(500, "x".repeat(64)),
+            _ => Reply::json(200, "[4, 5, 6]"),
+        });
+        let client = Client::builder(server.base_url())
+            .max_response_bytes(12)
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let get = |endpoint| {
+            client.request_with_endpoint::<_, Vec<u32>>(
+                reqwest::Method::GET,
+                &endpoint,
+                UrlParams::default(),
+                vec![],
+                (),
+            )
+        };
+        // Each page fits on its own, both together do not.
+        assert!(matches!(
+            get(Endpoint::Classes),
+            Err(ApiError::ResponseTooLarge { limit: 12, .. })
+        ));
+        assert!(matches!(
+            get(Endpoint::Groups),
+            Err(ApiError::ResponseTooLarge { limit: 12, .. })
+        ));
+    }
+
+    #[test]
+    fn test_subscribe_reconnects_on_unavailable_but_not_on_forbidden() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let seen = calls.clone();
+        let server = TestServer::start(move |_| match seen.fetch_add(1, Ordering::SeqCst) {
+            0 => Reply::events("retry: 1\n\n"),
+            1 => Reply::json(502, ""),
+            2 => Reply::json(503, ""),
+            3 => Reply::events("data: back\n\n"),
+            _ => Reply::json(403, "{}"),
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let mut events = vec![];
+        client
+            .subscribe(vec![], |event| {
+                events.push(event.data);
+                false
+            })
+            .unwrap();
+        assert_eq!(events, vec!["back"]);
+        let err = client.subscribe(vec![], |_| true).unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::FORBIDDEN));
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_subscribe_resumes_without_partial_event() {
+        let server = TestServer::start(|request| match request.header("Last-Event-ID") {
+            None => Reply::events("retry: 10\nid: 5\ndata: first\n\nevent: cut\ndata: ha"),
+            Some(_) => Reply::events("data: second\n\n"),
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let mut events = vec![];
+        client
+            .subscribe(vec![], |event| {
+                events.push(event);
+                events.len() < 2
+            })
+            .unwrap();
+        let events: Vec<(Option<&str>, &str, &str)> = events
+            .iter()
+            .map(|event| {
+                (
+                    event.id.as_deref(),
+                    event.kind.as_str(),
+                    event.data.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (Some("5"), "message", "first"),
+                (Some("5"), "message", "second")
+            ]
+        );
+        let resumed: Vec<Option<String>> = server
+            .requests()
+            .iter()
+            .map(|request| request.header("Last-Event-ID").map(str::to_string))
+            .collect();
+        let resumed: Vec<Option<&str>> = resumed.iter().map(Option::as_deref).collect();
+        assert_eq!(resumed, vec![None, Some("5")]);
+    }
 }
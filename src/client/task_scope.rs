@@ -0,0 +1,179 @@
+//! Running a group of async operations together, see
+//! `AsyncClient::task_scope`.
+//!
+//! ```no_run
+//! # use hubuum_client::{AsyncClient, BaseUrl, ClassPost, Credentials};
+//! # use std::str::FromStr;
+//! # async fn example(posts: Vec<ClassPost>) -> Result<(), Box<dyn std::error::Error>> {
+//! let client = AsyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//!     .login(Credentials::new("user".into(), "pass".into()))
+//!     .await?;
+//! let classes = client.classes();
+//! let results = client
+//!     .task_scope(|scope| {
+//!         scope.cancel_on_error(true);
+//!         for post in posts {
+//!             scope.spawn(classes.create(post));
+//!         }
+//!     })
+//!     .await;
+//! for result in results {
+//!     println!("{:?}", result.map(|class| class.id));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use futures::future::BoxFuture;
+use futures::{Future, StreamExt};
+
+use crate::errors::ApiError;
+use crate::logging::{debug, warn, HTTP};
+
+/// The operations spawned in a task scope, and how to run them.
+pub struct TaskScope<'a, T> {
+    tasks: Vec<BoxFuture<'a, Result<T, ApiError>>>,
+    cancel_on_error: bool,
+}
+
+impl<'a, T> TaskScope<'a, T> {
+    pub(crate) fn new() -> Self {
+        TaskScope {
+            tasks: vec![],
+            cancel_on_error: false,
+        }
+    }
+
+    /// Add an operation to the scope.
+    ///
+    /// Operations start once the closure passed to `task_scope` returns, in
+    /// the order they were spawned.
+    pub fn spawn<F>(&mut self, task: F)
+    where
+        F: Future<Output = Result<T, ApiError>> + Send + 'a,
+    {
+        self.tasks.push(Box::pin(task));
+    }
+
+    /// Stop at the first failed operation, cancelling those still running
+    /// and skipping those not started yet. Off by default, where every
+    /// operation runs regardless of the others failing.
+    pub fn cancel_on_error(&mut self, cancel: bool) {
+        self.cancel_on_error = cancel;
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Run the operations, at most `concurrency` at a time.
+    ///
+    /// The results are in the order the operations were spawned. Operations
+    /// cancelled or skipped after a failure yield `ApiError::Cancelled`.
+    pub(crate) async fn run(self, concurrency: usize) -> Vec<Result<T, ApiError>> {
+        let count = self.tasks.len();
+        debug!(
+            target: HTTP,
+            "Running {} scoped tasks, {} at a time",
+            count,
+            concurrency
+        );
+        let mut results: Vec<Option<Result<T, ApiError>>> = (0..count).map(|_| None).collect();
+        let mut running = futures::stream::iter(
+            self.tasks
+                .into_iter()
+                .enumerate()
+                .map(|(index, task)| async move { (index, task.await) }),
+        )
+        .buffer_unordered(concurrency.max(1));
+        while let Some((index, result)) = running.next().await {
+            let failed = result.is_err();
+            results[index] = Some(result);
+            if failed && self.cancel_on_error {
+                warn!(
+                    target: HTTP,
+                    "Scoped task {} failed, cancelling the remaining tasks", index
+                );
+                break;
+            }
+        }
+        // Dropping the stream cancels the operations still running.
+        drop(running);
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or(Err(ApiError::Cancelled)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn failure() -> ApiError {
+        ApiError::HttpWithBody {
+            status: StatusCode::CONFLICT,
+            message: "exists".to_string(),
+            url: "http://localhost/api/v1/classes/".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_results_in_spawn_order() {
+        let mut scope = TaskScope::new();
+        for (index, delay) in [30, 10, 20].into_iter().enumerate() {
+            scope.spawn(async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                match index {
+                    1 => Err(failure()),
+                    _ => Ok(index),
+                }
+            });
+        }
+        let results = scope.run(3).await;
+        assert!(matches!(results[0], Ok(0)));
+        assert!(matches!(results[1], Err(ApiError::HttpWithBody { .. })));
+        assert!(matches!(results[2], Ok(2)));
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        let (running, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let mut scope = TaskScope::new();
+        for _ in 0..6 {
+            scope.spawn(async {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            });
+        }
+        assert_eq!(scope.len(), 6);
+        let results = scope.run(2).await;
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_on_error() {
+        let mut scope = TaskScope::new();
+        scope.cancel_on_error(true);
+        scope.spawn(async { Err(failure()) });
+        scope.spawn(async {
+            std::future::pending::<()>().await;
+            Ok(1)
+        });
+        scope.spawn(async { Ok(2) });
+        let results = scope.run(2).await;
+        assert!(matches!(results[0], Err(ApiError::HttpWithBody { .. })));
+        assert!(matches!(results[1], Err(ApiError::Cancelled)));
+        assert!(matches!(results[2], Err(ApiError::Cancelled)));
+    }
+}
@@ -0,0 +1,210 @@
+//! Poll-based change detection for resources.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use std::{str::FromStr, time::Duration};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let client = SyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//! #     .login(Credentials::new("user".into(), "pass".into()))?;
+//! let objects = client.objects(1);
+//! for event in client.watch(objects, vec![], Duration::from_secs(30)) {
+//!     println!("{:?}", event?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use super::sync::{Client, Resource};
+use super::{Authenticated, GetID, GetUpdatedAt};
+use crate::errors::ApiError;
+use crate::logging::{debug, trace, EVENTS};
+use crate::resources::ApiResource;
+use crate::types::QueryFilter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent<T> {
+    Created(T),
+    Updated(T),
+    Deleted(i32),
+}
+
+/// Watches a set of resources by polling the server.
+///
+/// Every poll lists the resources matching the filters once. Creations and
+/// deletions are found by comparing the ids between polls, and updates by
+/// comparing each resource's `updated_at` with the one seen before, so that
+/// changes within the same tick as an earlier change are not missed.
+/// The first poll only records the current state and yields no events.
+pub struct Watcher<T: ApiResource> {
+    resource: Resource<T>,
+    filters: Vec<QueryFilter>,
+    interval: Duration,
+    known: HashMap<i32, chrono::NaiveDateTime>,
+    pending: VecDeque<WatchEvent<T::GetOutput>>,
+    primed: bool,
+}
+
+impl<T: ApiResource> Watcher<T>
+where
+    T::GetOutput: GetID + GetUpdatedAt,
+{
+    pub fn new(resource: Resource<T>, filters: Vec<QueryFilter>, interval: Duration) -> Self {
+        Watcher {
+            resource,
+            filters,
+            interval,
+            known: HashMap::new(),
+            pending: VecDeque::new(),
+            primed: false,
+        }
+    }
+
+    /// Poll the server once, returning the events since the previous poll.
+    pub fn poll(&mut self) -> Result<Vec<WatchEvent<T::GetOutput>>, ApiError> {
+        let current = self.resource.filter(self.filters.clone())?;
+
+        if !self.primed {
+            self.known = current
+                .iter()
+                .map(|item| (item.id(), item.updated_at()))
+                .collect();
+            self.primed = true;
+            debug!(target: EVENTS, "Watcher primed with {} resources", self.known.len());
+            return Ok(vec![]);
+        }
+
+        let mut events = vec![];
+        let mut previously_known = std::mem::take(&mut self.known);
+        for item in current {
+            let (id, updated_at) = (item.id(), item.updated_at());
+            match previously_known.remove(&id) {
+                None => events.push(WatchEvent::Created(item)),
+                Some(seen) if seen < updated_at => events.push(WatchEvent::Updated(item)),
+                Some(_) => {}
+            }
+            self.known.insert(id, updated_at);
+        }
+
+        let mut deleted: Vec<i32> = previously_known.into_keys().collect();
+        deleted.sort_unstable();
+        events.extend(deleted.into_iter().map(WatchEvent::Deleted));
+
+        trace!(target: EVENTS, "Watcher poll produced {} events", events.len());
+        Ok(events)
+    }
+}
+
+impl<T: ApiResource> Iterator for Watcher<T>
+where
+    T::GetOutput: GetID + GetUpdatedAt,
+{
+    type Item = Result<WatchEvent<T::GetOutput>, ApiError>;
+
+    /// Blocks until the next event is available, polling every `interval`.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.primed {
+                std::thread::sleep(self.interval);
+            }
+            match self.poll() {
+                Ok(events) => self.pending.extend(events),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl Client<Authenticated> {
+    /// Watch a resource for changes, see [`Watcher`].
+    pub fn watch<T: ApiResource>(
+        &self,
+        resource: Resource<T>,
+        filters: Vec<QueryFilter>,
+        interval: Duration,
+    ) -> Watcher<T>
+    where
+        T::GetOutput: GetID + GetUpdatedAt,
+    {
+        Watcher::new(resource, filters, interval)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::testing::{Reply, TestServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn group(id: i32, updated_at: &str) -> String {
+        format!(
+            r#"{{"id": {id}, "groupname": "g{id}", "description": "",
+            "created_at": "2024-01-01T00:00:00", "updated_at": "{updated_at}"}}"#
+        )
+    }
+
+    #[test]
+    fn test_poll_catches_changes_in_the_same_tick() {
+        const TICK: &str = "2024-01-01T00:00:05";
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = polls.clone();
+        let server = TestServer::start(move |request| {
+            let polls = counter.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(request.path, "/api/v1/iam/groups/");
+            let body = match polls {
+                0 => vec![group(1, TICK)],
+                _ => vec![group(1, TICK), group(2, TICK)],
+            };
+            Reply::json(200, format!("[{}]", body.join(",")))
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        let mut watcher = client.watch(client.groups(), vec![], Duration::ZERO);
+
+        assert!(watcher.poll().unwrap().is_empty());
+        let events = watcher.poll().unwrap();
+        assert_eq!(events.len(), 1, "{:?}", events);
+        assert!(matches!(&events[0], WatchEvent::Created(group) if group.id == 2));
+        // Both come back again at the same timestamp, without new events.
+        assert!(watcher.poll().unwrap().is_empty());
+        // Every poll lists once.
+        assert_eq!(polls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_poll_reports_later_updates_and_deletions() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = polls.clone();
+        let server = TestServer::start(move |_| {
+            let body = match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => vec![
+                    group(1, "2024-01-01T00:00:05"),
+                    group(2, "2024-01-01T00:00:05"),
+                ],
+                _ => vec![group(1, "2024-01-01T00:00:06")],
+            };
+            Reply::json(200, format!("[{}]", body.join(",")))
+        });
+        let client = Client::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        let mut watcher = client.watch(client.groups(), vec![], Duration::ZERO);
+
+        assert!(watcher.poll().unwrap().is_empty());
+        let events = watcher.poll().unwrap();
+        assert!(
+            matches!(&events[..], [WatchEvent::Updated(group), WatchEvent::Deleted(2)] if group.id == 1)
+        );
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+//! Differences between resources.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+
+/// Resources whose fields can be compared one by one.
+///
+/// Implemented by the `ApiResource` derive for every resource.
+pub trait Diffable {
+    /// The fields of the resource in declaration order, as JSON values.
+    fn field_values(&self) -> Vec<(&'static str, Value)>;
+}
+
+/// A field that differs between two instances of a resource.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: Value,
+    pub new: Value,
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+/// The fields that differ between `old` and `new`, in declaration order.
+pub fn resource<T: Diffable>(old: &T, new: &T) -> Vec<FieldChange> {
+    old.field_values()
+        .into_iter()
+        .zip(new.field_values())
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| FieldChange { field, old, new })
+        .collect()
+}
+
+/// A single change to a JSON document, as an RFC 6902 patch operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+}
+
+impl JsonOp {
+    /// The JSON pointer of the changed location.
+    pub fn path(&self) -> &str {
+        match self {
+            JsonOp::Add { path, .. } | JsonOp::Remove { path } | JsonOp::Replace { path, .. } => {
+                path
+            }
+        }
+    }
+}
+
+impl Display for JsonOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonOp::Add { path, value } => write!(f, "add {} = {}", path, value),
+            JsonOp::Remove { path } => write!(f, "remove {}", path),
+            JsonOp::Replace { path, value } => write!(f, "replace {} = {}", path, value),
+        }
+    }
+}
+
+/// The operations turning `old` into `new`.
+///
+/// Objects are compared key by key and arrays index by index, so a change
+/// deep inside a large document yields a single operation on its path.
+/// Applying the operations in order to `old` yields `new`.
+pub fn json(old: &Value, new: &Value) -> Vec<JsonOp> {
+    let mut ops = vec![];
+    diff_json(old, new, String::new(), &mut ops);
+    ops
+}
+
+fn diff_json(old: &Value, new: &Value, path: String, ops: &mut Vec<JsonOp>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let child = format!("{}/{}", path, escape(key));
+                match new.get(key) {
+                    Some(new_value) => diff_json(old_value, new_value, child, ops),
+                    None => ops.push(JsonOp::Remove { path: child }),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    ops.push(JsonOp::Add {
+                        path: format!("{}/{}", path, escape(key)),
+                        value: new_value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for (index, (old_value, new_value)) in old.iter().zip(new).enumerate() {
+                diff_json(old_value, new_value, format!("{}/{}", path, index), ops);
+            }
+            // Remove from the end so that the earlier indices stay valid.
+            for index in (new.len()..old.len()).rev() {
+                ops.push(JsonOp::Remove {
+                    path: format!("{}/{}", path, index),
+                });
+            }
+            for (index, value) in new.iter().enumerate().skip(old.len()) {
+                ops.push(JsonOp::Add {
+                    path: format!("{}/{}", path, index),
+                    value: value.clone(),
+                });
+            }
+        }
+        (old, new) if old != new => ops.push(JsonOp::Replace {
+            path,
+            value: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Escape a key for use in a JSON pointer, as per RFC 6901.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::Namespace;
+    use serde_json::json;
+
+    #[test]
+    fn test_resource_diff() {
+        let old = Namespace {
+            id: 1,
+            name: "prod".to_string(),
+            description: "Production".to_string(),
+            ..Default::default()
+        };
+        let new = Namespace {
+            description: "Production hosts".to_string(),
+            ..old.clone()
+        };
+
+        let changes = resource(&old, &new);
+        assert_eq!(
+            changes,
+            vec![FieldChange {
+                field: "description",
+                old: json!("Production"),
+                new: json!("Production hosts"),
+            }]
+        );
+        assert_eq!(
+            changes[0].to_string(),
+            r#"description: "Production" -> "Production hosts""#
+        );
+        assert!(resource(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_json_diff() {
+        let old = json!({
+            "os": "linux",
+            "interfaces": [{"ip": "10.0.0.1"}, {"ip": "10.0.0.2"}, {"ip": "10.0.0.3"}],
+            "a/b": 1,
+            "owner": "ops"
+        });
+        let new = json!({
+            "os": "linux",
+            "interfaces": [{"ip": "10.0.0.9"}],
+            "a/b": 2,
+            "rack": 4
+        });
+
+        let ops = json(&old, &new);
+        let paths: Vec<String> = ops.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "replace /a~1b = 2",
+                "replace /interfaces/0/ip = \"10.0.0.9\"",
+                "remove /interfaces/2",
+                "remove /interfaces/1",
+                "remove /owner",
+                "add /rack = 4",
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&ops[4]).unwrap(),
+            json!({"op": "remove", "path": "/owner"})
+        );
+        assert!(json(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_json_diff_root() {
+        assert_eq!(
+            json(&json!(1), &json!("x")),
+            vec![JsonOp::Replace {
+                path: String::new(),
+                value: json!("x")
+            }]
+        );
+    }
+}
@@ -1,34 +1,213 @@
+use reqwest::Method;
+
+use crate::client::UrlParams;
+use crate::errors::ApiError;
 use crate::types::BaseUrl;
 
+/// The methods of endpoints listing resources that are also changed
+/// through them.
+const RESOURCE_METHODS: &[Method] = &[
+    Method::GET,
+    Method::POST,
+    Method::PATCH,
+    Method::PUT,
+    Method::DELETE,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Endpoint {
     Login,
     LoginWithToken,
     Users,
+    UserTokens,
     Groups,
+    GroupPermissions,
     Classes,
     Namespaces,
+    NamespacePermissions,
     // ... other endpoints
     Objects,
+    ObjectHistory,
+    ObjectRelationsForObject,
+    ClassHistory,
 
     ClassRelations,
+    ClassRelationsForClass,
     ObjectRelations,
+
+    Events,
+    Webhooks,
+
+    OpenApiSpec,
 }
 
 impl Endpoint {
+    /// Every endpoint, in declaration order.
+    pub const ALL: &'static [Endpoint] = &[
+        Endpoint::Login,
+        Endpoint::LoginWithToken,
+        Endpoint::Users,
+        Endpoint::UserTokens,
+        Endpoint::Groups,
+        Endpoint::GroupPermissions,
+        Endpoint::Classes,
+        Endpoint::Namespaces,
+        Endpoint::NamespacePermissions,
+        Endpoint::Objects,
+        Endpoint::ObjectHistory,
+        Endpoint::ObjectRelationsForObject,
+        Endpoint::ClassHistory,
+        Endpoint::ClassRelations,
+        Endpoint::ClassRelationsForClass,
+        Endpoint::ObjectRelations,
+        Endpoint::Events,
+        Endpoint::Webhooks,
+        Endpoint::OpenApiSpec,
+    ];
+
     pub fn path(&self) -> &'static str {
         match self {
             Endpoint::Login => "/api/v0/auth/login",
             Endpoint::LoginWithToken => "/api/v0/auth/validate",
             Endpoint::Users => "/api/v1/iam/users/",
+            Endpoint::UserTokens => "/api/v1/iam/users/{user_id}/tokens/",
             Endpoint::Groups => "/api/v1/iam/groups/",
+            Endpoint::GroupPermissions => "/api/v1/iam/groups/{group_id}/permissions/",
             Endpoint::Classes => "/api/v1/classes/",
             Endpoint::Namespaces => "/api/v1/namespaces/",
+            Endpoint::NamespacePermissions => "/api/v1/namespaces/{namespace_id}/permissions/",
 
             Endpoint::Objects => "/api/v1/classes/{class_id}/",
+            Endpoint::ObjectHistory => "/api/v1/classes/{class_id}/{object_id}/history",
+            Endpoint::ObjectRelationsForObject => {
+                "/api/v1/classes/{class_id}/{object_id}/relations/"
+            }
+            Endpoint::ClassHistory => "/api/v1/classes/{class_id}/history",
 
             Endpoint::ClassRelations => "/api/v1/relations/classes/",
+            Endpoint::ClassRelationsForClass => "/api/v1/relations/classes/{class_id}/",
             Endpoint::ObjectRelations => "/api/v1/relations/objects/",
+
+            Endpoint::Events => "/api/v1/events",
+            Endpoint::Webhooks => "/api/v1/webhooks/",
+
+            Endpoint::OpenApiSpec => "/api-doc/openapi.json",
+        }
+    }
+
+    /// The placeholders in the path, which requests have to give values for.
+    pub fn required_params(&self) -> &'static [&'static str] {
+        match self {
+            Endpoint::UserTokens => &["user_id"],
+            Endpoint::GroupPermissions => &["group_id"],
+            Endpoint::NamespacePermissions => &["namespace_id"],
+            Endpoint::Objects | Endpoint::ClassHistory | Endpoint::ClassRelationsForClass => {
+                &["class_id"]
+            }
+            Endpoint::ObjectHistory | Endpoint::ObjectRelationsForObject => {
+                &["class_id", "object_id"]
+            }
+            Endpoint::Login
+            | Endpoint::LoginWithToken
+            | Endpoint::Users
+            | Endpoint::Groups
+            | Endpoint::Classes
+            | Endpoint::Namespaces
+            | Endpoint::ClassRelations
+            | Endpoint::ObjectRelations
+            | Endpoint::Events
+            | Endpoint::Webhooks
+            | Endpoint::OpenApiSpec => &[],
+        }
+    }
+
+    /// The methods the endpoint accepts, on its path or on the path of a
+    /// resource below it.
+    pub fn allowed_methods(&self) -> &'static [Method] {
+        match self {
+            Endpoint::Login => &[Method::POST],
+            Endpoint::UserTokens => &[Method::GET, Method::DELETE],
+            Endpoint::Users
+            | Endpoint::Groups
+            | Endpoint::Classes
+            | Endpoint::Namespaces
+            | Endpoint::NamespacePermissions
+            | Endpoint::Objects
+            | Endpoint::ClassRelations
+            | Endpoint::ObjectRelations
+            | Endpoint::Webhooks => RESOURCE_METHODS,
+            Endpoint::LoginWithToken
+            | Endpoint::GroupPermissions
+            | Endpoint::ObjectHistory
+            | Endpoint::ObjectRelationsForObject
+            | Endpoint::ClassHistory
+            | Endpoint::ClassRelationsForClass
+            | Endpoint::Events
+            | Endpoint::OpenApiSpec => &[Method::GET],
+        }
+    }
+
+    /// The paths of the vendored OpenAPI document the endpoint is served
+    /// under, its own and the item path below it.
+    #[cfg(feature = "codegen")]
+    pub fn documented(&self) -> Vec<crate::generated::Endpoint> {
+        use crate::spec::normalize;
+        let paths = [
+            normalize(self.path()),
+            normalize(&format!("{}{{id}}", self.path())),
+        ];
+        crate::generated::Endpoint::ALL
+            .iter()
+            .copied()
+            .filter(|endpoint| paths.contains(&normalize(endpoint.path())))
+            .collect()
+    }
+
+    /// Check a request against the endpoint before it is sent, so that a
+    /// method the endpoint lacks or a placeholder left in the path fails
+    /// with a descriptive error instead of a 404 or 405 from the server.
+    ///
+    /// With the `codegen` feature, the method also has to be offered on
+    /// the paths of the endpoint in the vendored OpenAPI document.
+    pub fn validate(&self, method: &Method, url_params: &UrlParams) -> Result<(), ApiError> {
+        if !self.allowed_methods().contains(method) {
+            return Err(ApiError::UnsupportedHttpOperation(format!(
+                "{} on {} (allowed: {})",
+                method,
+                self.path(),
+                self.allowed_methods()
+                    .iter()
+                    .map(Method::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
         }
+        #[cfg(feature = "codegen")]
+        {
+            let documented = self.documented();
+            let method = method.as_str().to_ascii_lowercase();
+            if !documented.is_empty()
+                && !documented
+                    .iter()
+                    .any(|endpoint| endpoint.methods().contains(&method.as_str()))
+            {
+                return Err(ApiError::UnsupportedHttpOperation(format!(
+                    "{} on {} (not in the OpenAPI document)",
+                    method.to_uppercase(),
+                    self.path()
+                )));
+            }
+        }
+        for param in self.required_params() {
+            if !url_params.iter().any(|(key, _)| key == param) {
+                return Err(ApiError::MissingUrlParameter(format!(
+                    "{} for {}",
+                    param,
+                    self.path()
+                )));
+            }
+        }
+        Ok(())
     }
 
     pub fn complete(&self, baseurl: &BaseUrl) -> String {
@@ -53,12 +232,60 @@ mod test {
     #[parameterized(
         login = { Endpoint::Login, "/api/v0/auth/login" },
         get_user = { Endpoint::Users, "/api/v1/iam/users/" },
-        get_class = { Endpoint::Classes, "/api/v1/classes/" }
+        get_class = { Endpoint::Classes, "/api/v1/classes/" },
+        namespace_permissions = { Endpoint::NamespacePermissions, "/api/v1/namespaces/{namespace_id}/permissions/" },
+        user_tokens = { Endpoint::UserTokens, "/api/v1/iam/users/{user_id}/tokens/" },
+        group_permissions = { Endpoint::GroupPermissions, "/api/v1/iam/groups/{group_id}/permissions/" },
+        class_relations_for_class = { Endpoint::ClassRelationsForClass, "/api/v1/relations/classes/{class_id}/" },
+        object_relations_for_object = { Endpoint::ObjectRelationsForObject, "/api/v1/classes/{class_id}/{object_id}/relations/" }
     )]
     fn test_endpoint_path(endpoint: Endpoint, expected: &str) {
         assert_eq!(endpoint.path(), expected);
     }
 
+    #[test]
+    fn test_all_endpoints_are_listed_once() {
+        for endpoint in Endpoint::ALL {
+            let count = Endpoint::ALL.iter().filter(|e| *e == endpoint).count();
+            assert_eq!(count, 1, "{:?}", endpoint);
+        }
+    }
+
+    #[test]
+    fn test_required_params_match_path() {
+        for endpoint in Endpoint::ALL {
+            let placeholders: Vec<&str> = endpoint
+                .path()
+                .split('{')
+                .skip(1)
+                .filter_map(|rest| rest.split_once('}'))
+                .map(|(param, _)| param)
+                .collect();
+            assert_eq!(endpoint.required_params(), placeholders, "{:?}", endpoint);
+        }
+    }
+
+    #[parameterized(
+        ok = { Endpoint::GroupPermissions, Method::GET, &[("group_id", "3")], None },
+        extra_params = { Endpoint::Classes, Method::PATCH, &[("patch_id", "3")], None },
+        missing_param = { Endpoint::GroupPermissions, Method::GET, &[], Some("Missing URL parameter: group_id for /api/v1/iam/groups/{group_id}/permissions/") },
+        second_param = { Endpoint::ObjectHistory, Method::GET, &[("class_id", "1")], Some("Missing URL parameter: object_id for /api/v1/classes/{class_id}/{object_id}/history") },
+        wrong_method = { Endpoint::Events, Method::POST, &[], Some("Unsupported HTTP operation: POST on /api/v1/events (allowed: GET)") },
+    )]
+    fn test_validate(
+        endpoint: Endpoint,
+        method: Method,
+        params: &[(&'static str, &'static str)],
+        expected: Option<&str>,
+    ) {
+        let params: UrlParams = params
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect();
+        let result = endpoint.validate(&method, &params);
+        assert_eq!(result.err().map(|err| err.to_string()).as_deref(), expected);
+    }
+
     #[parameterized(
         login = { Endpoint::Login, '/', "api/v0/auth/login" },
         get_user = { Endpoint::Users, '/', "api/v1/iam/users/" },
@@ -1,11 +1,21 @@
 use reqwest::StatusCode;
+use std::collections::BTreeMap;
+use std::fmt::Write;
 use thiserror::Error;
 
+/// The errors of the client.
+///
+/// New variants, and new fields of the variants marked `non_exhaustive`, are
+/// added without a breaking release, so matches need a wildcard arm.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum ApiError {
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
 
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
@@ -21,17 +31,51 @@ pub enum ApiError {
     #[error("Invalid URL: {0}")]
     UrlParse(#[from] url::ParseError),
 
+    #[error("Environment variable {0} is not set")]
+    MissingEnvVar(String),
+
+    #[error("Environment variable {var}='{value}' is invalid: {source}")]
+    InvalidEnvVar {
+        var: String,
+        value: String,
+        source: Box<ApiError>,
+    },
+
     #[error("Invalid token.")]
     InvalidToken,
 
+    #[error("Login failed: bad credentials")]
+    BadCredentials,
+
+    #[error("Login refused: {0}")]
+    AccountLocked(String),
+
+    #[error("Rate limited{}", .retry_after.map(|d| format!(", retry after {}s", d.as_secs())).unwrap_or_default())]
+    RateLimited {
+        retry_after: Option<std::time::Duration>,
+    },
+
+    #[error("Server unavailable ({status}): {message}")]
+    ServerUnavailable { status: StatusCode, message: String },
+
     #[error("URL serialization error: {0}")]
     UrlSerialize(#[from] serde_urlencoded::ser::Error),
 
     #[error("Missing location header for: {0}")]
     MissingLocationHeader(String),
 
+    /// An unsuccessful response, with the message from its body and the URL
+    /// of the request.
     #[error("HTTP error {status}: {message}")]
-    HttpWithBody { status: StatusCode, message: String },
+    #[non_exhaustive]
+    HttpWithBody {
+        status: StatusCode,
+        message: String,
+        url: String,
+    },
+
+    #[error("Response from {url} is larger than {limit} bytes")]
+    ResponseTooLarge { limit: u64, url: String },
 
     #[error("Deserialization error: {0}")]
     DeserializationError(String),
@@ -45,6 +89,285 @@ pub enum ApiError {
     #[error("Too many results: {0}")]
     TooManyResults(String),
 
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(String),
+
+    #[error("Invalid expansion: {0}")]
+    InvalidExpand(String),
+
+    #[error("Missing URL parameter: {0}")]
+    MissingUrlParameter(String),
+
     #[error("Missing URL identifier")]
     MissingUrlIdentifier,
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Move to namespace forbidden: {0}")]
+    MoveForbidden(String),
+
+    #[error("Event stream disconnected: {0}")]
+    EventStream(String),
+
+    #[error("Unsupported snapshot format version: {0}")]
+    UnsupportedSnapshotVersion(u64),
+
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+
+    #[error("Import conflicts: {0}")]
+    ImportConflict(String),
+
+    #[error("Template variable '{0}' has no value")]
+    MissingTemplateVariable(String),
+
+    #[error("Object data of class {class} violates its schema at '{pointer}': {message}")]
+    ObjectSchemaViolation {
+        class: i32,
+        /// A JSON pointer to the offending part of the object data.
+        pointer: String,
+        message: String,
+    },
+
+    #[error("Unknown column: {0}")]
+    UnknownColumn(String),
+
+    #[error("Outside of scope: {0}")]
+    OutOfScope(String),
+
+    #[error("Not empty: {0}")]
+    NotEmpty(String),
+
+    #[error("Confirmation token does not match: {0}")]
+    ConfirmationMismatch(String),
+
+    /// The resource changed on the server since it was fetched, see
+    /// `Handle<Object>::update_if_unchanged`.
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// The deadline set with `with_deadline` passed. Listings cut short hold
+    /// the items fetched so far in `partial`, see `ApiError::partial`.
+    #[error("Deadline exceeded{}", if .partial.is_empty() { String::new() } else { format!(" after {} results", .partial.len()) })]
+    DeadlineExceeded { partial: Vec<serde_json::Value> },
+
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    #[error("{}", summarize(.0))]
+    Multiple(Vec<ApiError>),
+}
+
+impl ApiError {
+    /// The items fetched before the deadline cut a listing short, decoded as
+    /// `T`, skipping any that do not decode. Empty for other errors.
+    pub fn partial<T: serde::de::DeserializeOwned>(&self) -> Vec<T> {
+        match self {
+            ApiError::DeadlineExceeded { partial } => partial
+                .iter()
+                .filter_map(|item| serde_json::from_value(item.clone()).ok())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Combine the errors of a batch operation, or `None` if there are none.
+    ///
+    /// A single error is returned as is, and nested `Multiple` errors are
+    /// flattened.
+    pub fn from_errors(errors: impl IntoIterator<Item = ApiError>) -> Option<ApiError> {
+        let mut flat: Vec<ApiError> = vec![];
+        for err in errors {
+            match err {
+                ApiError::Multiple(inner) => flat.extend(inner),
+                err => flat.push(err),
+            }
+        }
+        match flat.len() {
+            0 => None,
+            1 => flat.pop(),
+            _ => Some(ApiError::Multiple(flat)),
+        }
+    }
+
+    /// The errors this error consists of: itself, or the errors of `Multiple`.
+    pub fn errors(&self) -> Vec<&ApiError> {
+        match self {
+            ApiError::Multiple(errors) => errors.iter().flat_map(ApiError::errors).collect(),
+            err => vec![err],
+        }
+    }
+
+    /// The HTTP status of the response that caused the error, if any.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ApiError::HttpWithBody { status, .. } => Some(*status),
+            ApiError::ServerUnavailable { status, .. } => Some(*status),
+            ApiError::BadCredentials => Some(StatusCode::UNAUTHORIZED),
+            ApiError::AccountLocked(_) => Some(StatusCode::FORBIDDEN),
+            ApiError::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            ApiError::NotFound(_) => Some(StatusCode::NOT_FOUND),
+            ApiError::Http(err) => err.status(),
+            _ => None,
+        }
+    }
+
+    /// The errors grouped by HTTP status, with `None` for errors without one.
+    pub fn by_status(&self) -> BTreeMap<Option<u16>, Vec<&ApiError>> {
+        let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for err in self.errors() {
+            groups
+                .entry(err.status().map(|s| s.as_u16()))
+                .or_default()
+                .push(err);
+        }
+        groups
+    }
+
+    /// The errors grouped by the path of the request that failed.
+    ///
+    /// Numeric path segments are replaced by `{id}`, so failures on different
+    /// resources of the same endpoint end up in one group. Errors that do not
+    /// know their request are grouped under `None`.
+    pub fn by_endpoint(&self) -> BTreeMap<Option<String>, Vec<&ApiError>> {
+        let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for err in self.errors() {
+            groups.entry(err.endpoint()).or_default().push(err);
+        }
+        groups
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        let url = match self {
+            ApiError::Http(err) => err.url()?.clone(),
+            ApiError::HttpWithBody { url, .. } => reqwest::Url::parse(url).ok()?,
+            _ => return None,
+        };
+        let path = url
+            .path_segments()?
+            .map(|segment| match segment.parse::<u64>() {
+                Ok(_) => "{id}",
+                Err(_) => segment,
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        Some(format!("/{}", path))
+    }
+}
+
+/// A one line summary of several errors, grouped by status.
+fn summarize(errors: &[ApiError]) -> String {
+    let mut groups: BTreeMap<Option<u16>, (usize, &ApiError)> = BTreeMap::new();
+    for err in errors.iter().flat_map(ApiError::errors) {
+        groups
+            .entry(err.status().map(|s| s.as_u16()))
+            .or_insert((0, err))
+            .0 += 1;
+    }
+
+    let mut summary = format!(
+        "{} errors",
+        errors.iter().flat_map(ApiError::errors).count()
+    );
+    for (i, (status, (count, first))) in groups.iter().enumerate() {
+        let separator = if i == 0 { ": " } else { "; " };
+        let _ = match status {
+            Some(status) => write!(
+                summary,
+                "{}{} x HTTP {}, e.g. {}",
+                separator, count, status, first
+            ),
+            None => write!(summary, "{}{} x {}", separator, count, first),
+        };
+    }
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn http(status: StatusCode, message: &str) -> ApiError {
+        http_at(status, message, "https://api.example.com/api/v1/classes/")
+    }
+
+    fn http_at(status: StatusCode, message: &str, url: &str) -> ApiError {
+        ApiError::HttpWithBody {
+            status,
+            message: message.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_from_errors() {
+        assert!(ApiError::from_errors(vec![]).is_none());
+        assert!(matches!(
+            ApiError::from_errors(vec![ApiError::Cancelled]),
+            Some(ApiError::Cancelled)
+        ));
+
+        let nested = ApiError::Multiple(vec![ApiError::Cancelled, ApiError::InvalidToken]);
+        let err = ApiError::from_errors(vec![nested, ApiError::MissingUrlIdentifier]).unwrap();
+        assert!(matches!(&err, ApiError::Multiple(errors) if errors.len() == 3));
+    }
+
+    #[test]
+    fn test_by_endpoint_folds_ids() {
+        let err = ApiError::from_errors(vec![
+            http_at(
+                StatusCode::NOT_FOUND,
+                "gone",
+                "https://api.example.com/api/v1/classes/3/4?x=1",
+            ),
+            http_at(
+                StatusCode::CONFLICT,
+                "busy",
+                "https://api.example.com/api/v1/classes/5/6",
+            ),
+            http_at(
+                StatusCode::NOT_FOUND,
+                "gone",
+                "https://api.example.com/api/v1/namespaces/2",
+            ),
+        ])
+        .unwrap();
+        let groups = err.by_endpoint();
+        assert_eq!(
+            groups[&Some("/api/v1/classes/{id}/{id}".to_string())].len(),
+            2
+        );
+        assert_eq!(
+            groups[&Some("/api/v1/namespaces/{id}".to_string())].len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_grouping_and_display() {
+        let err = ApiError::from_errors(vec![
+            http(StatusCode::SERVICE_UNAVAILABLE, "down"),
+            http(StatusCode::NOT_FOUND, "gone"),
+            http(StatusCode::SERVICE_UNAVAILABLE, "still down"),
+            ApiError::Cancelled,
+        ])
+        .unwrap();
+
+        let groups = err.by_status();
+        assert_eq!(groups[&Some(503)].len(), 2);
+        assert_eq!(groups[&Some(404)].len(), 1);
+        assert_eq!(groups[&None].len(), 1);
+        assert_eq!(err.by_endpoint()[&None].len(), 1);
+        assert_eq!(
+            err.by_endpoint()[&Some("/api/v1/classes/".to_string())].len(),
+            3
+        );
+        assert_eq!(
+            err.to_string(),
+            "4 errors: 1 x Operation cancelled; \
+             1 x HTTP 404, e.g. HTTP error 404 Not Found: gone; \
+             2 x HTTP 503, e.g. HTTP error 503 Service Unavailable: down"
+        );
+    }
 }
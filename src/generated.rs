@@ -0,0 +1,77 @@
+//! Endpoints and schemas generated from the vendored OpenAPI document in
+//! `openapi/hubuum.json` by the build script.
+//!
+//! The clients check the method of every request against the paths of this
+//! `Endpoint` before sending it, see `crate::Endpoint::validate`. The
+//! document in the tree is a stand-in written from the client's own table,
+//! see `openapi/README.md`; refresh it from a server with `spec::fetch` and
+//! rebuild to pick up its paths and schemas.
+include!(concat!(env!("OUT_DIR"), "/generated.rs"));
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_generated_endpoints_cover_the_client() {
+        for endpoint in crate::Endpoint::ALL {
+            if *endpoint == crate::Endpoint::OpenApiSpec {
+                continue;
+            }
+            assert!(
+                Endpoint::ALL.iter().any(|e| e.path() == endpoint.path()),
+                "{:?} is not in the vendored document",
+                endpoint
+            );
+        }
+        assert_eq!(
+            Endpoint::ClassesByClassByObjectHistory.path(),
+            "/api/v1/classes/{class_id}/{object_id}/history"
+        );
+        assert_eq!(Endpoint::NamespacesById.methods(), &["delete", "patch"]);
+    }
+
+    #[test]
+    fn test_client_endpoints_are_documented() {
+        assert_eq!(
+            crate::Endpoint::Namespaces.documented(),
+            vec![Endpoint::Namespaces, Endpoint::NamespacesById]
+        );
+        assert!(crate::Endpoint::OpenApiSpec.documented().is_empty());
+    }
+
+    #[test]
+    fn test_validate_against_document() {
+        let endpoint = crate::Endpoint::Namespaces;
+        assert!(endpoint.validate(&reqwest::Method::PATCH, &vec![]).is_ok());
+        let Err(err) = endpoint.validate(&reqwest::Method::PUT, &vec![]) else {
+            panic!("PUT is not in the document");
+        };
+        assert!(
+            err.to_string().contains("not in the OpenAPI document"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_generated_schema() {
+        let namespace: Namespace = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": "prod",
+            "description": "",
+            "created_at": "2024-01-01T00:00:00",
+            "updated_at": "2024-01-01T00:00:00"
+        }))
+        .unwrap();
+        assert_eq!(namespace.name, "prod");
+        let user: User = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "username": "alice",
+            "created_at": "2024-01-01T00:00:00",
+            "updated_at": "2024-01-01T00:00:00"
+        }))
+        .unwrap();
+        assert_eq!(user.email, None);
+    }
+}
@@ -26,15 +26,33 @@
 //!    Ok(())
 //! }
 //! ```
+pub mod archive;
+pub mod capture;
 pub mod client;
+pub mod diff;
 pub mod errors;
+#[cfg(feature = "codegen")]
+pub mod generated;
+pub mod logging;
+pub mod offline;
 pub mod resources;
+pub mod snapshot;
+pub mod spec;
+pub mod table;
 pub mod types;
 
 mod endpoints;
 
 // Re-export commonly used items
-pub use client::{AsyncClient, Authenticated, IntoResourceFilter, SyncClient, Unauthenticated};
+pub use client::{
+    AsyncClient, Authenticated, BulkResult, CreateMode, CreateOptions, Handle, IntoResourceFilter,
+    MultiClient, ProgressSink, Resolved, RetryPolicy, SyncClient, Unauthenticated,
+};
+pub use endpoints::Endpoint;
 pub use errors::ApiError;
+pub use offline::OfflineClient;
 pub use resources::*;
-pub use types::{BaseUrl, ClassParams, Credentials, Token, UserParams};
+pub use tokio_util::sync::CancellationToken;
+pub use types::{
+    BaseUrl, ClassParams, Credentials, Cursor, CursorPage, Page, RequestSigner, Token, UserParams,
+};
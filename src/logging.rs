@@ -0,0 +1,253 @@
+//! Log targets of the crate, for tuning verbosity per subsystem.
+//!
+//! With `env_logger`, for example, `RUST_LOG=hubuum_client::http=debug`
+//! logs requests without their bodies, and adding
+//! `hubuum_client::http::body=debug` logs the bodies as well. Bodies can
+//! also be truncated, redacted or silenced per client, see [`LogPolicy`].
+//!
+//! With the `tracing` feature, the same targets are used for `tracing`
+//! events instead, and requests carry structured fields such as `status`,
+//! `bytes`, `elapsed_ms` and `attempt`.
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use log::{debug, error, trace, warn};
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{debug, error, trace, warn};
+
+/// Requests, responses and pagination.
+pub const HTTP: &str = "hubuum_client::http";
+/// Request and response bodies, which may contain sensitive data.
+pub const BODY: &str = "hubuum_client::http::body";
+/// Equivalent `curl` commands, see `CurlHook::log`.
+pub const CURL: &str = "hubuum_client::http::curl";
+/// Logins and token handling.
+pub const AUTH: &str = "hubuum_client::auth";
+/// The session cache.
+pub const CACHE: &str = "hubuum_client::cache";
+/// Event streams and watchers.
+pub const EVENTS: &str = "hubuum_client::events";
+/// Snapshots, archives and imports.
+pub const SNAPSHOT: &str = "hubuum_client::snapshot";
+
+/// How much of request and response bodies to log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogPolicy {
+    /// Never log bodies.
+    None,
+    /// Log at most the given number of characters of each body.
+    Truncated(usize),
+    /// Log bodies in full.
+    #[default]
+    Full,
+}
+
+/// Replaces redacted values in logged bodies.
+pub const REDACTED: &str = "[redacted]";
+
+/// The body logging settings of a client.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BodyLog {
+    pub policy: LogPolicy,
+    redact: Arc<[String]>,
+}
+
+impl BodyLog {
+    pub fn new(policy: LogPolicy, redact: &[String]) -> Self {
+        BodyLog {
+            policy,
+            redact: redact.iter().map(|p| p.to_lowercase()).collect(),
+        }
+    }
+
+    /// The body as it should appear in the log, or `None` if it should not.
+    pub fn render(&self, body: &[u8]) -> Option<String> {
+        if self.policy == LogPolicy::None {
+            return None;
+        }
+        let text = self.redact(body);
+        Some(match self.policy {
+            LogPolicy::Truncated(max) if text.chars().count() > max => {
+                let truncated: String = text.chars().take(max).collect();
+                format!("{}... ({} bytes)", truncated, body.len())
+            }
+            _ => text,
+        })
+    }
+
+    /// The body with the values of redacted JSON fields replaced, whatever
+    /// the policy.
+    pub fn redact(&self, body: &[u8]) -> String {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(mut value) if !self.redact.is_empty() => {
+                self.redact_value(&mut value);
+                value.to_string()
+            }
+            _ => String::from_utf8_lossy(body).into_owned(),
+        }
+    }
+
+    /// Whether values named `name`, as JSON fields or headers, are redacted.
+    pub fn redacts(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.redact.iter().any(|pattern| glob(pattern, &name))
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.redacts(key) {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_value(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            _ => {}
+        }
+    }
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters.
+fn glob(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .any(|i| glob(rest, &text[i..]))
+        }
+    }
+}
+
+/// Log a request or response body according to the client's settings.
+pub(crate) fn body(settings: &BodyLog, label: &str, body: &[u8]) {
+    #[cfg(not(feature = "tracing"))]
+    let enabled = log::log_enabled!(target: BODY, log::Level::Debug);
+    #[cfg(feature = "tracing")]
+    let enabled = tracing::enabled!(target: BODY, tracing::Level::DEBUG);
+    if !enabled {
+        return;
+    }
+    let Some(text) = settings.render(body) else {
+        return;
+    };
+    #[cfg(not(feature = "tracing"))]
+    debug!(target: BODY, "{}: {}", label, text);
+    #[cfg(feature = "tracing")]
+    debug!(target: BODY, bytes = body.len(), "{}: {}", label, text);
+}
+
+/// Log the response to a request.
+pub(crate) fn response(
+    method: &Method,
+    url: &str,
+    status: StatusCode,
+    bytes: Option<u64>,
+    elapsed: Duration,
+) {
+    #[cfg(not(feature = "tracing"))]
+    trace!(
+        target: HTTP,
+        "{} {} returned {} ({} bytes) in {:?}",
+        method,
+        url,
+        status,
+        bytes.map_or("unknown".to_string(), |b| b.to_string()),
+        elapsed
+    );
+    #[cfg(feature = "tracing")]
+    trace!(
+        target: HTTP,
+        %method,
+        url,
+        status = status.as_u16(),
+        bytes,
+        elapsed_ms = elapsed.as_millis() as u64,
+        "response"
+    );
+}
+
+/// Log a failed request that is about to be retried.
+pub(crate) fn retry(
+    method: &Method,
+    url: &str,
+    attempt: u32,
+    delay: Duration,
+    err: &dyn std::fmt::Display,
+) {
+    #[cfg(not(feature = "tracing"))]
+    debug!(
+        target: HTTP,
+        "{} {} failed ({}), retry {} in {:?}",
+        method,
+        url,
+        err,
+        attempt + 1,
+        delay
+    );
+    #[cfg(feature = "tracing")]
+    debug!(
+        target: HTTP,
+        %method,
+        url,
+        attempt = attempt + 1,
+        delay_ms = delay.as_millis() as u64,
+        error = %err,
+        "retrying request"
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        exact = { "password", "password", true },
+        exact_mismatch = { "password", "passwords", false },
+        prefix = { "secret*", "secret_key", true },
+        infix = { "*token*", "api_token_id", true },
+        suffix = { "*_key", "key", false },
+    )]
+    fn test_glob(pattern: &str, text: &str, expected: bool) {
+        assert_eq!(glob(pattern, text), expected);
+    }
+
+    #[test]
+    fn test_render_redacts_nested_fields() {
+        let log = BodyLog::new(LogPolicy::Full, &["Password".into(), "*secret*".into()]);
+        let body = br#"{"name":"web01","data":{"password":"x","ssh":[{"client_secret":"y"}]}}"#;
+        assert_eq!(
+            log.render(body).unwrap(),
+            r#"{"data":{"password":"[redacted]","ssh":[{"client_secret":"[redacted]"}]},"name":"web01"}"#
+        );
+    }
+
+    #[test]
+    fn test_render_policies() {
+        let body = "{\"name\":\"web01\"}".as_bytes();
+        assert_eq!(BodyLog::new(LogPolicy::None, &[]).render(body), None);
+        assert_eq!(
+            BodyLog::new(LogPolicy::Truncated(5), &[])
+                .render(body)
+                .unwrap(),
+            "{\"nam... (16 bytes)"
+        );
+        assert_eq!(
+            BodyLog::new(LogPolicy::Truncated(50), &[])
+                .render(body)
+                .unwrap(),
+            "{\"name\":\"web01\"}"
+        );
+        assert_eq!(BodyLog::default().render(b"not json").unwrap(), "not json");
+    }
+}
@@ -0,0 +1,316 @@
+//! A read-only client answering from a snapshot instead of the server.
+//!
+//! ```no_run
+//! # use hubuum_client::OfflineClient;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = OfflineClient::load("prod.snapshot.json")?;
+//! let hosts = client
+//!     .objects(1)
+//!     .find()
+//!     .add_filter_name_exact("web01")
+//!     .execute()?;
+//! # Ok(())
+//! # }
+//! ```
+use serde::Serialize;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::client::one_or_err;
+use crate::client::IntoResourceFilter;
+use crate::errors::ApiError;
+use crate::resources::{ApiResource, Class, Namespace, Object};
+use crate::snapshot::Snapshot;
+use crate::types::{FilterOperator, QueryFilter};
+
+/// Resources that can be read from a snapshot.
+pub trait OfflineSource: ApiResource
+where
+    Self::GetOutput: Serialize + Clone,
+{
+    fn items(snapshot: &Snapshot) -> &[Self::GetOutput];
+}
+
+impl OfflineSource for Namespace {
+    fn items(snapshot: &Snapshot) -> &[Namespace] {
+        &snapshot.namespaces
+    }
+}
+
+impl OfflineSource for Class {
+    fn items(snapshot: &Snapshot) -> &[Class] {
+        &snapshot.classes
+    }
+}
+
+impl OfflineSource for Object {
+    fn items(snapshot: &Snapshot) -> &[Object] {
+        &snapshot.objects
+    }
+}
+
+/// Offers the read side of `SyncClient` against a loaded snapshot.
+///
+/// Filters are evaluated locally with `QueryFilter::matches`. Filters that
+/// join other resources, and regex filters, are rejected with
+/// `ApiError::InvalidFilter`.
+#[derive(Debug, Clone)]
+pub struct OfflineClient {
+    snapshot: Arc<Snapshot>,
+}
+
+impl OfflineClient {
+    pub fn new(snapshot: Snapshot) -> Self {
+        OfflineClient {
+            snapshot: Arc::new(snapshot),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ApiError> {
+        Ok(OfflineClient::new(Snapshot::load(path)?))
+    }
+
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    pub fn namespaces(&self) -> Resource<Namespace> {
+        Resource::new(self.clone(), vec![])
+    }
+
+    pub fn classes(&self) -> Resource<Class> {
+        Resource::new(self.clone(), vec![])
+    }
+
+    pub fn objects(&self, class_id: i32) -> Resource<Object> {
+        Resource::new(
+            self.clone(),
+            vec![QueryFilter {
+                key: "hubuum_class_id".to_string(),
+                value: class_id.to_string(),
+                operator: FilterOperator::Equals { is_negated: false },
+            }],
+        )
+    }
+
+    fn search<T: OfflineSource>(
+        &self,
+        filters: &[QueryFilter],
+    ) -> Result<Vec<T::GetOutput>, ApiError>
+    where
+        T::GetOutput: Serialize + Clone,
+    {
+        for filter in filters {
+            T::validate_filter_key(&filter.key)?;
+            if filter.key.contains("__") {
+                return Err(ApiError::InvalidFilter(format!(
+                    "'{}' joins another resource, which is not supported offline",
+                    filter.key
+                )));
+            }
+        }
+
+        let mut found = vec![];
+        for item in T::items(&self.snapshot) {
+            let value = serde_json::to_value(item)?;
+            let mut matched = true;
+            for filter in filters {
+                if !filter.matches(&value)? {
+                    matched = false;
+                    break;
+                }
+            }
+            if matched {
+                found.push(item.clone());
+            }
+        }
+        Ok(found)
+    }
+}
+
+pub struct FilterBuilder<T: OfflineSource>
+where
+    T::GetOutput: Serialize + Clone,
+{
+    client: OfflineClient,
+    filters: Vec<QueryFilter>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: OfflineSource> FilterBuilder<T>
+where
+    T::GetOutput: Serialize + Clone,
+{
+    fn new(client: OfflineClient, scope: Vec<QueryFilter>) -> Self {
+        FilterBuilder {
+            client,
+            filters: scope,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn add_filter<V: ToString>(mut self, field: &str, op: FilterOperator, value: V) -> Self {
+        self.filters.push(QueryFilter {
+            key: field.to_string(),
+            value: value.to_string(),
+            operator: op,
+        });
+        self
+    }
+
+    pub fn add_filter_equals<V: ToString>(self, field: &str, value: V) -> Self {
+        self.add_filter(field, FilterOperator::Equals { is_negated: false }, value)
+    }
+
+    pub fn add_filter_id<V: ToString>(self, value: V) -> Self {
+        self.add_filter_equals("id", value)
+    }
+
+    pub fn add_filter_name_exact<V: ToString>(self, value: V) -> Self {
+        self.add_filter_equals(T::NAME_FIELD, value)
+    }
+
+    pub fn execute_expecting_single_result(self) -> Result<T::GetOutput, ApiError> {
+        let filters = self.filters.clone();
+        one_or_err(self.execute()?, &filters)
+    }
+
+    pub fn execute(self) -> Result<Vec<T::GetOutput>, ApiError> {
+        self.client.search::<T>(&self.filters)
+    }
+}
+
+pub struct Resource<T: OfflineSource>
+where
+    T::GetOutput: Serialize + Clone,
+{
+    client: OfflineClient,
+    scope: Vec<QueryFilter>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: OfflineSource> Resource<T>
+where
+    T::GetOutput: Serialize + Clone,
+{
+    fn new(client: OfflineClient, scope: Vec<QueryFilter>) -> Self {
+        Resource {
+            client,
+            scope,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn find(&self) -> FilterBuilder<T> {
+        FilterBuilder::new(self.client.clone(), self.scope.clone())
+    }
+
+    pub fn select(&self, id: i32) -> Result<T::GetOutput, ApiError> {
+        self.find()
+            .add_filter_id(id)
+            .execute_expecting_single_result()
+    }
+
+    pub fn select_by_name(&self, name: &str) -> Result<T::GetOutput, ApiError> {
+        self.find()
+            .add_filter_name_exact(name)
+            .execute_expecting_single_result()
+    }
+
+    pub fn filter(
+        &self,
+        filter: impl IntoResourceFilter<T>,
+    ) -> Result<Vec<T::GetOutput>, ApiError> {
+        let mut filters = self.scope.clone();
+        filters.extend(filter.into_resource_filter());
+        self.client.search::<T>(&filters)
+    }
+
+    pub fn filter_expecting_single_result(
+        &self,
+        filter: impl IntoResourceFilter<T>,
+    ) -> Result<T::GetOutput, ApiError> {
+        let filter = filter.into_resource_filter();
+        one_or_err(self.filter(filter.clone())?, &filter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::snapshot::SNAPSHOT_FORMAT_VERSION;
+
+    fn client() -> OfflineClient {
+        let object = |id: i32, name: &str, class_id: i32| Object {
+            id,
+            name: name.to_string(),
+            namespace_id: 1.into(),
+            hubuum_class_id: class_id.into(),
+            ..Default::default()
+        };
+        OfflineClient::new(Snapshot {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            captured_at: chrono::NaiveDateTime::default(),
+            namespaces: vec![],
+            classes: vec![Class {
+                id: 1,
+                name: "hosts".to_string(),
+                namespace: Namespace {
+                    id: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            objects: vec![
+                object(1, "web01", 1),
+                object(2, "web02", 1),
+                object(3, "web01", 2),
+            ],
+        })
+    }
+
+    #[test]
+    fn test_objects_are_scoped_to_class() {
+        let client = client();
+        let found = client.objects(1).find().execute().unwrap();
+        assert_eq!(found.iter().map(|o| o.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(client.objects(2).select_by_name("web01").unwrap().id, 3);
+    }
+
+    #[test]
+    fn test_filters() {
+        let client = client();
+        let found = client
+            .objects(1)
+            .find()
+            .add_filter("name", FilterOperator::EndsWith { is_negated: false }, "02")
+            .execute()
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            client
+                .classes()
+                .find()
+                .add_filter_equals("namespace_id", 1)
+                .execute_expecting_single_result()
+                .unwrap()
+                .name,
+            "hosts"
+        );
+        assert!(matches!(
+            client.objects(1).select(9),
+            Err(ApiError::EmptyResult(_))
+        ));
+    }
+
+    #[test]
+    fn test_joined_filters_are_rejected() {
+        let result = client()
+            .objects(1)
+            .find()
+            .add_filter_equals("namespaces__name", "prod")
+            .execute();
+        assert!(matches!(result, Err(ApiError::InvalidFilter(_))));
+    }
+}
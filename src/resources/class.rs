@@ -4,6 +4,7 @@ use super::Namespace;
 
 #[allow(dead_code)]
 #[derive(ApiResource)]
+#[api_resource(joins = "namespaces")]
 pub struct ClassResource {
     #[api(read_only)]
     pub id: i32,
@@ -2,6 +2,7 @@ use api_resource_derive::ApiResource;
 
 #[allow(dead_code)]
 #[derive(ApiResource)]
+#[api_resource(name_field = "groupname", joins = "users")]
 pub struct GroupResource {
     #[api(read_only)]
     pub id: i32,
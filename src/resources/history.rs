@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// A single entry in the change history of a class or an object.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Revision {
+    pub id: i32,
+    /// The user that made the change.
+    pub user_id: i32,
+    /// The changes made, as reported by the server.
+    pub diff: serde_json::Value,
+    pub created_at: chrono::NaiveDateTime,
+}
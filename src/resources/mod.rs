@@ -1,31 +1,49 @@
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::{Debug, Display};
 
 mod class;
 mod group;
+mod history;
 mod namespace;
 mod object;
 mod permission;
+mod reference;
+mod template;
+mod token;
 mod user;
+mod webhook;
 
 pub use self::class::{
     Class, ClassGet, ClassPatch, ClassPost, ClassRelation, ClassRelationGet, ClassRelationPatch,
     ClassRelationPost,
 };
 pub use self::group::{Group, GroupGet, GroupPatch, GroupPost};
+pub use self::history::Revision;
 pub use self::namespace::{Namespace, NamespaceGet, NamespacePatch, NamespacePost};
 pub use self::object::{
-    Object, ObjectGet, ObjectPatch, ObjectPost, ObjectRelation, ObjectRelationGet,
-    ObjectRelationPatch, ObjectRelationPost,
+    Neighbor, Object, ObjectGet, ObjectPatch, ObjectPost, ObjectRaw, ObjectRelation,
+    ObjectRelationGet, ObjectRelationPatch, ObjectRelationPost, RelationDirection,
 };
-pub use self::user::{User, UserGet, UserPatch, UserPost};
-pub use crate::types::{FilterOperator, QueryFilter};
+pub use self::permission::{
+    Permission, PermissionGet, PermissionPatch, PermissionPost, PermissionSet,
+};
+pub use self::reference::Ref;
+pub use self::template::Template;
+pub use self::token::{TokenEntry, TokenEntryGet, TokenEntryPatch, TokenEntryPost};
+pub use self::user::{User, UserExpanded, UserGet, UserPatch, UserPost};
+pub use self::webhook::{EventTypes, Webhook, WebhookGet, WebhookPatch, WebhookPost};
+pub use crate::types::{FilterOperator, FilterSet, Filterable, QueryFilter};
 
+use crate::client::{
+    short_type_name, Authenticated, FromId, GetID, IntoResourceFilter, SyncClient,
+};
 use crate::endpoints::Endpoint;
+use crate::errors::ApiError;
 
 // ApiResource trait
 pub trait ApiResource: Default {
-    type GetParams: Serialize + Debug + Default;
+    type GetParams: Serialize + Debug + Default + IntoResourceFilter<Self>;
     type GetOutput: DeserializeOwned + Debug;
     type PostParams: Serialize + Debug;
     type PostOutput: DeserializeOwned + Debug;
@@ -34,8 +52,134 @@ pub trait ApiResource: Default {
     type DeleteParams: Serialize + Debug;
     type DeleteOutput: DeserializeOwned + Debug;
 
+    /// The field holding the unique name of the resource, as it is named on
+    /// the wire, used for lookups by name.
+    const NAME_FIELD: &'static str = "name";
+
+    /// The relations that filter keys may traverse, as in `namespaces__name`.
+    const JOINS: &'static [&'static str] = &[];
+
     fn endpoint(&self) -> Endpoint;
     fn build_params(filters: Vec<(String, FilterOperator, String)>) -> Vec<QueryFilter>;
+
+    /// Check that a filter key is a plain field or traverses an allowed relation.
+    fn validate_filter_key(key: &str) -> Result<(), ApiError> {
+        let Some((join, field)) = key.split_once("__") else {
+            return Ok(());
+        };
+        if field.is_empty() || field.contains("__") {
+            return Err(ApiError::InvalidFilter(format!(
+                "'{}' must be of the form <relation>__<field>",
+                key
+            )));
+        }
+        if !Self::JOINS.contains(&join) {
+            return Err(ApiError::InvalidFilter(format!(
+                "'{}' cannot be joined from {} (allowed: {})",
+                join,
+                short_type_name::<Self>(),
+                if Self::JOINS.is_empty() {
+                    "none".to_string()
+                } else {
+                    Self::JOINS.join(", ")
+                }
+            )));
+        }
+        Ok(())
+    }
+
+    /// Resolve the named relations of the items in bulk, see `FilterBuilder::expand`.
+    fn expand(
+        _client: &SyncClient<Authenticated>,
+        _items: &[Self::GetOutput],
+        relations: &[&str],
+    ) -> Result<(), ApiError> {
+        match relations.first() {
+            Some(relation) => Err(ApiError::InvalidExpand(format!(
+                "{} has no relation '{}'",
+                short_type_name::<Self>(),
+                relation
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The number of ids asked for in one `id__in` filter, which keeps the URLs
+/// of lookups of many references within what servers accept.
+pub(crate) const EXPAND_CHUNK: usize = 100;
+
+/// A filter matching the resources with `ids`, a chunk of at most
+/// `EXPAND_CHUNK` of them.
+pub(crate) fn ids_filter(ids: &[i32]) -> QueryFilter {
+    match ids {
+        [id] => QueryFilter {
+            key: "id".to_string(),
+            value: id.to_string(),
+            operator: FilterOperator::Equals { is_negated: false },
+        },
+        _ => QueryFilter::any_of("id", ids),
+    }
+}
+
+/// Resolve a set of references with as few requests as possible.
+///
+/// References found in the session cache are resolved without a request.
+/// The rest are fetched by their ids, up to a hundred per request. Ids the
+/// server does not return, because they do not exist or are not visible to
+/// the user, fail with `ApiError::NotFound`, after the others are resolved.
+pub fn expand_refs<'a, R, I>(client: &SyncClient<Authenticated>, refs: I) -> Result<(), ApiError>
+where
+    R: ApiResource + 'a,
+    R::GetOutput: GetID + Clone + Send + Sync + 'static,
+    I: IntoIterator<Item = &'a Ref<R>>,
+{
+    let mut pending: Vec<&Ref<R>> = refs.into_iter().filter(|r| r.get().is_none()).collect();
+    if let Some(cache) = client.cache() {
+        pending.retain(|reference| match cache.get(reference.id()) {
+            Some(resource) => {
+                reference.fill(resource);
+                false
+            }
+            None => true,
+        });
+    }
+
+    let mut ids: Vec<i32> = pending.iter().map(|r| r.id()).collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let mut found: HashMap<i32, R::GetOutput> = HashMap::new();
+    for chunk in ids.chunks(EXPAND_CHUNK) {
+        found.extend(
+            client
+                .search(R::default(), vec![], vec![ids_filter(chunk)])?
+                .into_iter()
+                .map(|resource| (resource.id(), resource)),
+        );
+    }
+    for (id, resource) in &found {
+        reference::remember(client.cache(), *id, resource);
+    }
+    for reference in pending {
+        if let Some(resource) = found.get(&reference.id()) {
+            reference.fill(resource.clone());
+        }
+    }
+
+    let missing: Vec<String> = ids
+        .iter()
+        .filter(|id| !found.contains_key(id))
+        .map(i32::to_string)
+        .collect();
+    if !missing.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "{} {}",
+            short_type_name::<R>(),
+            missing.join(", ")
+        )));
+    }
+    Ok(())
 }
 
 pub fn tabled_display_option<T>(o: &Option<T>) -> String
@@ -65,7 +209,38 @@ where
     }
 }
 
-pub fn tabled_display<'a, T>(value: &'a T) -> String
+/// Deserialize a related resource given either as its id or as the embedded object.
+///
+/// Resources only known by id are filled with defaults apart from the id.
+pub fn id_or_object<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: DeserializeOwned + FromId,
+{
+    use serde::de::Error;
+    use serde_json::Value;
+    match Value::deserialize(deserializer)? {
+        Value::Number(n) => n
+            .as_i64()
+            .and_then(|id| i32::try_from(id).ok())
+            .map(T::from_id)
+            .ok_or_else(|| D::Error::custom(format!("invalid id: {}", n))),
+        value => serde_json::from_value(value).map_err(D::Error::custom),
+    }
+}
+
+/// Display an embedded resource by name, or by id if only the id is known.
+pub fn tabled_display_embedded<T>(value: &T) -> String
+where
+    T: Display + GetID,
+{
+    match value.to_string() {
+        name if name.is_empty() => value.id().to_string(),
+        name => name,
+    }
+}
+
+pub fn tabled_display<T>(value: &T) -> String
 where
     T: Display + 'static,
 {
@@ -75,3 +250,134 @@ where
 
     format!("{}", value)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tabled::Tabled;
+    use yare::parameterized;
+
+    #[parameterized(
+        id = { "7", "7" },
+        object = { r#"{"id": 7, "name": "prod", "description": "", "created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00"}"#, "prod" }
+    )]
+    fn test_class_namespace_id_or_object(namespace: &str, rendered: &str) {
+        let json = format!(
+            r#"{{"id": 1, "name": "hosts", "description": "", "namespace": {}, "created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00"}}"#,
+            namespace
+        );
+        let class: Class = serde_json::from_str(&json).unwrap();
+        assert_eq!(class.namespace.id, 7);
+        let fields = class.fields();
+        let headers = Class::headers();
+        let column = headers.iter().position(|h| h == "Namespace").unwrap();
+        assert_eq!(fields[column], rendered);
+    }
+
+    #[test]
+    fn test_expand_refs_by_id_and_missing() {
+        use crate::client::testing::{Reply, TestServer};
+
+        let server = TestServer::start(|request| {
+            assert!(request.path.ends_with("?id__in=1,2,3"), "{}", request.path);
+            let namespaces: Vec<String> = [1, 2]
+                .iter()
+                .map(|id| {
+                    format!(
+                        r#"{{"id": {id}, "name": "ns{id}", "description": "", "created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00"}}"#
+                    )
+                })
+                .collect();
+            Reply::json(200, format!("[{}]", namespaces.join(",")))
+        });
+        let client = SyncClient::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let refs: Vec<Ref<Namespace>> = vec![3.into(), 1.into(), 2.into(), 1.into()];
+        let err = expand_refs(&client, &refs).unwrap_err();
+        assert_eq!(err.to_string(), "Not found: Namespace 3");
+        let names: Vec<Option<String>> = refs
+            .iter()
+            .map(|reference| reference.get().map(|ns| ns.name.clone()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                None,
+                Some("ns1".into()),
+                Some("ns2".into()),
+                Some("ns1".into())
+            ]
+        );
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[allow(dead_code)]
+    #[derive(api_resource_derive::ApiResource)]
+    #[api_resource(endpoint = "Namespaces")]
+    struct RenamedResource {
+        #[api(read_only)]
+        pub id: i32,
+        #[api(json = "display_name")]
+        pub name: String,
+        #[api(optional, json = "notes")]
+        pub description: String,
+    }
+
+    #[test]
+    fn test_json_rename() {
+        let renamed: Renamed =
+            serde_json::from_str(r#"{"id": 1, "display_name": "prod", "notes": null}"#).unwrap();
+        assert_eq!(renamed.name, "prod");
+
+        let post = serde_json::to_value(RenamedPost {
+            name: "prod".to_string(),
+            description: Some("main".to_string()),
+        })
+        .unwrap();
+        assert_eq!(
+            post,
+            serde_json::json!({"display_name": "prod", "notes": "main"})
+        );
+        let patch = serde_json::to_value(RenamedPatch {
+            name: None,
+            description: Some("main".to_string()),
+        })
+        .unwrap();
+        assert_eq!(patch["notes"], "main");
+
+        assert_eq!(Renamed::NAME_FIELD, "display_name");
+        let params = RenamedGet {
+            name: Some("prod".to_string().into()),
+            description: Some(Filterable::icontains("main".to_string())),
+            ..Default::default()
+        };
+        let filters = IntoResourceFilter::<Renamed>::into_resource_filter(params);
+        assert_eq!(
+            filters.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec!["display_name__equals=prod", "notes__icontains=main"]
+        );
+    }
+
+    #[test]
+    fn test_get_params_into_filters() {
+        let params = ClassGet {
+            name: Some(Filterable::icontains("host".to_string())),
+            namespace_id: Some(7.into()),
+            validate_schema: Some(true.into()),
+            ..Default::default()
+        };
+        let filters = IntoResourceFilter::<Class>::into_resource_filter(params);
+        assert_eq!(
+            filters.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "name__icontains=host",
+                "namespace_id__equals=7",
+                "validate_schema__equals=true"
+            ]
+        );
+    }
+}
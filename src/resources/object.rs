@@ -1,16 +1,24 @@
 use api_resource_derive::ApiResource;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use super::{Class, Namespace, Ref};
 
 #[allow(dead_code)]
 #[derive(ApiResource)]
+#[api_resource(
+    joins = "namespaces, classes",
+    expand = "namespace:namespace_id, class:hubuum_class_id"
+)]
 pub struct ObjectResource {
     #[api(read_only)]
     pub id: i32,
     #[api(table_rename = "Name")]
     pub name: String,
     #[api(table_rename = "Namespace")]
-    pub namespace_id: i32,
+    pub namespace_id: Ref<Namespace>,
     #[api(table_rename = "Class")]
-    pub hubuum_class_id: i32,
+    pub hubuum_class_id: Ref<Class>,
     #[api(table_rename = "Description")]
     pub description: String,
     #[api(optional, table_rename = "Data")]
@@ -37,3 +45,94 @@ pub struct ObjectRelationResource {
     #[api(read_only, table_rename = "Updated")]
     pub updated_at: chrono::NaiveDateTime,
 }
+
+/// Which end of a relation the object it is seen from is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationDirection {
+    /// The object is the `from` end.
+    Outgoing,
+    /// The object is the `to` end.
+    Incoming,
+}
+
+/// An object related to another, see `Handle<Object>::neighbors`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbor {
+    pub relation: ObjectRelation,
+    pub direction: RelationDirection,
+    pub object: Object,
+}
+
+/// An object whose data is kept as the JSON text the server sent.
+///
+/// Tools that only move objects between servers, as in a migration, never
+/// look into the data, and skip parsing and serializing it again by fetching
+/// objects with `FilterBuilder<Object>::execute_raw` and creating them with
+/// `Resource<Object>::create_raw`. Use `Object` to work with the data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectRaw {
+    pub id: i32,
+    pub name: String,
+    pub namespace_id: Ref<Namespace>,
+    pub hubuum_class_id: Ref<Class>,
+    pub description: String,
+    #[serde(default)]
+    pub data: Option<Box<RawValue>>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// The body creating an `ObjectRaw`, with the data passed through as is.
+#[derive(Debug, Serialize)]
+pub(crate) struct ObjectRawPost<'a> {
+    name: &'a str,
+    namespace_id: i32,
+    hubuum_class_id: i32,
+    description: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<&'a RawValue>,
+}
+
+impl ObjectRaw {
+    /// The body creating the object in the class `class_id`.
+    pub(crate) fn as_post(&self, class_id: i32) -> ObjectRawPost<'_> {
+        ObjectRawPost {
+            name: &self.name,
+            namespace_id: self.namespace_id.id(),
+            hubuum_class_id: class_id,
+            description: &self.description,
+            data: self.data.as_deref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_object_raw_keeps_data_text() {
+        let json = r#"{
+            "id": 3,
+            "name": "sample-17",
+            "namespace_id": 1,
+            "hubuum_class_id": 2,
+            "description": "",
+            "data": {"mass":  1.50000000000000000001, "unit": "kg"},
+            "created_at": "2024-01-01T00:00:00",
+            "updated_at": "2024-01-01T00:00:00"
+        }"#;
+        let object: ObjectRaw = serde_json::from_str(json).unwrap();
+        let data = r#"{"mass":  1.50000000000000000001, "unit": "kg"}"#;
+        assert_eq!(object.data.as_ref().unwrap().get(), data);
+
+        let post = serde_json::to_string(&object.as_post(5)).unwrap();
+        assert_eq!(
+            post,
+            format!(
+                r#"{{"name":"sample-17","namespace_id":1,"hubuum_class_id":5,"description":"","data":{}}}"#,
+                data
+            )
+        );
+    }
+}
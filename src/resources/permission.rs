@@ -0,0 +1,173 @@
+use api_resource_derive::ApiResource;
+
+#[allow(dead_code)]
+#[derive(ApiResource)]
+#[api_resource(endpoint = "NamespacePermissions", joins = "groups")]
+pub struct PermissionResource {
+    #[api(read_only)]
+    pub id: i32,
+    #[api(read_only, table_rename = "Namespace")]
+    pub namespace_id: i32,
+    #[api(table_rename = "Group")]
+    pub group_id: i32,
+    #[api(table_rename = "ReadNamespace")]
+    pub has_read_namespace: bool,
+    #[api(table_rename = "UpdateNamespace")]
+    pub has_update_namespace: bool,
+    #[api(table_rename = "DeleteNamespace")]
+    pub has_delete_namespace: bool,
+    #[api(table_rename = "DelegateNamespace")]
+    pub has_delegate_namespace: bool,
+    #[api(table_rename = "CreateClass")]
+    pub has_create_class: bool,
+    #[api(table_rename = "ReadClass")]
+    pub has_read_class: bool,
+    #[api(table_rename = "UpdateClass")]
+    pub has_update_class: bool,
+    #[api(table_rename = "DeleteClass")]
+    pub has_delete_class: bool,
+    #[api(table_rename = "CreateObject")]
+    pub has_create_object: bool,
+    #[api(table_rename = "ReadObject")]
+    pub has_read_object: bool,
+    #[api(table_rename = "UpdateObject")]
+    pub has_update_object: bool,
+    #[api(table_rename = "DeleteObject")]
+    pub has_delete_object: bool,
+    #[api(read_only, table_rename = "Created")]
+    pub created_at: chrono::NaiveDateTime,
+    #[api(read_only, table_rename = "Updated")]
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// The namespace permissions a group can hold, without the bookkeeping
+/// fields of a `Permission` row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PermissionSet {
+    pub read_namespace: bool,
+    pub update_namespace: bool,
+    pub delete_namespace: bool,
+    pub delegate_namespace: bool,
+    pub create_class: bool,
+    pub read_class: bool,
+    pub update_class: bool,
+    pub delete_class: bool,
+    pub create_object: bool,
+    pub read_object: bool,
+    pub update_object: bool,
+    pub delete_object: bool,
+}
+
+impl PermissionSet {
+    /// Read access to the namespace and everything in it.
+    pub fn read_only() -> Self {
+        PermissionSet {
+            read_namespace: true,
+            read_class: true,
+            read_object: true,
+            ..Default::default()
+        }
+    }
+
+    /// Read and write access to the classes and objects of the namespace.
+    pub fn read_write() -> Self {
+        PermissionSet {
+            create_class: true,
+            update_class: true,
+            delete_class: true,
+            create_object: true,
+            update_object: true,
+            delete_object: true,
+            ..PermissionSet::read_only()
+        }
+    }
+
+    /// Every permission, including administering the namespace itself.
+    pub fn all() -> Self {
+        PermissionSet {
+            update_namespace: true,
+            delete_namespace: true,
+            delegate_namespace: true,
+            ..PermissionSet::read_write()
+        }
+    }
+
+    /// The parameters to grant this set to a group.
+    pub fn to_post(&self, group_id: i32) -> PermissionPost {
+        PermissionPost {
+            group_id,
+            has_read_namespace: self.read_namespace,
+            has_update_namespace: self.update_namespace,
+            has_delete_namespace: self.delete_namespace,
+            has_delegate_namespace: self.delegate_namespace,
+            has_create_class: self.create_class,
+            has_read_class: self.read_class,
+            has_update_class: self.update_class,
+            has_delete_class: self.delete_class,
+            has_create_object: self.create_object,
+            has_read_object: self.read_object,
+            has_update_object: self.update_object,
+            has_delete_object: self.delete_object,
+        }
+    }
+
+    /// The parameters to change an existing grant to this set.
+    pub fn to_patch(&self) -> PermissionPatch {
+        PermissionPatch {
+            group_id: None,
+            has_read_namespace: Some(self.read_namespace),
+            has_update_namespace: Some(self.update_namespace),
+            has_delete_namespace: Some(self.delete_namespace),
+            has_delegate_namespace: Some(self.delegate_namespace),
+            has_create_class: Some(self.create_class),
+            has_read_class: Some(self.read_class),
+            has_update_class: Some(self.update_class),
+            has_delete_class: Some(self.delete_class),
+            has_create_object: Some(self.create_object),
+            has_read_object: Some(self.read_object),
+            has_update_object: Some(self.update_object),
+            has_delete_object: Some(self.delete_object),
+        }
+    }
+}
+
+impl From<&Permission> for PermissionSet {
+    fn from(permission: &Permission) -> Self {
+        PermissionSet {
+            read_namespace: permission.has_read_namespace,
+            update_namespace: permission.has_update_namespace,
+            delete_namespace: permission.has_delete_namespace,
+            delegate_namespace: permission.has_delegate_namespace,
+            create_class: permission.has_create_class,
+            read_class: permission.has_read_class,
+            update_class: permission.has_update_class,
+            delete_class: permission.has_delete_class,
+            create_object: permission.has_create_object,
+            read_object: permission.has_read_object,
+            update_object: permission.has_update_object,
+            delete_object: permission.has_delete_object,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_permission_set_round_trip() {
+        let set = PermissionSet::read_write();
+        let post = set.to_post(3);
+        assert_eq!(post.group_id, 3);
+        assert!(post.has_create_object && !post.has_delegate_namespace);
+
+        let permission = Permission {
+            has_read_namespace: post.has_read_namespace,
+            has_read_class: post.has_read_class,
+            has_read_object: post.has_read_object,
+            ..Default::default()
+        };
+        assert_eq!(PermissionSet::from(&permission), PermissionSet::read_only());
+        assert_eq!(set.to_patch().has_delete_class, Some(true));
+    }
+}
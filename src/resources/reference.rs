@@ -0,0 +1,194 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::{Arc, OnceLock};
+
+use super::ApiResource;
+use crate::client::{AsyncClient, Authenticated, SessionCache, SyncClient};
+use crate::errors::ApiError;
+use crate::types::{FilterOperator, QueryFilter};
+
+/// A reference to another resource by id, resolved on demand.
+///
+/// On the wire a `Ref` is just the id. The referenced resource is fetched
+/// on the first call to `resolve` and cached, so resolving again, or
+/// resolving a clone of the same `Ref`, does not hit the server.
+pub struct Ref<T: ApiResource> {
+    id: i32,
+    resolved: Arc<OnceLock<T::GetOutput>>,
+}
+
+impl<T: ApiResource> Ref<T> {
+    pub fn new(id: i32) -> Self {
+        Ref {
+            id,
+            resolved: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// The referenced resource, if it has already been resolved.
+    pub fn get(&self) -> Option<&T::GetOutput> {
+        self.resolved.get()
+    }
+
+    /// Fetch the referenced resource, or return the cached copy.
+    ///
+    /// If the client has a [`SessionCache`], it is consulted before the
+    /// server and updated afterwards.
+    pub fn resolve(&self, client: &SyncClient<Authenticated>) -> Result<&T::GetOutput, ApiError>
+    where
+        T::GetOutput: Clone + Send + Sync + 'static,
+    {
+        if let Some(resource) = self.resolved.get() {
+            return Ok(resource);
+        }
+        if let Some(resource) = client.cache().and_then(|cache| cache.get(self.id)) {
+            return Ok(self.cache(resource));
+        }
+        let found = client.search(T::default(), vec![], vec![self.id_filter()])?;
+        let resource = crate::client::one_or_err(found, &[self.id_filter()])?;
+        remember(client.cache(), self.id, &resource);
+        Ok(self.cache(resource))
+    }
+
+    /// Fetch the referenced resource with the async client, or return the cached copy.
+    pub async fn resolve_async(
+        &self,
+        client: &AsyncClient<Authenticated>,
+    ) -> Result<&T::GetOutput, ApiError>
+    where
+        T::GetOutput: Clone + Send + Sync + 'static,
+    {
+        if let Some(resource) = self.resolved.get() {
+            return Ok(resource);
+        }
+        if let Some(resource) = client.cache().and_then(|cache| cache.get(self.id)) {
+            return Ok(self.cache(resource));
+        }
+        let found = client.search(T::default(), vec![self.id_filter()]).await?;
+        let resource = crate::client::one_or_err(found, &[self.id_filter()])?;
+        remember(client.cache(), self.id, &resource);
+        Ok(self.cache(resource))
+    }
+
+    /// Attach an already fetched copy of the referenced resource.
+    pub(crate) fn fill(&self, resource: T::GetOutput) {
+        self.cache(resource);
+    }
+
+    fn id_filter(&self) -> QueryFilter {
+        QueryFilter {
+            key: "id".to_string(),
+            value: self.id.to_string(),
+            operator: FilterOperator::Equals { is_negated: false },
+        }
+    }
+
+    fn cache(&self, resource: T::GetOutput) -> &T::GetOutput {
+        // Another thread may have resolved it meanwhile, either copy will do.
+        let _ = self.resolved.set(resource);
+        self.resolved.get().expect("resolved reference")
+    }
+}
+
+/// Add a resolved resource to the session cache, if there is one.
+pub(crate) fn remember<R: Clone + Send + Sync + 'static>(
+    cache: Option<&SessionCache>,
+    id: i32,
+    resource: &R,
+) {
+    if let Some(cache) = cache {
+        cache.insert(id, resource.clone());
+    }
+}
+
+impl<T: ApiResource> Clone for Ref<T> {
+    fn clone(&self) -> Self {
+        Ref {
+            id: self.id,
+            resolved: self.resolved.clone(),
+        }
+    }
+}
+
+impl<T: ApiResource> Default for Ref<T> {
+    fn default() -> Self {
+        Ref::new(0)
+    }
+}
+
+impl<T: ApiResource> Debug for Ref<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ref")
+            .field("id", &self.id)
+            .field("resolved", &self.resolved.get().is_some())
+            .finish()
+    }
+}
+
+impl<T: ApiResource> Display for Ref<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl<T: ApiResource> PartialEq for Ref<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T: ApiResource> PartialEq<i32> for Ref<T> {
+    fn eq(&self, other: &i32) -> bool {
+        self.id == *other
+    }
+}
+
+impl<T: ApiResource> From<i32> for Ref<T> {
+    fn from(id: i32) -> Self {
+        Ref::new(id)
+    }
+}
+
+impl<T: ApiResource> Serialize for Ref<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.id)
+    }
+}
+
+impl<'de, T: ApiResource> Deserialize<'de> for Ref<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        i32::deserialize(deserializer).map(Ref::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::{Namespace, Object};
+
+    #[test]
+    fn test_ref_is_an_id_on_the_wire() {
+        let json = r#"{"id": 1, "name": "host", "namespace_id": 2, "hubuum_class_id": 3, "description": "", "data": null, "created_at": "2024-01-01T00:00:00", "updated_at": "2024-01-01T00:00:00"}"#;
+        let object: Object = serde_json::from_str(json).unwrap();
+        assert_eq!(object.namespace_id, 2);
+        assert_eq!(object.hubuum_class_id.id(), 3);
+        assert!(object.namespace_id.get().is_none());
+        assert_eq!(serde_json::to_value(&object).unwrap()["namespace_id"], 2);
+    }
+
+    #[test]
+    fn test_ref_clones_share_cache() {
+        let reference: Ref<Namespace> = 2.into();
+        let clone = reference.clone();
+        reference.cache(Namespace {
+            id: 2,
+            name: "prod".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(clone.get().map(|ns| ns.name.as_str()), Some("prod"));
+    }
+}
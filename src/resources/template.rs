@@ -0,0 +1,148 @@
+//! Templates for provisioning many similarly shaped objects.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials, Template};
+//! # use serde_json::json;
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let client = SyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//! #     .login(Credentials::new("user".into(), "pass".into()))?;
+//! let template = Template::new(1, "web{{n}}")
+//!     .description("Web server {{n}} in {{site}}")
+//!     .data(json!({"site": "{{site}}", "tags": ["web", "{{site}}"]}));
+//! for n in 1..=3 {
+//!     let n = n.to_string();
+//!     client
+//!         .objects(1)
+//!         .create_from_template(&template, &[("n", &n), ("site", "osl")])?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::ObjectPost;
+use crate::errors::ApiError;
+
+/// A pattern for objects, with `{{variable}}` placeholders.
+///
+/// Placeholders are substituted in the name, the description and in every
+/// string inside the default data. A placeholder without a matching
+/// variable is an error rather than being left in place.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Template {
+    pub namespace_id: i32,
+    pub name: String,
+    pub description: String,
+    pub data: Option<Value>,
+}
+
+impl Template {
+    pub fn new(namespace_id: i32, name: &str) -> Self {
+        Template {
+            namespace_id,
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// Substitute the variables, producing the object to create in a class.
+    pub fn render(&self, class_id: i32, vars: &[(&str, &str)]) -> Result<ObjectPost, ApiError> {
+        Ok(ObjectPost {
+            name: substitute(&self.name, vars)?,
+            namespace_id: self.namespace_id.into(),
+            hubuum_class_id: class_id.into(),
+            description: substitute(&self.description, vars)?,
+            data: self
+                .data
+                .as_ref()
+                .map(|data| substitute_value(data, vars))
+                .transpose()?,
+        })
+    }
+}
+
+fn substitute_value(value: &Value, vars: &[(&str, &str)]) -> Result<Value, ApiError> {
+    Ok(match value {
+        Value::String(s) => Value::String(substitute(s, vars)?),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| substitute_value(item, vars))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, item)| Ok((key.clone(), substitute_value(item, vars)?)))
+                .collect::<Result<_, ApiError>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+fn substitute(pattern: &str, vars: &[(&str, &str)]) -> Result<String, ApiError> {
+    let mut out = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + end].trim();
+        let value = vars
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| ApiError::MissingTemplateVariable(name.to_string()))?;
+        out.push_str(&rest[..start]);
+        out.push_str(value);
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render() {
+        let template = Template::new(1, "vlan-{{ id }}")
+            .description("VLAN {{id}} for {{site}}")
+            .data(json!({"vid": "{{id}}", "sites": ["{{site}}"], "mtu": 1500}));
+        let post = template
+            .render(2, &[("id", "100"), ("site", "osl")])
+            .unwrap();
+
+        assert_eq!(post.name, "vlan-100");
+        assert_eq!(post.description, "VLAN 100 for osl");
+        assert_eq!(post.namespace_id, 1);
+        assert_eq!(post.hubuum_class_id, 2);
+        assert_eq!(
+            post.data,
+            Some(json!({"vid": "100", "sites": ["osl"], "mtu": 1500}))
+        );
+    }
+
+    #[test]
+    fn test_missing_variable() {
+        let result = Template::new(1, "web{{n}}").render(1, &[]);
+        assert!(matches!(
+            result,
+            Err(ApiError::MissingTemplateVariable(name)) if name == "n"
+        ));
+        assert_eq!(substitute("a {{b", &[]).unwrap(), "a {{b");
+    }
+}
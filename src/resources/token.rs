@@ -0,0 +1,31 @@
+use api_resource_derive::ApiResource;
+
+/// A token issued to a user, as listed by the server.
+///
+/// The token itself is never returned, only its metadata.
+#[allow(dead_code)]
+#[derive(ApiResource)]
+#[api_resource(endpoint = "UserTokens")]
+pub struct TokenEntryResource {
+    #[api(read_only)]
+    pub id: i32,
+    #[api(read_only, table_rename = "User")]
+    pub user_id: i32,
+    #[api(optional, table_rename = "Description")]
+    pub description: String,
+    #[api(read_only, table_rename = "Issued")]
+    pub created_at: chrono::NaiveDateTime,
+    #[api(optional, read_only, table_rename = "LastUsed")]
+    pub last_used_at: chrono::NaiveDateTime,
+    #[api(optional, read_only, table_rename = "Expires")]
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+impl TokenEntry {
+    /// Whether the token has not been used since `cutoff`.
+    ///
+    /// Tokens that were never used count from the time they were issued.
+    pub fn is_stale(&self, cutoff: chrono::NaiveDateTime) -> bool {
+        self.last_used_at.unwrap_or(self.created_at) < cutoff
+    }
+}
@@ -1,6 +1,11 @@
 use api_resource_derive::ApiResource;
+use serde::{Deserialize, Serialize};
+
+use super::Group;
+
 #[allow(dead_code)]
 #[derive(ApiResource)]
+#[api_resource(name_field = "username", joins = "groups")]
 pub struct UserResource {
     #[api(read_only)]
     pub id: i32,
@@ -14,3 +19,37 @@ pub struct UserResource {
     #[api(read_only)]
     pub updated_at: chrono::NaiveDateTime,
 }
+
+/// A user together with the groups it is a member of.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserExpanded {
+    #[serde(flatten)]
+    pub user: User,
+    pub groups: Vec<Group>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_user_expanded_from_server() {
+        let json = serde_json::json!({
+            "id": 1,
+            "username": "alice",
+            "email": null,
+            "created_at": "2024-01-01T00:00:00",
+            "updated_at": "2024-01-01T00:00:00",
+            "groups": [{
+                "id": 2,
+                "groupname": "admins",
+                "description": "",
+                "created_at": "2024-01-01T00:00:00",
+                "updated_at": "2024-01-01T00:00:00"
+            }]
+        });
+        let expanded: UserExpanded = serde_json::from_value(json).unwrap();
+        assert_eq!(expanded.user.username, "alice");
+        assert_eq!(expanded.groups[0].groupname, "admins");
+    }
+}
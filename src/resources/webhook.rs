@@ -0,0 +1,32 @@
+use api_resource_derive::ApiResource;
+use serde::{Deserialize, Serialize};
+
+/// The event types a webhook is triggered by, e.g. `object_created`.
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct EventTypes(pub Vec<String>);
+
+impl std::fmt::Display for EventTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0.join(", "))
+    }
+}
+
+#[allow(dead_code)]
+#[derive(ApiResource)]
+pub struct WebhookResource {
+    #[api(read_only)]
+    pub id: i32,
+    #[api(table_rename = "Name")]
+    pub name: String,
+    #[api(table_rename = "URL")]
+    pub url: String,
+    #[api(table_rename = "Events")]
+    pub event_types: EventTypes,
+    #[api(post_only)]
+    pub secret: String, // Used to sign deliveries, never returned by the server.
+    #[api(read_only, table_rename = "Created")]
+    pub created_at: chrono::NaiveDateTime,
+    #[api(read_only, table_rename = "Updated")]
+    pub updated_at: chrono::NaiveDateTime,
+}
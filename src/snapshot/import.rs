@@ -0,0 +1,614 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use super::Snapshot;
+use crate::client::bulk::Progress;
+use crate::client::{Authenticated, ProgressSink, SyncClient};
+use crate::errors::ApiError;
+use crate::logging::{debug, SNAPSHOT};
+use crate::resources::{
+    Class, ClassPatch, ClassPost, Namespace, NamespacePatch, NamespacePost, Object, ObjectPatch,
+    ObjectPost,
+};
+
+/// What to do when a resource in a snapshot already exists on the server.
+///
+/// Namespaces and classes conflict by name, objects by name within their
+/// class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictStrategy {
+    /// Keep the existing resource, and attach imported children to it.
+    Skip,
+    /// Update the existing resource with the imported fields.
+    Overwrite,
+    /// Create the imported resource under a new, unused name.
+    Rename,
+    /// Abort the import before anything is written.
+    #[default]
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Namespace,
+    Class,
+    Object,
+}
+
+impl Display for ResourceKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceKind::Namespace => write!(f, "namespace"),
+            ResourceKind::Class => write!(f, "class"),
+            ResourceKind::Object => write!(f, "object"),
+        }
+    }
+}
+
+/// A resource in a snapshot that already exists on the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub kind: ResourceKind,
+    pub name: String,
+    /// The id of the resource in the snapshot.
+    pub snapshot_id: i32,
+    /// The id of the resource already on the server.
+    pub existing_id: i32,
+    pub strategy: ConflictStrategy,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} '{}' exists as {} ({:?})",
+            self.kind, self.name, self.existing_id, self.strategy
+        )
+    }
+}
+
+/// How to import a snapshot, see `Snapshot::apply_with`.
+#[derive(Clone)]
+pub struct ImportOptions {
+    group_id: i32,
+    strategies: HashMap<ResourceKind, ConflictStrategy>,
+    dry_run: bool,
+    progress: Option<Arc<dyn ProgressSink>>,
+}
+
+impl std::fmt::Debug for ImportOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImportOptions")
+            .field("group_id", &self.group_id)
+            .field("strategies", &self.strategies)
+            .field("dry_run", &self.dry_run)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl ImportOptions {
+    /// Import with namespaces owned by `group_id`, failing on any conflict.
+    pub fn new(group_id: i32) -> Self {
+        ImportOptions {
+            group_id,
+            strategies: HashMap::new(),
+            dry_run: false,
+            progress: None,
+        }
+    }
+
+    pub fn on_conflict(mut self, kind: ResourceKind, strategy: ConflictStrategy) -> Self {
+        self.strategies.insert(kind, strategy);
+        self
+    }
+
+    /// Only report the conflicts, without writing anything.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Report each imported resource to `sink`.
+    pub fn progress(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    fn strategy(&self, kind: ResourceKind) -> ConflictStrategy {
+        self.strategies.get(&kind).copied().unwrap_or_default()
+    }
+}
+
+/// The outcome of importing a snapshot.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ImportReport {
+    /// Old namespace id to the id of the namespace on the server.
+    pub namespaces: HashMap<i32, i32>,
+    pub classes: HashMap<i32, i32>,
+    pub objects: HashMap<i32, i32>,
+    pub conflicts: Vec<Conflict>,
+    /// Whether this was a dry run, in which case nothing was written.
+    pub dry_run: bool,
+}
+
+/// Names of the resources on the server, with their ids.
+struct Existing {
+    namespaces: HashMap<String, i32>,
+    classes: HashMap<String, i32>,
+    objects: HashMap<i32, HashMap<String, i32>>,
+}
+
+impl Existing {
+    fn fetch(client: &SyncClient<Authenticated>) -> Result<Self, ApiError> {
+        Ok(Existing {
+            namespaces: client
+                .namespaces()
+                .filter(vec![])?
+                .into_iter()
+                .map(|n| (n.name, n.id))
+                .collect(),
+            classes: client
+                .classes()
+                .filter(vec![])?
+                .into_iter()
+                .map(|c| (c.name, c.id))
+                .collect(),
+            objects: HashMap::new(),
+        })
+    }
+
+    fn objects(
+        &mut self,
+        client: &SyncClient<Authenticated>,
+        class_id: i32,
+    ) -> Result<&mut HashMap<String, i32>, ApiError> {
+        match self.objects.entry(class_id) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let objects = client
+                    .objects(class_id)
+                    .filter(vec![])?
+                    .into_iter()
+                    .map(|o| (o.name, o.id))
+                    .collect();
+                Ok(entry.insert(objects))
+            }
+        }
+    }
+}
+
+/// The first of `name-2`, `name-3`, ... that is not taken.
+fn unused_name(name: &str, taken: &HashMap<String, i32>) -> String {
+    (2..)
+        .map(|n| format!("{}-{}", name, n))
+        .find(|candidate| !taken.contains_key(candidate))
+        .expect("an unused name")
+}
+
+impl Snapshot {
+    /// Create the resources of the snapshot on the server, failing on any conflict.
+    pub fn apply(
+        &self,
+        client: &SyncClient<Authenticated>,
+        group_id: i32,
+    ) -> Result<ImportReport, ApiError> {
+        self.apply_with(client, &ImportOptions::new(group_id))
+    }
+
+    /// Import the snapshot into the server the client is connected to.
+    ///
+    /// All conflicts are determined before anything is written, and if any
+    /// of them uses `ConflictStrategy::Fail` the import is aborted with
+    /// `ApiError::ImportConflict`. With `dry_run` the conflicts are only
+    /// reported. References between the resources are remapped to the ids
+    /// on the server, while references to resources outside the snapshot
+    /// are kept as they are.
+    pub fn apply_with(
+        &self,
+        client: &SyncClient<Authenticated>,
+        options: &ImportOptions,
+    ) -> Result<ImportReport, ApiError> {
+        let mut existing = Existing::fetch(client)?;
+        let conflicts = self.conflicts(client, options, &mut existing)?;
+
+        let mut report = ImportReport {
+            dry_run: options.dry_run,
+            ..Default::default()
+        };
+        let fatal: Vec<String> = conflicts
+            .iter()
+            .filter(|c| c.strategy == ConflictStrategy::Fail)
+            .map(Conflict::to_string)
+            .collect();
+        report.conflicts = conflicts;
+        if options.dry_run {
+            return Ok(report);
+        }
+        if !fatal.is_empty() {
+            return Err(ApiError::ImportConflict(fatal.join(", ")));
+        }
+
+        let total = self.namespaces.len() + self.classes.len() + self.objects.len();
+        let progress = Progress::new(options.progress.as_deref(), Some(total));
+
+        for namespace in &self.namespaces {
+            let id =
+                progress.track(self.import_namespace(client, options, &existing, namespace))?;
+            report.namespaces.insert(namespace.id, id);
+        }
+
+        for class in &self.classes {
+            let namespace_id = remap(&report.namespaces, class.namespace.id);
+            let id = progress.track(self.import_class(
+                client,
+                options,
+                &existing,
+                class,
+                namespace_id,
+            ))?;
+            report.classes.insert(class.id, id);
+        }
+
+        for object in &self.objects {
+            let class_id = remap(&report.classes, object.hubuum_class_id.id());
+            let namespace_id = remap(&report.namespaces, object.namespace_id.id());
+            let id = progress.track(existing.objects(client, class_id).and_then(|taken| {
+                import_object(client, options, taken, object, class_id, namespace_id)
+            }))?;
+            report.objects.insert(object.id, id);
+        }
+
+        debug!(
+            target: SNAPSHOT,
+            "Applied snapshot: {} namespaces, {} classes, {} objects, {} conflicts",
+            report.namespaces.len(),
+            report.classes.len(),
+            report.objects.len(),
+            report.conflicts.len()
+        );
+        Ok(report)
+    }
+
+    fn conflicts(
+        &self,
+        client: &SyncClient<Authenticated>,
+        options: &ImportOptions,
+        existing: &mut Existing,
+    ) -> Result<Vec<Conflict>, ApiError> {
+        let conflict = |kind, name: &str, snapshot_id, existing_id| Conflict {
+            kind,
+            name: name.to_string(),
+            snapshot_id,
+            existing_id,
+            strategy: options.strategy(kind),
+        };
+
+        let mut conflicts = vec![];
+        for namespace in &self.namespaces {
+            if let Some(&id) = existing.namespaces.get(&namespace.name) {
+                conflicts.push(conflict(
+                    ResourceKind::Namespace,
+                    &namespace.name,
+                    namespace.id,
+                    id,
+                ));
+            }
+        }
+
+        // Objects can only conflict in classes that are kept or overwritten.
+        let mut reused_classes = HashMap::new();
+        for class in &self.classes {
+            if let Some(&id) = existing.classes.get(&class.name) {
+                let class_conflict = conflict(ResourceKind::Class, &class.name, class.id, id);
+                if matches!(
+                    class_conflict.strategy,
+                    ConflictStrategy::Skip | ConflictStrategy::Overwrite
+                ) {
+                    reused_classes.insert(class.id, id);
+                }
+                conflicts.push(class_conflict);
+            }
+        }
+
+        for object in &self.objects {
+            let class_id = object.hubuum_class_id.id();
+            let class_id = match reused_classes.get(&class_id) {
+                Some(&id) => id,
+                None if self.classes.iter().any(|c| c.id == class_id) => continue,
+                None => class_id,
+            };
+            if let Some(&id) = existing.objects(client, class_id)?.get(&object.name) {
+                conflicts.push(conflict(ResourceKind::Object, &object.name, object.id, id));
+            }
+        }
+        Ok(conflicts)
+    }
+
+    fn import_namespace(
+        &self,
+        client: &SyncClient<Authenticated>,
+        options: &ImportOptions,
+        existing: &Existing,
+        namespace: &Namespace,
+    ) -> Result<i32, ApiError> {
+        let resource = client.namespaces();
+        let post = |name: String| NamespacePost {
+            name,
+            description: namespace.description.clone(),
+            group_id: options.group_id,
+        };
+        let Some(&existing_id) = existing.namespaces.get(&namespace.name) else {
+            return Ok(resource.create(post(namespace.name.clone()))?.id);
+        };
+        match options.strategy(ResourceKind::Namespace) {
+            ConflictStrategy::Skip => Ok(existing_id),
+            ConflictStrategy::Overwrite => {
+                let patch = NamespacePatch {
+                    name: Some(namespace.name.clone()),
+                    description: Some(namespace.description.clone()),
+                };
+                Ok(resource.update(existing_id, patch)?.id)
+            }
+            ConflictStrategy::Rename => {
+                let name = unused_name(&namespace.name, &existing.namespaces);
+                Ok(resource.create(post(name))?.id)
+            }
+            ConflictStrategy::Fail => unreachable!("fatal conflicts abort the import"),
+        }
+    }
+
+    fn import_class(
+        &self,
+        client: &SyncClient<Authenticated>,
+        options: &ImportOptions,
+        existing: &Existing,
+        class: &Class,
+        namespace_id: i32,
+    ) -> Result<i32, ApiError> {
+        let resource = client.classes();
+        let post = |name: String| ClassPost {
+            name,
+            description: class.description.clone(),
+            namespace_id,
+            json_schema: class.json_schema.clone(),
+            validate_schema: class.validate_schema,
+        };
+        let Some(&existing_id) = existing.classes.get(&class.name) else {
+            return Ok(resource.create(post(class.name.clone()))?.id);
+        };
+        match options.strategy(ResourceKind::Class) {
+            ConflictStrategy::Skip => Ok(existing_id),
+            ConflictStrategy::Overwrite => {
+                let patch = ClassPatch {
+                    name: Some(class.name.clone()),
+                    description: Some(class.description.clone()),
+                    namespace_id,
+                    json_schema: class.json_schema.clone(),
+                    validate_schema: class.validate_schema,
+                };
+                Ok(resource.update(existing_id, patch)?.id)
+            }
+            ConflictStrategy::Rename => {
+                let name = unused_name(&class.name, &existing.classes);
+                Ok(resource.create(post(name))?.id)
+            }
+            ConflictStrategy::Fail => unreachable!("fatal conflicts abort the import"),
+        }
+    }
+}
+
+fn import_object(
+    client: &SyncClient<Authenticated>,
+    options: &ImportOptions,
+    taken: &mut HashMap<String, i32>,
+    object: &Object,
+    class_id: i32,
+    namespace_id: i32,
+) -> Result<i32, ApiError> {
+    let resource = client.objects(class_id);
+    let post = |name: String| ObjectPost {
+        name,
+        namespace_id: namespace_id.into(),
+        hubuum_class_id: class_id.into(),
+        description: object.description.clone(),
+        data: object.data.clone(),
+    };
+    let id = match taken.get(&object.name) {
+        None => resource.create(post(object.name.clone()))?.id,
+        Some(&existing_id) => match options.strategy(ResourceKind::Object) {
+            ConflictStrategy::Skip => existing_id,
+            ConflictStrategy::Overwrite => {
+                let patch = ObjectPatch {
+                    name: Some(object.name.clone()),
+                    namespace_id: Some(namespace_id.into()),
+                    hubuum_class_id: Some(class_id.into()),
+                    description: Some(object.description.clone()),
+                    data: object.data.clone(),
+                };
+                resource.update(existing_id, patch)?.id
+            }
+            ConflictStrategy::Rename => {
+                let name = unused_name(&object.name, taken);
+                let id = resource.create(post(name.clone()))?.id;
+                taken.insert(name, id);
+                return Ok(id);
+            }
+            ConflictStrategy::Fail => unreachable!("fatal conflicts abort the import"),
+        },
+    };
+    taken.insert(object.name.clone(), id);
+    Ok(id)
+}
+
+fn remap(ids: &HashMap<i32, i32>, id: i32) -> i32 {
+    *ids.get(&id).unwrap_or(&id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::testing::{Reply, TestServer};
+
+    fn snapshot() -> Snapshot {
+        let namespace = Namespace {
+            id: 1,
+            name: "prod".to_string(),
+            ..Default::default()
+        };
+        Snapshot {
+            format_version: crate::snapshot::SNAPSHOT_FORMAT_VERSION,
+            captured_at: chrono::NaiveDateTime::default(),
+            classes: vec![Class {
+                id: 2,
+                name: "hosts".to_string(),
+                namespace: namespace.clone(),
+                ..Default::default()
+            }],
+            namespaces: vec![namespace],
+            objects: vec![Object {
+                id: 3,
+                name: "web".to_string(),
+                namespace_id: 1.into(),
+                hubuum_class_id: 2.into(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    /// A server where every resource of `snapshot()` exists under other ids.
+    fn server() -> (TestServer, SyncClient<Authenticated>) {
+        let server = TestServer::start(|request| {
+            let namespace = Namespace {
+                id: 10,
+                name: "prod".to_string(),
+                ..Default::default()
+            };
+            let body = match request.path.split('?').next().unwrap() {
+                "/api/v1/namespaces/" => serde_json::to_string(&[&namespace]),
+                "/api/v1/classes/" => serde_json::to_string(&[Class {
+                    id: 20,
+                    name: "hosts".to_string(),
+                    namespace,
+                    ..Default::default()
+                }]),
+                "/api/v1/classes/20/" => serde_json::to_string(&[Object {
+                    id: 30,
+                    name: "web".to_string(),
+                    namespace_id: 10.into(),
+                    hubuum_class_id: 20.into(),
+                    ..Default::default()
+                }]),
+                _ => return Reply::json(404, "{}"),
+            };
+            Reply::json(200, body.unwrap())
+        });
+        let client = SyncClient::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let (_server, client) = server();
+        let options =
+            ImportOptions::new(1).on_conflict(ResourceKind::Class, ConflictStrategy::Skip);
+        let report = snapshot().apply_with(&client, &options.dry_run()).unwrap();
+        let conflict = |kind, name: &str, snapshot_id, existing_id, strategy| Conflict {
+            kind,
+            name: name.to_string(),
+            snapshot_id,
+            existing_id,
+            strategy,
+        };
+        assert_eq!(
+            report.conflicts,
+            vec![
+                conflict(
+                    ResourceKind::Namespace,
+                    "prod",
+                    1,
+                    10,
+                    ConflictStrategy::Fail
+                ),
+                conflict(ResourceKind::Class, "hosts", 2, 20, ConflictStrategy::Skip),
+                conflict(ResourceKind::Object, "web", 3, 30, ConflictStrategy::Fail),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_objects_of_new_classes_do_not_conflict() {
+        let (server, client) = server();
+        let options =
+            ImportOptions::new(1).on_conflict(ResourceKind::Class, ConflictStrategy::Rename);
+        let report = snapshot().apply_with(&client, &options.dry_run()).unwrap();
+        let kinds: Vec<_> = report.conflicts.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [ResourceKind::Namespace, ResourceKind::Class]);
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| !request.path.starts_with("/api/v1/classes/20/")));
+    }
+
+    #[test]
+    fn test_dry_run_writes_nothing() {
+        let (server, client) = server();
+        let report = snapshot()
+            .apply_with(&client, &ImportOptions::new(1).dry_run())
+            .unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.conflicts.len(), 2);
+        assert!(report.namespaces.is_empty() && report.classes.is_empty());
+        assert!(!server.requests().is_empty());
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| request.method == "GET"));
+    }
+
+    #[test]
+    fn test_fatal_conflicts_abort_before_writing() {
+        let (server, client) = server();
+        let result = snapshot().apply(&client, 1);
+        assert!(
+            matches!(result, Err(ApiError::ImportConflict(_))),
+            "{:?}",
+            result
+        );
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| request.method == "GET"));
+    }
+
+    #[test]
+    fn test_unused_name() {
+        let taken: HashMap<String, i32> = [("web".to_string(), 1), ("web-2".to_string(), 2)]
+            .into_iter()
+            .collect();
+        assert_eq!(unused_name("web", &taken), "web-3");
+        assert_eq!(unused_name("db", &taken), "db-2");
+    }
+
+    #[test]
+    fn test_options_default_to_fail() {
+        let options = ImportOptions::new(1)
+            .on_conflict(ResourceKind::Object, ConflictStrategy::Skip)
+            .dry_run();
+        assert_eq!(
+            options.strategy(ResourceKind::Namespace),
+            ConflictStrategy::Fail
+        );
+        assert_eq!(
+            options.strategy(ResourceKind::Object),
+            ConflictStrategy::Skip
+        );
+        assert!(options.dry_run);
+    }
+}
@@ -0,0 +1,244 @@
+//! Point-in-time copies of server state.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use hubuum_client::snapshot::{Snapshot, SnapshotScope};
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let client = SyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//! #     .login(Credentials::new("user".into(), "pass".into()))?;
+//! let snapshot = Snapshot::capture(&client, &SnapshotScope::namespaces(&[1, 2]))?;
+//! snapshot.save("prod.snapshot.json")?;
+//!
+//! let snapshot = Snapshot::load("prod.snapshot.json")?;
+//! println!("{} objects", snapshot.objects.len());
+//! # Ok(())
+//! # }
+//! ```
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::client::{Authenticated, SyncClient};
+use crate::errors::ApiError;
+use crate::logging::{debug, SNAPSHOT};
+use crate::resources::{Class, Namespace, Object};
+use crate::types::QueryFilter;
+
+mod import;
+
+pub use import::{Conflict, ConflictStrategy, ImportOptions, ImportReport, ResourceKind};
+
+/// The version of the snapshot file format written by this crate.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// What to include in a snapshot.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SnapshotScope {
+    namespaces: Option<Vec<i32>>,
+    skip_objects: bool,
+}
+
+impl SnapshotScope {
+    /// Everything the client can see.
+    pub fn all() -> Self {
+        SnapshotScope::default()
+    }
+
+    /// Only the given namespaces, and the classes and objects within them.
+    pub fn namespaces(ids: &[i32]) -> Self {
+        SnapshotScope {
+            namespaces: Some(ids.to_vec()),
+            ..Default::default()
+        }
+    }
+
+    /// Leave out objects, capturing only namespaces and classes.
+    pub fn without_objects(mut self) -> Self {
+        self.skip_objects = true;
+        self
+    }
+
+    /// The filters keeping `key` within the namespaces in scope, or `None`
+    /// if the scope has no namespaces.
+    fn filters(&self, key: &str) -> Option<Vec<QueryFilter>> {
+        match &self.namespaces {
+            None => Some(vec![]),
+            Some(ids) if ids.is_empty() => None,
+            Some(ids) => Some(vec![QueryFilter::any_of(key, ids)]),
+        }
+    }
+}
+
+/// Namespaces, classes and objects as they were on the server at `captured_at`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub format_version: u32,
+    pub captured_at: chrono::NaiveDateTime,
+    pub namespaces: Vec<Namespace>,
+    pub classes: Vec<Class>,
+    pub objects: Vec<Object>,
+}
+
+impl Snapshot {
+    /// Fetch the resources in `scope` from the server.
+    ///
+    /// The scope is sent along as filters, so only what is in it is listed.
+    /// Objects are fetched per class in scope, so capturing a scope with
+    /// many classes costs one request per class.
+    pub fn capture(
+        client: &SyncClient<Authenticated>,
+        scope: &SnapshotScope,
+    ) -> Result<Self, ApiError> {
+        let captured_at = chrono::Utc::now().naive_utc();
+
+        let (mut namespaces, mut classes, mut objects) = (vec![], vec![], vec![]);
+        if let (Some(by_id), Some(by_namespace)) =
+            (scope.filters("id"), scope.filters("namespace_id"))
+        {
+            namespaces = client.namespaces().filter(by_id)?;
+            classes = client.classes().filter(by_namespace.clone())?;
+            if !scope.skip_objects {
+                for class in &classes {
+                    objects.extend(client.objects(class.id).filter(by_namespace.clone())?);
+                }
+            }
+        }
+
+        debug!(
+            target: SNAPSHOT,
+            "Captured {} namespaces, {} classes and {} objects",
+            namespaces.len(),
+            classes.len(),
+            objects.len()
+        );
+        Ok(Snapshot {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            captured_at,
+            namespaces,
+            classes,
+            objects,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ApiError> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Load a snapshot, refusing files written in another format version.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ApiError> {
+        let reader = BufReader::new(File::open(path)?);
+        let value: serde_json::Value = serde_json::from_reader(reader)?;
+        let version = value["format_version"].as_u64().unwrap_or_default();
+        if version != u64::from(SNAPSHOT_FORMAT_VERSION) {
+            return Err(ApiError::UnsupportedSnapshotVersion(version));
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            captured_at: chrono::NaiveDateTime::default(),
+            namespaces: vec![Namespace {
+                id: 1,
+                name: "prod".to_string(),
+                ..Default::default()
+            }],
+            classes: vec![Class {
+                id: 2,
+                namespace: Namespace {
+                    id: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            objects: vec![Object {
+                id: 3,
+                namespace_id: 1.into(),
+                hubuum_class_id: 2.into(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("snapshot-{}.json", std::process::id()));
+        let snapshot = snapshot();
+        snapshot.save(&path).unwrap();
+        let loaded = Snapshot::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_load_rejects_other_versions() {
+        let path = std::env::temp_dir().join(format!("snapshot-v0-{}.json", std::process::id()));
+        let mut snapshot = snapshot();
+        snapshot.format_version = 0;
+        snapshot.save(&path).unwrap();
+        let loaded = Snapshot::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            loaded,
+            Err(ApiError::UnsupportedSnapshotVersion(0))
+        ));
+    }
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(SnapshotScope::all().filters("id"), Some(vec![]));
+        let scope = SnapshotScope::namespaces(&[1, 3]).without_objects();
+        assert_eq!(
+            scope.filters("namespace_id"),
+            Some(vec![QueryFilter::any_of("namespace_id", [1, 3])])
+        );
+        assert!(scope.skip_objects);
+        assert_eq!(SnapshotScope::namespaces(&[]).filters("id"), None);
+    }
+
+    #[test]
+    fn test_capture_sends_the_scope() {
+        use crate::client::testing::{Reply, TestServer};
+
+        let server = TestServer::start(|request| {
+            let body = match request.path.as_str() {
+                "/api/v1/namespaces/?id__in=1,3" => serde_json::to_string(&[Namespace {
+                    id: 1,
+                    ..Default::default()
+                }]),
+                "/api/v1/classes/?namespace_id__in=1,3" => serde_json::to_string(&[Class {
+                    id: 2,
+                    ..Default::default()
+                }]),
+                "/api/v1/classes/2/?namespace_id__in=1,3" => serde_json::to_string(&[Object {
+                    id: 5,
+                    ..Default::default()
+                }]),
+                path => panic!("unexpected request for {}", path),
+            };
+            Reply::json(200, body.unwrap())
+        });
+        let client = SyncClient::builder(server.base_url())
+            .build()
+            .unwrap()
+            .login_with_token(TestServer::token())
+            .unwrap();
+
+        let snapshot = Snapshot::capture(&client, &SnapshotScope::namespaces(&[1, 3])).unwrap();
+        assert_eq!(snapshot.objects.len(), 1);
+        assert_eq!(server.requests().len(), 3);
+        let empty = Snapshot::capture(&client, &SnapshotScope::namespaces(&[])).unwrap();
+        assert!(empty.namespaces.is_empty());
+        assert_eq!(server.requests().len(), 3);
+    }
+}
@@ -0,0 +1,263 @@
+//! Checks of the `Endpoint` table against the OpenAPI spec of a server, so
+//! integration suites catch paths drifting before users do.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = SyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//!     .login(Credentials::new("user".into(), "pass".into()))?;
+//! let report = hubuum_client::spec::verify(&client)?;
+//! for mismatch in &report.mismatches {
+//!     eprintln!("{}", mismatch);
+//! }
+//! assert!(report.is_ok());
+//! # Ok(())
+//! # }
+//! ```
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::client::{Authenticated, SyncClient};
+use crate::endpoints::Endpoint;
+use crate::errors::ApiError;
+
+/// A way in which the server spec disagrees with the `Endpoint` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecMismatch {
+    /// The spec has no path matching the endpoint.
+    MissingPath { endpoint: Endpoint, path: String },
+    /// The spec has the path, but not a method the client uses on it.
+    MissingMethod {
+        endpoint: Endpoint,
+        path: String,
+        method: &'static str,
+    },
+}
+
+impl Display for SpecMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpecMismatch::MissingPath { endpoint, path } => {
+                write!(f, "{:?}: {} is not in the spec", endpoint, path)
+            }
+            SpecMismatch::MissingMethod {
+                endpoint,
+                path,
+                method,
+            } => write!(
+                f,
+                "{:?}: {} is in the spec, but without {}",
+                endpoint,
+                path,
+                method.to_uppercase()
+            ),
+        }
+    }
+}
+
+/// The outcome of checking the `Endpoint` table against a spec.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SpecReport {
+    /// The number of path and method pairs checked.
+    pub checked: usize,
+    pub mismatches: Vec<SpecMismatch>,
+}
+
+impl SpecReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Fetch the OpenAPI spec of the server and check the `Endpoint` table
+/// against it.
+pub fn verify(client: &SyncClient<Authenticated>) -> Result<SpecReport, ApiError> {
+    check(&fetch(client)?)
+}
+
+/// Fetch the OpenAPI spec of the server, as vendored in
+/// `openapi/hubuum.json` for the `codegen` feature.
+pub fn fetch(client: &SyncClient<Authenticated>) -> Result<Value, ApiError> {
+    client
+        .request_with_endpoint(
+            reqwest::Method::GET,
+            &Endpoint::OpenApiSpec,
+            vec![],
+            vec![],
+            (),
+        )?
+        .ok_or_else(|| ApiError::EmptyResult("OpenAPI spec returned empty result".into()))
+}
+
+/// Check the `Endpoint` table against an OpenAPI document.
+///
+/// Paths are compared without trailing slashes and with placeholders
+/// matching whatever their names, so `/classes/{class_id}/` matches
+/// `/classes/{id}`. Besides the collection paths, the item paths that
+/// resources are patched and deleted through are checked.
+pub fn check(spec: &Value) -> Result<SpecReport, ApiError> {
+    let mut paths: HashMap<String, HashSet<&str>> = HashMap::new();
+    for (path, item) in spec
+        .get("paths")
+        .and_then(Value::as_object)
+        .ok_or_else(|| ApiError::DeserializationError("OpenAPI spec has no paths".into()))?
+    {
+        // Paths differing only in the trailing slash, like a class and the
+        // objects of a class, share an entry.
+        let operations = paths.entry(normalize(path)).or_default();
+        if let Some(item) = item.as_object() {
+            operations.extend(item.keys().map(String::as_str));
+        }
+    }
+
+    let mut report = SpecReport::default();
+    for &endpoint in Endpoint::ALL {
+        let (collection, item) = methods(endpoint);
+        let item_path = format!("{}{{id}}", endpoint.path());
+        for (path, methods) in [(endpoint.path(), &collection), (item_path.as_str(), &item)] {
+            if methods.is_empty() {
+                continue;
+            }
+            report.checked += methods.len();
+            let Some(operations) = paths.get(&normalize(path)) else {
+                report.mismatches.push(SpecMismatch::MissingPath {
+                    endpoint,
+                    path: path.to_string(),
+                });
+                continue;
+            };
+            for &method in methods {
+                if !operations.contains(method) {
+                    report.mismatches.push(SpecMismatch::MissingMethod {
+                        endpoint,
+                        path: path.to_string(),
+                        method,
+                    });
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// The operations of an OpenAPI path item.
+const OPERATIONS: &[&str] = &["get", "put", "post", "delete", "options", "head", "patch"];
+
+/// The methods the client uses on the collection and item paths of an
+/// endpoint, in OpenAPI spelling, split from `Endpoint::allowed_methods`.
+///
+/// Resources are patched and deleted through their item path, everything
+/// else goes to the collection.
+fn methods(endpoint: Endpoint) -> (Vec<&'static str>, Vec<&'static str>) {
+    // Where the spec is served from is not part of the spec.
+    if endpoint == Endpoint::OpenApiSpec {
+        return (vec![], vec![]);
+    }
+    endpoint
+        .allowed_methods()
+        .iter()
+        .filter_map(|method| {
+            OPERATIONS
+                .iter()
+                .copied()
+                .find(|operation| operation.eq_ignore_ascii_case(method.as_str()))
+        })
+        .partition(|method| !matches!(*method, "put" | "patch" | "delete"))
+}
+
+/// Drop the trailing slash and the names of placeholders.
+pub(crate) fn normalize(path: &str) -> String {
+    let mut normalized = String::with_capacity(path.len());
+    let mut in_placeholder = false;
+    for c in path.trim_end_matches('/').chars() {
+        match c {
+            '{' => {
+                in_placeholder = true;
+                normalized.push_str("{}");
+            }
+            '}' => in_placeholder = false,
+            _ if in_placeholder => {}
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{json, Map};
+
+    /// A spec offering exactly what the client uses.
+    fn spec() -> Value {
+        let mut paths = Map::new();
+        for &endpoint in Endpoint::ALL {
+            let (collection, item) = methods(endpoint);
+            let operations = |methods: &[&str]| {
+                Value::Object(
+                    methods
+                        .iter()
+                        .map(|method| (method.to_string(), json!({})))
+                        .collect(),
+                )
+            };
+            if !collection.is_empty() {
+                paths.insert(endpoint.path().to_string(), operations(&collection));
+            }
+            if !item.is_empty() {
+                // Servers name and slash their placeholders as they like.
+                let path = format!("{}{{item_id}}/", endpoint.path());
+                paths.insert(path, operations(&item));
+            }
+        }
+        json!({ "openapi": "3.0.3", "paths": paths })
+    }
+
+    #[test]
+    fn test_check_matching_spec() {
+        let report = check(&spec()).unwrap();
+        assert!(report.is_ok(), "{:?}", report.mismatches);
+        assert!(report.checked > Endpoint::ALL.len());
+    }
+
+    #[test]
+    fn test_check_reports_drift() {
+        let mut spec = spec();
+        let paths = spec["paths"].as_object_mut().unwrap();
+        paths.remove("/api/v1/webhooks/");
+        paths["/api/v1/namespaces/"]
+            .as_object_mut()
+            .unwrap()
+            .remove("post");
+
+        let report = check(&spec).unwrap();
+        assert_eq!(
+            report.mismatches,
+            vec![
+                SpecMismatch::MissingMethod {
+                    endpoint: Endpoint::Namespaces,
+                    path: "/api/v1/namespaces/".to_string(),
+                    method: "post",
+                },
+                SpecMismatch::MissingPath {
+                    endpoint: Endpoint::Webhooks,
+                    path: "/api/v1/webhooks/".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            report.mismatches[0].to_string(),
+            "Namespaces: /api/v1/namespaces/ is in the spec, but without POST"
+        );
+    }
+
+    #[test]
+    fn test_check_requires_paths() {
+        assert!(matches!(
+            check(&json!({ "openapi": "3.0.3" })),
+            Err(ApiError::DeserializationError(_))
+        ));
+    }
+}
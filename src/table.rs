@@ -0,0 +1,321 @@
+//! Rendering resources as text tables, sorted and filtered in memory.
+//!
+//! ```no_run
+//! # use hubuum_client::{SyncClient, BaseUrl, Credentials};
+//! # use hubuum_client::table::{RowColor, RowStyle, TableOptions};
+//! # use std::str::FromStr;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = SyncClient::new(BaseUrl::from_str("https://api.example.com")?)
+//!     .login(Credentials::new("user".into(), "pass".into()))?;
+//! let classes = client.classes().find().execute()?;
+//! let table = TableOptions::new()
+//!     .sort_by("Name", false)
+//!     .filter("Validate", |value| value == "true")
+//!     .render(&classes)?;
+//! println!("{}", table);
+//!
+//! // Highlight classes that do not validate their objects.
+//! let table = TableOptions::new().render_styled(&classes, |class| match class.validate_schema {
+//!     Some(true) => RowStyle::default(),
+//!     _ => RowStyle::color(RowColor::Yellow),
+//! })?;
+//! println!("{}", table);
+//! # Ok(())
+//! # }
+//! ```
+use std::cmp::Ordering;
+use tabled::builder::Builder;
+use tabled::settings::object::Rows;
+use tabled::settings::Color;
+use tabled::Tabled;
+
+use crate::errors::ApiError;
+
+type Predicate = Box<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// A row of the table, along with the index of the item it shows.
+type Selected = (usize, Vec<String>);
+
+/// A color to highlight a row with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowColor {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+}
+
+/// Hints on how to highlight a row, as returned by the callback passed to
+/// `TableOptions::render_styled`.
+///
+/// The default leaves the row as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RowStyle {
+    pub color: Option<RowColor>,
+    pub bold: bool,
+    pub dim: bool,
+}
+
+impl RowStyle {
+    pub fn color(color: RowColor) -> Self {
+        RowStyle {
+            color: Some(color),
+            ..Default::default()
+        }
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub fn dim(mut self) -> Self {
+        self.dim = true;
+        self
+    }
+
+    /// The terminal color of the style, `None` for plain rows.
+    fn ansi(&self) -> Option<Color> {
+        let color = self.color.map(|color| match color {
+            RowColor::Red => Color::FG_RED,
+            RowColor::Green => Color::FG_GREEN,
+            RowColor::Yellow => Color::FG_YELLOW,
+            RowColor::Blue => Color::FG_BLUE,
+            RowColor::Magenta => Color::FG_MAGENTA,
+            RowColor::Cyan => Color::FG_CYAN,
+        });
+        [
+            color,
+            self.bold.then_some(Color::BOLD),
+            self.dim.then(|| Color::new("\u{1b}[2m", "\u{1b}[22m")),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| a | b)
+    }
+}
+
+/// How to present a list of resources as a table.
+///
+/// Columns are named by their headers, compared case-insensitively. Sorting
+/// and filtering work on the rendered cells, so they need no further request
+/// to the server.
+#[derive(Default)]
+pub struct TableOptions {
+    sort: Option<(String, bool)>,
+    filters: Vec<(String, Predicate)>,
+}
+
+impl TableOptions {
+    pub fn new() -> Self {
+        TableOptions::default()
+    }
+
+    /// Sort the rows by a column, descending if `desc` is set.
+    ///
+    /// Cells that both parse as numbers are compared as numbers, others as
+    /// text. Rows with equal cells keep their order.
+    pub fn sort_by(mut self, column: &str, desc: bool) -> Self {
+        self.sort = Some((column.to_string(), desc));
+        self
+    }
+
+    /// Only show rows where `predicate` holds for the cell of `column`.
+    ///
+    /// Filters add up, a row has to pass all of them.
+    pub fn filter<F>(mut self, column: &str, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.filters.push((column.to_string(), Box::new(predicate)));
+        self
+    }
+
+    /// The headers and the sorted, filtered rows of the table.
+    pub fn rows<T: Tabled>(
+        &self,
+        items: &[T],
+    ) -> Result<(Vec<String>, Vec<Vec<String>>), ApiError> {
+        let (headers, rows) = self.select(items)?;
+        Ok((headers, rows.into_iter().map(|(_, row)| row).collect()))
+    }
+
+    /// Render `items` as a table.
+    pub fn render<T: Tabled>(&self, items: &[T]) -> Result<String, ApiError> {
+        self.render_styled(items, |_| RowStyle::default())
+    }
+
+    /// Render `items` as a table, highlighting every row as `style` says for
+    /// the resource shown in it.
+    pub fn render_styled<T, F>(&self, items: &[T], style: F) -> Result<String, ApiError>
+    where
+        T: Tabled,
+        F: Fn(&T) -> RowStyle,
+    {
+        let (headers, rows) = self.select(items)?;
+        let mut builder = Builder::default();
+        builder.push_record(headers);
+        let mut styles = vec![];
+        for (line, (index, row)) in rows.into_iter().enumerate() {
+            builder.push_record(row);
+            if let Some(color) = style(&items[index]).ansi() {
+                // Line 0 holds the headers.
+                styles.push((line + 1, color));
+            }
+        }
+        let mut table = builder.build();
+        for (line, color) in styles {
+            table.modify(Rows::single(line), color);
+        }
+        Ok(table.to_string())
+    }
+
+    /// The headers, and the rows left after filtering in sorted order.
+    fn select<T: Tabled>(&self, items: &[T]) -> Result<(Vec<String>, Vec<Selected>), ApiError> {
+        let headers: Vec<String> = T::headers().into_iter().map(Into::into).collect();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    ApiError::UnknownColumn(format!("'{}' (columns: {})", name, headers.join(", ")))
+                })
+        };
+
+        let filters = self
+            .filters
+            .iter()
+            .map(|(name, predicate)| Ok((column(name)?, predicate)))
+            .collect::<Result<Vec<_>, ApiError>>()?;
+        let mut rows: Vec<Selected> = items
+            .iter()
+            .map(|item| item.fields().into_iter().map(Into::into).collect())
+            .enumerate()
+            .filter(|(_, row): &Selected| {
+                filters
+                    .iter()
+                    .all(|(index, predicate)| predicate(&row[*index]))
+            })
+            .collect();
+
+        if let Some((name, desc)) = &self.sort {
+            let index = column(name)?;
+            rows.sort_by(|(_, a), (_, b)| {
+                let ordering = compare_cells(&a[index], &b[index]);
+                if *desc {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+        Ok((headers, rows))
+    }
+}
+
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Tabled)]
+    struct Host {
+        #[tabled(rename = "Name")]
+        name: &'static str,
+        #[tabled(rename = "Cores")]
+        cores: u32,
+    }
+
+    fn hosts() -> Vec<Host> {
+        vec![
+            Host {
+                name: "web02",
+                cores: 16,
+            },
+            Host {
+                name: "db01",
+                cores: 4,
+            },
+            Host {
+                name: "web01",
+                cores: 8,
+            },
+        ]
+    }
+
+    fn names(rows: &[Vec<String>]) -> Vec<&str> {
+        rows.iter().map(|row| row[0].as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let (headers, rows) = TableOptions::new()
+            .sort_by("name", false)
+            .rows(&hosts())
+            .unwrap();
+        assert_eq!(headers, vec!["Name", "Cores"]);
+        assert_eq!(names(&rows), vec!["db01", "web01", "web02"]);
+
+        // Numbers sort as numbers, not as text where "16" < "4".
+        let (_, rows) = TableOptions::new()
+            .sort_by("Cores", true)
+            .rows(&hosts())
+            .unwrap();
+        assert_eq!(names(&rows), vec!["web02", "web01", "db01"]);
+    }
+
+    #[test]
+    fn test_filter() {
+        let options = TableOptions::new()
+            .filter("Name", |name| name.starts_with("web"))
+            .filter("Cores", |cores| cores.parse::<u32>().unwrap() > 8)
+            .sort_by("Name", false);
+        let (_, rows) = options.rows(&hosts()).unwrap();
+        assert_eq!(names(&rows), vec!["web02"]);
+
+        let table = options.render(&hosts()).unwrap();
+        assert!(table.contains("web02"));
+        assert!(!table.contains("db01"));
+    }
+
+    #[test]
+    fn test_render_styled() {
+        let plain = TableOptions::new().render(&hosts()).unwrap();
+        assert!(!plain.contains('\u{1b}'));
+
+        let styled = TableOptions::new()
+            .sort_by("Name", false)
+            .render_styled(&hosts(), |host| match host.cores {
+                0..=4 => RowStyle::color(RowColor::Red).bold(),
+                _ => RowStyle::default(),
+            })
+            .unwrap();
+        let db01 = styled.lines().find(|line| line.contains("db01")).unwrap();
+        assert!(db01.contains("\u{1b}[31m"), "{:?}", db01);
+        assert!(db01.contains("\u{1b}[1m"), "{:?}", db01);
+        let web01 = styled.lines().find(|line| line.contains("web01")).unwrap();
+        assert!(!web01.contains('\u{1b}'), "{:?}", web01);
+        // Colors do not count towards the width of the columns.
+        assert_eq!(plain.lines().count(), styled.lines().count());
+    }
+
+    #[test]
+    fn test_unknown_column() {
+        let err = TableOptions::new()
+            .sort_by("Memory", false)
+            .render(&hosts())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown column: 'Memory' (columns: Name, Cores)"
+        );
+    }
+}
@@ -1,6 +1,19 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
 
-#[derive(Debug, Serialize, Deserialize)]
+use super::env_var;
+use crate::errors::ApiError;
+
+/// The environment variable `Credentials::from_env` reads the username from.
+pub const USERNAME_ENV: &str = "HUBUUM_USERNAME";
+/// The environment variable `Credentials::from_env` reads the password from.
+pub const PASSWORD_ENV: &str = "HUBUUM_PASSWORD";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Credentials {
     username: String,
     password: String,
@@ -10,9 +23,220 @@ impl Credentials {
     pub fn new(username: String, password: String) -> Self {
         Self { username, password }
     }
+
+    /// Read the credentials from `HUBUUM_USERNAME` and `HUBUUM_PASSWORD`.
+    pub fn from_env() -> Result<Self, ApiError> {
+        Credentials::from_env_vars(USERNAME_ENV, PASSWORD_ENV)
+    }
+
+    /// Read the credentials from the given environment variables.
+    ///
+    /// Errors about the password variable never include its value.
+    pub fn from_env_vars(username_var: &str, password_var: &str) -> Result<Self, ApiError> {
+        let username = env_var(username_var)?;
+        let password = env_var(password_var).map_err(|err| match err {
+            ApiError::InvalidEnvVar { var, source, .. } => ApiError::InvalidEnvVar {
+                var,
+                value: "…".to_string(),
+                source,
+            },
+            err => err,
+        })?;
+        Ok(Credentials::new(username, password))
+    }
+
+    /// Ask for the username and password on the terminal, without echoing
+    /// the password.
+    ///
+    /// The username defaults to `HUBUUM_USERNAME` if it is set, in which
+    /// case only the password is asked for.
+    #[cfg(feature = "interactive")]
+    pub fn prompt() -> Result<Self, ApiError> {
+        use std::io::Write;
+
+        let username = match env_var(USERNAME_ENV) {
+            Ok(username) => username,
+            Err(_) => {
+                eprint!("Username: ");
+                std::io::stderr().flush()?;
+                let mut username = String::new();
+                std::io::stdin().read_line(&mut username)?;
+                username.trim().to_string()
+            }
+        };
+        let password = rpassword::prompt_password(format!("Password for {}: ", username))?;
+        Ok(Credentials::new(username, password))
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A bearer token issued by the server.
+///
+/// `Display` and `Debug` show only the first characters, so tokens can be
+/// logged without leaking them, and comparison runs in constant time.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Token {
-    pub token: String,
+    token: String,
+}
+
+/// The claims of a JWT-like token that the client cares about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub struct TokenClaims {
+    /// Seconds since the epoch after which the token is no longer valid.
+    pub exp: Option<i64>,
+    /// Seconds since the epoch at which the token was issued.
+    pub iat: Option<i64>,
+}
+
+impl Token {
+    pub fn new(token: String) -> Self {
+        Token { token }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.token
+    }
+
+    pub fn into_string(self) -> String {
+        self.token
+    }
+
+    /// The claims of the token, if it is a JWT with a readable payload.
+    ///
+    /// The signature is not checked; only the server can do that.
+    pub fn claims(&self) -> Option<TokenClaims> {
+        let mut parts = self.token.split('.');
+        let (_header, payload, _signature) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() {
+            return None;
+        }
+        let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+        serde_json::from_slice(&payload).ok()
+    }
+
+    /// When the token expires, if it says so.
+    pub fn expires_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::from_timestamp(self.claims()?.exp?, 0)
+    }
+
+    /// Whether the token claims to have expired.
+    ///
+    /// Opaque tokens, and tokens without an expiry claim, are never
+    /// considered expired; only the server knows for sure.
+    pub fn is_probably_expired(&self) -> bool {
+        self.expires_at()
+            .is_some_and(|expires_at| expires_at <= chrono::Utc::now())
+    }
+}
+
+impl FromStr for Token {
+    type Err = ApiError;
+
+    /// Parse a token, rejecting empty ones and ones with whitespace.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() || s.contains(char::is_whitespace) {
+            return Err(ApiError::InvalidToken);
+        }
+        Ok(Token::new(s.to_string()))
+    }
+}
+
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.token.as_bytes().ct_eq(other.token.as_bytes()).into()
+    }
+}
+
+impl Eq for Token {}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let prefix: String = self.token.chars().take(4).collect();
+        if self.token.chars().count() > 8 {
+            write!(f, "{}…", prefix)
+        } else {
+            write!(f, "…")
+        }
+    }
+}
+
+impl Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Token")
+            .field("token", &format_args!("{}", self))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_credentials_from_env() {
+        let (user_var, password_var) = ("HUBUUM_CLIENT_TEST_USER", "HUBUUM_CLIENT_TEST_PASSWORD");
+        std::env::set_var(user_var, "alice");
+        std::env::remove_var(password_var);
+        assert!(matches!(
+            Credentials::from_env_vars(user_var, password_var),
+            Err(ApiError::MissingEnvVar(name)) if name == password_var
+        ));
+
+        std::env::set_var(password_var, "secret");
+        let credentials = Credentials::from_env_vars(user_var, password_var).unwrap();
+        assert_eq!(credentials.username(), "alice");
+        assert_eq!(credentials.password, "secret");
+        std::env::remove_var(user_var);
+        std::env::remove_var(password_var);
+    }
+
+    fn jwt(payload: &str) -> Token {
+        Token::new(format!(
+            "eyJhbGciOiJIUzI1NiJ9.{}.c2lnbmF0dXJl",
+            URL_SAFE_NO_PAD.encode(payload)
+        ))
+    }
+
+    #[test]
+    fn test_claims() {
+        let expired = jwt(r#"{"exp": 1000, "iat": 10}"#);
+        assert_eq!(
+            expired.claims(),
+            Some(TokenClaims {
+                exp: Some(1000),
+                iat: Some(10)
+            })
+        );
+        assert!(expired.is_probably_expired());
+
+        let valid = jwt(&format!(
+            r#"{{"exp": {}}}"#,
+            chrono::Utc::now().timestamp() + 60
+        ));
+        assert!(!valid.is_probably_expired());
+
+        let opaque = Token::from_str("f00dfeedf00dfeed").unwrap();
+        assert_eq!(opaque.claims(), None);
+        assert!(!opaque.is_probably_expired());
+    }
+
+    #[test]
+    fn test_parse_and_redact() {
+        assert!(matches!(Token::from_str("  "), Err(ApiError::InvalidToken)));
+        assert!(matches!(
+            Token::from_str("a b"),
+            Err(ApiError::InvalidToken)
+        ));
+
+        let token = Token::from_str(" f00dfeedf00dfeed\n").unwrap();
+        assert_eq!(token, Token::new("f00dfeedf00dfeed".to_string()));
+        assert_ne!(token, Token::new("f00dfeedf00dfeee".to_string()));
+        assert_eq!(token.to_string(), "f00d…");
+        assert_eq!(format!("{:?}", token), "Token { token: f00d… }");
+        assert_eq!(Token::new("short".to_string()).to_string(), "…");
+    }
 }
@@ -1,3 +1,5 @@
+use percent_encoding::percent_decode_str;
+use std::path::PathBuf;
 use std::str::FromStr;
 use url::Url;
 
@@ -13,12 +15,74 @@ impl BaseUrl {
 
     // New method to get the base URL with a guaranteed trailing slash
     pub fn with_trailing_slash(&self) -> String {
-        let mut url_str = self.0.to_string();
+        let mut url_str = match self.unix_socket() {
+            // Requests over a unix socket still need a host for the HTTP layer.
+            Some(_) => format!("http://localhost{}", self.0.path()),
+            None => self.0.to_string(),
+        };
         if !url_str.ends_with('/') {
             url_str.push('/');
         }
         url_str
     }
+
+    /// Append a path prefix, for servers mounted below the root of a host.
+    pub fn with_path_prefix(&self, prefix: &str) -> BaseUrl {
+        let prefix = prefix.trim_matches('/');
+        let mut url = self.0.clone();
+        if !prefix.is_empty() {
+            url.set_path(&format!("{}{}/", url.path(), prefix));
+        }
+        BaseUrl(url)
+    }
+
+    /// The socket path for `http+unix://` URLs, where the host is the
+    /// percent-encoded path to the socket, e.g. `http+unix://%2Frun%2Fhubuum.sock/`.
+    pub fn unix_socket(&self) -> Option<PathBuf> {
+        if self.0.scheme() != UNIX_SCHEME {
+            return None;
+        }
+        let host = self.0.host_str()?;
+        Some(PathBuf::from(
+            percent_decode_str(host).decode_utf8_lossy().into_owned(),
+        ))
+    }
+}
+
+const UNIX_SCHEME: &str = "http+unix";
+
+impl BaseUrl {
+    /// Parse user input that may lack a scheme, defaulting to https.
+    ///
+    /// Accepts everything `from_str` does, as well as bare hostnames,
+    /// `host:port` and IPv6 literals with or without brackets, such as `::1`
+    /// or `[::1]:8443`. Port 0 is rejected.
+    pub fn parse_lenient(s: &str) -> Result<Self, ApiError> {
+        let s = s.trim();
+        let base_url = if s.contains("://") {
+            BaseUrl::from_str(s)?
+        } else if s.parse::<std::net::Ipv6Addr>().is_ok() {
+            BaseUrl::from_str(&format!("https://[{}]", s))?
+        } else {
+            BaseUrl::from_str(&format!("https://{}", s))?
+        };
+        if base_url.0.port() == Some(0) {
+            return Err(ApiError::UrlParse(url::ParseError::InvalidPort));
+        }
+        Ok(base_url)
+    }
+
+    /// Read the base URL from the environment variable `var`.
+    ///
+    /// The error names the variable and, if it is set, its value.
+    pub fn from_env(var: &str) -> Result<Self, ApiError> {
+        let value = env_var(var)?;
+        BaseUrl::from_str(&value).map_err(|err| ApiError::InvalidEnvVar {
+            var: var.to_string(),
+            value,
+            source: Box::new(err),
+        })
+    }
 }
 
 impl FromStr for BaseUrl {
@@ -28,9 +92,13 @@ impl FromStr for BaseUrl {
         let mut url = Url::parse(s)?;
 
         // Additional validation
-        if !(url.scheme() == "http" || url.scheme() == "https") {
+        let unix = cfg!(unix) && url.scheme() == UNIX_SCHEME;
+        if !(url.scheme() == "http" || url.scheme() == "https" || unix) {
             return Err(ApiError::InvalidScheme(url.scheme().to_string()));
         }
+        if unix && url.host_str().is_none_or(|host| host.is_empty()) {
+            return Err(ApiError::UrlNotBase(url.to_string()));
+        }
         if url.cannot_be_a_base() {
             return Err(ApiError::UrlNotBase(url.to_string()));
         }
@@ -44,6 +112,20 @@ impl FromStr for BaseUrl {
     }
 }
 
+/// Read an environment variable, naming it in the error if it is unset or
+/// not valid unicode.
+pub(crate) fn env_var(var: &str) -> Result<String, ApiError> {
+    match std::env::var(var) {
+        Ok(value) => Ok(value),
+        Err(std::env::VarError::NotPresent) => Err(ApiError::MissingEnvVar(var.to_string())),
+        Err(std::env::VarError::NotUnicode(value)) => Err(ApiError::InvalidEnvVar {
+            var: var.to_string(),
+            value: value.to_string_lossy().into_owned(),
+            source: Box::new(ApiError::Api("not valid unicode".to_string())),
+        }),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -64,6 +146,18 @@ mod test {
         assert_eq!(base_url.with_trailing_slash(), expected);
     }
 
+    #[parameterized(
+        in_url = { "https://example.com/hubuum", "", "https://example.com/hubuum/" },
+        in_url_with_slash = { "https://example.com/hubuum/", "", "https://example.com/hubuum/" },
+        prefix = { "https://example.com", "hubuum", "https://example.com/hubuum/" },
+        prefix_with_slashes = { "https://example.com", "/hubuum/", "https://example.com/hubuum/" },
+        nested = { "https://example.com/apps/", "/hubuum", "https://example.com/apps/hubuum/" }
+    )]
+    fn test_path_prefix(url: &str, prefix: &str, expected: &str) {
+        let base_url = BaseUrl::from_str(url).unwrap().with_path_prefix(prefix);
+        assert_eq!(base_url.with_trailing_slash(), expected);
+    }
+
     #[parameterized(
         http = { "http" },
         https = { "https" } 
@@ -87,6 +181,31 @@ mod test {
         );
     }
 
+    #[parameterized(
+        root = {
+            "http+unix://%2Frun%2Fhubuum.sock",
+            "/run/hubuum.sock",
+            "http://localhost/",
+        },
+        prefix = {
+            "http+unix://%2Frun%2Fhubuum.sock/api",
+            "/run/hubuum.sock",
+            "http://localhost/api/",
+        }
+    )]
+    #[cfg(unix)]
+    fn test_unix_socket(url: &str, socket: &str, expected: &str) {
+        let base_url = BaseUrl::from_str(url).unwrap();
+        assert_eq!(base_url.unix_socket(), Some(PathBuf::from(socket)));
+        assert_eq!(base_url.with_trailing_slash(), expected);
+    }
+
+    #[test]
+    fn test_tcp_url_has_no_unix_socket() {
+        let base_url = BaseUrl::from_str("https://api.example.com").unwrap();
+        assert_eq!(base_url.unix_socket(), None);
+    }
+
     #[test]
     fn test_base_url_with_trailing_slash() {
         let base_url = BaseUrl::from_str("https://api.example.com").unwrap();
@@ -98,4 +217,52 @@ mod test {
         let base_url = BaseUrl::from_str("https://api.example.com").unwrap();
         assert_eq!(base_url.as_str(), "https://api.example.com/");
     }
+
+    #[parameterized(
+        hostname = { "api.example.com", "https://api.example.com/" },
+        host_and_port = { "api.example.com:8443", "https://api.example.com:8443/" },
+        with_path = { "api.example.com/hubuum", "https://api.example.com/hubuum/" },
+        with_scheme = { "http://api.example.com", "http://api.example.com/" },
+        ipv4 = { "10.0.0.1:8080", "https://10.0.0.1:8080/" },
+        ipv6 = { "::1", "https://[::1]/" },
+        ipv6_with_port = { "[::1]:8443", "https://[::1]:8443/" },
+        padded = { "  api.example.com\n", "https://api.example.com/" }
+    )]
+    fn test_parse_lenient(input: &str, expected: &str) {
+        assert_eq!(BaseUrl::parse_lenient(input).unwrap().as_str(), expected);
+    }
+
+    #[parameterized(
+        port_out_of_range = { "api.example.com:70000" },
+        port_zero = { "api.example.com:0" },
+        bad_scheme = { "ftp://api.example.com" },
+        empty = { "" }
+    )]
+    fn test_parse_lenient_rejects(input: &str) {
+        assert!(BaseUrl::parse_lenient(input).is_err());
+    }
+
+    #[test]
+    fn test_from_env() {
+        let var = "HUBUUM_CLIENT_TEST_BASE_URL";
+        std::env::remove_var(var);
+        assert!(matches!(
+            BaseUrl::from_env(var),
+            Err(ApiError::MissingEnvVar(name)) if name == var
+        ));
+
+        std::env::set_var(var, "ftp://api.example.com");
+        let err = BaseUrl::from_env(var).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Environment variable HUBUUM_CLIENT_TEST_BASE_URL='ftp://api.example.com' is invalid: Invalid URL scheme: ftp"
+        );
+
+        std::env::set_var(var, "https://api.example.com");
+        assert_eq!(
+            BaseUrl::from_env(var).unwrap().as_str(),
+            "https://api.example.com/"
+        );
+        std::env::remove_var(var);
+    }
 }
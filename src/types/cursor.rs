@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// The header the server uses to pass the cursor of the next page.
+pub(crate) const NEXT_CURSOR_HEADER: &str = "X-Next-Cursor";
+
+/// An opaque position in a keyset paginated listing.
+///
+/// Unlike offsets, a cursor points at the last resource seen rather than at a
+/// row number, so resources created while iterating do not shift the pages
+/// and cause items to be skipped or returned twice. Cursors are issued by the
+/// server and should be passed back unmodified.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cursor(String);
+
+impl Cursor {
+    pub fn new(cursor: impl Into<String>) -> Self {
+        Cursor(cursor.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// One page of a keyset paginated listing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    /// The cursor to pass to `after` for the following page, `None` on the last page.
+    pub next: Option<Cursor>,
+}
@@ -1,24 +1,62 @@
 // use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+use crate::errors::ApiError;
 
 // FilterOperator enum
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum FilterOperator {
-    Equals { is_negated: bool },
-    IEquals { is_negated: bool },
-    Contains { is_negated: bool },
-    IContains { is_negated: bool },
-    StartsWith { is_negated: bool },
-    IStartsWith { is_negated: bool },
-    EndsWith { is_negated: bool },
-    IEndsWith { is_negated: bool },
-    Like { is_negated: bool },
-    Regex { is_negated: bool },
-    Gt { is_negated: bool },
-    Gte { is_negated: bool },
-    Lt { is_negated: bool },
-    Lte { is_negated: bool },
-    Between { is_negated: bool },
+    Equals {
+        is_negated: bool,
+    },
+    IEquals {
+        is_negated: bool,
+    },
+    Contains {
+        is_negated: bool,
+    },
+    IContains {
+        is_negated: bool,
+    },
+    StartsWith {
+        is_negated: bool,
+    },
+    IStartsWith {
+        is_negated: bool,
+    },
+    EndsWith {
+        is_negated: bool,
+    },
+    IEndsWith {
+        is_negated: bool,
+    },
+    Like {
+        is_negated: bool,
+    },
+    Regex {
+        is_negated: bool,
+    },
+    Gt {
+        is_negated: bool,
+    },
+    Gte {
+        is_negated: bool,
+    },
+    Lt {
+        is_negated: bool,
+    },
+    Lte {
+        is_negated: bool,
+    },
+    Between {
+        is_negated: bool,
+    },
+    /// Any of a comma separated list of values.
+    In {
+        is_negated: bool,
+    },
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -34,7 +72,7 @@ impl FilterOperator {
     pub fn is_applicable_to(&self, data_type: DataType) -> bool {
         type SO = FilterOperator;
         match self {
-            SO::Equals { .. } => true,
+            SO::Equals { .. } | SO::In { .. } => true,
             SO::Gt { .. }
             | SO::Gte { .. }
             | SO::Lt { .. }
@@ -146,10 +184,69 @@ impl std::fmt::Display for FilterOperator {
                     "between"
                 }
             ),
+            FilterOperator::In { is_negated } => {
+                write!(f, "{}", if *is_negated { "not_in" } else { "in" })
+            }
+        }
+    }
+}
+
+/// A value to filter a field by, together with the operator to apply.
+///
+/// Used by the generated `*Get` parameters, where `Some(Filterable)` becomes
+/// a `<field>__<operator>=<value>` query key. A plain value converts into an
+/// exact match.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Filterable<T> {
+    pub operator: FilterOperator,
+    pub value: T,
+}
+
+impl<T> Filterable<T> {
+    pub fn new(operator: FilterOperator, value: T) -> Self {
+        Filterable { operator, value }
+    }
+
+    pub fn equals(value: T) -> Self {
+        Filterable::new(FilterOperator::Equals { is_negated: false }, value)
+    }
+
+    pub fn icontains(value: T) -> Self {
+        Filterable::new(FilterOperator::IContains { is_negated: false }, value)
+    }
+
+    pub fn gt(value: T) -> Self {
+        Filterable::new(FilterOperator::Gt { is_negated: false }, value)
+    }
+
+    pub fn lt(value: T) -> Self {
+        Filterable::new(FilterOperator::Lt { is_negated: false }, value)
+    }
+}
+
+impl<T: Serialize> Filterable<T> {
+    /// The query filter for the field `key`.
+    pub fn to_query_filter(&self, key: &str) -> QueryFilter {
+        let value = match serde_json::to_value(&self.value) {
+            Ok(Value::String(value)) => value,
+            Ok(value) => value.to_string(),
+            Err(_) => String::new(),
+        };
+        QueryFilter {
+            key: key.to_string(),
+            value,
+            operator: self.operator.clone(),
         }
     }
 }
 
+impl<T> From<T> for Filterable<T> {
+    fn from(value: T) -> Self {
+        Filterable::equals(value)
+    }
+}
+
+#[allow(clippy::wrong_self_convention)]
 pub trait IntoQueryTuples {
     fn into_tuples(&self) -> Vec<(String, String, String)>;
     fn into_query_string(&self) -> String;
@@ -162,6 +259,21 @@ pub struct QueryFilter {
     pub operator: FilterOperator,
 }
 
+impl QueryFilter {
+    /// A filter for resources whose `key` is any of `values`.
+    pub fn any_of<V: ToString>(key: &str, values: impl IntoIterator<Item = V>) -> Self {
+        QueryFilter {
+            key: key.to_string(),
+            value: values
+                .into_iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            operator: FilterOperator::In { is_negated: false },
+        }
+    }
+}
+
 impl std::fmt::Display for QueryFilter {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}__{}={}", self.key, self.operator, self.value)
@@ -190,3 +302,310 @@ impl IntoQueryTuples for Vec<QueryFilter> {
         query_string
     }
 }
+
+/// Set operations on lists of filters, for composing filters from several
+/// sources.
+pub trait FilterSet {
+    /// The filters with exact duplicates removed, keeping the first of each.
+    fn deduplicated(&self) -> Vec<QueryFilter>;
+
+    /// Pairs of filters that no resource can satisfy together, such as
+    /// `name__equals=a` and `name__equals=b`.
+    fn contradictions(&self) -> Vec<(&QueryFilter, &QueryFilter)>;
+
+    /// Combine two lists of filters, rejecting the result if it contradicts itself.
+    fn merge(&self, other: &[QueryFilter]) -> Result<Vec<QueryFilter>, ApiError>;
+}
+
+impl FilterSet for [QueryFilter] {
+    fn deduplicated(&self) -> Vec<QueryFilter> {
+        let mut filters: Vec<QueryFilter> = Vec::with_capacity(self.len());
+        for filter in self {
+            if !filters.contains(filter) {
+                filters.push(filter.clone());
+            }
+        }
+        filters
+    }
+
+    fn contradictions(&self) -> Vec<(&QueryFilter, &QueryFilter)> {
+        let mut found = vec![];
+        for (i, a) in self.iter().enumerate() {
+            for b in &self[i + 1..] {
+                if a.key == b.key && a.contradicts(b) {
+                    found.push((a, b));
+                }
+            }
+        }
+        found
+    }
+
+    fn merge(&self, other: &[QueryFilter]) -> Result<Vec<QueryFilter>, ApiError> {
+        let merged = [self, other].concat().deduplicated();
+        if let Some((a, b)) = merged.contradictions().first() {
+            return Err(ApiError::InvalidFilter(format!(
+                "'{}' contradicts '{}'",
+                a, b
+            )));
+        }
+        Ok(merged)
+    }
+}
+
+impl QueryFilter {
+    /// Whether the filter is an equality, whether it ignores case and
+    /// whether it is negated.
+    fn equality(&self) -> Option<(bool, bool)> {
+        match self.operator {
+            FilterOperator::Equals { is_negated } => Some((false, is_negated)),
+            FilterOperator::IEquals { is_negated } => Some((true, is_negated)),
+            _ => None,
+        }
+    }
+
+    fn contradicts(&self, other: &QueryFilter) -> bool {
+        let (Some((a_ci, a_negated)), Some((b_ci, b_negated))) =
+            (self.equality(), other.equality())
+        else {
+            return false;
+        };
+        let same_ignoring_case = self.value.to_lowercase() == other.value.to_lowercase();
+        match (a_negated, b_negated) {
+            (false, false) if a_ci || b_ci => !same_ignoring_case,
+            (false, false) => self.value != other.value,
+            (true, true) => false,
+            // A negation only rules out every match of the other filter if
+            // it ignores case at least as much.
+            (false, true) | (true, false) => {
+                let (positive_ci, negated_ci) = if a_negated {
+                    (b_ci, a_ci)
+                } else {
+                    (a_ci, b_ci)
+                };
+                match (positive_ci, negated_ci) {
+                    (_, true) => same_ignoring_case,
+                    (true, false) => false,
+                    (false, false) => self.value == other.value,
+                }
+            }
+        }
+    }
+}
+
+impl QueryFilter {
+    /// Evaluate the filter against a resource serialized to JSON.
+    ///
+    /// This mirrors the server's semantics closely enough for offline use.
+    /// Fields are looked up by key, and `<relation>_id` keys fall back to the
+    /// id of an embedded `<relation>` object. Regex filters cannot be
+    /// evaluated locally and are rejected.
+    pub fn matches(&self, resource: &Value) -> Result<bool, ApiError> {
+        let field = match resource.get(&self.key) {
+            Some(field) => field,
+            None => match self
+                .key
+                .strip_suffix("_id")
+                .and_then(|relation| resource.get(relation))
+                .and_then(|embedded| embedded.get("id"))
+            {
+                Some(field) => field,
+                None => return Ok(false),
+            },
+        };
+
+        type FO = FilterOperator;
+        let text = value_to_string(field);
+        let wanted = &self.value;
+        let (matched, is_negated) = match &self.operator {
+            FO::Equals { is_negated } => (text == *wanted, is_negated),
+            FO::IEquals { is_negated } => {
+                (text.to_lowercase() == wanted.to_lowercase(), is_negated)
+            }
+            FO::Contains { is_negated } => match field {
+                Value::Array(items) => (
+                    items.iter().any(|item| value_to_string(item) == *wanted),
+                    is_negated,
+                ),
+                _ => (text.contains(wanted.as_str()), is_negated),
+            },
+            FO::IContains { is_negated } => (
+                text.to_lowercase().contains(&wanted.to_lowercase()),
+                is_negated,
+            ),
+            FO::StartsWith { is_negated } => (text.starts_with(wanted.as_str()), is_negated),
+            FO::IStartsWith { is_negated } => (
+                text.to_lowercase().starts_with(&wanted.to_lowercase()),
+                is_negated,
+            ),
+            FO::EndsWith { is_negated } => (text.ends_with(wanted.as_str()), is_negated),
+            FO::IEndsWith { is_negated } => (
+                text.to_lowercase().ends_with(&wanted.to_lowercase()),
+                is_negated,
+            ),
+            FO::Like { is_negated } => (like(&text, wanted), is_negated),
+            FO::Regex { .. } => {
+                return Err(ApiError::InvalidFilter(format!(
+                    "'{}' uses a regex, which cannot be evaluated locally",
+                    self.key
+                )))
+            }
+            FO::Gt { is_negated } => (compare(&text, wanted).is_gt(), is_negated),
+            FO::Gte { is_negated } => (compare(&text, wanted).is_ge(), is_negated),
+            FO::Lt { is_negated } => (compare(&text, wanted).is_lt(), is_negated),
+            FO::Lte { is_negated } => (compare(&text, wanted).is_le(), is_negated),
+            FO::Between { is_negated } => {
+                let (low, high) = wanted.split_once(',').ok_or_else(|| {
+                    ApiError::InvalidFilter(format!(
+                        "'{}' between needs two comma separated values",
+                        self.key
+                    ))
+                })?;
+                (
+                    compare(&text, low.trim()).is_ge() && compare(&text, high.trim()).is_le(),
+                    is_negated,
+                )
+            }
+            FO::In { is_negated } => (
+                wanted.split(',').any(|value| value.trim() == text),
+                is_negated,
+            ),
+        };
+        Ok(matched != *is_negated)
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Compare numerically when both sides are numbers, and as text otherwise.
+///
+/// Timestamps in ISO 8601 format compare correctly as text.
+fn compare(field: &str, wanted: &str) -> Ordering {
+    match (field.parse::<f64>(), wanted.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => field.cmp(wanted),
+    }
+}
+
+/// SQL style LIKE, where `%` matches any run of characters and `_` any one character.
+fn like(text: &str, pattern: &str) -> bool {
+    fn matches(text: &[char], pattern: &[char]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some(('%', rest)) => (0..=text.len()).any(|skip| matches(&text[skip..], rest)),
+            Some((&c, rest)) => match text.split_first() {
+                Some((&t, text)) if c == '_' || c == t => matches(text, rest),
+                _ => false,
+            },
+        }
+    }
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    matches(&text, &pattern)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use yare::parameterized;
+
+    fn filter(key: &str, operator: FilterOperator, value: &str) -> QueryFilter {
+        QueryFilter {
+            key: key.to_string(),
+            value: value.to_string(),
+            operator,
+        }
+    }
+
+    #[parameterized(
+        equals = { "name", FilterOperator::Equals { is_negated: false }, "web01", true },
+        not_equals = { "name", FilterOperator::Equals { is_negated: true }, "web01", false },
+        iequals = { "name", FilterOperator::IEquals { is_negated: false }, "WEB01", true },
+        number = { "id", FilterOperator::Equals { is_negated: false }, "3", true },
+        embedded_id = { "namespace_id", FilterOperator::Equals { is_negated: false }, "7", true },
+        contains_array = { "tags", FilterOperator::Contains { is_negated: false }, "db", true },
+        icontains = { "name", FilterOperator::IContains { is_negated: false }, "EB", true },
+        startswith = { "name", FilterOperator::StartsWith { is_negated: false }, "web", true },
+        endswith = { "name", FilterOperator::EndsWith { is_negated: false }, "02", false },
+        like = { "name", FilterOperator::Like { is_negated: false }, "w_b%", true },
+        gt_numeric = { "id", FilterOperator::Gt { is_negated: false }, "10", false },
+        lte_date = { "created_at", FilterOperator::Lte { is_negated: false }, "2024-02-01T00:00:00", true },
+        between = { "id", FilterOperator::Between { is_negated: false }, "1,5", true },
+        any_of = { "id", FilterOperator::In { is_negated: false }, "1, 3", true },
+        not_any_of = { "name", FilterOperator::In { is_negated: true }, "web01,web02", false },
+        missing = { "owner", FilterOperator::Equals { is_negated: false }, "x", false }
+    )]
+    fn test_matches(key: &str, operator: FilterOperator, value: &str, expected: bool) {
+        let resource = json!({
+            "id": 3,
+            "name": "web01",
+            "namespace": { "id": 7, "name": "prod" },
+            "tags": ["web", "db"],
+            "created_at": "2024-01-01T00:00:00"
+        });
+        assert_eq!(
+            filter(key, operator, value).matches(&resource).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_regex_is_rejected() {
+        let result = filter("name", FilterOperator::Regex { is_negated: false }, "^w")
+            .matches(&json!({"name": "w"}));
+        assert!(matches!(result, Err(ApiError::InvalidFilter(_))));
+    }
+
+    #[test]
+    fn test_filter_set() {
+        let equals = |key: &str, value: &str| {
+            filter(key, FilterOperator::Equals { is_negated: false }, value)
+        };
+        let a = vec![equals("name", "web01"), equals("namespace_id", "1")];
+        let b = vec![equals("namespace_id", "1"), equals("class_id", "2")];
+        let merged = a.merge(&b).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged.deduplicated(), merged);
+
+        let conflicting = vec![equals("name", "web02")];
+        assert!(matches!(
+            a.merge(&conflicting),
+            Err(ApiError::InvalidFilter(_))
+        ));
+
+        let negated = filter("name", FilterOperator::Equals { is_negated: true }, "web01");
+        assert_eq!(a.as_slice().contradictions().len(), 0);
+        assert_eq!([a[0].clone(), negated].contradictions().len(), 1);
+
+        let iequals = filter(
+            "name",
+            FilterOperator::IEquals { is_negated: false },
+            "WEB01",
+        );
+        assert!([a[0].clone(), iequals].contradictions().is_empty());
+    }
+
+    #[parameterized(
+        iequals_not_equals = { FilterOperator::IEquals { is_negated: false }, FilterOperator::Equals { is_negated: true }, "web", false },
+        iequals_not_iequals = { FilterOperator::IEquals { is_negated: false }, FilterOperator::IEquals { is_negated: true }, "WEB", true },
+        equals_not_iequals = { FilterOperator::Equals { is_negated: false }, FilterOperator::IEquals { is_negated: true }, "WEB", true },
+        equals_not_equals_other_case = { FilterOperator::Equals { is_negated: false }, FilterOperator::Equals { is_negated: true }, "WEB", false },
+        equals_iequals_other_value = { FilterOperator::Equals { is_negated: false }, FilterOperator::IEquals { is_negated: false }, "db", true },
+    )]
+    fn test_contradicts(
+        positive: FilterOperator,
+        other: FilterOperator,
+        value: &str,
+        expected: bool,
+    ) {
+        let a = filter("name", positive, "web");
+        let b = filter("name", other, value);
+        assert_eq!(a.contradicts(&b), expected);
+        assert_eq!(b.contradicts(&a), expected);
+    }
+}
@@ -1,9 +1,18 @@
 mod auth;
 mod baseurl;
+mod cursor;
 mod filter;
+mod page;
 mod params;
+mod signing;
 
-pub use auth::{Credentials, Token};
+pub use auth::{Credentials, Token, TokenClaims, PASSWORD_ENV, USERNAME_ENV};
+pub(crate) use baseurl::env_var;
 pub use baseurl::BaseUrl;
-pub use filter::{FilterOperator, IntoQueryTuples, QueryFilter};
+pub(crate) use cursor::NEXT_CURSOR_HEADER;
+pub use cursor::{Cursor, CursorPage};
+pub use filter::{FilterOperator, FilterSet, Filterable, IntoQueryTuples, QueryFilter};
+pub use page::Page;
+pub(crate) use page::TOTAL_COUNT_HEADER;
 pub use params::{ClassParams, UserParams};
+pub use signing::{RequestSigner, DEFAULT_SIGNATURE_HEADER};
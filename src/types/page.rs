@@ -0,0 +1,104 @@
+/// The header servers use to report the number of matching resources.
+pub(crate) const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// One page of an offset paginated listing.
+///
+/// Converts into the `Vec` of its items for callers that only want those.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// The number of resources on all pages, where the server reports it.
+    pub total: Option<u64>,
+    /// The number of this page, starting at 1.
+    pub page: usize,
+    pub per_page: usize,
+    /// The number of the following page, `None` on the last page.
+    pub next: Option<usize>,
+}
+
+impl<T> Page<T> {
+    /// Build a page, working out whether another follows from the total
+    /// where known, and otherwise from `more` or a full page.
+    pub(crate) fn new(
+        items: Vec<T>,
+        total: Option<u64>,
+        page: usize,
+        per_page: usize,
+        more: bool,
+    ) -> Self {
+        let has_next = match total {
+            Some(total) => ((page * per_page) as u64) < total,
+            None => more || (per_page > 0 && items.len() >= per_page),
+        };
+        Page {
+            items,
+            total,
+            page,
+            per_page,
+            next: has_next.then_some(page + 1),
+        }
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.next.is_none()
+    }
+
+    /// The number of pages, where the server reports the total.
+    pub fn pages(&self) -> Option<usize> {
+        let total = self.total? as usize;
+        Some(match self.per_page {
+            0 => 0,
+            per_page => total.div_ceil(per_page),
+        })
+    }
+}
+
+impl<T> From<Page<T>> for Vec<T> {
+    fn from(page: Page<T>) -> Self {
+        page.items
+    }
+}
+
+impl<T> IntoIterator for Page<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        total_left = { 1, 10, Some(25), false, Some(2), Some(3) },
+        total_reached = { 3, 5, Some(25), false, None, Some(3) },
+        full_page = { 1, 10, None, false, Some(2), None },
+        short_page = { 1, 4, None, false, None, None },
+        server_says_more = { 1, 4, None, true, Some(2), None },
+    )]
+    fn test_page_next(
+        page: usize,
+        count: usize,
+        total: Option<u64>,
+        more: bool,
+        next: Option<usize>,
+        pages: Option<usize>,
+    ) {
+        let page = Page::new(vec![0; count], total, page, 10, more);
+        assert_eq!(page.next, next);
+        assert_eq!(page.is_last(), next.is_none());
+        assert_eq!(page.pages(), pages);
+    }
+
+    #[test]
+    fn test_page_into_vec() {
+        let page = Page::new(vec![1, 2], Some(2), 1, 10, false);
+        let items: Vec<i32> = page.clone().into();
+        assert_eq!(items, vec![1, 2]);
+        assert_eq!(page.into_iter().sum::<i32>(), 3);
+    }
+}
@@ -0,0 +1,446 @@
+//! A CRUD conformance suite run against a live server.
+//!
+//! Every resource goes through the same create, get, filter, patch and
+//! delete cycle, so a resource added with the derive only needs a
+//! `Conformance` impl and a line in `conformance!` to be covered. Resources
+//! without a name are found by id, and those the server does not patch
+//! skip that step.
+//!
+//! The suite is behind the `live-tests` feature and reads the server from
+//! `HUBUUM_URL` and the credentials from `HUBUUM_USERNAME` and
+//! `HUBUUM_PASSWORD`:
+//!
+//! ```text
+//! HUBUUM_URL=http://localhost:8080 HUBUUM_USERNAME=admin HUBUUM_PASSWORD=secret \
+//!     cargo test --features live-tests --test live
+//! ```
+#![cfg(feature = "live-tests")]
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use hubuum_client::client::sync::Resource;
+use hubuum_client::client::GetID;
+use hubuum_client::resources::ApiResource;
+use hubuum_client::{
+    Authenticated, BaseUrl, Class, ClassRelation, Credentials, Group, Namespace, Object,
+    ObjectRelation, Permission, PermissionSet, SyncClient, TokenEntry, User, Webhook,
+};
+
+const URL_ENV: &str = "HUBUUM_URL";
+const USER_PASSWORD: &str = "conformance-suite-password";
+
+/// A resource the suite knows how to create and patch.
+trait Conformance:
+    ApiResource<GetOutput = Self, PostOutput = Self, PatchOutput = Self> + GetID + Serialize
+{
+    /// Whether the resource has a name to create it with and filter on,
+    /// rather than being found by id.
+    const NAMED: bool = true;
+
+    fn resource(client: &SyncClient<Authenticated>, fixture: &Fixture) -> Resource<Self>;
+
+    /// The fields to create the resource with, besides its name.
+    fn post_fields(fixture: &Fixture) -> Value;
+
+    /// Create the resource, for those the server does not create on a post.
+    fn create(resource: &Resource<Self>, _: &Fixture, post: Value) -> Self
+    where
+        Self::PostParams: DeserializeOwned,
+    {
+        resource.create(params(post)).expect("create")
+    }
+
+    /// The fields to patch, which must differ from those it was created
+    /// with. Empty for resources the server does not patch.
+    fn patch_fields() -> Value {
+        json!({ "description": "patched by the conformance suite" })
+    }
+}
+
+impl Conformance for Group {
+    fn resource(client: &SyncClient<Authenticated>, _: &Fixture) -> Resource<Self> {
+        client.groups()
+    }
+
+    fn post_fields(_: &Fixture) -> Value {
+        json!({ "description": "conformance suite" })
+    }
+}
+
+impl Conformance for User {
+    fn resource(client: &SyncClient<Authenticated>, _: &Fixture) -> Resource<Self> {
+        client.users()
+    }
+
+    fn post_fields(_: &Fixture) -> Value {
+        json!({ "password": "conformance-suite-password", "email": "created@example.com" })
+    }
+
+    fn patch_fields() -> Value {
+        json!({ "email": "patched@example.com" })
+    }
+}
+
+impl Conformance for Namespace {
+    fn resource(client: &SyncClient<Authenticated>, _: &Fixture) -> Resource<Self> {
+        client.namespaces()
+    }
+
+    fn post_fields(fixture: &Fixture) -> Value {
+        json!({ "description": "conformance suite", "group_id": fixture.group.id })
+    }
+}
+
+impl Conformance for Class {
+    fn resource(client: &SyncClient<Authenticated>, _: &Fixture) -> Resource<Self> {
+        client.classes()
+    }
+
+    fn post_fields(fixture: &Fixture) -> Value {
+        json!({
+            "description": "conformance suite",
+            "namespace_id": fixture.namespace.id,
+            "validate_schema": false,
+        })
+    }
+}
+
+impl Conformance for Object {
+    fn resource(client: &SyncClient<Authenticated>, fixture: &Fixture) -> Resource<Self> {
+        client.objects(fixture.class.id)
+    }
+
+    fn post_fields(fixture: &Fixture) -> Value {
+        json!({
+            "description": "conformance suite",
+            "namespace_id": fixture.namespace.id,
+            "hubuum_class_id": fixture.class.id,
+            "data": { "suite": "conformance" },
+        })
+    }
+}
+
+impl Conformance for Permission {
+    const NAMED: bool = false;
+
+    fn resource(client: &SyncClient<Authenticated>, fixture: &Fixture) -> Resource<Self> {
+        client.namespace_permissions(fixture.namespace.id)
+    }
+
+    fn post_fields(fixture: &Fixture) -> Value {
+        let post = PermissionSet::read_only().to_post(fixture.peer_group.id);
+        serde_json::to_value(post).expect("serializing the permissions")
+    }
+
+    fn patch_fields() -> Value {
+        json!({ "has_update_class": true })
+    }
+}
+
+impl Conformance for ClassRelation {
+    const NAMED: bool = false;
+
+    fn resource(client: &SyncClient<Authenticated>, _: &Fixture) -> Resource<Self> {
+        client.class_relation()
+    }
+
+    fn post_fields(fixture: &Fixture) -> Value {
+        json!({
+            "from_hubuum_class_id": fixture.class.id,
+            "to_hubuum_class_id": fixture.unrelated_class.id,
+        })
+    }
+
+    fn patch_fields() -> Value {
+        json!({})
+    }
+}
+
+impl Conformance for ObjectRelation {
+    const NAMED: bool = false;
+
+    fn resource(client: &SyncClient<Authenticated>, _: &Fixture) -> Resource<Self> {
+        client.object_relation()
+    }
+
+    fn post_fields(fixture: &Fixture) -> Value {
+        json!({
+            "from_hubuum_object_id": fixture.object.id,
+            "to_hubuum_object_id": fixture.related_object.id,
+            "class_relation_id": fixture.relation.id,
+        })
+    }
+
+    fn patch_fields() -> Value {
+        json!({})
+    }
+}
+
+impl Conformance for Webhook {
+    fn resource(client: &SyncClient<Authenticated>, _: &Fixture) -> Resource<Self> {
+        client.webhooks()
+    }
+
+    fn post_fields(_: &Fixture) -> Value {
+        json!({
+            "url": "https://example.com/created",
+            "event_types": ["object.created"],
+            "secret": "conformance-suite-secret",
+        })
+    }
+
+    fn patch_fields() -> Value {
+        json!({ "url": "https://example.com/patched" })
+    }
+}
+
+impl Conformance for TokenEntry {
+    const NAMED: bool = false;
+
+    fn resource(client: &SyncClient<Authenticated>, fixture: &Fixture) -> Resource<Self> {
+        client.user_tokens(fixture.user.id)
+    }
+
+    fn post_fields(_: &Fixture) -> Value {
+        json!({})
+    }
+
+    /// Tokens are issued by logging in, as the fixture user who has no
+    /// other tokens.
+    fn create(resource: &Resource<Self>, fixture: &Fixture, _: Value) -> Self {
+        let credentials = Credentials::new(fixture.user.username.clone(), USER_PASSWORD.into());
+        SyncClient::new(base_url())
+            .login(credentials)
+            .expect("logging in as the fixture user");
+        let mut tokens = resource.find().execute().expect("listing the issued token");
+        assert_eq!(tokens.len(), 1, "tokens issued to the fixture user");
+        tokens.remove(0)
+    }
+
+    fn patch_fields() -> Value {
+        json!({})
+    }
+}
+
+/// The resources that others are created in or refer to, removed again
+/// when dropped.
+struct Fixture {
+    client: SyncClient<Authenticated>,
+    group: Group,
+    /// A group without permissions on the namespace.
+    peer_group: Group,
+    user: User,
+    namespace: Namespace,
+    class: Class,
+    related_class: Class,
+    unrelated_class: Class,
+    /// The relation from `class` to `related_class`.
+    relation: ClassRelation,
+    object: Object,
+    related_object: Object,
+}
+
+impl Fixture {
+    fn new(client: &SyncClient<Authenticated>) -> Self {
+        let group = |kind| {
+            client
+                .groups()
+                .create(params(json!({
+                    "groupname": unique_name(kind),
+                    "description": "conformance suite fixture",
+                })))
+                .expect("creating a fixture group")
+        };
+        let (group, peer_group) = (group("group"), group("peer-group"));
+        let user = client
+            .users()
+            .create(params(json!({
+                "username": unique_name("user"),
+                "password": USER_PASSWORD,
+                "email": "fixture@example.com",
+            })))
+            .expect("creating the fixture user");
+        let namespace = client
+            .namespaces()
+            .create(params(json!({
+                "name": unique_name("namespace"),
+                "description": "conformance suite fixture",
+                "group_id": group.id,
+            })))
+            .expect("creating the fixture namespace");
+        let class = || {
+            client
+                .classes()
+                .create(params(json!({
+                    "name": unique_name("class"),
+                    "description": "conformance suite fixture",
+                    "namespace_id": namespace.id,
+                    "validate_schema": false,
+                })))
+                .expect("creating a fixture class")
+        };
+        let (class, related_class, unrelated_class) = (class(), class(), class());
+        let relation = client
+            .class_relation()
+            .create(params(json!({
+                "from_hubuum_class_id": class.id,
+                "to_hubuum_class_id": related_class.id,
+            })))
+            .expect("creating the fixture class relation");
+        let object = |class: &Class| {
+            client
+                .objects(class.id)
+                .create(params(json!({
+                    "name": unique_name("object"),
+                    "description": "conformance suite fixture",
+                    "namespace_id": namespace.id,
+                    "hubuum_class_id": class.id,
+                    "data": {},
+                })))
+                .expect("creating a fixture object")
+        };
+        let (object, related_object) = (object(&class), object(&related_class));
+        Fixture {
+            client: client.clone(),
+            group,
+            peer_group,
+            user,
+            namespace,
+            class,
+            related_class,
+            unrelated_class,
+            relation,
+            object,
+            related_object,
+        }
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        // Best effort, a failed cycle may have left resources behind.
+        let _ = self.client.class_relation().delete(self.relation.id);
+        for class in [&self.class, &self.related_class, &self.unrelated_class] {
+            let _ = self.client.classes().delete(class.id);
+        }
+        let _ = self.client.namespaces().delete(self.namespace.id);
+        let _ = self.client.users().delete(self.user.id);
+        let _ = self.client.groups().delete(self.peer_group.id);
+        let _ = self.client.groups().delete(self.group.id);
+    }
+}
+
+fn base_url() -> BaseUrl {
+    let url = std::env::var(URL_ENV).unwrap_or_else(|_| panic!("{} is not set", URL_ENV));
+    BaseUrl::from_str(&url).expect("parsing the server URL")
+}
+
+fn client() -> SyncClient<Authenticated> {
+    let credentials = Credentials::from_env().expect("reading the credentials");
+    SyncClient::new(base_url())
+        .login(credentials)
+        .expect("logging in")
+}
+
+/// A name no other run or test uses, so runs against a shared server do not
+/// collide.
+fn unique_name(kind: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "conformance-{}-{}-{}-{}",
+        kind,
+        std::process::id(),
+        chrono::Utc::now().timestamp_millis(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn params<P: DeserializeOwned>(value: Value) -> P {
+    serde_json::from_value(value).expect("building request parameters")
+}
+
+fn field<T: Serialize>(resource: &T, name: &str) -> Value {
+    serde_json::to_value(resource).expect("serializing the resource")[name].clone()
+}
+
+/// Create, get, filter, patch and delete one resource of type `T`.
+fn crud<T>()
+where
+    T: Conformance,
+    T::PostParams: DeserializeOwned,
+    T::PatchParams: DeserializeOwned,
+{
+    let client = client();
+    let fixture = Fixture::new(&client);
+    let resource = T::resource(&client, &fixture);
+
+    let name = unique_name("resource");
+    let mut post = T::post_fields(&fixture);
+    if T::NAMED {
+        post[T::NAME_FIELD] = json!(name);
+    }
+    let created = T::create(&resource, &fixture, post);
+    let id = created.id();
+    let key = |resource: &T| match T::NAMED {
+        true => field(resource, T::NAME_FIELD),
+        false => json!(resource.id()),
+    };
+    let expected = key(&created);
+    if T::NAMED {
+        assert_eq!(expected, json!(name));
+    }
+
+    let fetched = resource.select(id).expect("get");
+    assert_eq!(fetched.id(), id);
+    assert_eq!(key(fetched.resource()), expected);
+
+    let find = resource.find();
+    let find = match T::NAMED {
+        true => find.add_filter_name_exact(&name),
+        false => find.add_filter_id(id),
+    };
+    let found = find.execute().expect("filter");
+    assert_eq!(found.iter().map(GetID::id).collect::<Vec<_>>(), vec![id]);
+
+    let patch = T::patch_fields();
+    let patch = patch.as_object().expect("patch fields are an object");
+    if !patch.is_empty() {
+        let patched = resource
+            .update(id, params(Value::Object(patch.clone())))
+            .expect("patch");
+        for (key, value) in patch {
+            assert_eq!(&field(&patched, key), value, "patched field {}", key);
+        }
+    }
+
+    resource.delete(id).expect("delete");
+    assert!(resource.select(id).is_err(), "deleted resource still found");
+}
+
+macro_rules! conformance {
+    ($($test:ident: $resource:ty),* $(,)?) => {
+        $(
+            #[test]
+            fn $test() {
+                crud::<$resource>();
+            }
+        )*
+    };
+}
+
+conformance! {
+    group_crud: Group,
+    user_crud: User,
+    namespace_crud: Namespace,
+    class_crud: Class,
+    object_crud: Object,
+    permission_crud: Permission,
+    class_relation_crud: ClassRelation,
+    object_relation_crud: ObjectRelation,
+    webhook_crud: Webhook,
+    token_crud: TokenEntry,
+}